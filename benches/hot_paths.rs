@@ -0,0 +1,184 @@
+use bless::{
+    file_buffer::{bzip2::Bz2FileBuffer, raw::RawFileBuffer, FileBuffer},
+    file_view::FileView,
+    utils::algorithm::{find_nth_or_last, rfind_nth_or_last},
+};
+use bzip2::{write::BzEncoder, Compression};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use regex::bytes::Regex;
+use std::{fs, io::Write, sync::atomic::AtomicBool};
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+
+// lines of the long input, about 6MB
+const LONG_LINES: usize = 100_000;
+// lines of the wide input, and their length
+const WIDE_LINES: usize = 200;
+const WIDE_LINE_LEN: usize = 0x10000;
+
+// files of the inputs, removed once the benchmarks ran
+struct BenchDir(TempDir);
+
+impl BenchDir {
+    fn new() -> Self {
+        let dir = tempfile::Builder::new()
+            .prefix("bless-bench-")
+            .tempdir()
+            .unwrap();
+        return Self(dir);
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> String {
+        let path = self.0.path().join(name);
+        fs::write(&path, data).unwrap();
+        return path.to_str().unwrap().to_owned();
+    }
+}
+
+fn long_input() -> Vec<u8> {
+    let mut data = Vec::new();
+    writeln!(data, "the needle at the start").unwrap();
+    for n in 0..LONG_LINES {
+        writeln!(data, "line {} request-id-{} hello world", n, n % 7).unwrap();
+    }
+    writeln!(data, "the needle at the end").unwrap();
+    return data;
+}
+
+fn wide_input() -> Vec<u8> {
+    let mut data = Vec::new();
+    for n in 0..WIDE_LINES {
+        let word = format!("word-{} ", n);
+        let line = word.repeat(WIDE_LINE_LEN / word.len());
+        writeln!(data, "{}", line).unwrap();
+    }
+    return data;
+}
+
+fn bz2_input(data: &[u8]) -> Vec<u8> {
+    let mut encoder = BzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data).unwrap();
+    return encoder.finish().unwrap();
+}
+
+fn bench_find_nth(c: &mut Criterion, long: &[u8], wide: &[u8]) {
+    let mut group = c.benchmark_group("find_nth_or_last");
+    // many short lines, and a few long ones like minified JSON
+    for (name, data, lines) in [("long", long, LONG_LINES), ("wide", wide, WIDE_LINES)] {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(format!("{} forward", name), |b| {
+            b.iter(|| find_nth_or_last(black_box(data), b'\n', lines / 2))
+        });
+        group.bench_function(format!("{} backward", name), |b| {
+            b.iter(|| rfind_nth_or_last(black_box(data), b'\n', lines / 2))
+        });
+        group.bench_function(format!("{} last", name), |b| {
+            b.iter(|| find_nth_or_last(black_box(data), b'\n', usize::MAX))
+        });
+    }
+    group.finish();
+}
+
+fn bench_view(c: &mut Criterion, rt: &Runtime, long: &str, wide: &str) {
+    let mut group = c.benchmark_group("FileView::view");
+    for (name, path, ncols) in [
+        ("long", long, None),
+        ("wide", wide, None),
+        ("wide-chopped", wide, Some(200)),
+    ] {
+        let mut view = rt.block_on(FileView::new(path)).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| rt.block_on(view.view(50, ncols)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+// a few lines down then up, from the middle of the file
+fn bench_scroll(c: &mut Criterion, rt: &Runtime, long: &str, wide: &str) {
+    let mut group = c.benchmark_group("FileView scroll");
+    for (name, path, lines) in [("long", long, LONG_LINES), ("wide", wide, WIDE_LINES)] {
+        let mut view = rt.block_on(FileView::new(path)).unwrap();
+        rt.block_on(view.jump_to_line(lines as i64 / 2)).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                rt.block_on(async {
+                    view.down(10).await.unwrap();
+                    view.up(10).await.unwrap();
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+// decodes the first block after the header, 900kB with the best compression
+fn bench_bz2_decode(c: &mut Criterion, rt: &Runtime, path: &str) {
+    let mut group = c.benchmark_group("bz2");
+    group.sample_size(10);
+    group.bench_function("block decode", |b| {
+        b.iter_batched(
+            || {
+                let mut buffer = rt.block_on(Bz2FileBuffer::new(path)).unwrap();
+                buffer.jump(0).unwrap();
+                return buffer;
+            },
+            |mut buffer| rt.block_on(buffer.load_next()).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+fn bench_seek(c: &mut Criterion, rt: &Runtime, path: &str, size: usize) {
+    let cancelled = AtomicBool::new(false);
+    let mut buffer = rt.block_on(RawFileBuffer::new(path)).unwrap();
+    let mut group = c.benchmark_group("raw seek");
+    group.throughput(Throughput::Bytes(size as u64));
+    // each search goes through the whole file, to the needle at the other end
+    for (name, forward, backward) in [
+        ("literal", "needle at the end", "needle at the start"),
+        (
+            "regex",
+            r"needle\s+at\s+the\s+e\w+",
+            r"needle\s+at\s+the\s+s\w+",
+        ),
+    ] {
+        let forward = Regex::new(forward).unwrap();
+        let backward = Regex::new(backward).unwrap();
+        group.bench_function(format!("{} forward", name), |b| {
+            b.iter(|| {
+                buffer.jump(0).unwrap();
+                rt.block_on(buffer.seek_from(&forward, 0, &cancelled))
+                    .unwrap()
+            })
+        });
+        group.bench_function(format!("{} backward", name), |b| {
+            b.iter(|| {
+                buffer.jump(size as u64).unwrap();
+                rt.block_on(buffer.rseek_from(&backward, 0, &cancelled))
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn hot_paths(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let dir = BenchDir::new();
+    let long = long_input();
+    let wide = wide_input();
+    let long_path = dir.write("long.log", &long);
+    let wide_path = dir.write("wide.log", &wide);
+    let bz2_path = dir.write("long.log.bz2", &bz2_input(&long));
+
+    bench_find_nth(c, &long, &wide);
+    bench_view(c, &rt, &long_path, &wide_path);
+    bench_scroll(c, &rt, &long_path, &wide_path);
+    bench_bz2_decode(c, &rt, &bz2_path);
+    bench_seek(c, &rt, &long_path, long.len());
+}
+
+criterion_group!(benches, hot_paths);
+criterion_main!(benches);
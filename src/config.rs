@@ -0,0 +1,162 @@
+use crate::errors::Result;
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::PathBuf};
+use tracing::info;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct StyleConfig {
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ThemeConfig {
+    // built-in theme to start from, defaults to "dark"
+    pub base: Option<String>,
+    pub header: Option<StyleConfig>,
+    pub header_title: Option<StyleConfig>,
+    pub status: Option<StyleConfig>,
+    pub search_match: Option<StyleConfig>,
+    pub search_current: Option<StyleConfig>,
+    pub eof: Option<StyleConfig>,
+    pub log_timestamp: Option<StyleConfig>,
+    pub diff_added: Option<StyleConfig>,
+    pub diff_removed: Option<StyleConfig>,
+    // trace, debug, info, warn, error, fatal
+    pub log_levels: Option<Vec<StyleConfig>>,
+    pub entropy: Option<Vec<StyleConfig>>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogHighlight {
+    // color the whole line
+    Line,
+    // color only the matched level token
+    Token,
+    // color the matched level token and the timestamp
+    TokenAndTimestamp,
+}
+
+impl Default for LogHighlight {
+    fn default() -> Self {
+        return LogHighlight::Line;
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HeaderMode {
+    // bordered header with the file name, position and status
+    Full,
+    // single line with the position and status
+    Compact,
+    // no header, the text uses the whole screen
+    Hidden,
+}
+
+impl Default for HeaderMode {
+    fn default() -> Self {
+        return HeaderMode::Full;
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LogRuleConfig {
+    pub pattern: String,
+    pub style: Option<StyleConfig>,
+    pub highlight: Option<LogHighlight>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LogConfig {
+    // default highlight for rules that don't define one
+    pub highlight: LogHighlight,
+    pub timestamp_pattern: Option<String>,
+    // replace the built-in log level rules when not empty
+    pub rules: Vec<LogRuleConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub theme: Option<String>,
+    // "16", "256" or "truecolor", detected from the environment if not set
+    pub color_depth: Option<String>,
+    pub themes: HashMap<String, ThemeConfig>,
+    pub log: LogConfig,
+    // regex with capture groups used by the fields color mode
+    pub fields_pattern: Option<String>,
+    // capture mouse events, this disables the terminal text selection
+    pub mouse: bool,
+    // maximum number of screen updates per second while the file grows
+    pub max_fps: Option<u32>,
+    // header position line, with {line}, {offset}, {byte}, {percent}, {file},
+    // {size}, {flags}, {search}, {matches}, {clock}, {format}, {mtime} and
+    // {inode} placeholders
+    pub header_format: Option<String>,
+    pub header: HeaderMode,
+    // reopen files at the position they were last closed
+    pub resume: bool,
+    // columns of the file name shown before each line of views of several
+    // files, 0 to hide it
+    pub source_tag_width: Option<usize>,
+    // characters splitting words on top of whitespace
+    pub word_separators: Option<String>,
+    // names for command lines, "errors = \"filter ERROR\"" makes :errors
+    // filter the errors, the arguments of an alias are appended to it
+    pub aliases: HashMap<String, String>,
+    // initial values of the options of :set, like "tab-width = 8"
+    pub options: HashMap<String, toml::Value>,
+    // exit code of Q and :q!, 1 by default
+    pub abort_exit_code: Option<i32>,
+    // exit code when the search given at startup matched nothing, 0 by default
+    pub not_found_exit_code: Option<i32>,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        return dirs::config_dir().map(|dir| dir.join("bless").join("config.toml"));
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = match Config::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Config::default()),
+        };
+        info!("loading config from {}", path.display());
+        let content = fs::read_to_string(&path)?;
+        return Ok(toml::from_str(&content)?);
+    }
+
+    pub fn rc_path() -> Option<PathBuf> {
+        return dirs::config_dir().map(|dir| dir.join("bless").join("blessrc"));
+    }
+
+    // commands run at startup, one per line of `path` or of the default rc
+    // file when it exists; # starts a comment line
+    pub fn rc_commands(path: Option<&str>) -> Result<Vec<String>> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => match Config::rc_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Vec::new()),
+            },
+        };
+        info!("running commands of {}", path.display());
+        let content = fs::read_to_string(&path)?;
+        return Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty() && !x.starts_with('#'))
+            .map(str::to_owned)
+            .collect());
+    }
+}
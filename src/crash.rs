@@ -0,0 +1,108 @@
+use chrono::Local;
+use std::{
+    backtrace::Backtrace,
+    collections::{BTreeMap, VecDeque},
+    env,
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, Write},
+    panic::PanicHookInfo,
+    path::PathBuf,
+    process,
+    sync::{Mutex, MutexGuard, TryLockError},
+};
+
+// commands kept for the crash report
+const COMMAND_HISTORY: usize = 50;
+
+// what the backends did last, written to the crash report when bless panics
+struct CrashContext {
+    // most recent last, with the path of the view
+    commands: VecDeque<String>,
+    // state of the view of each path
+    views: BTreeMap<String, String>,
+}
+
+static CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    commands: VecDeque::new(),
+    views: BTreeMap::new(),
+});
+
+// the panic may happen while the context is held, never wait for it
+fn context() -> Option<MutexGuard<'static, CrashContext>> {
+    return match CONTEXT.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    };
+}
+
+pub fn record_command(path: &str, command: String) {
+    if let Some(mut context) = context() {
+        if context.commands.len() >= COMMAND_HISTORY {
+            context.commands.pop_front();
+        }
+        context.commands.push_back(format!(
+            "{} {}: {}",
+            Local::now().format("%H:%M:%S%.3f"),
+            path,
+            command
+        ));
+    }
+}
+
+pub fn record_view(path: &str, view: String) {
+    if let Some(mut context) = context() {
+        context.views.insert(path.to_owned(), view);
+    }
+}
+
+fn report(info: &PanicHookInfo) -> String {
+    let mut report = String::new();
+    writeln!(report, "bless {} crashed", env!("CARGO_PKG_VERSION")).ok();
+    writeln!(report, "date: {}", Local::now().format("%Y-%m-%d %H:%M:%S")).ok();
+    writeln!(report, "args: {:?}", env::args().collect::<Vec<_>>()).ok();
+    writeln!(report, "\n{}", info).ok();
+    writeln!(report, "\nbacktrace:\n{}", Backtrace::force_capture()).ok();
+    match context() {
+        Some(context) => {
+            writeln!(report, "last commands:").ok();
+            for command in context.commands.iter() {
+                writeln!(report, "  {}", command).ok();
+            }
+            writeln!(report, "\nviews:").ok();
+            for (path, view) in context.views.iter() {
+                writeln!(report, "  {}\n{}", path, view).ok();
+            }
+        }
+        None => {
+            writeln!(
+                report,
+                "context unavailable, it was in use when the panic happened"
+            )
+            .ok();
+        }
+    }
+    return report;
+}
+
+// write the crash report in the state directory of the user, or its cache
+// directory, and return its path
+pub fn write_report(info: &PanicHookInfo) -> io::Result<PathBuf> {
+    let dir = dirs::state_dir()
+        .or_else(dirs::cache_dir)
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no state directory"))?
+        .join("bless");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "crash-{}-{}.txt",
+        Local::now().format("%Y%m%d%H%M%S"),
+        process::id()
+    ));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    file.write_all(report(info).as_bytes())?;
+    return Ok(path);
+}
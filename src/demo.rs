@@ -0,0 +1,106 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+use tracing::{error, info};
+
+const LEVELS: [&str; 5] = ["DEBUG", "INFO", "INFO", "WARN", "ERROR"];
+const ROUTES: [&str; 4] = ["/api/items", "/api/users", "/health", "/login"];
+
+// delay between two lines added to the demo log
+const APPEND_INTERVAL: Duration = Duration::from_millis(200);
+
+// "500", "10KB", "200MB" or "2GB", powers of 1024
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim().to_uppercase();
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let count: u64 = value[..digits].parse().ok()?;
+    let unit: u64 = match value[digits..].trim() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return None,
+    };
+    return count.checked_mul(unit);
+}
+
+// synthetic log lines, the same ones on every run
+struct LogGenerator {
+    seed: u64,
+    line: u64,
+}
+
+impl LogGenerator {
+    fn new() -> Self {
+        return Self { seed: 42, line: 0 };
+    }
+
+    // xorshift, good enough for logs
+    fn next_random(&mut self) -> u64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        return self.seed;
+    }
+
+    fn next_line(&mut self) -> String {
+        self.line += 1;
+        let millis = self.line * 37;
+        let random = self.next_random();
+        let level = LEVELS[(random % LEVELS.len() as u64) as usize];
+        let route = ROUTES[(random >> 8) as usize % ROUTES.len()];
+        let duration = (random >> 16) % 500;
+        let line = format!(
+            "2022-03-01T{:02}:{:02}:{:02}.{:03}Z {:<5} [worker-{}] request-id={:06x} GET {} {}ms",
+            millis / 3_600_000 % 24,
+            millis / 60_000 % 60,
+            millis / 1000 % 60,
+            millis % 1000,
+            level,
+            (random >> 24) % 8,
+            (random >> 32) & 0xffffff,
+            route,
+            duration,
+        );
+        return match level {
+            "ERROR" => format!("{} failed: connection reset by peer\n", line),
+            _ => format!("{}\n", line),
+        };
+    }
+}
+
+// write about `size` bytes of logs to `dir`/demo.log, then keep adding lines
+// in the background to try following
+pub fn write_demo_log(dir: &Path, size: u64) -> io::Result<PathBuf> {
+    let path = dir.join("demo.log");
+    let mut generator = LogGenerator::new();
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    let mut writer = BufWriter::new(file);
+    let mut written = 0;
+    while written < size {
+        let line = generator.next_line();
+        writer.write_all(line.as_bytes())?;
+        written += line.len() as u64;
+    }
+    writer.flush()?;
+    info!("demo log of {} bytes written to {:?}", written, path);
+
+    let mut file = OpenOptions::new().append(true).open(&path)?;
+    thread::spawn(move || loop {
+        thread::sleep(APPEND_INTERVAL);
+        if let Err(e) = file.write_all(generator.next_line().as_bytes()) {
+            error!("error writing the demo log: {}", e);
+            break;
+        }
+    });
+    return Ok(path);
+}
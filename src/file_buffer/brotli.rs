@@ -0,0 +1,140 @@
+use super::stream::StreamDecoder;
+use crate::utils::text::looks_like_text;
+use brotli_decompressor::{BrotliDecompressStream, BrotliResult, BrotliState, StandardAlloc};
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read},
+    path::Path,
+};
+
+const ALLOC_SIZE: usize = 0x100000;
+// bytes read to recognize the format
+const DETECT_SIZE: u64 = 0x10000;
+
+pub struct BrotliDecoder {
+    state: BrotliState<StandardAlloc, StandardAlloc, StandardAlloc>,
+    done: bool,
+}
+
+impl Default for BrotliDecoder {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl BrotliDecoder {
+    pub fn new() -> Self {
+        return Self {
+            state: BrotliState::new(
+                StandardAlloc::default(),
+                StandardAlloc::default(),
+                StandardAlloc::default(),
+            ),
+            done: false,
+        };
+    }
+}
+
+impl StreamDecoder for BrotliDecoder {
+    fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<(usize, usize)> {
+        if self.done {
+            return Ok((0, 0));
+        }
+        let size_before = output.len();
+        output.resize(size_before + ALLOC_SIZE, 0);
+        let mut available_in = input.len();
+        let mut input_offset = 0;
+        let mut available_out = ALLOC_SIZE;
+        let mut output_offset = size_before;
+        let mut total_out = 0;
+        let result = BrotliDecompressStream(
+            &mut available_in,
+            &mut input_offset,
+            input,
+            &mut available_out,
+            &mut output_offset,
+            output,
+            &mut total_out,
+            &mut self.state,
+        );
+        output.truncate(output_offset);
+        match result {
+            BrotliResult::ResultFailure => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "invalid brotli data",
+                ))
+            }
+            BrotliResult::ResultSuccess => self.done = true,
+            _ => (),
+        }
+        return Ok((input_offset, output_offset - size_before));
+    }
+}
+
+// brotli has no magic number, the file needs the extension and its start
+// must decode to text
+pub fn detect(path: &str) -> io::Result<bool> {
+    if Path::new(path).extension().is_none_or(|x| x != "br") {
+        return Ok(false);
+    }
+    let mut data = Vec::new();
+    File::open(path)?.take(DETECT_SIZE).read_to_end(&mut data)?;
+    let mut output = Vec::new();
+    return match BrotliDecoder::new().decode(&data, &mut output) {
+        Ok((_, produced)) => Ok(produced > 0 && looks_like_text(&output)),
+        Err(_) => Ok(false),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file_buffer::{
+            format::{sniff, Format},
+            make_file_buffer,
+        },
+        testing::{load_backward, load_forward, text, TestDir},
+    };
+    use ::brotli::CompressorWriter;
+    use std::io::Write;
+
+    fn brotli(data: &[u8]) -> Vec<u8> {
+        let mut encoder = CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(data).unwrap();
+        return encoder.into_inner();
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = TestDir::new("brotli");
+        let data = text(50000);
+        let path = dir.write("data.br", brotli(&data));
+        assert_eq!(sniff(&path).unwrap(), Format::Brotli);
+        let mut buffer = make_file_buffer(&path).await.unwrap();
+        assert!(load_forward(buffer.as_mut()).await == data);
+        assert!(load_backward(buffer.as_mut()).await == data);
+    }
+
+    #[test]
+    fn needs_the_extension() {
+        let dir = TestDir::new("brotli-name");
+        let compressed = brotli(&text(100));
+        assert!(detect(&dir.write("data.br", &compressed)).unwrap());
+        assert!(!detect(&dir.write("data", &compressed)).unwrap());
+    }
+
+    #[tokio::test]
+    async fn truncated() {
+        let dir = TestDir::new("brotli-truncated");
+        let data = text(50000);
+        let compressed = brotli(&data);
+        let path = dir.write("data.br", &compressed[..compressed.len() / 2]);
+        let mut buffer = make_file_buffer(&path).await.unwrap();
+        // what was decoded before the end of the file is shown
+        let decoded = load_forward(buffer.as_mut()).await;
+        assert!(!decoded.is_empty());
+        assert!(data.starts_with(&decoded));
+    }
+}
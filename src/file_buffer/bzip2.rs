@@ -1,14 +1,11 @@
-use crate::utils::infinite_loop_breaker::InfiniteLoopBreaker;
-
-use super::FileBuffer;
+use super::{corrupted_block, ChecksumErrors, DecodedOffsets, FileBuffer};
 use async_trait::async_trait;
-use bzip2::Decompress;
+use bzip2::{Decompress, Status};
 use human_bytes::human_bytes;
-use log::{debug, info};
 use memmap2::{Advice, Mmap, MmapOptions};
 use regex::bytes::Regex;
 use std::{
-    cmp::min,
+    cmp::{max, min},
     collections::VecDeque,
     fmt,
     io::{self, ErrorKind},
@@ -17,13 +14,16 @@ use std::{
     vec::Vec,
 };
 use tokio::{fs::File, io::AsyncReadExt, task::yield_now};
+use tracing::{debug, debug_span, info, warn};
 
 const ALLOC_SIZE: usize = 0x100000;
 const MAGIC_RFIND_WINDOW: usize = 0x10000;
 const MAGIC_RFIND_OVERLAP: usize = 8;
-const MAX_INVALID_BLOCKS: u64 = 10;
 const FIND_WINDOW: usize = 0x100000;
 const FIND_OVERLAP: usize = 0x1000;
+// the level of a stream only bounds the size of its blocks, the blocks of
+// every stream decode after the header of the largest level
+const DECODE_HEADER: &[u8] = b"BZh9";
 
 struct Block {
     file_range: Range<usize>,
@@ -36,6 +36,11 @@ pub struct Bz2FileBuffer {
     decoded: Vec<u8>,
     blocks: VecDeque<Block>,
     magic_re: Regex,
+    // libbzip2 always checks the crc of the blocks, the mismatches are
+    // reported when verifying
+    verify_checksums: bool,
+    checksum_errors: ChecksumErrors,
+    offsets: DecodedOffsets,
 }
 
 impl fmt::Debug for Bz2FileBuffer {
@@ -56,15 +61,15 @@ impl Bz2FileBuffer {
         file.read_exact(header.as_mut_slice()).await.unwrap();
         return Ok(Self {
             file,
+            offsets: DecodedOffsets::new(header.len() as u64),
             header,
             decoded: Vec::new(),
             blocks: VecDeque::new(),
             magic_re,
+            verify_checksums: false,
+            checksum_errors: ChecksumErrors::default(),
         });
     }
-    pub fn is_valid(&self) -> bool {
-        return Regex::new("BZ[h0][1-9]").unwrap().is_match(&self.header);
-    }
     fn mmap(&self) -> io::Result<Mmap> {
         let mmap = unsafe { MmapOptions::new().map(&self.file) }?;
         mmap.advise(Advice::Sequential)?;
@@ -85,7 +90,11 @@ impl Bz2FileBuffer {
         let mmap = self.mmap()?;
 
         let mut in_data = &mmap[block.file_range.clone()];
-        decoder.decompress(self.header.as_slice(), &mut block.data)?;
+        let _span = debug_span!("decode", format = "bz2", input = in_data.len()).entered();
+        let invalid_data = |e| io::Error::new(ErrorKind::InvalidData, e);
+        decoder
+            .decompress(DECODE_HEADER, &mut block.data)
+            .map_err(invalid_data)?;
 
         info!("decoding {}", human_bytes(in_data.len() as f64));
         loop {
@@ -94,19 +103,56 @@ impl Bz2FileBuffer {
             if block.data.capacity() - block.data.len() < ALLOC_SIZE {
                 block.data.reserve(ALLOC_SIZE);
             }
-            decoder.decompress_vec(in_data, &mut block.data)?;
+            let status = decoder
+                .decompress_vec(in_data, &mut block.data)
+                .map_err(invalid_data)?;
             let consumed = decoder.total_in() - before_in;
             let produced = decoder.total_out() - before_out;
 
             in_data = &in_data[consumed as usize..];
-            if produced == 0 && consumed == 0 {
-                return Ok(block);
+            // files written by pbzip2 or concatenated are several streams, the
+            // rest of the range is the footer and the header of the next one
+            if status == Status::StreamEnd || (produced == 0 && consumed == 0) {
+                break;
             }
         }
+        if block.data.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "truncated block"));
+        }
+        return Ok(block);
+    }
+    // a block that fails to decode is replaced by a placeholder, so that the
+    // rest of a damaged file stays readable
+    fn decode_or_skip(&mut self, file_range: Range<usize>) -> io::Result<Block> {
+        let block = match self.decode_block(file_range.clone()) {
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                warn!("corrupted block {:?}: {}", file_range, e);
+                if self.verify_checksums {
+                    self.checksum_errors.push(
+                        file_range.start,
+                        format!(
+                            "bz2 block at byte {} failed its check: {}",
+                            file_range.start, e
+                        ),
+                    );
+                }
+                Block {
+                    data: corrupted_block(file_range.len()),
+                    file_range,
+                }
+            }
+            res => res?,
+        };
+        self.offsets.push(
+            block.file_range.start as u64..block.file_range.end as u64,
+            block.data.len(),
+        );
+        return Ok(block);
     }
     fn find_block_from(&self, byte: usize) -> io::Result<usize> {
         debug!("searching next block from {}", byte);
         let mmap = self.mmap()?;
+        let byte = min(byte, mmap.len());
         if let Some(m) = self.magic_re.find(&mmap[byte..]) {
             debug!("found at {}", byte + m.range().start);
             return Ok(byte + m.range().start);
@@ -210,25 +256,10 @@ impl FileBuffer for Bz2FileBuffer {
         };
     }
     fn jump(&mut self, byte: u64) -> io::Result<u64> {
-        let mut breaker = InfiniteLoopBreaker::new(MAX_INVALID_BLOCKS);
-
-        let mut start = byte as usize;
-        let mut end = byte as usize;
-
-        let block = loop {
-            end = self.find_block_from(end)?;
-            start = self.rfind_block_from(start)?;
-
-            let block_range = Range { start, end };
-            match self.decode_block(block_range) {
-                Ok(block) => break block,
-                Err(err) => {
-                    if let Err(_) = breaker.it() {
-                        return Err(err);
-                    }
-                }
-            }
-        };
+        let start = self.rfind_block_from(byte as usize)?;
+        // before the first block, the range would be empty
+        let end = self.find_block_from(max(byte as usize, start + 1))?;
+        let block = self.decode_or_skip(Range { start, end })?;
 
         info!("jump to {:?} (requested {})", block.file_range, byte);
         self.blocks.clear();
@@ -236,36 +267,21 @@ impl FileBuffer for Bz2FileBuffer {
         self.rebuild_data();
         return Ok(self.blocks[0].file_range.start as u64);
     }
-    async fn total_size(&self) -> u64 {
-        return self.file.metadata().await.unwrap().len();
+    async fn total_size(&self) -> io::Result<u64> {
+        return Ok(self.file.metadata().await?.len());
     }
     async fn load_next(&mut self) -> io::Result<usize> {
         debug!("load next");
         yield_now().await;
 
-        let mut breaker = InfiniteLoopBreaker::new(MAX_INVALID_BLOCKS);
         let size_before = self.data().len();
 
         let start = self.range().end as usize;
-        let mut end = start + 1;
-
-        let block = loop {
-            end = self.find_block_from(end)?;
-            if end <= start {
-                return Ok(0);
-            }
-
-            let block_range = Range { start, end };
-            match self.decode_block(block_range) {
-                Ok(block) => break block,
-                Err(err) => {
-                    info!("error decoding block: {}", err);
-                    if let Err(_) = breaker.it() {
-                        return Err(err);
-                    }
-                }
-            }
-        };
+        let end = self.find_block_from(start + 1)?;
+        if end <= start {
+            return Ok(0);
+        }
+        let block = self.decode_or_skip(Range { start, end })?;
 
         self.decoded.extend(block.data.iter());
         self.blocks.push_back(block);
@@ -275,34 +291,37 @@ impl FileBuffer for Bz2FileBuffer {
         debug!("load previous");
         yield_now().await;
 
-        let mut breaker = InfiniteLoopBreaker::new(MAX_INVALID_BLOCKS);
         let size_before = self.data().len();
 
         let end = self.range().start as usize;
-        let mut start = end;
-
-        let block = loop {
-            start = self.rfind_block_from(start)?;
-            if start >= end {
-                return Ok(0);
-            }
-
-            let block_range = Range { start, end };
-            match self.decode_block(block_range) {
-                Ok(block) => break block,
-                Err(err) => {
-                    if let Err(_) = breaker.it() {
-                        return Err(err);
-                    }
-                }
-            }
-        };
+        let start = self.rfind_block_from(end)?;
+        if start >= end {
+            return Ok(0);
+        }
+        let block = self.decode_or_skip(Range { start, end })?;
 
         self.decoded.extend(block.data.iter());
         self.decoded.rotate_right(block.data.len());
         self.blocks.push_front(block);
         return Ok(self.data().len() - size_before);
     }
+    fn is_compressed(&self) -> bool {
+        return true;
+    }
+    async fn decoded_offset(&self, offset: u64) -> u64 {
+        return self.offsets.get(offset, self.range(), self.decoded.len());
+    }
+    fn shrink_front(&mut self, min_size: usize) -> usize {
+        // always keep the last block, the range would be lost otherwise
+        let last_block_size = self.blocks.back().map(|x| x.data.len()).unwrap_or(0);
+        return self.shrink_from_front(max(min_size, last_block_size));
+    }
+    fn set_verify_checksums(&mut self, verify: bool) {
+        self.verify_checksums = verify;
+    }
+    fn take_checksum_errors(&mut self) -> Vec<String> {
+        return self.checksum_errors.take();
+    }
     async fn seek_from(
         &mut self,
         re: &Regex,
@@ -375,3 +394,102 @@ impl FileBuffer for Bz2FileBuffer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file_buffer::format::{sniff, Format},
+        testing::{load_backward, load_forward, text, TestDir},
+    };
+    use ::bzip2::{write::BzEncoder, Compression};
+    use std::io::Write;
+
+    // pbzip2 compresses chunks of the input in parallel, and writes them as
+    // concatenated streams
+    fn pbzip2(data: &[u8], chunk_size: usize, levels: &[u32]) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        for (idx, chunk) in data.chunks(chunk_size).enumerate() {
+            let level = levels[idx % levels.len()];
+            let mut encoder = BzEncoder::new(Vec::new(), Compression::new(level));
+            encoder.write_all(chunk).unwrap();
+            compressed.extend(encoder.finish().unwrap());
+        }
+        return compressed;
+    }
+
+    fn position(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        return haystack.windows(needle.len()).position(|x| x == needle);
+    }
+
+    async fn open(name: &str, data: &[u8]) -> Bz2FileBuffer {
+        let dir = TestDir::new(name);
+        let path = dir.write("data.bz2", data);
+        assert_eq!(sniff(&path).unwrap(), Format::Bzip2);
+        // the file stays readable through the open handle
+        return Bz2FileBuffer::new(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn multi_stream_forward() {
+        let data = text(50000);
+        let mut buffer = open("forward.bz2", &pbzip2(&data, 150000, &[1, 9, 5])).await;
+        assert!(load_forward(&mut buffer).await == data);
+    }
+
+    #[tokio::test]
+    async fn multi_stream_backward() {
+        let data = text(50000);
+        let mut buffer = open("backward.bz2", &pbzip2(&data, 150000, &[9, 1])).await;
+        assert!(load_backward(&mut buffer).await == data);
+    }
+
+    #[tokio::test]
+    async fn corrupted_block_skipped() {
+        let data = text(50000);
+        let mut compressed = pbzip2(&data, 100000, &[1]);
+        let middle = compressed.len() / 2;
+        compressed[middle..middle + 200].fill(0);
+        let mut buffer = open("corrupted.bz2", &compressed).await;
+        buffer.jump(0).unwrap();
+        while buffer.load_next().await.unwrap() > 0 {}
+        let decoded = String::from_utf8_lossy(buffer.data());
+        assert!(decoded.contains("<<corrupted block: "));
+        // the blocks after it are still read
+        assert!(decoded.ends_with("line 49999 hello world\n"));
+    }
+
+    #[tokio::test]
+    async fn multi_stream_jump() {
+        let data = text(50000);
+        let compressed = pbzip2(&data, 100000, &[1, 2]);
+        let mut buffer = open("jump.bz2", &compressed).await;
+        for percent in [0, 10, 50, 90, 100] {
+            let offset = compressed.len() as u64 * percent / 100;
+            let start = buffer.jump(offset).unwrap();
+            // the first block follows the header of the file
+            assert!(start <= offset.max(4));
+            let decoded = buffer.data();
+            assert!(!decoded.is_empty());
+            // each stream starts a new chunk of the input
+            let idx = position(&data, &decoded[..64]).unwrap();
+            assert_eq!(idx % 100000, 0);
+            assert!(&data[idx..idx + decoded.len()] == decoded);
+        }
+    }
+
+    #[tokio::test]
+    async fn decoded_offsets() {
+        let data = text(50000);
+        let compressed = pbzip2(&data, 50000, &[1]);
+        let size = compressed.len() as u64;
+        let mut buffer = open("offsets.bz2", &compressed).await;
+        // estimated from the ratio of the last block
+        buffer.jump(size).unwrap();
+        let estimate = buffer.decoded_offset(size).await as f64 / data.len() as f64;
+        assert!(estimate > 0.5 && estimate < 2.0, "{}", estimate);
+        load_forward(&mut buffer).await;
+        let end = buffer.range().end;
+        assert_eq!(buffer.decoded_offset(end).await, data.len() as u64);
+    }
+}
@@ -0,0 +1,379 @@
+use crate::{
+    errors::Result,
+    file_buffer::{make_single_file_buffer, FileBuffer},
+};
+use async_trait::async_trait;
+use regex::bytes::Regex;
+use std::{
+    cmp::{max, min},
+    fmt,
+    io::{self, ErrorKind},
+    ops::Range,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tracing::info;
+
+const FIND_OVERLAP: usize = 0x1000;
+
+// several buffers seen as one file, the offsets of a buffer start after the
+// size of the previous ones. The loaded data is a copy of the data of the
+// buffers from `first` to `last`: `first` ends at the end of its file when it
+// is not `last`, `last` starts at the start of its file when it is not `first`,
+// and the buffers between them are fully loaded.
+pub struct ChainFileBuffer {
+    paths: Vec<String>,
+    buffers: Vec<Box<dyn FileBuffer>>,
+    // offset of each buffer in the chain
+    starts: Vec<u64>,
+    first: usize,
+    last: usize,
+    data: Vec<u8>,
+}
+
+impl fmt::Debug for ChainFileBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ChainFileBuffer")
+            .field("paths", &self.paths)
+            .field("starts", &self.starts)
+            .field("first", &self.first)
+            .field("last", &self.last)
+            .field("data.len", &self.data.len())
+            .finish()
+    }
+}
+
+impl ChainFileBuffer {
+    pub async fn new(paths: Vec<String>) -> Result<Self> {
+        if paths.is_empty() {
+            return Err(io::Error::new(ErrorKind::NotFound, "no file to chain").into());
+        }
+        let mut buffers = Vec::new();
+        let mut starts = Vec::new();
+        let mut start = 0;
+        for path in paths.iter() {
+            let buffer = make_single_file_buffer(path).await?;
+            starts.push(start);
+            start += buffer.total_size().await?;
+            buffers.push(buffer);
+        }
+        info!("chaining {:?}, starts: {:?}", paths, starts);
+        return Ok(Self {
+            paths,
+            buffers,
+            starts,
+            first: 0,
+            last: 0,
+            data: Vec::new(),
+        });
+    }
+
+    // index of the buffer containing an offset of the chain
+    fn buffer_at(&self, bytes: u64) -> usize {
+        return self
+            .starts
+            .iter()
+            .rposition(|start| *start <= bytes)
+            .unwrap_or(0);
+    }
+
+    // make a single buffer the loaded data
+    fn select(&mut self, index: usize) {
+        self.first = index;
+        self.last = index;
+        self.data = self.buffers[index].data().to_vec();
+    }
+}
+
+// move a buffer to the start or the end of its file, with some data loaded
+async fn rewind(buffer: &mut Box<dyn FileBuffer>, to_end: bool) -> io::Result<()> {
+    if to_end {
+        buffer.jump(buffer.total_size().await?)?;
+        if buffer.data().is_empty() {
+            buffer.load_prev().await?;
+        }
+    } else {
+        buffer.jump(0)?;
+        if buffer.data().is_empty() {
+            buffer.load_next().await?;
+        }
+    }
+    return Ok(());
+}
+
+#[async_trait]
+impl FileBuffer for ChainFileBuffer {
+    fn data(&self) -> &[u8] {
+        return self.data.as_slice();
+    }
+    fn range(&self) -> Range<u64> {
+        return Range {
+            start: self.starts[self.first] + self.buffers[self.first].range().start,
+            end: self.starts[self.last] + self.buffers[self.last].range().end,
+        };
+    }
+    fn jump(&mut self, bytes: u64) -> io::Result<u64> {
+        let index = self.buffer_at(bytes);
+        let actual = self.buffers[index].jump(bytes - self.starts[index])?;
+        self.select(index);
+        return Ok(self.starts[index] + actual);
+    }
+    async fn total_size(&self) -> io::Result<u64> {
+        return Ok(self.starts.last().unwrap() + self.buffers.last().unwrap().total_size().await?);
+    }
+    fn is_compressed(&self) -> bool {
+        return self.buffers.iter().any(|x| x.is_compressed());
+    }
+    async fn decoded_offset(&self, offset: u64) -> u64 {
+        let index = self.buffer_at(offset);
+        let mut decoded = 0;
+        for (i, buffer) in self.buffers[..index].iter().enumerate() {
+            decoded += buffer
+                .decoded_offset(self.starts[i + 1] - self.starts[i])
+                .await;
+        }
+        return decoded
+            + self.buffers[index]
+                .decoded_offset(offset - self.starts[index])
+                .await;
+    }
+    async fn load_prev(&mut self) -> io::Result<usize> {
+        let mut loaded = self.buffers[self.first].load_prev().await?;
+        if loaded == 0 && self.first > 0 {
+            // continue at the end of the previous file
+            self.first -= 1;
+            rewind(&mut self.buffers[self.first], true).await?;
+            loaded = self.buffers[self.first].data().len();
+        }
+        let data = self.buffers[self.first].data();
+        self.data.splice(0..0, data[..loaded].iter().cloned());
+        return Ok(loaded);
+    }
+    async fn load_next(&mut self) -> io::Result<usize> {
+        let mut loaded = self.buffers[self.last].load_next().await?;
+        if loaded == 0 && self.last + 1 < self.buffers.len() {
+            // continue at the start of the next file
+            self.last += 1;
+            rewind(&mut self.buffers[self.last], false).await?;
+            loaded = self.buffers[self.last].data().len();
+        }
+        let data = self.buffers[self.last].data();
+        self.data.extend_from_slice(&data[data.len() - loaded..]);
+        return Ok(loaded);
+    }
+    fn shrink_front(&mut self, min_size: usize) -> usize {
+        let mut extra_space = self.data.len().saturating_sub(min_size);
+        let mut dropped = 0;
+        while self.first < self.last && extra_space >= self.buffers[self.first].data().len() {
+            let size = self.buffers[self.first].data().len();
+            extra_space -= size;
+            dropped += size;
+            self.first += 1;
+        }
+        let first_size = self.buffers[self.first].data().len();
+        dropped += self.buffers[self.first].shrink_front(first_size - min(extra_space, first_size));
+        self.data.drain(..dropped);
+        return dropped;
+    }
+    async fn seek_from(
+        &mut self,
+        re: &Regex,
+        offset: u64,
+        cancelled: &AtomicBool,
+    ) -> io::Result<Option<Range<u64>>> {
+        let offset = min(offset as usize, self.data.len());
+        if let Some(m) = re.find(&self.data[offset..]) {
+            return Ok(Some(Range {
+                start: (offset + m.start()) as u64,
+                end: (offset + m.end()) as u64,
+            }));
+        }
+
+        // continue in each buffer, from the end of the loaded data
+        let mut index = self.last;
+        let last_size = self.buffers[index].data().len();
+        let last_start = self.data.len() - last_size;
+        let mut buffer_offset = max(
+            last_size.saturating_sub(FIND_OVERLAP),
+            offset.saturating_sub(last_start),
+        );
+        loop {
+            let buffer = &mut self.buffers[index];
+            match buffer.seek_from(re, buffer_offset as u64, cancelled).await {
+                Ok(Some(m)) => {
+                    self.select(index);
+                    return Ok(Some(m));
+                }
+                // stay where the search stopped
+                Err(e) if e.kind() == ErrorKind::Interrupted => {
+                    self.select(index);
+                    return Err(e);
+                }
+                res => res?,
+            };
+            if cancelled.load(Ordering::Acquire) {
+                self.select(index);
+                return Err(io::Error::from(ErrorKind::Interrupted));
+            }
+            if index + 1 == self.buffers.len() {
+                return Ok(None);
+            }
+            index += 1;
+            rewind(&mut self.buffers[index], false).await?;
+            buffer_offset = 0;
+        }
+    }
+    async fn rseek_from(
+        &mut self,
+        re: &Regex,
+        offset: u64,
+        cancelled: &AtomicBool,
+    ) -> io::Result<Option<Range<u64>>> {
+        let offset = min(offset as usize, self.data.len());
+        if let Some(m) = re.find_iter(&self.data[..offset]).last() {
+            return Ok(Some(Range {
+                start: m.start() as u64,
+                end: m.end() as u64,
+            }));
+        }
+
+        // continue in each buffer, from the start of the loaded data
+        let mut index = self.first;
+        let mut buffer_offset = min(offset, self.buffers[index].data().len());
+        loop {
+            let buffer = &mut self.buffers[index];
+            match buffer.rseek_from(re, buffer_offset as u64, cancelled).await {
+                Ok(Some(m)) => {
+                    self.select(index);
+                    return Ok(Some(m));
+                }
+                // stay where the search stopped
+                Err(e) if e.kind() == ErrorKind::Interrupted => {
+                    self.select(index);
+                    return Err(e);
+                }
+                res => res?,
+            };
+            if cancelled.load(Ordering::Acquire) {
+                self.select(index);
+                return Err(io::Error::from(ErrorKind::Interrupted));
+            }
+            if index == 0 {
+                return Ok(None);
+            }
+            index -= 1;
+            rewind(&mut self.buffers[index], true).await?;
+            buffer_offset = self.buffers[index].data().len();
+        }
+    }
+    fn source_at(&self, offset: usize) -> Option<&str> {
+        let mut end = 0;
+        for index in self.first..self.last {
+            end += self.buffers[index].data().len();
+            if offset < end {
+                return Some(&self.paths[index]);
+            }
+        }
+        return Some(&self.paths[self.last]);
+    }
+    fn set_verify_checksums(&mut self, verify: bool) {
+        for buffer in self.buffers.iter_mut() {
+            buffer.set_verify_checksums(verify);
+        }
+    }
+    fn set_no_cache(&mut self, no_cache: bool) {
+        for buffer in self.buffers.iter_mut() {
+            buffer.set_no_cache(no_cache);
+        }
+    }
+    fn take_checksum_errors(&mut self) -> Vec<String> {
+        return self
+            .buffers
+            .iter_mut()
+            .flat_map(|x| x.take_checksum_errors())
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{load_backward, load_forward, text, TestDir};
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    fn others(count: usize) -> Vec<u8> {
+        return (0..count)
+            .map(|n| format!("other {}\n", n))
+            .collect::<String>()
+            .into_bytes();
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = TestDir::new("chain");
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&others(50000)).unwrap();
+        let paths = vec![
+            dir.write("first", text(20000)),
+            dir.write("second.z", encoder.finish().unwrap()),
+            dir.write("third", text(10)),
+        ];
+        let data = [text(20000), others(50000), text(10)].concat();
+        let mut buffer = ChainFileBuffer::new(paths).await.unwrap();
+        assert!(load_forward(&mut buffer).await == data);
+        assert!(load_backward(&mut buffer).await == data);
+    }
+
+    #[tokio::test]
+    async fn seek_across_files() {
+        let dir = TestDir::new("chain-seek");
+        let paths = vec![
+            dir.write("first", text(20000)),
+            dir.write("second", others(20000)),
+        ];
+        let data = [text(20000), others(20000)].concat();
+        let mut buffer = ChainFileBuffer::new(paths.clone()).await.unwrap();
+        let cancelled = AtomicBool::new(false);
+
+        buffer.jump(0).unwrap();
+        buffer.load_next().await.unwrap();
+        let re = Regex::new("other 12345\n").unwrap();
+        let m = buffer.seek_from(&re, 0, &cancelled).await.unwrap().unwrap();
+        let start = buffer.range().start + m.start;
+        assert_eq!(&data[start as usize..][..12], b"other 12345\n");
+        assert_eq!(buffer.source_at(m.start as usize), Some(paths[1].as_str()));
+
+        let re = Regex::new("line 123 hello").unwrap();
+        let end = buffer.data().len() as u64;
+        let m = buffer
+            .rseek_from(&re, end, &cancelled)
+            .await
+            .unwrap()
+            .unwrap();
+        let start = buffer.range().start + m.start;
+        assert_eq!(&data[start as usize..][..14], b"line 123 hello");
+        assert_eq!(buffer.source_at(m.start as usize), Some(paths[0].as_str()));
+    }
+
+    #[tokio::test]
+    async fn load_across_files() {
+        let dir = TestDir::new("chain-load");
+        let paths = vec![
+            dir.write("first", text(10)),
+            dir.write("second", others(10)),
+        ];
+        let mut buffer = ChainFileBuffer::new(paths.clone()).await.unwrap();
+        let first = text(10).len();
+
+        buffer.jump(0).unwrap();
+        while buffer.load_next().await.unwrap() > 0 {}
+        assert_eq!(buffer.range(), 0..(first + others(10).len()) as u64);
+        assert_eq!(buffer.source_at(0), Some(paths[0].as_str()));
+        assert_eq!(buffer.source_at(first), Some(paths[1].as_str()));
+
+        // the first file is dropped as a whole
+        assert_eq!(buffer.shrink_front(others(10).len()), first);
+        assert_eq!(buffer.data(), others(10).as_slice());
+        assert_eq!(buffer.range().start, first as u64);
+    }
+}
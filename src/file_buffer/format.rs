@@ -0,0 +1,232 @@
+use super::{brotli, zlib};
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{self, ErrorKind, Read},
+    sync::OnceLock,
+};
+
+// bytes read to recognize the format, tar headers are the longest
+const SNIFF_SIZE: u64 = 512;
+const TAR_MAGIC_OFFSET: usize = 257;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Plain,
+    Bzip2,
+    Gzip,
+    Zlib,
+    Deflate,
+    Snappy,
+    Brotli,
+    Utf16Le,
+    Utf16Be,
+    // recognized, but not readable
+    Zstd,
+    Xz,
+    Lz4,
+    Zip,
+    // read as plain text, the headers of the files show as garbage
+    Tar,
+}
+
+// formats --format accepts, those bless can read
+const FORCEABLE: [Format; 9] = [
+    Format::Plain,
+    Format::Bzip2,
+    Format::Gzip,
+    Format::Zlib,
+    Format::Deflate,
+    Format::Snappy,
+    Format::Brotli,
+    Format::Utf16Le,
+    Format::Utf16Be,
+];
+
+// magic numbers at the start of the files, the first match wins
+const MAGICS: [(&[u8], Format); 10] = [
+    (b"\x1f\x8b", Format::Gzip),
+    (b"\x28\xb5\x2f\xfd", Format::Zstd),
+    (b"\xfd7zXZ\x00", Format::Xz),
+    (b"\x04\x22\x4d\x18", Format::Lz4),
+    (b"PK\x03\x04", Format::Zip),
+    // empty archive
+    (b"PK\x05\x06", Format::Zip),
+    (b"\xff\x06\x00\x00sNaPpY", Format::Snappy),
+    (b"\xff\xfe", Format::Utf16Le),
+    (b"\xfe\xff", Format::Utf16Be),
+    (b"ustar", Format::Tar),
+];
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Self::Plain => "plain",
+            Self::Bzip2 => "bz2",
+            Self::Gzip => "gzip",
+            Self::Zlib => "zlib",
+            Self::Deflate => "deflate",
+            Self::Snappy => "snappy",
+            Self::Brotli => "brotli",
+            Self::Utf16Le => "utf16le",
+            Self::Utf16Be => "utf16be",
+            Self::Zstd => "zstd",
+            Self::Xz => "xz",
+            Self::Lz4 => "lz4",
+            Self::Zip => "zip",
+            Self::Tar => "tar",
+        };
+    }
+
+    // the formats --format accepts
+    pub fn from_name(name: &str) -> Option<Self> {
+        return FORCEABLE.iter().copied().find(|x| x.name() == name);
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        return FORCEABLE.iter().map(|x| x.name()).collect();
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// set by --format, every file is read with it
+static FORCED_FORMAT: OnceLock<Format> = OnceLock::new();
+
+pub fn force_format(format: Format) {
+    FORCED_FORMAT.set(format).ok();
+}
+
+pub fn unsupported(format: Format) -> io::Error {
+    return io::Error::new(
+        ErrorKind::Unsupported,
+        format!(
+            "{} files are not supported, use --format to read the file anyway",
+            format
+        ),
+    );
+}
+
+fn bzip2_header(data: &[u8]) -> bool {
+    return data.len() >= 4
+        && &data[..2] == b"BZ"
+        && (data[2] == b'h' || data[2] == b'0')
+        && (b'1'..=b'9').contains(&data[3]);
+}
+
+// utf-16 without byte order mark, most of the text is ascii so one byte
+// of each pair is 0
+fn utf16_without_bom(data: &[u8]) -> Option<Format> {
+    let pairs = data.len() / 2;
+    if pairs < 8 {
+        return None;
+    }
+    let zeros = |parity: usize| {
+        data[..pairs * 2]
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|&&x| x == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd * 10 >= pairs * 9 && even * 10 <= pairs {
+        return Some(Format::Utf16Le);
+    }
+    if even * 10 >= pairs * 9 && odd * 10 <= pairs {
+        return Some(Format::Utf16Be);
+    }
+    return None;
+}
+
+// the format of a file from its content, the extension is only used for
+// brotli which has no magic number
+pub fn sniff(path: &str) -> io::Result<Format> {
+    if let Some(format) = FORCED_FORMAT.get() {
+        return Ok(*format);
+    }
+    let mut data = Vec::new();
+    File::open(path)?.take(SNIFF_SIZE).read_to_end(&mut data)?;
+    if bzip2_header(&data) {
+        return Ok(Format::Bzip2);
+    }
+    for (magic, format) in MAGICS {
+        let offset = match format {
+            Format::Tar => TAR_MAGIC_OFFSET,
+            _ => 0,
+        };
+        if data.get(offset..offset + magic.len()) == Some(magic) {
+            return Ok(format);
+        }
+    }
+    if let Some(format) = utf16_without_bom(&data) {
+        return Ok(format);
+    }
+    match zlib::detect(path)? {
+        Some(zlib::ZlibFormat::Zlib) => return Ok(Format::Zlib),
+        Some(zlib::ZlibFormat::RawDeflate) => return Ok(Format::Deflate),
+        None => (),
+    }
+    if brotli::detect(path)? {
+        return Ok(Format::Brotli);
+    }
+    return Ok(Format::Plain);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{text, TestDir};
+
+    fn sniff_data(dir: &TestDir, name: &str, data: &[u8]) -> Format {
+        return sniff(&dir.write(name, data)).unwrap();
+    }
+
+    #[test]
+    fn magic_numbers() {
+        let dir = TestDir::new("magics");
+        let padding = [0x55u8; 64];
+        for (magic, format) in MAGICS {
+            let data = match format {
+                Format::Tar => [&[0; TAR_MAGIC_OFFSET][..], magic, &padding].concat(),
+                _ => [magic, &padding[..]].concat(),
+            };
+            assert_eq!(sniff_data(&dir, format.name(), &data), format);
+        }
+        assert_eq!(sniff_data(&dir, "bz2", b"BZh91AY&SY"), Format::Bzip2);
+        // like a text file starting with BZ
+        assert_eq!(sniff_data(&dir, "bz", b"BZh hello\n"), Format::Plain);
+    }
+
+    #[test]
+    fn utf16_byte_order() {
+        let dir = TestDir::new("utf16-order");
+        let text = "hello world, bless\n".repeat(4);
+        let le: Vec<u8> = text.encode_utf16().flat_map(|x| x.to_le_bytes()).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(|x| x.to_be_bytes()).collect();
+        assert_eq!(sniff_data(&dir, "le", &le), Format::Utf16Le);
+        assert_eq!(sniff_data(&dir, "be", &be), Format::Utf16Be);
+        // too short to tell
+        assert_eq!(sniff_data(&dir, "short", &le[..8]), Format::Plain);
+    }
+
+    #[test]
+    fn plain_text() {
+        let dir = TestDir::new("plain");
+        assert_eq!(sniff_data(&dir, "log", &text(100)), Format::Plain);
+        assert_eq!(sniff_data(&dir, "empty", b""), Format::Plain);
+    }
+
+    #[test]
+    fn forced_names() {
+        assert_eq!(Format::from_name("gzip"), Some(Format::Gzip));
+        assert_eq!(Format::from_name("utf16be"), Some(Format::Utf16Be));
+        // recognized but not readable
+        assert_eq!(Format::from_name("zstd"), None);
+        assert_eq!(Format::from_name("tar"), None);
+    }
+}
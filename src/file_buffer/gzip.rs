@@ -0,0 +1,249 @@
+use super::{
+    stream::StreamDecoder,
+    zlib::{ZlibDecoder, ZlibFormat},
+};
+use flate2::Crc;
+use memmap2::MmapOptions;
+use std::{
+    cmp::min,
+    fs::File,
+    io::{self, ErrorKind, Read},
+};
+
+const MAGIC: [u8; 3] = [0x1f, 0x8b, 0x08];
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+// crc and size after the compressed data of a member
+const TRAILER_SIZE: usize = 8;
+// longer headers are garbage, names and comments are never that long
+const MAX_HEADER_SIZE: usize = 0x10000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GzipFormat {
+    Gzip,
+    // block-gzip, each member is a block whose size is in the header,
+    // with the offsets of the blocks
+    Bgzf(Vec<usize>),
+}
+
+struct Header {
+    size: usize,
+    // size of the member, in the "BC" extra field of bgzf files
+    block_size: Option<usize>,
+}
+
+pub fn invalid_header() -> io::Error {
+    return io::Error::new(ErrorKind::InvalidData, "invalid gzip header");
+}
+
+// skip a zero terminated field
+fn skip_string(data: &[u8], offset: usize) -> Option<usize> {
+    let end = data.get(offset..)?.iter().position(|&x| x == 0)?;
+    return Some(offset + end + 1);
+}
+
+// the header of a member, None until all of it is in the data
+fn parse_header(data: &[u8]) -> io::Result<Option<Header>> {
+    if data[..min(data.len(), MAGIC.len())] != MAGIC[..min(data.len(), MAGIC.len())] {
+        return Err(invalid_header());
+    }
+    if data.len() < 10 {
+        return Ok(None);
+    }
+    let flags = data[3];
+    let mut size = 10;
+    let mut block_size = None;
+    if flags & FLAG_EXTRA != 0 {
+        let extra_size = match data.get(size..size + 2) {
+            Some(x) => u16::from_le_bytes([x[0], x[1]]) as usize,
+            None => return Ok(None),
+        };
+        let mut extra = match data.get(size + 2..size + 2 + extra_size) {
+            Some(extra) => extra,
+            None => return Ok(None),
+        };
+        while extra.len() >= 4 {
+            let field_size = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+            if &extra[..2] == b"BC" && field_size == 2 && extra.len() >= 6 {
+                block_size = Some(u16::from_le_bytes([extra[4], extra[5]]) as usize + 1);
+            }
+            extra = &extra[min(extra.len(), 4 + field_size)..];
+        }
+        size += 2 + extra_size;
+    }
+    for flag in [FLAG_NAME, FLAG_COMMENT] {
+        if flags & flag != 0 {
+            size = match skip_string(data, size) {
+                Some(size) => size,
+                None => return Ok(None),
+            };
+        }
+    }
+    if flags & FLAG_HCRC != 0 {
+        size += 2;
+    }
+    if data.len() < size {
+        return Ok(None);
+    }
+    return Ok(Some(Header { size, block_size }));
+}
+
+enum State {
+    Header,
+    Data,
+    Trailer,
+}
+
+// gzip files made of one or more members
+pub struct GzipDecoder {
+    state: State,
+    // start of a header cut between two inputs
+    header: Vec<u8>,
+    data: ZlibDecoder,
+    // trailer cut between two inputs
+    trailer: Vec<u8>,
+    verify: bool,
+    // crc of the decoded data of the member, when verifying it
+    crc: Option<Crc>,
+    checksum_errors: Vec<String>,
+    done: bool,
+}
+
+impl Default for GzipDecoder {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl GzipDecoder {
+    pub fn new() -> Self {
+        return Self {
+            state: State::Header,
+            header: Vec::new(),
+            data: ZlibDecoder::new(ZlibFormat::RawDeflate),
+            trailer: Vec::new(),
+            verify: false,
+            crc: None,
+            checksum_errors: Vec::new(),
+            done: false,
+        };
+    }
+
+    // compare the crc and size of the member with those of its trailer
+    fn check_trailer(&mut self) {
+        let crc = match self.crc.take() {
+            Some(crc) => crc,
+            None => return,
+        };
+        let expected_crc = u32::from_le_bytes(self.trailer[..4].try_into().unwrap());
+        // size modulo 2^32
+        let expected_size = u32::from_le_bytes(self.trailer[4..].try_into().unwrap());
+        if crc.sum() != expected_crc {
+            self.checksum_errors.push(format!(
+                "crc32 {:08x} instead of {:08x}",
+                crc.sum(),
+                expected_crc
+            ));
+        } else if crc.amount() != expected_size {
+            self.checksum_errors.push(format!(
+                "{} bytes instead of {}",
+                crc.amount(),
+                expected_size
+            ));
+        }
+    }
+}
+
+impl StreamDecoder for GzipDecoder {
+    fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<(usize, usize)> {
+        if self.done || input.is_empty() {
+            return Ok((0, 0));
+        }
+        match self.state {
+            State::Header => {
+                let before = self.header.len();
+                self.header.extend_from_slice(input);
+                return match parse_header(&self.header) {
+                    Ok(Some(header)) => {
+                        self.header.clear();
+                        self.state = State::Data;
+                        self.data = ZlibDecoder::new(ZlibFormat::RawDeflate);
+                        // a member is verified from its start only
+                        self.crc = self.verify.then(Crc::new);
+                        Ok((header.size - before, 0))
+                    }
+                    Ok(None) if self.header.len() < MAX_HEADER_SIZE => Ok((input.len(), 0)),
+                    // garbage after the last member is ignored
+                    _ => {
+                        self.done = true;
+                        Ok((0, 0))
+                    }
+                };
+            }
+            State::Data => {
+                let (consumed, produced) = self.data.decode(input, output)?;
+                if let Some(crc) = self.crc.as_mut() {
+                    crc.update(&output[output.len() - produced..]);
+                }
+                if self.data.finished() {
+                    self.state = State::Trailer;
+                }
+                return Ok((consumed, produced));
+            }
+            State::Trailer => {
+                let size = min(TRAILER_SIZE - self.trailer.len(), input.len());
+                self.trailer.extend_from_slice(&input[..size]);
+                if self.trailer.len() == TRAILER_SIZE {
+                    self.check_trailer();
+                    self.trailer.clear();
+                    self.state = State::Header;
+                }
+                return Ok((size, 0));
+            }
+        }
+    }
+    fn set_verify_checksums(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+    fn take_checksum_errors(&mut self) -> Vec<String> {
+        return std::mem::take(&mut self.checksum_errors);
+    }
+}
+
+// offsets of the members of a bgzf file, found from the size of each block
+fn bgzf_blocks(path: &str) -> io::Result<Vec<usize>> {
+    let file = File::open(path)?;
+    let mmap = unsafe { MmapOptions::new().map(&file) }?;
+    let mut blocks = Vec::new();
+    let mut offset = 0;
+    while offset < mmap.len() {
+        match parse_header(&mmap[offset..]) {
+            Ok(Some(Header {
+                block_size: Some(block_size),
+                ..
+            })) => {
+                blocks.push(offset);
+                offset += block_size;
+            }
+            _ => break,
+        }
+    }
+    return Ok(blocks);
+}
+
+pub fn detect(path: &str) -> io::Result<Option<GzipFormat>> {
+    let mut data = Vec::new();
+    File::open(path)?
+        .take(MAX_HEADER_SIZE as u64)
+        .read_to_end(&mut data)?;
+    return match parse_header(&data) {
+        Ok(Some(Header {
+            block_size: Some(_),
+            ..
+        })) => Ok(Some(GzipFormat::Bgzf(bgzf_blocks(path)?))),
+        Ok(Some(_)) => Ok(Some(GzipFormat::Gzip)),
+        _ => Ok(None),
+    };
+}
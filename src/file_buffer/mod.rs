@@ -1,13 +1,31 @@
+pub mod brotli;
 pub mod bzip2;
+pub mod chain;
+pub mod format;
+pub mod gzip;
 pub mod raw;
+pub mod slow;
+pub mod snappy;
+pub mod stream;
+pub mod utf16;
+pub mod zlib;
 
 use crate::errors::Result;
 use async_trait::async_trait;
+use format::Format;
 use regex::bytes::Regex;
-use std::{fmt::Debug, io, ops::Range, sync::atomic::AtomicBool};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Debug,
+    fs, io,
+    ops::Range,
+    path::Path,
+    sync::atomic::AtomicBool,
+};
+use tracing::info;
 
 #[async_trait]
-pub trait FileBuffer: Debug {
+pub trait FileBuffer: Debug + Send + Sync {
     // slice to the file data
     fn data(&self) -> &[u8];
     // range of the data on file, the size may be different
@@ -17,11 +35,21 @@ pub trait FileBuffer: Debug {
     // the actual jump position may be diffent, and is returned
     fn jump(&mut self, bytes: u64) -> io::Result<u64>;
     // total size of the file
-    async fn total_size(&self) -> u64;
+    async fn total_size(&self) -> io::Result<u64>;
+    // true when the file size and offsets are those of compressed data
+    fn is_compressed(&self) -> bool;
+    // offset in the decoded data of an offset of the file, estimated for the
+    // parts of compressed files not decoded from their start yet
+    async fn decoded_offset(&self, offset: u64) -> u64 {
+        return offset;
+    }
     // load more data at the front
     async fn load_prev(&mut self) -> io::Result<usize>;
     // load more data at the back
     async fn load_next(&mut self) -> io::Result<usize>;
+    // drop data at the front, keeping at least min_size bytes
+    // returns the number of bytes dropped
+    fn shrink_front(&mut self, min_size: usize) -> usize;
     // find a pattern forward
     async fn seek_from(
         &mut self,
@@ -36,13 +64,210 @@ pub trait FileBuffer: Debug {
         offset: u64,
         cancelled: &AtomicBool,
     ) -> io::Result<Option<Range<u64>>>;
+    // file the data at an offset comes from, for buffers made of several files
+    fn source_at(&self, _offset: usize) -> Option<&str> {
+        return None;
+    }
+    // verify the checksums of the decoded data, for the compressed formats
+    // having some
+    fn set_verify_checksums(&mut self, _verify: bool) {}
+    // checksum mismatches found since the last call
+    fn take_checksum_errors(&mut self) -> Vec<String> {
+        return Vec::new();
+    }
+    // drop the data read and passed from the page cache, for one pass scans
+    // of huge files
+    fn set_no_cache(&mut self, _no_cache: bool) {}
 }
 
-pub async fn make_file_buffer(path: &str) -> Result<Box<dyn FileBuffer>> {
-    let bz = bzip2::Bz2FileBuffer::new(path).await?;
-    if bz.is_valid() {
-        return Ok(Box::from(bz));
+// decoded in place of a compressed block that fails to decode
+pub fn corrupted_block(skipped: usize) -> Vec<u8> {
+    return format!("<<corrupted block: {} bytes skipped>>\n", skipped).into_bytes();
+}
+
+// checksum mismatches of a buffer, each block is reported once even when
+// decoded again
+#[derive(Debug, Default)]
+pub struct ChecksumErrors {
+    reported: HashSet<usize>,
+    pending: Vec<String>,
+}
+
+impl ChecksumErrors {
+    pub fn push(&mut self, offset: usize, error: String) {
+        if self.reported.insert(offset) {
+            self.pending.push(error);
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<String> {
+        return std::mem::take(&mut self.pending);
+    }
+}
+
+// decoded offsets of the block boundaries of a compressed file reached by
+// decoding from its start, the offsets between or past them are estimated
+// from the compression ratio
+#[derive(Debug)]
+pub struct DecodedOffsets {
+    known: BTreeMap<u64, u64>,
+}
+
+impl DecodedOffsets {
+    // the decoded data starts at this offset, after the headers of the file
+    pub fn new(start: u64) -> Self {
+        return Self {
+            known: BTreeMap::from([(start, 0)]),
+        };
+    }
+
+    // a block of the file decoded to `size` bytes
+    pub fn push(&mut self, file_range: Range<u64>, size: usize) {
+        if let Some(&start) = self.known.get(&file_range.start) {
+            self.known.insert(file_range.end, start + size as u64);
+        }
+    }
+
+    // the ratio of the loaded data is used until something is decoded from
+    // the start of the file
+    pub fn get(&self, offset: u64, loaded: Range<u64>, loaded_size: usize) -> u64 {
+        let (&from, &decoded) = match self.known.range(..=offset).next_back() {
+            Some(known) => known,
+            None => return 0,
+        };
+        if from == offset {
+            return decoded;
+        }
+        let (&first, _) = self.known.first_key_value().unwrap();
+        let (&last, &last_decoded) = self.known.last_key_value().unwrap();
+        let ratio = if last > first {
+            last_decoded as f64 / (last - first) as f64
+        } else if !loaded.is_empty() {
+            loaded_size as f64 / (loaded.end - loaded.start) as f64
+        } else {
+            1.0
+        };
+        return decoded + ((offset - from) as f64 * ratio) as u64;
     }
+}
+
+// separator of the files of a concatenation, they are read as one
+pub const CAT_SEPARATOR: &str = " + ";
+
+pub fn cat_path(paths: &[String]) -> String {
+    return paths.join(CAT_SEPARATOR);
+}
+
+// true if the path is a concatenation of several files rather than an existing file
+pub fn is_cat(path: &str) -> bool {
+    return !Path::new(path).exists() && path.contains(CAT_SEPARATOR);
+}
+
+// true if the path is a glob pattern rather than an existing file
+pub fn is_pattern(path: &str) -> bool {
+    return !Path::new(path).exists() && path.contains(['*', '?', '[']);
+}
+
+// files matching a pattern, the least recently modified first
+pub fn expand_pattern(pattern: &str) -> Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for entry in glob::glob(pattern)? {
+        let path = entry?;
+        if path.is_file() {
+            paths.push((fs::metadata(&path)?.modified()?, path));
+        }
+    }
+    // rotated files are usually numbered from the most recent
+    paths.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    return Ok(paths
+        .into_iter()
+        .map(|(_, path)| path.to_string_lossy().into_owned())
+        .collect());
+}
+
+// canonical path of a file, or the pattern and its number of files
+pub fn real_path(path: &str) -> Result<String> {
+    if is_cat(path) {
+        return Ok(path.to_owned());
+    }
+    if is_pattern(path) {
+        return Ok(format!("{} ({} files)", path, expand_pattern(path)?.len()));
+    }
+    return Ok(fs::canonicalize(path)?.to_string_lossy().to_string());
+}
+
+pub async fn make_file_buffer(path: &str) -> Result<Box<dyn FileBuffer>> {
+    let buffer: Box<dyn FileBuffer> = if is_cat(path) {
+        let paths = path.split(CAT_SEPARATOR).map(|x| x.to_owned()).collect();
+        Box::from(chain::ChainFileBuffer::new(paths).await?)
+    } else if is_pattern(path) {
+        let paths = expand_pattern(path)?;
+        Box::from(chain::ChainFileBuffer::new(paths).await?)
+    } else {
+        make_single_file_buffer(path).await?
+    };
+    return match slow::SlowIo::from_env() {
+        Some(slow_io) => Ok(Box::from(slow::SlowFileBuffer::new(buffer, slow_io))),
+        None => Ok(buffer),
+    };
+}
 
-    return Ok(Box::from(raw::RawFileBuffer::new(path).await?));
+async fn make_single_file_buffer(path: &str) -> Result<Box<dyn FileBuffer>> {
+    let format = format::sniff(path)?;
+    info!("{} format: {}", path, format);
+    return Ok(match format {
+        Format::Plain | Format::Tar => Box::from(raw::RawFileBuffer::new(path).await?),
+        Format::Bzip2 => Box::from(bzip2::Bz2FileBuffer::new(path).await?),
+        Format::Gzip => {
+            let new_decoder: stream::NewDecoder = Box::new(|| Box::new(gzip::GzipDecoder::new()));
+            match gzip::detect(path)? {
+                Some(gzip::GzipFormat::Gzip) => {
+                    Box::from(stream::StreamFileBuffer::new(path, "gzip", new_decoder).await?)
+                }
+                Some(gzip::GzipFormat::Bgzf(blocks)) => Box::from(
+                    stream::StreamFileBuffer::new(path, "bgzf", new_decoder)
+                        .await?
+                        .with_restart_points(blocks),
+                ),
+                None => return Err(gzip::invalid_header().into()),
+            }
+        }
+        Format::Zlib | Format::Deflate => {
+            let zlib_format = match format {
+                Format::Zlib => zlib::ZlibFormat::Zlib,
+                _ => zlib::ZlibFormat::RawDeflate,
+            };
+            let new_decoder: stream::NewDecoder =
+                Box::new(move || Box::new(zlib::ZlibDecoder::new(zlib_format)));
+            Box::from(stream::StreamFileBuffer::new(path, format.name(), new_decoder).await?)
+        }
+        Format::Snappy => {
+            let new_decoder: stream::NewDecoder =
+                Box::new(|| Box::new(snappy::SnappyDecoder::new()));
+            Box::from(
+                stream::StreamFileBuffer::new(path, "snappy", new_decoder)
+                    .await?
+                    .with_restart_points(snappy::chunk_offsets(path)?),
+            )
+        }
+        Format::Brotli => {
+            let new_decoder: stream::NewDecoder =
+                Box::new(|| Box::new(brotli::BrotliDecoder::new()));
+            Box::from(stream::StreamFileBuffer::new(path, "brotli", new_decoder).await?)
+        }
+        Format::Utf16Le | Format::Utf16Be => {
+            let big_endian = format == Format::Utf16Be;
+            let new_decoder: stream::NewDecoder =
+                Box::new(move || Box::new(utf16::Utf16Decoder::new(big_endian)));
+            let size = fs::metadata(path)?.len() as usize;
+            Box::from(
+                stream::StreamFileBuffer::new(path, format.name(), new_decoder)
+                    .await?
+                    .with_restart_points(utf16::restart_points(size)),
+            )
+        }
+        Format::Zstd | Format::Xz | Format::Lz4 | Format::Zip => {
+            return Err(format::unsupported(format).into())
+        }
+    });
 }
@@ -4,56 +4,195 @@ use memmap2::{Advice, Mmap, MmapOptions};
 use regex::bytes::Regex;
 use std::{
     cmp::min,
+    fs::File,
     io::{self, ErrorKind},
     ops::Range,
+    os::unix::io::AsRawFd,
     sync::atomic::{AtomicBool, Ordering},
 };
-use tokio::{fs::File, task::yield_now};
+use tokio::task::yield_now;
 
 const BUFFER_SIZE: u64 = 0x10000;
 const FIND_WINDOW: u64 = 0x100000;
 const FIND_OVERLAP: u64 = 0x1000;
+// bytes mapped on each side of the range, only this window of the file is
+// mapped so huge files do not exhaust the address space
+const WINDOW_MARGIN: u64 = 0x2000000;
+// the window starts on a page, 64KB is a multiple of the usual page sizes
+const WINDOW_ALIGN: u64 = 0x10000;
+// bytes loaded after a jump before the view is considered scrolling, loading
+// the screen around the jump is still random access
+const SCROLL_THRESHOLD: u64 = 0x100000;
+
+// how the file is read, the kernel is advised to read ahead or not
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    // random reads after a jump, reading ahead would be wasted
+    Jump,
+    Scroll,
+    // one pass over the file, the next pages are needed soon
+    Search,
+}
 
 #[derive(Debug)]
 pub struct RawFileBuffer {
     range: Range<u64>,
     path: String,
     file: File,
+    // part of the file mapped, it always contains the range
+    window: Range<u64>,
     mmap: Mmap,
+    // size of the file when it was last checked
+    size: u64,
+    // access the window was advised for
+    access: Access,
+    // bytes loaded by scrolling since the last jump
+    scrolled: u64,
+    // drop what searches and scans passed from the page cache
+    no_cache: bool,
+}
+
+fn map_window(file: &File, window: &Range<u64>) -> io::Result<Mmap> {
+    let mmap = unsafe {
+        MmapOptions::new()
+            .offset(window.start)
+            .len((window.end - window.start) as usize)
+            .map(file)
+    }?;
+    return Ok(mmap);
 }
 
 impl RawFileBuffer {
     pub async fn new(path: &str) -> io::Result<Self> {
         let path = path.to_owned();
-        let file = File::open(&path).await?;
-        let mmap = unsafe { MmapOptions::new().map(&file) }?;
+        let file = File::open(&path)?;
+        let size = file.metadata()?.len();
+        let window = Range {
+            start: 0,
+            end: min(WINDOW_MARGIN, size),
+        };
+        let mmap = map_window(&file, &window)?;
         mmap.advise(Advice::Sequential)?;
         return Ok(Self {
             range: Range { start: 0, end: 0 },
             path,
             file,
+            window,
             mmap,
+            size,
+            access: Access::Scroll,
+            scrolled: 0,
+            no_cache: false,
         });
     }
 
-    fn remmap(&mut self) -> io::Result<()> {
-        self.mmap = unsafe { MmapOptions::new().map(&self.file) }?;
-        self.mmap.advise(Advice::Sequential)?;
+    fn advise(&mut self, access: Access) -> io::Result<()> {
+        match access {
+            Access::Jump => self.mmap.advise(Advice::Random)?,
+            Access::Scroll => self.mmap.advise(Advice::Sequential)?,
+            Access::Search => {
+                self.mmap.advise(Advice::Sequential)?;
+                self.mmap.advise(Advice::WillNeed)?;
+            }
+        }
+        self.access = access;
         return Ok(());
     }
 
-    async fn maybe_remmap(&mut self) -> io::Result<()> {
-        if self.total_size().await != self.mmap.len() as u64 {
-            self.remmap()?;
+    // the file grew or was truncated since it was last checked
+    fn update_size(&mut self) -> io::Result<u64> {
+        self.size = self.file.metadata()?.len();
+        if self.window.end > self.size {
+            self.range.end = min(self.range.end, self.size);
+            self.range.start = min(self.range.start, self.range.end);
+            self.window.end = self.size;
+            self.window.start = min(self.window.start, self.size & !(WINDOW_ALIGN - 1));
+            self.mmap = map_window(&self.file, &self.window)?;
+            self.advise(self.access)?;
+        }
+        return Ok(self.size);
+    }
+
+    // loading around a jump is random until the view scrolled further
+    fn scroll_access(&mut self) -> Access {
+        self.scrolled += BUFFER_SIZE;
+        if self.access == Access::Jump && self.scrolled < SCROLL_THRESHOLD {
+            return Access::Jump;
+        }
+        return Access::Scroll;
+    }
+
+    // move the window so it contains `range`, which must be in the file
+    fn map(&mut self, range: &Range<u64>, access: Access) -> io::Result<()> {
+        if self.window.start <= range.start && range.end <= self.window.end {
+            if access != self.access {
+                self.advise(access)?;
+            }
+            return Ok(());
+        }
+        let window = Range {
+            start: range.start.saturating_sub(WINDOW_MARGIN) & !(WINDOW_ALIGN - 1),
+            end: min(range.end + WINDOW_MARGIN, self.size),
+        };
+        let mmap = map_window(&self.file, &window)?;
+        // the pages of a window far from the new one are not needed anymore
+        if window.start >= self.window.end || window.end <= self.window.start {
+            self.mmap.advise(Advice::DontNeed)?;
+        }
+        self.mmap = mmap;
+        self.window = window;
+        return self.advise(access);
+    }
+
+    // drop the pages of `range` from the page cache, the kernel keeps the
+    // pages still mapped so they are unmapped first
+    fn drop_cache(&self, range: &Range<u64>) -> io::Result<()> {
+        if !self.no_cache {
+            return Ok(());
+        }
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        let start = range.start.clamp(self.window.start, self.window.end) - self.window.start;
+        let end = range.end.clamp(self.window.start, self.window.end) - self.window.start;
+        // only the pages fully in the range, the window starts on a page
+        let (start, end) = (start.div_ceil(page) * page, end / page * page);
+        if start < end {
+            let ret = unsafe {
+                libc::madvise(
+                    self.mmap.as_ptr().add(start as usize) as *mut libc::c_void,
+                    (end - start) as usize,
+                    libc::MADV_DONTNEED,
+                )
+            };
+            if ret != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        let ret = unsafe {
+            libc::posix_fadvise(
+                self.file.as_raw_fd(),
+                range.start as libc::off_t,
+                (range.end - range.start) as libc::off_t,
+                libc::POSIX_FADV_DONTNEED,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::from_raw_os_error(ret));
         }
         return Ok(());
     }
+
+    // mapped bytes of `range`, which must be in the window
+    fn slice(&self, range: &Range<u64>) -> &[u8] {
+        let start = (range.start - self.window.start) as usize;
+        let end = (range.end - self.window.start) as usize;
+        return &self.mmap[start..end];
+    }
 }
 
 #[async_trait]
 impl FileBuffer for RawFileBuffer {
     fn data(&self) -> &[u8] {
-        return &self.mmap[(self.range.start as usize)..(self.range.end as usize)];
+        return self.slice(&self.range);
     }
     fn range(&self) -> Range<u64> {
         return Range {
@@ -62,26 +201,63 @@ impl FileBuffer for RawFileBuffer {
         };
     }
     fn jump(&mut self, bytes: u64) -> io::Result<u64> {
-        self.range.start = bytes;
-        self.range.end = bytes;
+        // the file grew since it was last checked
+        if bytes >= self.size {
+            self.update_size()?;
+        }
+        let bytes = min(bytes, self.size);
+        let range = Range {
+            start: bytes,
+            end: bytes,
+        };
+        self.map(&range, Access::Jump)?;
+        self.range = range;
+        self.scrolled = 0;
         return Ok(bytes);
     }
-    async fn total_size(&self) -> u64 {
-        return self.file.metadata().await.unwrap().len();
+    async fn total_size(&self) -> io::Result<u64> {
+        return Ok(self.file.metadata()?.len());
     }
     async fn load_prev(&mut self) -> io::Result<usize> {
-        let start_before = self.range.start;
-        self.range.start = self.range.start.saturating_sub(BUFFER_SIZE as u64);
-        return Ok((start_before - self.range.start) as usize);
+        let range = Range {
+            start: self.range.start.saturating_sub(BUFFER_SIZE),
+            end: self.range.end,
+        };
+        let access = self.scroll_access();
+        self.map(&range, access)?;
+        let loaded = self.range.start - range.start;
+        self.range = range;
+        return Ok(loaded as usize);
     }
     async fn load_next(&mut self) -> std::io::Result<usize> {
-        let end_before = self.range.end;
-        self.range.end += BUFFER_SIZE;
-        if self.range.end > self.mmap.len() as u64 {
-            self.maybe_remmap().await?;
+        let mut end = self.range.end + BUFFER_SIZE;
+        if end > self.size {
+            end = min(end, self.update_size()?);
         }
-        self.range.end = min(self.range.end, self.mmap.len() as u64);
-        return Ok((self.range.end - end_before) as usize);
+        let range = Range {
+            start: self.range.start,
+            end: end.max(self.range.end),
+        };
+        let access = self.scroll_access();
+        self.map(&range, access)?;
+        let loaded = range.end - self.range.end;
+        self.range = range;
+        return Ok(loaded as usize);
+    }
+    fn is_compressed(&self) -> bool {
+        return false;
+    }
+    fn set_no_cache(&mut self, no_cache: bool) {
+        self.no_cache = no_cache;
+    }
+    fn shrink_front(&mut self, min_size: usize) -> usize {
+        let size = self.range.end - self.range.start;
+        let dropped = size.saturating_sub(min_size as u64);
+        // scans drop what they passed, the cache is only an optimization
+        self.drop_cache(&(self.range.start..self.range.start + dropped))
+            .ok();
+        self.range.start += dropped;
+        return dropped as usize;
     }
     async fn seek_from(
         &mut self,
@@ -91,27 +267,36 @@ impl FileBuffer for RawFileBuffer {
     ) -> io::Result<Option<Range<u64>>> {
         let mut begin = self.range.start + offset;
         loop {
-            let end = min(begin + FIND_WINDOW, self.mmap.len() as u64);
-            if let Some(m) = re.find(&self.mmap[begin as usize..end as usize]) {
-                self.range.start = begin + m.range().start as u64;
-                self.range.end = begin + m.range().end as u64;
+            let window = Range {
+                start: min(begin, self.size),
+                end: min(begin + FIND_WINDOW, self.size),
+            };
+            self.map(&window, Access::Search)?;
+            if let Some(m) = re.find(self.slice(&window)).map(|x| x.range()) {
+                self.range.start = window.start + m.start as u64;
+                self.range.end = window.start + m.end as u64;
                 return Ok(Some(Range {
                     start: 0,
-                    end: m.range().len() as u64,
+                    end: m.len() as u64,
                 }));
             }
 
+            self.drop_cache(&window)?;
+
             if cancelled.load(Ordering::Acquire) {
+                // stay where the search stopped
+                self.range = Range {
+                    start: window.end,
+                    end: window.end,
+                };
                 return Err(io::Error::from(ErrorKind::Interrupted));
             }
 
-            if end == self.mmap.len() as u64 {
-                match self.load_next().await {
-                    Ok(0) => break,
-                    _ => (),
-                }
+            // search what was appended since the search started
+            if window.end == self.size && self.update_size()? == window.end {
+                break;
             }
-            begin = end - FIND_OVERLAP;
+            begin = window.end.saturating_sub(FIND_OVERLAP).max(window.start);
             yield_now().await;
         }
         return Ok(None);
@@ -122,32 +307,162 @@ impl FileBuffer for RawFileBuffer {
         offset: u64,
         cancelled: &AtomicBool,
     ) -> io::Result<Option<Range<u64>>> {
-        let mut end = min(self.range.start + offset, self.mmap.len() as u64);
+        let mut end = min(self.range.start + offset, self.size);
         loop {
-            let begin = end.saturating_sub(FIND_WINDOW);
-            if let Some(m) = re
-                .find_iter(&self.mmap[begin as usize..end as usize])
-                .last()
-            {
-                self.range.start = begin + m.range().start as u64;
-                self.range.end = begin + m.range().end as u64;
+            let window = Range {
+                start: end.saturating_sub(FIND_WINDOW),
+                end,
+            };
+            self.map(&window, Access::Search)?;
+            if let Some(m) = re.find_iter(self.slice(&window)).last().map(|x| x.range()) {
+                self.range.start = window.start + m.start as u64;
+                self.range.end = window.start + m.end as u64;
                 return Ok(Some(Range {
                     start: 0,
-                    end: m.range().len() as u64,
+                    end: m.len() as u64,
                 }));
             }
 
+            self.drop_cache(&window)?;
+
             if cancelled.load(Ordering::Acquire) {
+                self.range = Range {
+                    start: window.start,
+                    end: window.start,
+                };
                 return Err(io::Error::from(ErrorKind::Interrupted));
             }
 
-            if begin == 0 {
+            if window.start == 0 {
                 break;
             }
 
-            end = min(begin + FIND_OVERLAP, self.mmap.len() as u64);
+            end = min(window.start + FIND_OVERLAP, self.size);
             yield_now().await;
         }
         return Ok(None);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{load_backward, load_forward, text, TestDir};
+    use std::fs::OpenOptions;
+    use std::io::{Seek, SeekFrom, Write};
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = TestDir::new("raw");
+        let data = text(50000);
+        let path = dir.write("log", &data);
+        let mut buffer = RawFileBuffer::new(&path).await.unwrap();
+        assert!(load_forward(&mut buffer).await == data);
+        assert!(load_backward(&mut buffer).await == data);
+    }
+
+    #[tokio::test]
+    async fn window_of_large_file() {
+        let dir = TestDir::new("raw-window");
+        let path = dir.path("log");
+        // sparse, only the marks take space on disk
+        let size = 3 * WINDOW_MARGIN;
+        let mut file = File::create(&path).unwrap();
+        file.set_len(size).unwrap();
+        file.seek(SeekFrom::Start(2 * WINDOW_MARGIN)).unwrap();
+        file.write_all(b"middle\n").unwrap();
+        file.seek(SeekFrom::End(-5)).unwrap();
+        file.write_all(b"last\n").unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let mut buffer = RawFileBuffer::new(&path).await.unwrap();
+        assert!((buffer.mmap.len() as u64) < size);
+
+        buffer.jump(size).unwrap();
+        buffer.load_prev().await.unwrap();
+        assert!(buffer.data().ends_with(b"last\n"));
+        assert!(buffer.window.start > 0);
+
+        buffer.jump(0).unwrap();
+        buffer.load_next().await.unwrap();
+        let cancelled = AtomicBool::new(false);
+        let re = Regex::new("middle").unwrap();
+        buffer.seek_from(&re, 0, &cancelled).await.unwrap().unwrap();
+        assert_eq!(buffer.range(), 2 * WINDOW_MARGIN..2 * WINDOW_MARGIN + 6);
+        assert_eq!(buffer.data(), b"middle");
+    }
+
+    #[tokio::test]
+    async fn grown_and_truncated() {
+        let dir = TestDir::new("raw-size");
+        let path = dir.write("log", "first\n");
+        let mut buffer = RawFileBuffer::new(&path).await.unwrap();
+        assert_eq!(load_forward(&mut buffer).await, b"first\n");
+
+        dir.append("log", "second\n");
+        assert_eq!(buffer.total_size().await.unwrap(), 13);
+        assert_eq!(buffer.load_next().await.unwrap(), 7);
+        assert_eq!(buffer.data(), b"first\nsecond\n");
+
+        // the loaded data past the end is dropped
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(3)
+            .unwrap();
+        assert_eq!(buffer.load_next().await.unwrap(), 0);
+        assert_eq!(buffer.range(), 0..3);
+        assert_eq!(buffer.data(), b"fir");
+    }
+
+    #[tokio::test]
+    async fn access_advised() {
+        let dir = TestDir::new("raw-access");
+        let data = text(200000);
+        let path = dir.write("log", &data);
+        let mut buffer = RawFileBuffer::new(&path).await.unwrap();
+
+        // reading around a jump is random until the view scrolled further
+        buffer.jump(data.len() as u64 / 2).unwrap();
+        buffer.load_next().await.unwrap();
+        assert_eq!(buffer.access, Access::Jump);
+        while buffer.scrolled < SCROLL_THRESHOLD {
+            buffer.load_next().await.unwrap();
+        }
+        assert_eq!(buffer.access, Access::Scroll);
+
+        let cancelled = AtomicBool::new(false);
+        let re = Regex::new("line 199999 ").unwrap();
+        buffer.seek_from(&re, 0, &cancelled).await.unwrap().unwrap();
+        assert_eq!(buffer.access, Access::Search);
+    }
+
+    #[tokio::test]
+    async fn search_without_cache() {
+        let dir = TestDir::new("raw-no-cache");
+        let data = text(200000);
+        let path = dir.write("log", &data);
+        let mut buffer = RawFileBuffer::new(&path).await.unwrap();
+        buffer.set_no_cache(true);
+        let cancelled = AtomicBool::new(false);
+
+        // the pages dropped are read again
+        for _ in 0..2 {
+            buffer.jump(0).unwrap();
+            buffer.load_next().await.unwrap();
+            let re = Regex::new("line 199999 hello").unwrap();
+            buffer.seek_from(&re, 0, &cancelled).await.unwrap().unwrap();
+            assert_eq!(buffer.data(), b"line 199999 hello");
+        }
+        let end = buffer.range().start;
+        let re = Regex::new("line 10 hello").unwrap();
+        buffer
+            .rseek_from(&re, 0, &cancelled)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(buffer.data(), b"line 10 hello");
+        assert!(buffer.range().end < end);
+        assert_eq!(buffer.shrink_front(0), 13);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::file_buffer::FileBuffer;
+use async_trait::async_trait;
+use regex::bytes::Regex;
+use std::{env, io, ops::Range, sync::atomic::AtomicBool};
+use tokio::time::{sleep, Duration};
+use tracing::info;
+
+// "<latency in ms>[,<fail every n loads>]" makes every file read slowly, to
+// reproduce the stalls of network or huge files during development
+const SLOW_IO_VAR: &str = "BLESS_SLOW_IO";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlowIo {
+    // wait before each load and search
+    pub latency: Duration,
+    // every nth load fails, 0 for none
+    pub fail_every: u64,
+}
+
+impl SlowIo {
+    pub fn from_env() -> Option<Self> {
+        let value = env::var(SLOW_IO_VAR).ok()?;
+        let (latency, fail_every) = value.split_once(',').unwrap_or((&value, "0"));
+        return Some(Self {
+            latency: Duration::from_millis(latency.trim().parse().ok()?),
+            fail_every: fail_every.trim().parse().ok()?,
+        });
+    }
+}
+
+// a buffer with the latency and the failures of a slow disk
+#[derive(Debug)]
+pub struct SlowFileBuffer {
+    inner: Box<dyn FileBuffer>,
+    slow: SlowIo,
+    loads: u64,
+}
+
+impl SlowFileBuffer {
+    pub fn new(inner: Box<dyn FileBuffer>, slow: SlowIo) -> Self {
+        info!("slow io: {:?}", slow);
+        return Self {
+            inner,
+            slow,
+            loads: 0,
+        };
+    }
+
+    async fn wait_load(&mut self) -> io::Result<()> {
+        sleep(self.slow.latency).await;
+        self.loads += 1;
+        if self.slow.fail_every > 0 && self.loads % self.slow.fail_every == 0 {
+            return Err(io::Error::other("injected read failure"));
+        }
+        return Ok(());
+    }
+}
+
+#[async_trait]
+impl FileBuffer for SlowFileBuffer {
+    fn data(&self) -> &[u8] {
+        return self.inner.data();
+    }
+    fn range(&self) -> Range<u64> {
+        return self.inner.range();
+    }
+    fn jump(&mut self, bytes: u64) -> io::Result<u64> {
+        return self.inner.jump(bytes);
+    }
+    async fn total_size(&self) -> io::Result<u64> {
+        return self.inner.total_size().await;
+    }
+    fn is_compressed(&self) -> bool {
+        return self.inner.is_compressed();
+    }
+    async fn decoded_offset(&self, offset: u64) -> u64 {
+        return self.inner.decoded_offset(offset).await;
+    }
+    async fn load_prev(&mut self) -> io::Result<usize> {
+        self.wait_load().await?;
+        return self.inner.load_prev().await;
+    }
+    async fn load_next(&mut self) -> io::Result<usize> {
+        self.wait_load().await?;
+        return self.inner.load_next().await;
+    }
+    fn shrink_front(&mut self, min_size: usize) -> usize {
+        return self.inner.shrink_front(min_size);
+    }
+    async fn seek_from(
+        &mut self,
+        re: &Regex,
+        offset: u64,
+        cancelled: &AtomicBool,
+    ) -> io::Result<Option<Range<u64>>> {
+        self.wait_load().await?;
+        return self.inner.seek_from(re, offset, cancelled).await;
+    }
+    async fn rseek_from(
+        &mut self,
+        re: &Regex,
+        offset: u64,
+        cancelled: &AtomicBool,
+    ) -> io::Result<Option<Range<u64>>> {
+        self.wait_load().await?;
+        return self.inner.rseek_from(re, offset, cancelled).await;
+    }
+    fn source_at(&self, offset: usize) -> Option<&str> {
+        return self.inner.source_at(offset);
+    }
+    fn set_verify_checksums(&mut self, verify: bool) {
+        self.inner.set_verify_checksums(verify);
+    }
+    fn take_checksum_errors(&mut self) -> Vec<String> {
+        return self.inner.take_checksum_errors();
+    }
+    fn set_no_cache(&mut self, no_cache: bool) {
+        self.inner.set_no_cache(no_cache);
+    }
+}
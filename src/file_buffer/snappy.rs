@@ -0,0 +1,231 @@
+use super::stream::StreamDecoder;
+use memmap2::MmapOptions;
+use snap::raw::Decoder;
+use std::{
+    fs::File,
+    io::{self, ErrorKind},
+};
+
+const CHUNK_HEADER_SIZE: usize = 4;
+// masked crc of the uncompressed data, before the data of a chunk
+const CHECKSUM_SIZE: usize = 4;
+
+const COMPRESSED_CHUNK: u8 = 0x00;
+const UNCOMPRESSED_CHUNK: u8 = 0x01;
+const LAST_UNSKIPPABLE_CHUNK: u8 = 0x7f;
+
+// reflected polynomial of crc32c
+const CRC32C_POLYNOMIAL: u32 = 0x82f63b78;
+const CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                0 => crc >> 1,
+                _ => (crc >> 1) ^ CRC32C_POLYNOMIAL,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    return table;
+}
+
+// the crc32c of the data, masked like in the chunks
+fn masked_crc(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc = CRC32C_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc = !crc;
+    return crc.rotate_right(15).wrapping_add(0xa282ead8);
+}
+
+fn invalid_data(error: &str) -> io::Error {
+    return io::Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid snappy data: {}", error),
+    );
+}
+
+// kind and size of the data of the chunk at the start of the data
+fn chunk_header(data: &[u8]) -> Option<(u8, usize)> {
+    let header = data.get(..CHUNK_HEADER_SIZE)?;
+    return Some((
+        header[0],
+        u32::from_le_bytes([header[1], header[2], header[3], 0]) as usize,
+    ));
+}
+
+// the snappy framing format
+pub struct SnappyDecoder {
+    // chunks cut between two inputs
+    pending: Vec<u8>,
+    decoder: Decoder,
+    verify: bool,
+    checksum_errors: Vec<String>,
+}
+
+impl Default for SnappyDecoder {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl SnappyDecoder {
+    pub fn new() -> Self {
+        return Self {
+            pending: Vec::new(),
+            decoder: Decoder::new(),
+            verify: false,
+            checksum_errors: Vec::new(),
+        };
+    }
+}
+
+impl StreamDecoder for SnappyDecoder {
+    fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<(usize, usize)> {
+        if input.is_empty() {
+            return Ok((0, 0));
+        }
+        self.pending.extend_from_slice(input);
+        let size_before = output.len();
+        let mut offset = 0;
+        while let Some((kind, size)) = chunk_header(&self.pending[offset..]) {
+            let start = offset + CHUNK_HEADER_SIZE;
+            let chunk = match self.pending.get(start..start + size) {
+                Some(chunk) => chunk,
+                None => break,
+            };
+            let chunk_start = output.len();
+            match kind {
+                COMPRESSED_CHUNK | UNCOMPRESSED_CHUNK if size < CHECKSUM_SIZE => {
+                    return Err(invalid_data("chunk too short"));
+                }
+                COMPRESSED_CHUNK => output.extend(
+                    self.decoder
+                        .decompress_vec(&chunk[CHECKSUM_SIZE..])
+                        .map_err(|e| invalid_data(&e.to_string()))?,
+                ),
+                UNCOMPRESSED_CHUNK => output.extend_from_slice(&chunk[CHECKSUM_SIZE..]),
+                x if x <= LAST_UNSKIPPABLE_CHUNK => {
+                    return Err(invalid_data("unknown chunk"));
+                }
+                // stream identifiers, padding and skippable chunks
+                _ => (),
+            }
+            if self.verify && (kind == COMPRESSED_CHUNK || kind == UNCOMPRESSED_CHUNK) {
+                let expected = u32::from_le_bytes(chunk[..CHECKSUM_SIZE].try_into().unwrap());
+                let crc = masked_crc(&output[chunk_start..]);
+                if crc != expected {
+                    self.checksum_errors
+                        .push(format!("crc32c {:08x} instead of {:08x}", crc, expected));
+                }
+            }
+            offset = start + size;
+        }
+        self.pending.drain(..offset);
+        return Ok((input.len(), output.len() - size_before));
+    }
+    fn set_verify_checksums(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+    fn take_checksum_errors(&mut self) -> Vec<String> {
+        return std::mem::take(&mut self.checksum_errors);
+    }
+}
+
+// the chunks are independent, each one is a restart point
+pub fn chunk_offsets(path: &str) -> io::Result<Vec<usize>> {
+    let file = File::open(path)?;
+    let mmap = unsafe { MmapOptions::new().map(&file) }?;
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    while let Some((_, size)) = chunk_header(&mmap[offset..]) {
+        offsets.push(offset);
+        offset += CHUNK_HEADER_SIZE + size;
+        if offset >= mmap.len() {
+            break;
+        }
+    }
+    return Ok(offsets);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file_buffer::{
+            format::{sniff, Format},
+            make_file_buffer,
+        },
+        testing::{load_backward, load_forward, text, TestDir},
+    };
+    use snap::write::FrameEncoder;
+    use std::io::Write;
+
+    // uncompressed bytes of the chunks written by the encoder
+    const ENCODER_CHUNK_SIZE: usize = 0x10000;
+    // stream identifier, then the header and the checksum of the first chunk
+    const FIRST_CHECKSUM: usize = 10 + CHUNK_HEADER_SIZE;
+
+    fn snappy(data: &[u8]) -> Vec<u8> {
+        let mut encoder = FrameEncoder::new(Vec::new());
+        encoder.write_all(data).unwrap();
+        return encoder.into_inner().unwrap();
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = TestDir::new("snappy");
+        let data = text(50000);
+        let path = dir.write("data.sz", snappy(&data));
+        assert_eq!(sniff(&path).unwrap(), Format::Snappy);
+        let mut buffer = make_file_buffer(&path).await.unwrap();
+        assert!(load_forward(buffer.as_mut()).await == data);
+        assert!(load_backward(buffer.as_mut()).await == data);
+    }
+
+    #[tokio::test]
+    async fn jump_to_chunk() {
+        let dir = TestDir::new("snappy-jump");
+        let data = text(50000);
+        let compressed = snappy(&data);
+        let path = dir.write("data.sz", &compressed);
+        let mut buffer = make_file_buffer(&path).await.unwrap();
+        for percent in [10, 50, 90] {
+            let offset = compressed.len() as u64 * percent / 100;
+            assert!(buffer.jump(offset).unwrap() <= offset);
+            // decoding starts at the chunk holding the offset
+            let decoded = buffer.data();
+            let idx = data.windows(64).position(|x| x == &decoded[..64]).unwrap();
+            assert_eq!(idx % ENCODER_CHUNK_SIZE, 0);
+            assert!(&data[idx..idx + decoded.len()] == decoded);
+        }
+    }
+
+    #[tokio::test]
+    async fn checksum_mismatch() {
+        let dir = TestDir::new("snappy-crc");
+        let data = text(50000);
+        let mut compressed = snappy(&data);
+        compressed[FIRST_CHECKSUM] ^= 1;
+        let path = dir.write("data.sz", compressed);
+        let mut buffer = make_file_buffer(&path).await.unwrap();
+        assert!(load_forward(buffer.as_mut()).await == data);
+        assert!(buffer.take_checksum_errors().is_empty());
+
+        // the chunks are decoded again from the start to verify them
+        buffer.set_verify_checksums(true);
+        assert!(load_forward(buffer.as_mut()).await == data);
+        let errors = buffer.take_checksum_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("crc32c"));
+    }
+}
@@ -0,0 +1,512 @@
+use super::{corrupted_block, ChecksumErrors, DecodedOffsets, FileBuffer};
+use async_trait::async_trait;
+use human_bytes::human_bytes;
+use memmap2::{Advice, Mmap, MmapOptions};
+use regex::bytes::Regex;
+use std::{
+    cmp::{max, min},
+    collections::VecDeque,
+    fmt,
+    io::{self, ErrorKind},
+    ops::Range,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tokio::{fs::File, task::yield_now};
+use tracing::{debug, debug_span, info, warn};
+
+// compressed bytes decoded at once
+const CHUNK_SIZE: usize = 0x10000;
+// decoded bytes loaded at once when loading backwards
+const PREV_LOAD_SIZE: usize = 0x1000000;
+// decoded bytes kept before the ones loaded backwards, the stream is decoded
+// again from its start or the last restart point once they are used
+const PREV_CACHE_SIZE: usize = 0x4000000;
+const FIND_WINDOW: usize = 0x100000;
+const FIND_OVERLAP: usize = 0x1000;
+
+// a compressed format without blocks to start decoding from
+pub trait StreamDecoder: Send + Sync {
+    // decode some of the input at the end of the output, returns the bytes
+    // consumed and produced, both are 0 at the end of the stream
+    fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<(usize, usize)>;
+    // verify the checksums of the decoded data, for formats having some
+    fn set_verify_checksums(&mut self, _verify: bool) {}
+    // checksum mismatches found since the last call
+    fn take_checksum_errors(&mut self) -> Vec<String> {
+        return Vec::new();
+    }
+}
+
+pub type NewDecoder = Box<dyn Fn() -> Box<dyn StreamDecoder> + Send + Sync>;
+
+struct Block {
+    file_range: Range<usize>,
+    data: Vec<u8>,
+}
+
+// a compressed stream, decoded in chunks of CHUNK_SIZE compressed bytes.
+// Offsets are those of the compressed data like for bz2 files.
+pub struct StreamFileBuffer {
+    name: &'static str,
+    mmap: Mmap,
+    new_decoder: NewDecoder,
+    decoder: Box<dyn StreamDecoder>,
+    // offsets a new decoder can start from other than 0, in order
+    restart_points: Vec<usize>,
+    // compressed bytes given to the decoder
+    position: usize,
+    decoded: Vec<u8>,
+    blocks: VecDeque<Block>,
+    // blocks right before the loaded ones, decoded by a backward load
+    behind: VecDeque<Block>,
+    prev_load_size: usize,
+    prev_cache_size: usize,
+    verify_checksums: bool,
+    checksum_errors: ChecksumErrors,
+    offsets: DecodedOffsets,
+    // keeps the file open while mapped
+    _file: File,
+}
+
+impl fmt::Debug for StreamFileBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StreamFileBuffer")
+            .field("name", &self.name)
+            .field("position", &self.position)
+            .field("blocks.len", &self.blocks.len())
+            .field("decoded.len", &self.decoded.len())
+            .finish()
+    }
+}
+
+impl StreamFileBuffer {
+    pub async fn new(path: &str, name: &'static str, new_decoder: NewDecoder) -> io::Result<Self> {
+        let file = File::open(path).await?;
+        let mmap = unsafe { MmapOptions::new().map(&file) }?;
+        mmap.advise(Advice::Sequential)?;
+        info!("{} stream of {}", name, human_bytes(mmap.len() as f64));
+        return Ok(Self {
+            name,
+            mmap,
+            decoder: new_decoder(),
+            new_decoder,
+            restart_points: Vec::new(),
+            position: 0,
+            decoded: Vec::new(),
+            blocks: VecDeque::new(),
+            behind: VecDeque::new(),
+            prev_load_size: PREV_LOAD_SIZE,
+            prev_cache_size: PREV_CACHE_SIZE,
+            verify_checksums: false,
+            checksum_errors: ChecksumErrors::default(),
+            offsets: DecodedOffsets::new(0),
+            _file: file,
+        });
+    }
+
+    // formats made of independent blocks are decoded from the block
+    // containing an offset rather than from the start
+    pub fn with_restart_points(mut self, restart_points: Vec<usize>) -> Self {
+        info!("{} restart points", restart_points.len());
+        self.restart_points = restart_points;
+        return self;
+    }
+
+    // last restart point at or before the offset
+    fn restart_point(&self, offset: usize) -> usize {
+        let idx = self.restart_points.partition_point(|&x| x <= offset);
+        return match idx {
+            0 => 0,
+            idx => self.restart_points[idx - 1],
+        };
+    }
+
+    fn restart(&mut self, position: usize) {
+        debug!("restart decoding at {}", position);
+        self.decoder = (self.new_decoder)();
+        self.decoder.set_verify_checksums(self.verify_checksums);
+        self.position = position;
+    }
+
+    // the mismatches found by the decoder in the chunk starting at `start`
+    fn collect_checksum_errors(&mut self, start: usize) {
+        let errors = self.decoder.take_checksum_errors();
+        if errors.is_empty() {
+            return;
+        }
+        let error = format!(
+            "{} checksum mismatch after byte {}: {}",
+            self.name,
+            start,
+            errors.join(", ")
+        );
+        warn!("{}", error);
+        self.checksum_errors.push(start, error);
+    }
+
+    fn decode_next(&mut self) -> io::Result<Option<Block>> {
+        let block = self.decode_chunk()?;
+        if let Some(block) = &block {
+            self.offsets.push(
+                block.file_range.start as u64..block.file_range.end as u64,
+                block.data.len(),
+            );
+        }
+        return Ok(block);
+    }
+
+    // chunks stop at the restart points, so that decoding from one of
+    // them gives the same chunks
+    fn decode_chunk(&mut self) -> io::Result<Option<Block>> {
+        let start = self.position;
+        let next_point = self.restart_points.partition_point(|&x| x <= start);
+        let next_point = *self
+            .restart_points
+            .get(next_point)
+            .unwrap_or(&self.mmap.len());
+        let end = min(start + CHUNK_SIZE, next_point);
+        if start >= end {
+            return Ok(None);
+        }
+        let _span = debug_span!("decode", format = self.name, start, end).entered();
+        let mut data = Vec::new();
+        let mut input = &self.mmap[start..end];
+        loop {
+            let (consumed, produced) = match self.decoder.decode(input, &mut data) {
+                Ok(res) => res,
+                // the data up to the next restart point is lost, up to the end
+                // of the file without restart points
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    warn!("{} corrupted from {}: {}", self.name, start, e);
+                    data.extend(corrupted_block(next_point - start));
+                    self.collect_checksum_errors(start);
+                    self.restart(next_point);
+                    return Ok(Some(Block {
+                        file_range: Range {
+                            start,
+                            end: next_point,
+                        },
+                        data,
+                    }));
+                }
+                Err(e) => return Err(e),
+            };
+            input = &input[consumed..];
+            // the decoder may still hold output once the input is consumed
+            if produced == 0 && (consumed == 0 || input.is_empty()) {
+                break;
+            }
+        }
+        self.collect_checksum_errors(start);
+        self.position = end;
+        return Ok(Some(Block {
+            file_range: Range { start, end },
+            data,
+        }));
+    }
+
+    // the block containing the offset and the ones before it, up to
+    // `keep` decoded bytes
+    fn decode_to(&mut self, offset: usize, keep: usize) -> io::Result<VecDeque<Block>> {
+        let restart_point = self.restart_point(offset);
+        if offset < self.position || restart_point > self.position {
+            self.restart(restart_point);
+        }
+        let mut blocks: VecDeque<Block> = VecDeque::new();
+        let mut size = 0;
+        while let Some(block) = self.decode_next()? {
+            let found = block.file_range.end > offset;
+            size += block.data.len();
+            blocks.push_back(block);
+            while size > keep && blocks.len() > 1 {
+                size -= blocks.pop_front().unwrap().data.len();
+            }
+            if found {
+                break;
+            }
+        }
+        return Ok(blocks);
+    }
+
+    // the blocks ending where the loaded ones start, up to the size of a
+    // backward load
+    fn blocks_behind(&mut self) -> io::Result<VecDeque<Block>> {
+        let end = self.range().start as usize;
+        if self.behind.back().map(|x| x.file_range.end) != Some(end) {
+            self.behind = self.decode_to(end - 1, self.prev_load_size + self.prev_cache_size)?;
+        }
+        let mut blocks = VecDeque::new();
+        let mut size = 0;
+        while let Some(block) = self.behind.back() {
+            if !blocks.is_empty() && size + block.data.len() > self.prev_load_size {
+                break;
+            }
+            size += block.data.len();
+            blocks.push_front(self.behind.pop_back().unwrap());
+        }
+        return Ok(blocks);
+    }
+
+    fn shrink_from_front(&mut self, min_size: usize) -> usize {
+        let mut extra_space = self.decoded.len().saturating_sub(min_size);
+        let mut dropped = 0;
+        while let Some(block) = self.blocks.front() {
+            if extra_space < block.data.len() {
+                break;
+            }
+            extra_space -= block.data.len();
+            dropped += block.data.len();
+            self.blocks.pop_front();
+        }
+        self.decoded.drain(..dropped);
+        debug!("shrink from front {}", human_bytes(dropped as f64));
+        return dropped;
+    }
+
+    fn shrink_from_back(&mut self, min_size: usize) -> usize {
+        let mut extra_space = self.decoded.len().saturating_sub(min_size);
+        let mut dropped = 0;
+        while let Some(block) = self.blocks.back() {
+            if extra_space < block.data.len() {
+                break;
+            }
+            extra_space -= block.data.len();
+            dropped += block.data.len();
+            self.blocks.pop_back();
+        }
+        self.decoded.truncate(self.decoded.len() - dropped);
+        debug!("shrink from back {}", human_bytes(dropped as f64));
+        return dropped;
+    }
+}
+
+#[async_trait]
+impl FileBuffer for StreamFileBuffer {
+    fn data(&self) -> &[u8] {
+        return self.decoded.as_slice();
+    }
+    fn range(&self) -> Range<u64> {
+        return Range {
+            start: self
+                .blocks
+                .front()
+                .map(|x| x.file_range.start as u64)
+                .unwrap_or(0),
+            end: self
+                .blocks
+                .back()
+                .map(|x| x.file_range.end as u64)
+                .unwrap_or(0),
+        };
+    }
+    fn jump(&mut self, byte: u64) -> io::Result<u64> {
+        let offset = min(byte as usize, self.mmap.len().saturating_sub(1));
+        let blocks = self.decode_to(offset, 0)?;
+        self.behind.clear();
+        info!("{} jump to {} (requested {})", self.name, offset, byte);
+        self.blocks = blocks;
+        self.decoded = self
+            .blocks
+            .iter()
+            .flat_map(|x| x.data.iter())
+            .copied()
+            .collect();
+        return Ok(self.range().start);
+    }
+    async fn total_size(&self) -> io::Result<u64> {
+        return Ok(self.mmap.len() as u64);
+    }
+    fn is_compressed(&self) -> bool {
+        return true;
+    }
+    async fn decoded_offset(&self, offset: u64) -> u64 {
+        return self.offsets.get(offset, self.range(), self.decoded.len());
+    }
+    // blocks without data, like the headers of some formats, are skipped
+    async fn load_next(&mut self) -> io::Result<usize> {
+        yield_now().await;
+        loop {
+            let start = self.range().end as usize;
+            if start >= self.mmap.len() {
+                return Ok(0);
+            }
+            let block = match self.decode_to(start, 0)?.pop_back() {
+                Some(block) => block,
+                None => return Ok(0),
+            };
+            let loaded = block.data.len();
+            self.decoded.extend_from_slice(&block.data);
+            self.blocks.push_back(block);
+            if loaded > 0 {
+                return Ok(loaded);
+            }
+        }
+    }
+    async fn load_prev(&mut self) -> io::Result<usize> {
+        yield_now().await;
+        loop {
+            let end = self.range().start as usize;
+            if end == 0 {
+                return Ok(0);
+            }
+            let blocks = self.blocks_behind()?;
+            let mut data: Vec<u8> = blocks.iter().flat_map(|x| x.data.iter()).copied().collect();
+            let loaded = data.len();
+            data.append(&mut self.decoded);
+            self.decoded = data;
+            for block in blocks.into_iter().rev() {
+                self.blocks.push_front(block);
+            }
+            if loaded > 0 {
+                return Ok(loaded);
+            }
+        }
+    }
+    fn shrink_front(&mut self, min_size: usize) -> usize {
+        // always keep the last block, the range would be lost otherwise
+        let last_block_size = self.blocks.back().map(|x| x.data.len()).unwrap_or(0);
+        return self.shrink_from_front(max(min_size, last_block_size));
+    }
+    // blocks already decoded are not verified again
+    fn set_verify_checksums(&mut self, verify: bool) {
+        self.verify_checksums = verify;
+        self.decoder.set_verify_checksums(verify);
+    }
+    fn take_checksum_errors(&mut self) -> Vec<String> {
+        return self.checksum_errors.take();
+    }
+    async fn seek_from(
+        &mut self,
+        re: &Regex,
+        offset: u64,
+        cancelled: &AtomicBool,
+    ) -> io::Result<Option<Range<u64>>> {
+        let mut begin = min(offset as usize, self.decoded.len());
+        let mut end = min(begin + FIND_WINDOW, self.decoded.len());
+        loop {
+            if let Some(m) = re.find(&self.decoded[begin..end]) {
+                return Ok(Some(Range {
+                    start: (begin + m.range().start) as u64,
+                    end: (begin + m.range().end) as u64,
+                }));
+            }
+
+            if cancelled.load(Ordering::Acquire) {
+                return Err(io::Error::from(ErrorKind::Interrupted));
+            }
+
+            if end == self.decoded.len() {
+                let loaded = match self.load_next().await? {
+                    0 => return Ok(None),
+                    loaded => loaded,
+                };
+                end -= self.shrink_from_front(loaded + FIND_OVERLAP);
+            }
+
+            begin = end.saturating_sub(FIND_OVERLAP);
+            end = min(begin + FIND_WINDOW, self.decoded.len());
+            yield_now().await;
+        }
+    }
+    async fn rseek_from(
+        &mut self,
+        re: &Regex,
+        offset: u64,
+        cancelled: &AtomicBool,
+    ) -> io::Result<Option<Range<u64>>> {
+        let mut end = min(offset as usize, self.decoded.len());
+        let mut begin = end.saturating_sub(FIND_WINDOW);
+        loop {
+            if let Some(m) = re.find_iter(&self.decoded[begin..end]).last() {
+                return Ok(Some(Range {
+                    start: (begin + m.range().start) as u64,
+                    end: (begin + m.range().end) as u64,
+                }));
+            }
+
+            if cancelled.load(Ordering::Acquire) {
+                return Err(io::Error::from(ErrorKind::Interrupted));
+            }
+
+            if begin == 0 {
+                match self.load_prev().await? {
+                    0 => return Ok(None),
+                    loaded => begin += loaded,
+                }
+                self.shrink_from_back(begin + FIND_OVERLAP);
+            }
+
+            end = min(begin + FIND_OVERLAP, self.decoded.len());
+            begin = end.saturating_sub(FIND_WINDOW);
+            yield_now().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{text, TestDir};
+    use std::sync::{atomic::AtomicUsize, Arc};
+
+    // copies its input, counting the bytes it decoded
+    struct CopyDecoder(Arc<AtomicUsize>);
+
+    impl StreamDecoder for CopyDecoder {
+        fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<(usize, usize)> {
+            output.extend_from_slice(input);
+            self.0.fetch_add(input.len(), Ordering::Relaxed);
+            return Ok((input.len(), input.len()));
+        }
+    }
+
+    // loads backwards of 2 chunks, with 32 chunks decoded ahead
+    async fn open(dir: &TestDir, data: &[u8], decoded: Arc<AtomicUsize>) -> StreamFileBuffer {
+        let path = dir.write("data", data);
+        let new_decoder: NewDecoder =
+            Box::new(move || Box::new(CopyDecoder(decoded.clone())) as Box<dyn StreamDecoder>);
+        let mut buffer = StreamFileBuffer::new(&path, "copy", new_decoder)
+            .await
+            .unwrap();
+        buffer.prev_load_size = 2 * CHUNK_SIZE;
+        buffer.prev_cache_size = 32 * CHUNK_SIZE;
+        return buffer;
+    }
+
+    #[tokio::test]
+    async fn backward_loads_decode_once() {
+        let dir = TestDir::new("stream-backward");
+        let data = text(200000);
+        let decoded = Arc::new(AtomicUsize::new(0));
+        let mut buffer = open(&dir, &data, decoded.clone()).await;
+        buffer.jump(data.len() as u64).unwrap();
+        let mut loads = 0;
+        while buffer.load_prev().await.unwrap() > 0 {
+            loads += 1;
+        }
+        assert!(buffer.data() == data.as_slice());
+        assert!(loads > data.len() / (2 * CHUNK_SIZE));
+        // the stream is decoded again once every 16 loads only
+        assert!(decoded.load(Ordering::Relaxed) < 3 * data.len());
+    }
+
+    #[tokio::test]
+    async fn backward_search() {
+        let dir = TestDir::new("stream-rseek");
+        let data = text(200000);
+        let mut buffer = open(&dir, &data, Arc::new(AtomicUsize::new(0))).await;
+        buffer.jump(data.len() as u64).unwrap();
+        let end = buffer.data().len() as u64;
+        let re = Regex::new("line 10 ").unwrap();
+        let found = buffer
+            .rseek_from(&re, end, &AtomicBool::new(false))
+            .await
+            .unwrap()
+            .unwrap();
+        let found = found.start as usize..found.end as usize;
+        assert_eq!(&buffer.data()[found.clone()], b"line 10 ");
+        // decoded by copies, the offsets in the data are those in the file
+        let start = buffer.range().start as usize;
+        assert!(data[start + found.start..start + found.end] == *b"line 10 ");
+    }
+}
@@ -0,0 +1,114 @@
+use super::stream::StreamDecoder;
+use std::{char::REPLACEMENT_CHARACTER, io};
+
+// decoding can restart at any even offset, a surrogate pair cut by a
+// restart point shows as replacement characters
+const RESTART_INTERVAL: usize = 0x100000;
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+// utf-16 text, decoded to utf-8
+pub struct Utf16Decoder {
+    big_endian: bool,
+    // odd byte or high surrogate cut between two inputs
+    pending: Vec<u8>,
+}
+
+impl Utf16Decoder {
+    pub fn new(big_endian: bool) -> Self {
+        return Self {
+            big_endian,
+            pending: Vec::new(),
+        };
+    }
+
+    fn unit(&self, bytes: &[u8]) -> u16 {
+        return match self.big_endian {
+            true => u16::from_be_bytes([bytes[0], bytes[1]]),
+            false => u16::from_le_bytes([bytes[0], bytes[1]]),
+        };
+    }
+}
+
+impl StreamDecoder for Utf16Decoder {
+    fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<(usize, usize)> {
+        if input.is_empty() {
+            return Ok((0, 0));
+        }
+        self.pending.extend_from_slice(input);
+        let mut end = self.pending.len() & !1;
+        // keep a high surrogate at the end for the next input
+        if end >= 2 && (0xd800..0xdc00).contains(&self.unit(&self.pending[end - 2..end])) {
+            end -= 2;
+        }
+        let units: Vec<u16> = self.pending[..end]
+            .chunks(2)
+            .map(|x| self.unit(x))
+            .collect();
+        let size_before = output.len();
+        let mut buf = [0; 4];
+        for c in char::decode_utf16(units) {
+            match c.unwrap_or(REPLACEMENT_CHARACTER) {
+                BYTE_ORDER_MARK => (),
+                c => output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes()),
+            }
+        }
+        self.pending.drain(..end);
+        return Ok((input.len(), output.len() - size_before));
+    }
+}
+
+pub fn restart_points(size: usize) -> Vec<usize> {
+    return (RESTART_INTERVAL..size).step_by(RESTART_INTERVAL).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file_buffer::{
+            format::{sniff, Format},
+            make_file_buffer,
+        },
+        testing::{load_backward, load_forward, TestDir},
+    };
+
+    // more than a restart interval of text out of ascii
+    fn text() -> String {
+        return (0..60000)
+            .map(|n| format!("ligne {} déjà vue\n", n))
+            .collect();
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = TestDir::new("utf16");
+        let text = text();
+        let le: Vec<u8> = ['\u{feff}'.to_string(), text.clone()]
+            .concat()
+            .encode_utf16()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(|x| x.to_be_bytes()).collect();
+        assert!(le.len() > 2 * RESTART_INTERVAL);
+        for (name, data, format) in [("le", le, Format::Utf16Le), ("be", be, Format::Utf16Be)] {
+            let path = dir.write(name, data);
+            assert_eq!(sniff(&path).unwrap(), format);
+            let mut buffer = make_file_buffer(&path).await.unwrap();
+            // the byte order mark is dropped
+            assert!(load_forward(buffer.as_mut()).await == text.as_bytes());
+            assert!(load_backward(buffer.as_mut()).await == text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn pairs_cut_between_inputs() {
+        let text = "a\u{1f600}b\u{e9}";
+        let data: Vec<u8> = text.encode_utf16().flat_map(|x| x.to_le_bytes()).collect();
+        let mut decoder = Utf16Decoder::new(false);
+        let mut output = Vec::new();
+        for byte in data.chunks(1) {
+            decoder.decode(byte, &mut output).unwrap();
+        }
+        assert_eq!(output, text.as_bytes());
+    }
+}
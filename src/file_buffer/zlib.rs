@@ -0,0 +1,175 @@
+use super::stream::StreamDecoder;
+use crate::utils::text::looks_like_text;
+use flate2::{Decompress, FlushDecompress, Status};
+use std::{
+    fs::File,
+    io::{self, Read},
+};
+
+const ALLOC_SIZE: usize = 0x100000;
+// bytes read to recognize the format
+const DETECT_SIZE: u64 = 0x10000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZlibFormat {
+    Zlib,
+    // deflate without header nor checksum
+    RawDeflate,
+}
+
+// the adler32 of zlib streams is always checked, a mismatch fails to decode
+pub struct ZlibDecoder {
+    inner: Decompress,
+    done: bool,
+}
+
+impl ZlibDecoder {
+    pub fn new(format: ZlibFormat) -> Self {
+        return Self {
+            inner: Decompress::new(format == ZlibFormat::Zlib),
+            done: false,
+        };
+    }
+
+    // true once the end of the deflate stream was decoded
+    pub fn finished(&self) -> bool {
+        return self.done;
+    }
+}
+
+impl StreamDecoder for ZlibDecoder {
+    fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> io::Result<(usize, usize)> {
+        if self.done {
+            return Ok((0, 0));
+        }
+        if output.capacity() - output.len() < ALLOC_SIZE {
+            output.reserve(ALLOC_SIZE);
+        }
+        let before_in = self.inner.total_in();
+        let before_out = self.inner.total_out();
+        let status = self
+            .inner
+            .decompress_vec(input, output, FlushDecompress::None)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.done = status == Status::StreamEnd;
+        return Ok((
+            (self.inner.total_in() - before_in) as usize,
+            (self.inner.total_out() - before_out) as usize,
+        ));
+    }
+}
+
+fn zlib_header(data: &[u8]) -> bool {
+    return data.len() >= 2
+        && data[0] & 0x0f == 8
+        && data[0] >> 4 <= 7
+        && u16::from_be_bytes([data[0], data[1]]) % 31 == 0;
+}
+
+// raw deflate has no magic number, its start must decode to text
+pub fn detect(path: &str) -> io::Result<Option<ZlibFormat>> {
+    let mut data = Vec::new();
+    File::open(path)?.take(DETECT_SIZE).read_to_end(&mut data)?;
+    if data.is_empty() || looks_like_text(&data) {
+        return Ok(None);
+    }
+    let format = match zlib_header(&data) {
+        true => ZlibFormat::Zlib,
+        false => ZlibFormat::RawDeflate,
+    };
+    let mut decoder = ZlibDecoder::new(format);
+    let mut output = Vec::new();
+    return match decoder.decode(&data, &mut output) {
+        Ok((_, produced)) if produced > 0 && looks_like_text(&output) => Ok(Some(format)),
+        _ => Ok(None),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        file_buffer::{
+            format::{sniff, Format},
+            make_file_buffer,
+        },
+        testing::{load_backward, load_forward, text, TestDir},
+    };
+    use flate2::{
+        write::{DeflateEncoder, ZlibEncoder},
+        Compression,
+    };
+    use std::io::Write;
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        return encoder.finish().unwrap();
+    }
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        return encoder.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn zlib_round_trip() {
+        let dir = TestDir::new("zlib");
+        let data = text(50000);
+        let path = dir.write("data.z", zlib(&data));
+        assert_eq!(sniff(&path).unwrap(), Format::Zlib);
+        let mut buffer = make_file_buffer(&path).await.unwrap();
+        assert!(load_forward(buffer.as_mut()).await == data);
+        assert!(load_backward(buffer.as_mut()).await == data);
+    }
+
+    #[tokio::test]
+    async fn deflate_round_trip() {
+        let dir = TestDir::new("deflate");
+        let data = text(50000);
+        let path = dir.write("data", deflate(&data));
+        assert_eq!(sniff(&path).unwrap(), Format::Deflate);
+        let mut buffer = make_file_buffer(&path).await.unwrap();
+        assert!(load_forward(buffer.as_mut()).await == data);
+        assert!(load_backward(buffer.as_mut()).await == data);
+    }
+
+    #[tokio::test]
+    async fn decoded_offsets() {
+        let dir = TestDir::new("zlib-offsets");
+        let data = text(50000);
+        let compressed = zlib(&data);
+        let size = compressed.len() as u64;
+        let path = dir.write("data.z", compressed);
+        let mut buffer = make_file_buffer(&path).await.unwrap();
+        assert_eq!(buffer.decoded_offset(0).await, 0);
+        load_forward(buffer.as_mut()).await;
+        // decoded from the start up to the end
+        assert_eq!(buffer.decoded_offset(size).await, data.len() as u64);
+        let middle = buffer.decoded_offset(size / 2).await as f64 / data.len() as f64;
+        assert!(middle > 0.4 && middle < 0.6, "{}", middle);
+    }
+
+    #[tokio::test]
+    async fn adler32_mismatch() {
+        let dir = TestDir::new("zlib-adler");
+        let data = text(50000);
+        let mut compressed = zlib(&data);
+        *compressed.last_mut().unwrap() ^= 1;
+        let path = dir.write("data.z", compressed);
+        let mut buffer = make_file_buffer(&path).await.unwrap();
+        let decoded = String::from_utf8_lossy(&load_forward(buffer.as_mut()).await).into_owned();
+        assert!(decoded.starts_with("line 0 hello world\n"));
+        // the end of the stream is lost with its checksum
+        assert!(decoded.contains("<<corrupted block: "));
+    }
+
+    #[test]
+    fn text_is_not_deflate() {
+        let dir = TestDir::new("not-deflate");
+        let path = dir.write("log", text(100));
+        assert_eq!(detect(&path).unwrap(), None);
+        assert_eq!(sniff(&path).unwrap(), Format::Plain);
+    }
+}
@@ -9,7 +9,7 @@ pub enum ViewError {
     EOF,
     NoMatchFound,
     Cancelled,
-    InvalidRegex,
+    InvalidRegex(String),
 }
 
 impl Display for ViewError {
@@ -19,7 +19,7 @@ impl Display for ViewError {
             Self::EOF => f.write_str("end of file"),
             Self::NoMatchFound => f.write_str("no match found"),
             Self::Cancelled => f.write_str("cancelled"),
-            Self::InvalidRegex => f.write_str("invalid regex"),
+            Self::InvalidRegex(x) => write!(f, "invalid regex: {}", x),
         }
     }
 }
@@ -1,24 +1,86 @@
 use crate::{
     errors::Result,
-    file_buffer::{make_file_buffer, FileBuffer},
-    file_view::ViewError,
+    file_buffer::{make_file_buffer, real_path, FileBuffer},
+    file_view::{newlines::NewlineIndex, ViewError},
     utils::{
         algorithm::{find_nth_or_last, rfind_nth_or_last},
+        filter::Predicate,
         infinite_loop_breaker::InfiniteLoopBreaker,
-        text::decode_utf8,
+        log::{parse_timestamp, Timestamp},
+        text::{
+            ansi_styles, escape_controls, text_ranges, truncate_text, truncation_marker,
+            DEFAULT_MAX_RENDER_COLS,
+        },
+        wrap::{expanded_width, wrapped_rows, TabStops, WrapIndent, DEFAULT_TAB_WIDTH},
     },
 };
-use log::{debug, info, warn};
 use num_integer::div_ceil;
 use regex::bytes;
-use std::{borrow::Cow, fs::canonicalize, io::ErrorKind, sync::atomic::AtomicBool, time::Instant};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io::ErrorKind,
+    ops::Range,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+use tokio::task::yield_now;
+use tracing::{debug, info, warn};
+use tui::style::Style;
 use unicode_width::UnicodeWidthStr;
 
+// lines tested against the filter between two cancellation checks
+const FILTER_STEP_LINES: u64 = 10000;
+// lines a filtered view goes through looking for matches, a sparse filter
+// shows fewer lines instead of reading up to the end of the file
+const VIEW_SCAN_LINES: u64 = 50000;
+
 #[derive(Debug)]
 pub struct ViewState {
     view_offset: usize,
     buffer_pos: u64,
     current_line: Option<i64>,
+    offset: u64,
+}
+
+impl ViewState {
+    pub fn offset(&self) -> u64 {
+        return self.offset;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    // line number from the top of the file, when known
+    pub number: Option<u64>,
+    // offset of the start of the line in the file, estimated in compressed files
+    pub byte_offset: u64,
+    // file the line comes from, for views of several files
+    pub source: Option<String>,
+    pub text: String,
+    // columns of the whole text with its tabs expanded, even when the
+    // frontend cuts it
+    pub width: usize,
+    // invalid UTF-8 sequences were replaced with U+FFFD in the text
+    pub invalid_utf8: bool,
+    // matches of the highlighted pattern, from the start of the line
+    pub matches: Vec<LineMatch>,
+    // ranges of the text styled by escape sequences, with ansi-passthrough
+    pub styles: Vec<(Range<usize>, Style)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineMatch {
+    // bytes of the line in the file
+    pub bytes: Range<usize>,
+    // bytes of the text, the same unless characters were escaped or replaced
+    pub text: Range<usize>,
+}
+
+impl AsRef<str> for Line {
+    fn as_ref(&self) -> &str {
+        return &self.text;
+    }
 }
 
 #[derive(Debug)]
@@ -27,61 +89,189 @@ pub struct FileView {
     buffer: Box<dyn FileBuffer>,
     view_offset: usize,
     current_line: Option<i64>,
+    // remove the carriage return of CRLF line endings, show it as ^M otherwise
+    strip_cr: bool,
+    // the first line of the file ends with CRLF, None until it is loaded
+    crlf: Option<bool>,
+    tab_stops: TabStops,
+    wrap_indent: WrapIndent,
+    // show the control characters as they are, for files with colors
+    ansi_passthrough: bool,
+    // longer lines are cut by the frontend, they take the rows of what is shown
+    max_render_cols: usize,
+    // the matches of this pattern are given with the lines
+    highlight: Option<bytes::Regex>,
+    // the last view reached the end of the file
+    eof_in_view: bool,
+    // only the lines matching the filter are shown and moved through
+    filter: Option<Predicate>,
+    // only the lines of the files whose path contains this text are shown
+    source_filter: Option<String>,
+    // offset reached by the last search when it was cancelled
+    search_stop: Option<u64>,
+    // newlines of the loaded data already found
+    newlines: NewlineIndex,
+    // size of the file when the newlines were found, the loaded data is
+    // other content once it shrinks
+    newlines_size: u64,
 }
 
 impl FileView {
     pub async fn new(path: &str) -> Result<Self> {
-        let real_file_path = canonicalize(path)?.to_string_lossy().to_string();
-        let buffer = make_file_buffer(&real_file_path).await?;
-        return Ok(Self {
+        let real_file_path = real_path(path)?;
+        let mut buffer = make_file_buffer(path).await?;
+        buffer.jump(0)?;
+        buffer.load_next().await?;
+        let mut view = Self {
             real_file_path,
             buffer: Box::from(buffer),
             view_offset: 0,
             current_line: Some(1),
-        });
+            strip_cr: true,
+            crlf: None,
+            tab_stops: TabStops::new(DEFAULT_TAB_WIDTH),
+            wrap_indent: WrapIndent::default(),
+            ansi_passthrough: false,
+            max_render_cols: DEFAULT_MAX_RENDER_COLS,
+            highlight: None,
+            eof_in_view: false,
+            filter: None,
+            source_filter: None,
+            search_stop: None,
+            newlines: NewlineIndex::default(),
+            newlines_size: 0,
+        };
+        view.detect_crlf().await;
+        return Ok(view);
+    }
+    pub fn is_compressed(&self) -> bool {
+        return self.buffer.is_compressed();
+    }
+    // the line number is unknown after a byte jump, even to the start
+    pub async fn at_top(&self) -> bool {
+        return self.current_line == Some(1) || self.decoded_offset(self.offset()).await == 0;
+    }
+    // offset of the decoded data of a file offset, the offsets of the loaded
+    // data are mapped back to the bytes they were computed from
+    pub async fn decoded_offset(&self, offset: u64) -> u64 {
+        let range = self.buffer.range();
+        let data_size = self.buffer.data().len();
+        if !range.contains(&offset) || data_size == 0 {
+            return self.buffer.decoded_offset(offset).await;
+        }
+        let data_offset = ((offset - range.start) as f64 * data_size as f64
+            / (range.end - range.start) as f64) as u64;
+        return self.buffer.decoded_offset(range.start).await + data_offset;
+    }
+    pub fn eof_in_view(&self) -> bool {
+        return self.eof_in_view;
+    }
+    // how the frontend lays out the lines, to count their rows
+    pub fn set_layout(&mut self, tab_stops: TabStops, wrap_indent: WrapIndent) {
+        self.tab_stops = tab_stops;
+        self.wrap_indent = wrap_indent;
+    }
+    pub fn set_strip_cr(&mut self, strip_cr: bool) {
+        self.strip_cr = strip_cr;
+    }
+    pub fn set_ansi_passthrough(&mut self, ansi_passthrough: bool) {
+        self.ansi_passthrough = ansi_passthrough;
+    }
+    pub fn set_highlight(&mut self, highlight: Option<bytes::Regex>) {
+        self.highlight = highlight;
     }
-    pub async fn file_size(&self) -> u64 {
-        return self.buffer.total_size().await;
+    pub fn set_max_render_cols(&mut self, max_render_cols: usize) {
+        self.max_render_cols = max_render_cols;
+    }
+    pub fn set_verify_checksums(&mut self, verify: bool) {
+        self.buffer.set_verify_checksums(verify);
+    }
+    // checksum mismatches of the compressed data read since the last call
+    pub fn take_checksum_errors(&mut self) -> Vec<String> {
+        return self.buffer.take_checksum_errors();
+    }
+    pub fn set_no_cache(&mut self, no_cache: bool) {
+        self.buffer.set_no_cache(no_cache);
+    }
+    // true if the first line of the file ends with CRLF, it is not known
+    // before the file has a full line
+    pub async fn is_crlf(&mut self) -> bool {
+        self.detect_crlf().await;
+        return self.crlf.unwrap_or(false);
+    }
+    async fn detect_crlf(&mut self) {
+        if self.crlf.is_some() || self.buffer.decoded_offset(self.buffer.range().start).await > 0 {
+            return;
+        }
+        let data = self.buffer.data();
+        if let Some((_, pos)) = find_nth_or_last(data, b'\n', 0) {
+            self.crlf = Some(pos > 0 && data[pos - 1] == b'\r');
+        }
+    }
+    pub async fn file_size(&mut self) -> Result<u64> {
+        let size = self.buffer.total_size().await?;
+        if size < self.newlines_size {
+            self.newlines.clear();
+        }
+        self.newlines_size = size;
+        return Ok(size);
     }
     pub fn real_file_path(&self) -> &str {
         return self.real_file_path.as_str();
     }
+    // file of the top line, for views of several files
+    pub fn source(&self) -> Option<&str> {
+        return self.buffer.source_at(self.view_offset);
+    }
+    pub fn search_stop(&self) -> Option<u64> {
+        return self.search_stop;
+    }
     pub fn current_line(&self) -> Option<i64> {
         return self.current_line;
     }
+    // for a line number known from a previous visit of the same line
+    pub fn set_current_line(&mut self, line: i64) {
+        self.current_line = Some(line);
+    }
     pub fn offset(&self) -> u64 {
-        let buffer_size = self.buffer.range().count();
-        let data_size = self.buffer.data().len();
-        return self.buffer.range().start
-            + (self.view_offset as f64 * buffer_size as f64 / data_size as f64) as u64;
+        return self.file_offset(self.view_offset);
+    }
+    // range of the file loaded in the buffer
+    pub fn buffer_range(&self) -> Range<u64> {
+        return self.buffer.range();
     }
-    pub async fn view(&mut self, nlines: usize, ncols: Option<usize>) -> Result<Vec<String>> {
+    pub async fn view(&mut self, nlines: usize, ncols: Option<usize>) -> Result<Vec<Line>> {
         info!("building view for {}x{}", nlines, ncols.unwrap_or(0));
+        self.eof_in_view = false;
+        if self.is_filtered() {
+            return self.filtered_view(nlines, ncols).await;
+        }
 
         loop {
             let mut in_lines = 0;
             let mut out_lines = 0;
-            let view = self.current_view_utf8();
+            let mut start = 0;
 
-            for line in view.lines() {
-                if ncols.is_some() {
-                    out_lines += div_ceil(UnicodeWidthStr::width(line), ncols.unwrap());
-                } else {
-                    out_lines += 1;
-                }
+            while let Some(end) = self.line_end(start) {
+                out_lines += self.out_lines(start, end, ncols);
 
                 if out_lines > nlines {
-                    return Ok(view.lines().take(in_lines).map(|x| x.to_string()).collect());
+                    // a top line taller than the screen shows its first rows
+                    return Ok(self.view_lines(in_lines.max(1)));
                 }
 
                 in_lines += 1;
                 if out_lines == nlines {
-                    return Ok(view.lines().take(in_lines).map(|x| x.to_string()).collect());
+                    return Ok(self.view_lines(in_lines));
                 }
+                start = end + 1;
             }
 
             match self.load_next().await {
-                Ok(0) => break,
+                Ok(0) => {
+                    self.eof_in_view = true;
+                    break;
+                }
                 Ok(_) => (),
                 Err(e) => return Err(e.into()),
             }
@@ -89,30 +279,21 @@ impl FileView {
 
         loop {
             if self.up(1).await.is_err() {
-                return Ok(self
-                    .current_view_utf8()
-                    .lines()
-                    .map(|x| x.to_string())
-                    .collect());
+                return Ok(self.view_lines(usize::MAX));
             }
 
-            let out_lines = self.current_view_utf8().lines().fold(0, |acc, line| {
-                if ncols.is_some() {
-                    acc + div_ceil(UnicodeWidthStr::width(line), ncols.unwrap())
-                } else {
-                    acc + 1
-                }
-            });
+            let mut out_lines = 0;
+            let mut start = 0;
+            while let Some(end) = self.line_end(start) {
+                out_lines += self.out_lines(start, end, ncols);
+                start = end + 1;
+            }
             if out_lines >= nlines {
                 if out_lines > nlines {
                     self.down(1).await.ok();
                 }
 
-                return Ok(self
-                    .current_view_utf8()
-                    .lines()
-                    .map(|x| x.to_string())
-                    .collect());
+                return Ok(self.view_lines(usize::MAX));
             }
         }
     }
@@ -123,9 +304,11 @@ impl FileView {
         loop {
             breaker.it()?;
 
-            let view = self.above_view();
-
-            match rfind_nth_or_last(view, b'\n', lines as usize) {
+            let data = self.buffer.data();
+            match self
+                .newlines
+                .rfind_nth_or_last(data, self.view_offset, lines as usize)
+            {
                 Some((nth, pos)) => {
                     self.view_offset = pos + 1;
                     self.current_line = self.current_line.map(|x| x - nth as i64);
@@ -160,28 +343,74 @@ impl FileView {
             }
         }
     }
+    // set the filter and move to the first matching line from the top of the view,
+    // the filter is removed if no line matches
+    pub async fn set_filter(
+        &mut self,
+        filter: Option<Predicate>,
+        cancelled: &AtomicBool,
+    ) -> Result<()> {
+        self.filter = filter;
+        if self.move_to_selected_line(cancelled).await? {
+            return Ok(());
+        }
+        self.filter = None;
+        return Err(ViewError::NoMatchFound.into());
+    }
+    // same as set_filter, selecting the lines by the path of their file
+    pub async fn set_source_filter(
+        &mut self,
+        source: Option<String>,
+        cancelled: &AtomicBool,
+    ) -> Result<()> {
+        self.source_filter = source;
+        if self.move_to_selected_line(cancelled).await? {
+            return Ok(());
+        }
+        self.source_filter = None;
+        return Err(ViewError::NoMatchFound.into());
+    }
+    // move through the lines matching the filter, or all lines without filter
+    pub async fn move_filtered(&mut self, lines: i64, cancelled: &AtomicBool) -> Result<()> {
+        if !self.is_filtered() {
+            return match lines {
+                0 => Ok(()),
+                x if x > 0 => self.down(x as u64).await,
+                x => self.up((-x) as u64).await,
+            };
+        }
+        let mut budget = u64::MAX;
+        for _ in 0..lines.unsigned_abs() {
+            self.next_filtered_line(lines > 0, &mut budget, cancelled)
+                .await?;
+        }
+        return Ok(());
+    }
     pub async fn up_to_line_matching(
         &mut self,
         regex: &bytes::Regex,
         cancelled: &AtomicBool,
     ) -> Result<()> {
         info!("up to line matching {}", regex.as_str());
+        self.search_stop = None;
 
         let state = self.save_state();
 
         let start = Instant::now();
-        match self
+        let result = self
             .buffer
             .rseek_from(&regex, self.view_offset as u64, cancelled)
-            .await
-        {
+            .await;
+        // the buffer moved to the match or where the search stopped
+        self.newlines.clear();
+        match result {
             // fast path: the buffer implements find
             Ok(maybe_match) => {
                 if let Some(m) = maybe_match {
                     info!("match found in {:?} ", start.elapsed());
                     self.view_offset = m.start as usize;
                     self.current_line = None;
-                    return self.up(0).await;
+                    return self.up_to_line_start().await;
                 } else {
                     self.load_state(&state)?;
                     info!("no match found in {:?} ", start.elapsed());
@@ -190,6 +419,8 @@ impl FileView {
             }
             Err(e) if e.kind() == ErrorKind::Interrupted => {
                 info!("search cancelled");
+                self.search_stop = Some(self.buffer.range().start);
+                self.restore_state(&state).await?;
                 return Err(ViewError::Cancelled.into());
             }
             // the buffer does implement find, but encountered and error
@@ -199,13 +430,22 @@ impl FileView {
             }
         }
     }
+    // a match on the first line is found, not the beginning of the file
+    async fn up_to_line_start(&mut self) -> Result<()> {
+        return match self.up(0).await {
+            Err(e) if matches!(e.downcast_ref::<ViewError>(), Some(ViewError::BOF)) => Ok(()),
+            res => res,
+        };
+    }
     pub async fn down(&mut self, mut lines: u64) -> Result<()> {
         let mut breaker = InfiniteLoopBreaker::new(10);
 
         debug!("down {}", lines);
         while lines > 0 {
             breaker.it()?;
-            match find_nth_or_last(self.current_view(), b'\n', lines.saturating_sub(1) as usize) {
+            let data = self.buffer.data();
+            let nth = lines.saturating_sub(1) as usize;
+            match self.newlines.find_nth_or_last(data, self.view_offset, nth) {
                 Some((nth, pos)) => {
                     self.view_offset += pos + 1;
                     self.current_line = self.current_line.map(|x| x + 1 + nth as i64);
@@ -228,6 +468,7 @@ impl FileView {
         cancelled: &AtomicBool,
     ) -> Result<()> {
         info!("down to line matching {}", regex.as_str());
+        self.search_stop = None;
 
         let state = self.save_state();
         if skip_current {
@@ -235,18 +476,20 @@ impl FileView {
         }
 
         let start = Instant::now();
-        match self
+        let result = self
             .buffer
             .seek_from(&regex, self.view_offset as u64, cancelled)
-            .await
-        {
+            .await;
+        // the buffer moved to the match or where the search stopped
+        self.newlines.clear();
+        match result {
             // fast path: the buffer implements find
             Ok(maybe_match) => {
                 if let Some(m) = maybe_match {
                     info!("match found in {:?}", start.elapsed());
                     self.view_offset = m.start as usize;
                     self.current_line = None;
-                    return self.up(0).await;
+                    return self.up_to_line_start().await;
                 } else {
                     self.load_state(&state)?;
                     info!("no match found in {:?} ", start.elapsed());
@@ -255,6 +498,8 @@ impl FileView {
             }
             Err(e) if e.kind() == ErrorKind::Interrupted => {
                 info!("search cancelled");
+                self.search_stop = Some(self.buffer.range().start);
+                self.restore_state(&state).await?;
                 return Err(ViewError::Cancelled.into());
             }
             // the buffer does implement find, but encountered and error
@@ -264,6 +509,102 @@ impl FileView {
             }
         }
     }
+    // call f on every line from the current position to the end of the file,
+    // or until f returns false, dropping the data behind us to keep the memory
+    // usage bounded
+    pub async fn scan_lines<F: FnMut(u64, &[u8]) -> bool>(
+        &mut self,
+        cancelled: &AtomicBool,
+        mut f: F,
+    ) -> Result<()> {
+        info!("scanning lines");
+
+        while self.scan_step(&mut f).await? {
+            if cancelled.load(Ordering::Acquire) {
+                info!("scan cancelled");
+                return Err(ViewError::Cancelled.into());
+            }
+            yield_now().await;
+        }
+        return Ok(());
+    }
+    // call f on the lines currently loaded, with the offset of the line in the file,
+    // then load more data. Returns false once the scan is over.
+    pub async fn scan_step<F: FnMut(u64, &[u8]) -> bool>(&mut self, f: &mut F) -> Result<bool> {
+        let view = self.current_view();
+        let (consumed, nlines) = match rfind_nth_or_last(view, b'\n', 0) {
+            Some((_, pos)) => {
+                let mut nlines = 0;
+                let mut line_start = 0;
+                for line in view[..pos].split(|&c| c == b'\n') {
+                    if !f(self.file_offset(self.view_offset + line_start), line) {
+                        return Ok(false);
+                    }
+                    line_start += line.len() + 1;
+                    nlines += 1;
+                }
+                (pos + 1, nlines)
+            }
+            None => (0, 0),
+        };
+        self.view_offset += consumed;
+        self.current_line = self.current_line.map(|x| x + nlines);
+        self.shrink_front();
+
+        if self.load_next().await? > 0 {
+            return Ok(true);
+        }
+
+        let rest = self.current_view();
+        if !rest.is_empty() {
+            f(self.file_offset(self.view_offset), rest);
+            self.view_offset += rest.len();
+        }
+        return Ok(false);
+    }
+    // move to the end of the file, returns the number of lines passed. A partial
+    // line at the end of the file is left for the next call.
+    pub async fn count_lines_to_end(&mut self) -> Result<u64> {
+        let mut count = 0;
+        loop {
+            let view = self.current_view();
+            if let Some((_, pos)) = rfind_nth_or_last(view, b'\n', 0) {
+                let nlines = view[..=pos].iter().filter(|&&c| c == b'\n').count();
+                count += nlines as u64;
+                self.view_offset += pos + 1;
+                self.current_line = self.current_line.map(|x| x + nlines as i64);
+            }
+            self.shrink_front();
+
+            if self.load_next().await? == 0 {
+                return Ok(count);
+            }
+        }
+    }
+    // set the current line, counting the lines from `start`, the start of line `number`
+    pub async fn resolve_current_line(&mut self, start: u64, number: u64) -> Result<()> {
+        let state = self.save_state();
+        self.buffer.jump(start).map_err(|e| Box::new(e))?;
+        self.newlines.clear();
+        self.view_offset = 0;
+
+        let mut count = 0;
+        while self.offset() < state.offset() {
+            let view = self.current_view();
+            let size = view.len().min((state.offset() - self.offset()) as usize);
+            count += view[..size].iter().filter(|&&c| c == b'\n').count() as u64;
+            self.view_offset += size;
+            self.shrink_front();
+
+            if self.offset() < state.offset() && self.load_next().await? == 0 {
+                break;
+            }
+        }
+
+        self.restore_state(&state).await?;
+        self.current_line = Some((number + count) as i64);
+        return Ok(());
+    }
     pub async fn jump_to_line(&mut self, line: i64) -> Result<()> {
         info!("jump to line {}", line);
 
@@ -289,6 +630,9 @@ impl FileView {
             self.down(offset.abs() as u64).await
         } else if offset < 0 {
             self.up(offset.abs() as u64).await
+        } else if line < 0 && self.offset() > 0 {
+            // the bottom is the end of the last line, move to its start
+            self.up(0).await
         } else {
             Ok(())
         };
@@ -297,6 +641,7 @@ impl FileView {
         info!("jump to byte {}", bytes);
 
         self.buffer.jump(bytes).map_err(|e| Box::new(e))?;
+        self.newlines.clear();
         self.view_offset = 0;
 
         if bytes == 0 {
@@ -315,18 +660,57 @@ impl FileView {
     pub async fn bottom(&mut self) -> Result<()> {
         info!("jump to bottom");
 
-        self.buffer
-            .jump(self.buffer.total_size().await - 1)
-            .map_err(|e| Box::new(e))?;
+        let size = self.buffer.total_size().await?;
+        self.newlines.clear();
+        // an empty file has no last byte, the bottom is its start
+        if size == 0 {
+            self.buffer.jump(0).map_err(|e| Box::new(e))?;
+            self.view_offset = 0;
+            self.current_line = Some(-1);
+            return Ok(());
+        }
+        self.buffer.jump(size - 1).map_err(|e| Box::new(e))?;
         self.view_offset = self.buffer.data().len();
-        self.current_line = Some(0);
+        // lines from the bottom are negative, -1 is the last line
+        self.current_line = Some(-1);
         Ok(())
     }
+    // byte ranges of the matches in the top line, from its start
+    pub async fn matches_in_top_line(&mut self, regex: &bytes::Regex) -> Result<Vec<Range<usize>>> {
+        let end = match self.top_line_end().await? {
+            Some(end) => end,
+            None => return Ok(Vec::new()),
+        };
+        return Ok(regex
+            .find_iter(&self.current_view()[..end])
+            .map(|m| m.range())
+            .collect());
+    }
+    // timestamp of the top line, None without one or at the end of the file
+    pub async fn top_line_timestamp(&mut self) -> Result<Option<Timestamp>> {
+        let end = match self.top_line_end().await? {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        return Ok(parse_timestamp(&self.current_view()[..end]));
+    }
+    // hash of the top line, to recognize it after the file changed. None at
+    // the end of the file.
+    pub async fn top_line_fingerprint(&mut self) -> Result<Option<u64>> {
+        let end = match self.top_line_end().await? {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        let mut hasher = DefaultHasher::new();
+        self.current_view()[..end].hash(&mut hasher);
+        return Ok(Some(hasher.finish()));
+    }
     pub fn save_state(&self) -> ViewState {
         return ViewState {
             view_offset: self.view_offset,
             current_line: self.current_line,
             buffer_pos: self.buffer.range().start,
+            offset: self.offset(),
         };
     }
     pub fn load_state(&mut self, state: &ViewState) -> Result<()> {
@@ -335,16 +719,344 @@ impl FileView {
         self.buffer
             .jump(state.buffer_pos)
             .map_err(|e| Box::new(e))?;
+        self.newlines.clear();
         Ok(())
     }
+    // load_state, with the data of the view loaded again
+    pub async fn restore_state(&mut self, state: &ViewState) -> Result<()> {
+        self.load_state(state)?;
+        while self.buffer.data().len() < self.view_offset {
+            if self.load_next().await? == 0 {
+                break;
+            }
+        }
+        return Ok(());
+    }
+    // end of the top line in the current view, None at the end of the file
+    async fn top_line_end(&mut self) -> Result<Option<usize>> {
+        loop {
+            let data = self.buffer.data();
+            if let Some((_, pos)) = self.newlines.find_nth_or_last(data, self.view_offset, 0) {
+                return Ok(Some(pos));
+            }
+            if self.load_next().await? == 0 {
+                let size = self.current_view().len();
+                return Ok(if size == 0 { None } else { Some(size) });
+            }
+        }
+    }
+    fn is_filtered(&self) -> bool {
+        return self.filter.is_some() || self.source_filter.is_some();
+    }
+    // true if the line between two positions of the current view passes the filters
+    fn line_selected(&self, start: usize, end: usize) -> bool {
+        if let Some(filter) = &self.filter {
+            if !filter.is_match(&self.current_view()[start..end]) {
+                return false;
+            }
+        }
+        if let Some(source) = &self.source_filter {
+            return self
+                .buffer
+                .source_at(self.view_offset + start)
+                .is_some_and(|x| x.contains(source.as_str()));
+        }
+        return true;
+    }
+    async fn top_line_matches(&mut self) -> Result<bool> {
+        if !self.is_filtered() {
+            return Ok(true);
+        }
+        let end = match self.top_line_end().await? {
+            Some(end) => end,
+            None => return Ok(false),
+        };
+        return Ok(self.line_selected(0, end));
+    }
+    // stay on the top line or move to the closest line passing the filters,
+    // false if there is none
+    async fn move_to_selected_line(&mut self, cancelled: &AtomicBool) -> Result<bool> {
+        if self.top_line_matches().await? {
+            return Ok(true);
+        }
+        let mut budget = u64::MAX;
+        return Ok(self
+            .next_filtered_line(true, &mut budget, cancelled)
+            .await
+            .is_ok()
+            || self
+                .next_filtered_line(false, &mut budget, cancelled)
+                .await
+                .is_ok());
+    }
+    // move to the next or previous line matching the filter,
+    // the view does not move if there is none within `budget` lines,
+    // which is decreased by the lines gone through
+    async fn next_filtered_line(
+        &mut self,
+        forward: bool,
+        budget: &mut u64,
+        cancelled: &AtomicBool,
+    ) -> Result<()> {
+        let state = self.save_state();
+        let mut count = 0;
+        loop {
+            if *budget == 0 {
+                self.restore_state(&state).await?;
+                return Err(ViewError::NoMatchFound.into());
+            }
+            *budget -= 1;
+            let res = if forward {
+                self.down(1).await
+            } else {
+                self.up(1).await
+            };
+            let res = match res {
+                Ok(()) => self.top_line_matches().await,
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(true) => return Ok(()),
+                Ok(_) if forward && self.current_view().is_empty() => {
+                    self.restore_state(&state).await?;
+                    return Err(ViewError::EOF.into());
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    self.restore_state(&state).await?;
+                    return Err(e);
+                }
+            }
+
+            count += 1;
+            if count % FILTER_STEP_LINES == 0 {
+                if cancelled.load(Ordering::Acquire) {
+                    self.restore_state(&state).await?;
+                    return Err(ViewError::Cancelled.into());
+                }
+                if forward {
+                    // lines above the view are not needed anymore
+                    self.shrink_front();
+                }
+                yield_now().await;
+            }
+        }
+    }
+    // lines matching the filter from the top of the view
+    async fn filtered_view(&mut self, nlines: usize, ncols: Option<usize>) -> Result<Vec<Line>> {
+        let max_render_cols = self.max_render_cols;
+        let out_size = |line: &Line| match ncols {
+            Some(ncols) => div_ceil(
+                match truncate_text(&line.text, max_render_cols) {
+                    Some((text, length)) => {
+                        UnicodeWidthStr::width(text)
+                            + UnicodeWidthStr::width(truncation_marker(length).as_str())
+                    }
+                    None => UnicodeWidthStr::width(line.text.as_str()),
+                },
+                ncols,
+            ),
+            None => 1,
+        };
+        // bounded by VIEW_SCAN_LINES rather than cancelled
+        let not_cancelled = AtomicBool::new(false);
+        if !self.top_line_matches().await? {
+            let mut budget = VIEW_SCAN_LINES;
+            match self
+                .next_filtered_line(true, &mut budget, &not_cancelled)
+                .await
+            {
+                Ok(()) => (),
+                Err(e) => {
+                    let mut budget = VIEW_SCAN_LINES;
+                    if self
+                        .next_filtered_line(false, &mut budget, &not_cancelled)
+                        .await
+                        .is_err()
+                    {
+                        // no match nearby is not the end of the file
+                        self.eof_in_view =
+                            matches!(e.downcast_ref::<ViewError>(), Some(ViewError::EOF));
+                        return Ok(Vec::new());
+                    }
+                }
+            }
+        }
+
+        let first_number = self.current_line.filter(|x| *x > 0).map(|x| x as u64);
+        let mut lines = Vec::new();
+        let mut out_lines = 0;
+        let mut start = 0;
+        let mut index = 0;
+        while out_lines < nlines && index < VIEW_SCAN_LINES {
+            let data = self.buffer.data();
+            let from = self.view_offset + start;
+            let end = match self.newlines.find_nth_or_last(data, from, 0) {
+                Some((_, pos)) => start + pos,
+                None if self.load_next().await? > 0 => continue,
+                None if start < self.current_view().len() => self.current_view().len(),
+                None => {
+                    self.eof_in_view = true;
+                    break;
+                }
+            };
+            if self.line_selected(start, end) {
+                let line = self.line_at(start, end, first_number.map(|x| x + index));
+                out_lines += out_size(&line);
+                if out_lines > nlines {
+                    break;
+                }
+                lines.push(line);
+            }
+            start = end + 1;
+            index += 1;
+        }
+
+        // fill the view with the matching lines above at the end of the file
+        let mut budget = VIEW_SCAN_LINES;
+        while self.eof_in_view && out_lines < nlines {
+            if self
+                .next_filtered_line(false, &mut budget, &not_cancelled)
+                .await
+                .is_err()
+            {
+                break;
+            }
+            let end = self.top_line_end().await?.unwrap_or(0);
+            let number = self.current_line.filter(|x| *x > 0).map(|x| x as u64);
+            let line = self.line_at(0, end, number);
+            out_lines += out_size(&line);
+            if out_lines > nlines {
+                let mut budget = u64::MAX;
+                self.next_filtered_line(true, &mut budget, &not_cancelled)
+                    .await
+                    .ok();
+                break;
+            }
+            lines.insert(0, line);
+        }
+        return Ok(lines);
+    }
+    // estimate the offset in the file of a position in the loaded data
+    fn file_offset(&self, data_offset: usize) -> u64 {
+        let range = self.buffer.range();
+        let data_size = self.buffer.data().len();
+        if data_size == 0 {
+            return range.start;
+        }
+        return range.start
+            + (data_offset as f64 * (range.end - range.start) as f64 / data_size as f64) as u64;
+    }
+    // the first lines of the view, with their number and offset
+    fn view_lines(&mut self, count: usize) -> Vec<Line> {
+        let first_number = self.current_line.filter(|x| *x > 0).map(|x| x as u64);
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        while lines.len() < count {
+            let end = match self.line_end(start) {
+                Some(end) => end,
+                None => break,
+            };
+            lines.push(self.line_at(start, end, first_number.map(|x| x + lines.len() as u64)));
+            start = end + 1;
+        }
+        return lines;
+    }
+    // end of the line starting at `start` in the current view, None past the
+    // end of the view
+    fn line_end(&mut self, start: usize) -> Option<usize> {
+        let size = self.current_view().len();
+        if start >= size {
+            return None;
+        }
+        let from = self.view_offset + start;
+        return match self.newlines.find_nth_or_last(self.buffer.data(), from, 0) {
+            Some((_, pos)) => Some(start + pos),
+            None => Some(size),
+        };
+    }
+    // rows taken by the line between two positions of the current view, once
+    // wrapped by the frontend
+    fn out_lines(&self, start: usize, end: usize, ncols: Option<usize>) -> usize {
+        let ncols = match ncols {
+            Some(ncols) => ncols,
+            None => return 1,
+        };
+        let (mut text, _) = self.display_text(&self.current_view()[start..end]);
+        if let Some((cut, length)) = truncate_text(&text, self.max_render_cols) {
+            text = cut.to_owned() + &truncation_marker(length);
+        }
+        return wrapped_rows(&text, ncols, &self.tab_stops, &self.wrap_indent);
+    }
+    // bytes of a line without the carriage return of CRLF when it is hidden
+    fn line_data<'a>(&self, line: &'a [u8]) -> (&'a [u8], &'static str) {
+        return match line.strip_suffix(b"\r") {
+            Some(data) if self.strip_cr => (data, ""),
+            Some(data) => (data, "^M"),
+            None => (line, ""),
+        };
+    }
+    // text shown for a line, with the controls escaped or styled
+    fn display_text(&self, line: &[u8]) -> (String, Vec<(Range<usize>, Style)>) {
+        let (data, suffix) = self.line_data(line);
+        let decoded = String::from_utf8_lossy(data);
+        let (text, styles) = if self.ansi_passthrough {
+            ansi_styles(&decoded)
+        } else {
+            (escape_controls(&decoded).into_owned(), Vec::new())
+        };
+        return (text + suffix, styles);
+    }
+    // line between two positions of the current view
+    fn line_at(&self, start: usize, end: usize, number: Option<u64>) -> Line {
+        let line = &self.current_view()[start..end];
+        let (data, _) = self.line_data(line);
+        let invalid_utf8 = std::str::from_utf8(data).is_err();
+        let (text, styles) = self.display_text(line);
+        // matched like the searches, on the line with its carriage return
+        let ranges = match self.highlight.as_ref() {
+            Some(re) => re
+                .find_iter(line)
+                .map(|m| m.range())
+                .filter(|x| !x.is_empty())
+                .collect(),
+            None => Vec::new(),
+        };
+        let matches = text_ranges(data, &ranges, self.ansi_passthrough)
+            .into_iter()
+            .zip(ranges)
+            .map(|(text_range, bytes)| LineMatch {
+                text: if bytes.end > data.len() {
+                    text_range.start..text.len()
+                } else {
+                    text_range
+                },
+                bytes,
+            })
+            .collect();
+        return Line {
+            number,
+            byte_offset: self.file_offset(self.view_offset + start),
+            source: self
+                .buffer
+                .source_at(self.view_offset + start)
+                .map(|x| x.to_owned()),
+            width: expanded_width(&text, &self.tab_stops),
+            text,
+            invalid_utf8,
+            matches,
+            styles,
+        };
+    }
     fn current_view(&self) -> &[u8] {
         return self.buffer.data().get(self.view_offset..).unwrap_or(b"");
     }
-    fn current_view_utf8(&self) -> Cow<str> {
-        return decode_utf8(self.current_view());
-    }
-    fn above_view(&self) -> &[u8] {
-        return self.buffer.data().get(..self.view_offset).unwrap_or(b"");
+    // drop the data above the view, scans keep the memory usage bounded
+    fn shrink_front(&mut self) {
+        let dropped = self.buffer.shrink_front(self.current_view().len());
+        self.view_offset -= dropped;
+        self.newlines.dropped(dropped);
     }
     async fn load_next(&mut self) -> Result<usize> {
         let load_size = self.buffer.load_next().await?;
@@ -354,6 +1066,7 @@ impl FileView {
     async fn load_prev(&mut self) -> Result<usize> {
         let load_size = self.buffer.load_prev().await?;
         self.view_offset += load_size;
+        self.newlines.prepended(load_size);
         debug!("loaded {} previous bytes", load_size);
         return Ok(load_size);
     }
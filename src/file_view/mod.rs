@@ -1,5 +1,6 @@
 mod errors;
 mod file_view;
+mod newlines;
 
 pub use errors::ViewError;
-pub use file_view::{FileView, ViewState};
+pub use file_view::{FileView, Line, ViewState};
@@ -0,0 +1,198 @@
+use memchr::{memchr_iter, memrchr_iter};
+use std::collections::VecDeque;
+
+// bytes searched at once when the index grows
+const INDEX_CHUNK: usize = 0x10000;
+
+// positions of the newlines in a region of the loaded data, kept between
+// motions so the same data is not searched again. The positions are stored
+// shifted, data loaded or dropped at the front of the buffer does not move
+// them.
+#[derive(Debug, Default)]
+pub struct NewlineIndex {
+    // added to a position in the data to get its stored position
+    shift: i64,
+    // indexed region, in stored positions
+    start: i64,
+    end: i64,
+    newlines: VecDeque<i64>,
+}
+
+impl NewlineIndex {
+    // the loaded data changed, after a jump or a search
+    pub fn clear(&mut self) {
+        self.shift = 0;
+        self.start = 0;
+        self.end = 0;
+        self.newlines.clear();
+    }
+
+    // `size` bytes were loaded at the front of the data
+    pub fn prepended(&mut self, size: usize) {
+        self.shift -= size as i64;
+    }
+
+    // `size` bytes were dropped from the front of the data
+    pub fn dropped(&mut self, size: usize) {
+        self.shift += size as i64;
+        while self.newlines.front().is_some_and(|&x| x < self.shift) {
+            self.newlines.pop_front();
+        }
+        self.start = self.start.max(self.shift);
+        self.end = self.end.max(self.start);
+    }
+
+    // the nth newline of data[from..], or the last one when there are fewer,
+    // with its rank and its position in data[from..]
+    pub fn find_nth_or_last(
+        &mut self,
+        data: &[u8],
+        from: usize,
+        nth: usize,
+    ) -> Option<(usize, usize)> {
+        if from > data.len() {
+            return None;
+        }
+        let mut to = from;
+        loop {
+            self.cover(data, from, to);
+            let first = self
+                .newlines
+                .partition_point(|&x| x < from as i64 + self.shift);
+            let count = self.newlines.len() - first;
+            let end = (self.end - self.shift) as usize;
+            if count > nth || (end >= data.len() && count > 0) {
+                let rank = nth.min(count - 1);
+                let pos = (self.newlines[first + rank] - self.shift) as usize;
+                return Some((rank, pos - from));
+            }
+            if end >= data.len() {
+                return None;
+            }
+            to = (end + INDEX_CHUNK).min(data.len());
+        }
+    }
+
+    // the nth newline of data[..to] from the end, or the first one when
+    // there are fewer, with its rank and its position
+    pub fn rfind_nth_or_last(
+        &mut self,
+        data: &[u8],
+        to: usize,
+        nth: usize,
+    ) -> Option<(usize, usize)> {
+        let to = to.min(data.len());
+        let mut from = to;
+        loop {
+            self.cover(data, from, to);
+            let count = self
+                .newlines
+                .partition_point(|&x| x < to as i64 + self.shift);
+            let start = (self.start - self.shift) as usize;
+            if count > nth || (start == 0 && count > 0) {
+                let rank = nth.min(count - 1);
+                let pos = (self.newlines[count - 1 - rank] - self.shift) as usize;
+                return Some((rank, pos));
+            }
+            if start == 0 {
+                return None;
+            }
+            from = start.saturating_sub(INDEX_CHUNK);
+        }
+    }
+
+    // index data[from..to], on top of the region already indexed when they
+    // touch
+    fn cover(&mut self, data: &[u8], from: usize, to: usize) {
+        // data may have been dropped at the back
+        let limit = data.len() as i64 + self.shift;
+        if self.end > limit {
+            self.end = limit;
+            while self.newlines.back().is_some_and(|&x| x >= limit) {
+                self.newlines.pop_back();
+            }
+            self.start = self.start.min(self.end);
+        }
+
+        let (from, to) = (from as i64 + self.shift, to as i64 + self.shift);
+        if self.start == self.end || to < self.start || from > self.end {
+            self.newlines.clear();
+            self.start = from;
+            self.end = from;
+        }
+        if from < self.start {
+            let begin = (from - self.shift) as usize;
+            let region = &data[begin..(self.start - self.shift) as usize];
+            for pos in memrchr_iter(b'\n', region) {
+                self.newlines.push_front((begin + pos) as i64 + self.shift);
+            }
+            self.start = from;
+        }
+        if to > self.end {
+            let begin = (self.end - self.shift) as usize;
+            let region = &data[begin..(to - self.shift) as usize];
+            for pos in memchr_iter(b'\n', region) {
+                self.newlines.push_back((begin + pos) as i64 + self.shift);
+            }
+            self.end = to;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::algorithm::{find_nth_or_last, rfind_nth_or_last};
+
+    fn data() -> Vec<u8> {
+        let mut data = Vec::new();
+        for n in 0..20000 {
+            data.extend_from_slice(format!("line {}\n", "x".repeat(n % 13)).as_bytes());
+        }
+        data.extend_from_slice(b"no newline at the end");
+        return data;
+    }
+
+    #[test]
+    fn same_as_plain_search() {
+        let data = data();
+        let mut index = NewlineIndex::default();
+        for from in [0, 5, 1000, 70000, 150000, data.len() - 3, data.len()] {
+            for nth in [0, 1, 10, 5000, usize::MAX] {
+                assert_eq!(
+                    index.find_nth_or_last(&data, from, nth),
+                    find_nth_or_last(&data[from..], b'\n', nth)
+                );
+                assert_eq!(
+                    index.rfind_nth_or_last(&data, from, nth),
+                    rfind_nth_or_last(&data[..from], b'\n', nth)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn front_changes() {
+        let data = data();
+        let mut index = NewlineIndex::default();
+        // the second half is loaded, then the first half before it
+        let half = data.len() / 2;
+        index.find_nth_or_last(&data[half..], 0, 100);
+        index.prepended(half);
+        assert_eq!(
+            index.rfind_nth_or_last(&data, half + 10, 3),
+            rfind_nth_or_last(&data[..half + 10], b'\n', 3)
+        );
+        // the first 1000 bytes are dropped
+        index.dropped(1000);
+        let data = &data[1000..];
+        assert_eq!(
+            index.find_nth_or_last(data, 10, 200),
+            find_nth_or_last(&data[10..], b'\n', 200)
+        );
+        assert_eq!(
+            index.rfind_nth_or_last(data, 500, 2),
+            rfind_nth_or_last(&data[..500], b'\n', 2)
+        );
+    }
+}
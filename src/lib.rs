@@ -0,0 +1,14 @@
+pub mod config;
+pub mod crash;
+pub mod demo;
+pub mod errors;
+pub mod file_buffer;
+pub mod file_view;
+pub mod man;
+pub mod pager;
+pub mod state;
+pub mod term;
+#[cfg(test)]
+mod testing;
+pub mod ui;
+pub mod utils;
@@ -1,42 +1,418 @@
-mod errors;
-mod file_buffer;
-mod file_view;
-mod term;
-mod ui;
-mod utils;
-
-use crate::{errors::Result, term::ConfigureTerm, ui::Ui};
-use clap::Parser;
-use env_logger;
+use bless::{
+    config::{Config, HeaderMode},
+    crash, demo,
+    errors::Result,
+    file_buffer::{
+        cat_path,
+        format::{force_format, Format},
+    },
+    man, pager,
+    state::{FilePosition, Session, State},
+    term::ConfigureTerm,
+    ui::{picker::pick_file, theme::Theme, Ui},
+    utils::{diff::spawn_diff, less::LessOptions},
+};
+use clap::{CommandFactory, ErrorKind, Parser};
+use clap_complete::{generate, Shell};
+use crossterm::terminal;
 use std::{
+    env, fs,
+    io::{self, Write},
     panic,
+    path::{Path, PathBuf},
+    process,
     sync::{Arc, Mutex},
 };
+use tracing::warn;
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
 
 #[derive(Parser)]
 struct Args {
-    /// Path to the file to read
-    path: String,
+    /// Path to the file to read, a directory to pick a file from, or a glob
+    /// pattern like 'app.log*' to read the matching files as one, oldest first.
+    /// Piped input is read without path
+    path: Option<String>,
+
+    /// Read several files as one, in the given order
+    #[clap(
+        long,
+        value_name = "FILE",
+        multiple_values = true,
+        min_values = 2,
+        conflicts_with = "path"
+    )]
+    cat: Option<Vec<String>>,
+
+    /// Show the lines added and removed from OLD to NEW, ]c and [c jump between changes
+    #[clap(long, value_names = &["OLD", "NEW"], number_of_values = 2, conflicts_with_all = &["path", "cat"])]
+    diff: Option<Vec<String>>,
+
+    /// Read the files as FORMAT rather than the one detected from their content:
+    /// plain, bz2, gzip, zlib, deflate, snappy, brotli, utf16le or utf16be
+    #[clap(long, value_name = "FORMAT")]
+    format: Option<String>,
+
+    /// Color theme: dark, light, solarized or a theme defined in the config file
+    #[clap(long)]
+    theme: Option<String>,
+
+    /// Start at the line containing this byte offset
+    #[clap(long)]
+    byte: Option<u64>,
+
+    /// Hide the header, the text uses the whole screen
+    #[clap(long)]
+    no_header: bool,
+
+    /// Print the final line and byte offset when quitting, to stdout or to
+    /// FILE given as --print-position-on-exit=FILE
+    #[clap(long, value_name = "FILE", require_equals = true)]
+    print_position_on_exit: Option<Option<String>>,
+
+    /// Reopen the file at the position it was last closed, positions are
+    /// only kept when resuming
+    #[clap(long)]
+    resume: bool,
+
+    /// Restore the tabs of a session saved with :mksession, the file of the
+    /// first tab defaults to the session one
+    #[clap(long, value_name = "NAME")]
+    session: Option<String>,
+
+    /// Print the file and exit when it fits on the screen
+    #[clap(short = 'F', long)]
+    quit_if_one_screen: bool,
+
+    /// Do not use the alternate screen, the last screen stays after quitting
+    #[clap(short = 'X', long)]
+    no_init: bool,
+
+    /// Search a pattern at startup
+    #[clap(long, value_name = "PATTERN")]
+    search: Option<String>,
+
+    /// Run COMMAND at startup, after the commands of the rc file. Commands are
+    /// those of the command line, like ':set number', or a search like '/ERROR'
+    /// or a filter like '&ERROR'. Can be given several times
+    #[clap(long, value_name = "COMMAND", multiple_occurrences = true)]
+    cmd: Vec<String>,
+
+    /// Run the commands of FILE at startup, one per line, rather than those of
+    /// blessrc in the config directory
+    #[clap(long, value_name = "FILE")]
+    rc: Option<String>,
+
+    /// Exit with CODE when the pattern of --search matched nothing before quitting
+    #[clap(long, value_name = "CODE")]
+    not_found_exit_code: Option<i32>,
+
+    /// Open a generated log of SIZE, like 2GB, which grows while it is open.
+    /// Set BLESS_SLOW_IO to <ms>[,<n>] to wait ms milliseconds on each read and
+    /// fail every nth read
+    #[clap(long, value_name = "SIZE", conflicts_with_all = &["path", "cat", "diff", "session"])]
+    demo: Option<Option<String>>,
+
+    /// Run COMMAND with sh and follow its output, like 'make 2>&1'. The header
+    /// shows its exit status once it finished
+    #[clap(long, value_name = "COMMAND", conflicts_with_all = &["path", "cat", "diff", "session", "demo"])]
+    exec: Option<String>,
+
+    /// Print the completion script of SHELL and exit
+    #[clap(long, value_name = "SHELL", arg_enum)]
+    completions: Option<Shell>,
+
+    /// Print the man page, with the options of :set, and exit
+    #[clap(long)]
+    man: bool,
+
+    /// Write debug logs to FILE, with the time taken by each command, decode
+    /// and search. RUST_LOG selects what is written, like with stderr logs
+    #[clap(long, value_name = "FILE")]
+    debug_log: Option<String>,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    env_logger::Builder::from_default_env()
-        .format_timestamp_micros()
+// logs go to stderr as selected by RUST_LOG, or to the file of --debug-log
+// with the spans and their duration
+fn init_tracing(debug_log: Option<&str>) -> Result<()> {
+    let path = match debug_log {
+        Some(path) => path,
+        None => {
+            tracing_subscriber::fmt()
+                .with_env_filter(EnvFilter::from_default_env())
+                .with_writer(io::stderr)
+                .init();
+            return Ok(());
+        }
+    };
+    let filter = match env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(directives) => EnvFilter::new(directives),
+        Err(_) => EnvFilter::new("bless=debug"),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false)
+        .with_writer(Mutex::new(fs::File::create(path)?))
         .init();
+    return Ok(());
+}
 
+// completion script of a shell, the themes and formats are completed with
+// those known when it is generated, themes of the config included
+fn print_completions(shell: Shell, config: &Config) {
+    let themes = Theme::names(config);
+    let formats = Format::names();
+    let mut command = Args::command()
+        .mut_arg("theme", |arg| {
+            arg.possible_values(themes.iter().map(String::as_str))
+        })
+        .mut_arg("format", |arg| arg.possible_values(formats.iter().copied()));
+    generate(shell, &mut command, "bless", &mut io::stdout());
+}
+
+// size of the log of --demo without size
+const DEMO_SIZE: u64 = 10 << 20;
+
+#[tokio::main]
+async fn main() -> Result<()> {
     let args = Args::parse();
-    let term = Arc::new(Mutex::new(Some(ConfigureTerm::new()?)));
+    init_tracing(args.debug_log.as_deref())?;
+    let mut config = Config::load()?;
+    if args.man {
+        print!("{}", man::man_page(Args::command()));
+        return Ok(());
+    }
+    if let Some(shell) = args.completions {
+        print_completions(shell, &config);
+        return Ok(());
+    }
+    if args.no_header {
+        config.header = HeaderMode::Hidden;
+    }
+    if args.not_found_exit_code.is_some() {
+        config.not_found_exit_code = args.not_found_exit_code;
+    }
+    // bless used as PAGER follows the options of less, unless its config sets them
+    let less = LessOptions::from_env();
+    let less_options = [
+        ("ignore-case", less.ignore_case, true),
+        ("ansi-passthrough", less.ansi_colors, true),
+        ("wrap", less.chop_long_lines, false),
+        ("number", less.line_numbers, true),
+        ("follow", less.follow, true),
+    ];
+    for (name, set, value) in less_options {
+        if set {
+            config
+                .options
+                .entry(name.to_owned())
+                .or_insert(toml::Value::Boolean(value));
+        }
+    }
+    if let Some(name) = &args.format {
+        match Format::from_name(name) {
+            Some(format) => force_format(format),
+            None => Args::command()
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!(
+                        "unknown format, expected one of {}",
+                        Format::names().join(", ")
+                    ),
+                )
+                .exit(),
+        }
+    }
+    let session = args.session.as_deref().map(Session::load).transpose()?;
+    let mut commands = Config::rc_commands(args.rc.as_deref())?;
+    commands.extend(args.cmd.iter().cloned());
+    let quit_if_one_screen = args.quit_if_one_screen || less.quit_if_one_screen;
+    // a file fitting on a screen is printed like bless would show it
+    let ansi_colors = config.options.get("ansi-passthrough") == Some(&toml::Value::Boolean(true));
+    let (width, height) = terminal::size()?;
+    // the diff, the demo log, the output of --exec and the piped input are
+    // written to a private temporary directory, removed when quitting
+    let mut temp_dir = None;
+    let mut make_temp_dir = || -> io::Result<PathBuf> {
+        let dir = tempfile::Builder::new().prefix("bless-").tempdir()?;
+        return Ok(temp_dir.insert(dir).path().to_owned());
+    };
+    let mut piped = false;
+    let mut exec = None;
+    let path = match (args.path, args.cat, &args.diff, &session) {
+        (Some(path), _, _, _) => path,
+        (None, Some(paths), _, _) => cat_path(&paths),
+        (None, None, Some(paths), _) => {
+            let (old, new) = (Path::new(&paths[0]), Path::new(&paths[1]));
+            let name = |path: &Path| {
+                path.file_name()
+                    .map(|x| x.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            };
+            let output = make_temp_dir()?.join(format!("{}..{}.diff", name(old), name(new)));
+            spawn_diff(old, new, &output)?;
+            output.to_string_lossy().into_owned()
+        }
+        (None, None, None, _) if args.exec.is_some() => {
+            let output = make_temp_dir()?.join("exec");
+            exec = Some(pager::Exec::spawn(&output, args.exec.as_ref().unwrap())?);
+            output.to_string_lossy().into_owned()
+        }
+        (None, None, None, _) if args.demo.is_some() => {
+            let size = match args.demo.as_ref().unwrap() {
+                Some(size) => match demo::parse_size(size) {
+                    Some(size) => size,
+                    None => Args::command()
+                        .error(ErrorKind::InvalidValue, "invalid size, expected like 200MB")
+                        .exit(),
+                },
+                None => DEMO_SIZE,
+            };
+            demo::write_demo_log(&make_temp_dir()?, size)?
+                .to_string_lossy()
+                .into_owned()
+        }
+        (None, None, None, Some(session)) => session.tabs[0].path.clone(),
+        (None, None, None, None) if atty::is(atty::Stream::Stdin) => {
+            Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "no path and no piped input",
+                )
+                .exit();
+        }
+        (None, None, None, None) => {
+            let mut head = Vec::new();
+            if quit_if_one_screen
+                && pager::read_screen(&mut io::stdin(), &mut head, width.into(), height.into())?
+            {
+                pager::print_screen(&head, ansi_colors)?;
+                return Ok(());
+            }
+            let output = make_temp_dir()?.join("stdin");
+            pager::spool_stdin(&output, &head)?;
+            piped = true;
+            output.to_string_lossy().into_owned()
+        }
+    };
+    if quit_if_one_screen && args.diff.is_none() && !piped && exec.is_none() && session.is_none() {
+        if let Ok(Some(data)) = pager::one_screen_file(&path, width.into(), height.into()) {
+            pager::print_screen(&data, ansi_colors)?;
+            return Ok(());
+        }
+    }
+
+    let theme = Theme::from_config(args.theme.as_deref(), &config)?;
+
+    let alternate_screen = !(args.no_init || less.no_init);
+    let term = Arc::new(Mutex::new(Some(ConfigureTerm::new(
+        config.mouse,
+        alternate_screen,
+    )?)));
     let term_copy = term.clone();
 
     let default_panic = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
-        term_copy.lock().unwrap().take().unwrap();
+        // restore the terminal first, so the messages show in it
+        if let Ok(mut term) = term_copy.lock() {
+            term.take();
+        }
         default_panic(panic_info);
+        match crash::write_report(panic_info) {
+            Ok(path) => eprintln!("crash report written to {}", path.display()),
+            Err(e) => eprintln!("could not write the crash report: {}", e),
+        }
     }));
 
-    let mut ui = Ui::new(&args.path).await?;
+    let path = if Path::new(&path).is_dir() {
+        match pick_file(Path::new(&path), &theme).await {
+            Ok(Some(path)) => path,
+            res => {
+                term.lock().unwrap().as_mut().unwrap().cleanup();
+                return res.map(|_| ());
+            }
+        }
+    } else {
+        path
+    };
+
+    // positions are only kept for those resuming
+    let resume = args.resume || config.resume;
+    let mut state = match resume {
+        true => State::load().unwrap_or_else(|e| {
+            warn!("cannot load the state: {}", e);
+            State::default()
+        }),
+        false => State::default(),
+    };
+    let mut start_byte = args.byte.or(session.as_ref().map(|x| x.tabs[0].offset));
+    if start_byte.is_none() && resume {
+        start_byte = state.position(&path).map(|x| x.offset);
+    }
+
+    let mut ui = Ui::new(&path, &config, theme, start_byte).await?;
+    if let Some(session) = &session {
+        ui.restore_session(session).await;
+    }
+    if args.diff.is_some() {
+        ui.show_diff();
+    }
+    if let Some(exec) = &exec {
+        ui.follow_command(exec.status.clone());
+    }
+    for command in commands.iter() {
+        ui.execute(command);
+    }
+    if let Some(pattern) = args.search.as_ref().or(less.search.as_ref()) {
+        ui.search(pattern);
+    }
     let res = ui.run().await;
     term.lock().unwrap().as_mut().unwrap().cleanup();
-    return res;
+    if let Some(exec) = &exec {
+        exec.kill();
+    }
+
+    let (path, line, offset) = ui.position();
+    if let Some(temp_dir) = temp_dir {
+        temp_dir.close().ok();
+    } else if resume {
+        state.set_position(
+            &path,
+            FilePosition {
+                line,
+                offset,
+                size: fs::metadata(&path).map(|x| x.len()).unwrap_or(0),
+                ..Default::default()
+            },
+        );
+        if let Err(e) = state.save() {
+            warn!("cannot save the position: {}", e);
+        }
+    }
+
+    for path in ui.print_on_exit() {
+        io::copy(&mut fs::File::open(path)?, &mut io::stdout())?;
+        fs::remove_file(path)?;
+    }
+
+    if let Some(output) = args.print_position_on_exit {
+        let position = format!(
+            "line={} byte={}\n",
+            line.map(|x| x.to_string()).unwrap_or("?".to_owned()),
+            offset
+        );
+        match output {
+            Some(path) => fs::write(path, position)?,
+            None => print!("{}", position),
+        }
+    }
+    res?;
+
+    // set by Q, :q <code> or a --search that matched nothing
+    let exit_code = ui.exit_code();
+    if exit_code != 0 {
+        io::stdout().flush()?;
+        process::exit(exit_code);
+    }
+    return Ok(());
 }
@@ -0,0 +1,98 @@
+use crate::ui::options::OPTIONS;
+use clap::Command;
+
+// text for roff: backslashes and dashes escaped, and no line starting with a
+// dot or a quote which would make it a request
+fn escape(text: &str) -> String {
+    return text
+        .replace('\\', "\\e")
+        .replace('-', "\\-")
+        .lines()
+        .map(|line| {
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{}", line)
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+}
+
+// man page of the command line flags and of the options of :set
+pub fn man_page(mut command: Command) -> String {
+    // adds --help, and drops --version without version
+    command.build();
+    let name = command.get_name();
+    let mut page = vec![
+        format!(".TH {} 1", name.to_uppercase()),
+        ".SH NAME".to_owned(),
+        format!("{} \\- better less, a pager for large log files", name),
+        ".SH SYNOPSIS".to_owned(),
+        format!("\\fB{}\\fR [\\fIOPTIONS\\fR] [\\fIPATH\\fR]", name),
+        ".SH DESCRIPTION".to_owned(),
+        "Shows a file with search, filters, marks and colors, without reading it \
+         all first. Press ? for the keys."
+            .to_owned(),
+        ".SH OPTIONS".to_owned(),
+    ];
+    for arg in command.get_arguments().filter(|x| !x.is_hide_set()) {
+        let mut flag = match (arg.get_short(), arg.get_long()) {
+            (Some(short), Some(long)) => {
+                format!("\\fB\\-{}\\fR, \\fB\\-\\-{}\\fR", short, escape(long))
+            }
+            (None, Some(long)) => format!("\\fB\\-\\-{}\\fR", escape(long)),
+            (Some(short), None) => format!("\\fB\\-{}\\fR", short),
+            (None, None) => format!("\\fI{}\\fR", arg.get_id().to_uppercase()),
+        };
+        if arg.get_long().is_some() && arg.is_takes_value_set() {
+            match arg.get_value_names() {
+                Some(values) => {
+                    for value in values {
+                        flag.push_str(&format!(" \\fI{}\\fR", value));
+                    }
+                }
+                None => flag.push_str(&format!(" \\fI{}\\fR", arg.get_id().to_uppercase())),
+            }
+        }
+        page.push(".TP".to_owned());
+        page.push(flag);
+        page.push(escape(arg.get_help().unwrap_or("")));
+    }
+
+    page.push(".SH SET OPTIONS".to_owned());
+    page.push(
+        "Set with :set <option>=<value> while running, or in the options of the config file."
+            .to_owned(),
+    );
+    for spec in OPTIONS.iter() {
+        let mut name = format!("\\fB{}\\fR", escape(spec.name));
+        for alias in spec.aliases.iter() {
+            name.push_str(&format!(", \\fB{}\\fR", escape(alias)));
+        }
+        page.push(".TP".to_owned());
+        page.push(format!("{} ({})", name, spec.kind.name()));
+        page.push(escape(spec.help));
+    }
+
+    page.push(".SH FILES".to_owned());
+    page.push(".TP".to_owned());
+    page.push("\\fI~/.config/bless/config.toml\\fR".to_owned());
+    page.push("Configuration, themes, aliases and initial option values.".to_owned());
+    page.push(".TP".to_owned());
+    page.push("\\fI~/.config/bless/blessrc\\fR".to_owned());
+    page.push("Commands run at startup, one per line.".to_owned());
+    page.push(String::new());
+    return page.join("\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roff_escapes() {
+        assert_eq!(escape("--no-header"), "\\-\\-no\\-header");
+        assert_eq!(escape("a\\b\n.x\n'y"), "a\\eb\n\\&.x\n\\&'y");
+    }
+}
@@ -0,0 +1,180 @@
+use crate::utils::text::{escape_controls, escape_controls_but_sgr};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tracing::{error, info};
+use unicode_width::UnicodeWidthChar;
+
+// columns taken by a tab when counting the rows of the text
+const TAB_WIDTH: usize = 4;
+
+// files larger than this are never shown on one screen, without reading them
+const MAX_ONE_SCREEN_SIZE: u64 = 0x100000;
+
+// interval between two checks of whether the command of --exec exited
+const EXEC_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// exit status of the command of --exec, none while it runs
+pub type ExecStatus = Arc<Mutex<Option<ExitStatus>>>;
+
+// rows taken by the text on a screen of `width` columns, with wrapped lines
+fn screen_rows(data: &[u8], width: usize) -> usize {
+    let text = String::from_utf8_lossy(data);
+    return text
+        .split_terminator('\n')
+        .map(|line| {
+            let columns = line
+                .chars()
+                .map(|c| match c {
+                    '\t' => TAB_WIDTH,
+                    c => UnicodeWidthChar::width(c).unwrap_or(0),
+                })
+                .sum::<usize>();
+            return columns.div_ceil(width.max(1)).max(1);
+        })
+        .sum();
+}
+
+// read until the end of the input or until it fills more than a screen,
+// returns true at the end of the input
+pub fn read_screen(
+    reader: &mut impl Read,
+    data: &mut Vec<u8>,
+    width: usize,
+    height: usize,
+) -> io::Result<bool> {
+    let mut chunk = [0; 0x1000];
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        data.extend_from_slice(&chunk[..read]);
+        if screen_rows(data, width) > height {
+            return Ok(false);
+        }
+    }
+}
+
+// write a text read by read_screen with its controls escaped like bless shows
+// them, its colors are kept when `colors` is set
+pub fn print_screen(data: &[u8], colors: bool) -> io::Result<()> {
+    let text = String::from_utf8_lossy(data);
+    let mut output = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let (line, end) = match line.strip_suffix("\r\n") {
+            Some(line) => (line, "\r\n"),
+            None => match line.strip_suffix('\n') {
+                Some(line) => (line, "\n"),
+                None => (line, ""),
+            },
+        };
+        match colors {
+            true => output.push_str(&escape_controls_but_sgr(line)),
+            false => output.push_str(&escape_controls(line)),
+        }
+        output.push_str(end);
+    }
+    if colors {
+        output.push_str("\x1b[0m");
+    }
+    let mut stdout = io::stdout();
+    stdout.write_all(output.as_bytes())?;
+    return stdout.flush();
+}
+
+// content of a file when it fits on a screen
+pub fn one_screen_file(path: &str, width: usize, height: usize) -> io::Result<Option<Vec<u8>>> {
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_file() || metadata.len() > MAX_ONE_SCREEN_SIZE {
+        return Ok(None);
+    }
+    let mut data = Vec::new();
+    if read_screen(&mut File::open(path)?, &mut data, width, height)? {
+        return Ok(Some(data));
+    }
+    return Ok(None);
+}
+
+// write the data already read from stdin to `path`, then copy the rest of
+// stdin in the background, bless reads the file as it grows
+pub fn spool_stdin(path: &Path, head: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(head)?;
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut chunk = [0; 0x10000];
+        loop {
+            let read = match stdin.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    error!("error reading stdin: {}", e);
+                    break;
+                }
+            };
+            if let Err(e) = file.write_all(&chunk[..read]) {
+                error!("error writing stdin: {}", e);
+                break;
+            }
+        }
+        info!("end of stdin");
+    });
+    return Ok(());
+}
+
+// a command run by --exec, its output is written to a file bless follows
+pub struct Exec {
+    child: Arc<Mutex<Child>>,
+    pub status: ExecStatus,
+}
+
+impl Exec {
+    // run `command` with sh, stdout and stderr are both written to `path`
+    pub fn spawn(path: &Path, command: &str) -> io::Result<Self> {
+        let output = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            // the terminal belongs to bless
+            .stdin(Stdio::null())
+            .stdout(output.try_clone()?)
+            .stderr(output)
+            .spawn()?;
+        let child = Arc::new(Mutex::new(child));
+        let status: ExecStatus = Arc::new(Mutex::new(None));
+        let (child_copy, status_copy) = (child.clone(), status.clone());
+        thread::spawn(move || loop {
+            match child_copy.lock().unwrap().try_wait() {
+                Ok(Some(exit_status)) => {
+                    info!("command exited: {}", exit_status);
+                    *status_copy.lock().unwrap() = Some(exit_status);
+                    break;
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    error!("error waiting for the command: {}", e);
+                    break;
+                }
+            }
+            thread::sleep(EXEC_POLL_INTERVAL);
+        });
+        return Ok(Self { child, status });
+    }
+
+    // stop the command when quitting before it exited
+    pub fn kill(&self) {
+        let mut child = self.child.lock().unwrap();
+        if let Ok(None) = child.try_wait() {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    errors::Result,
+    file_buffer::{cat_path, is_cat, CAT_SEPARATOR},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs,
+    io::Write,
+    path::{self, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tempfile::NamedTempFile;
+use tracing::info;
+
+// files whose position is kept, the ones closed the longest ago are dropped
+const MAX_POSITIONS: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub enum StateError {
+    NoDataDir,
+    InvalidSessionName(String),
+    UnknownSession(String),
+    EmptySession(String),
+}
+
+impl Display for StateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoDataDir => f.write_str("no data directory"),
+            Self::InvalidSessionName(x) => write!(f, "invalid session name: {}", x),
+            Self::UnknownSession(x) => write!(f, "unknown session: {}", x),
+            Self::EmptySession(x) => write!(f, "session without file: {}", x),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct FilePosition {
+    pub line: Option<i64>,
+    pub offset: u64,
+    // size of the file when it was closed, used to detect truncated files
+    pub size: u64,
+    // when the file was closed, in seconds since the epoch
+    #[serde(default)]
+    pub closed: u64,
+}
+
+// data kept between sessions, indexed by canonical file path
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct State {
+    pub positions: HashMap<String, FilePosition>,
+}
+
+impl State {
+    pub fn path() -> Option<PathBuf> {
+        return dirs::data_dir().map(|dir| dir.join("bless").join("state.json"));
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = match State::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(State::default()),
+        };
+        info!("loading state from {}", path.display());
+        let content = fs::read_to_string(&path)?;
+        return Ok(serde_json::from_str(&content)?);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = match State::path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        return write_atomically(&path, &serde_json::to_string_pretty(self)?);
+    }
+
+    pub fn key(path: &str) -> String {
+        return fs::canonicalize(path)
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or(path.to_owned());
+    }
+
+    // last position in the file, None if the file shrunk since
+    pub fn position(&self, path: &str) -> Option<FilePosition> {
+        let position = self.positions.get(&State::key(path))?;
+        let size = fs::metadata(path).map(|x| x.len()).unwrap_or(0);
+        if size < position.size {
+            return None;
+        }
+        return Some(*position);
+    }
+
+    // the position is stamped with the current time
+    pub fn set_position(&mut self, path: &str, position: FilePosition) {
+        let closed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+        self.positions
+            .insert(State::key(path), FilePosition { closed, ..position });
+        let extra = self.positions.len().saturating_sub(MAX_POSITIONS);
+        if extra > 0 {
+            let mut by_age: Vec<(u64, String)> = self
+                .positions
+                .iter()
+                .map(|(key, x)| (x.closed, key.clone()))
+                .collect();
+            by_age.sort_unstable();
+            for (_, key) in by_age.into_iter().take(extra) {
+                self.positions.remove(&key);
+            }
+        }
+    }
+}
+
+// tabs and search saved with :mksession
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Session {
+    pub tabs: Vec<TabSession>,
+    // index of the tab shown
+    pub current: usize,
+    pub search: Option<String>,
+}
+
+// file of a tab, with where it was read and what it showed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct TabSession {
+    pub path: String,
+    pub offset: u64,
+    // byte offset of each mark
+    pub marks: HashMap<String, u64>,
+    pub filter: Option<String>,
+    pub source_filter: Option<String>,
+}
+
+impl Session {
+    pub fn path(name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(StateError::InvalidSessionName(name.to_owned()).into());
+        }
+        let dir = dirs::data_dir().ok_or(StateError::NoDataDir)?;
+        return Ok(dir
+            .join("bless")
+            .join("sessions")
+            .join(format!("{}.json", name)));
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        let path = Session::path(name)?;
+        if !path.exists() {
+            return Err(StateError::UnknownSession(name.to_owned()).into());
+        }
+        info!("loading session from {}", path.display());
+        let content = fs::read_to_string(&path)?;
+        let session: Session = serde_json::from_str(&content)?;
+        if session.tabs.is_empty() {
+            return Err(StateError::EmptySession(name.to_owned()).into());
+        }
+        return Ok(session);
+    }
+
+    pub fn save(&self, name: &str) -> Result<()> {
+        let path = Session::path(name)?;
+        info!("saving session to {}", path.display());
+        return write_atomically(&path, &serde_json::to_string_pretty(self)?);
+    }
+}
+
+// the path of a tab made absolute, each file of several read as one
+pub fn absolute_path(path: &str) -> String {
+    if is_cat(path) {
+        let paths: Vec<String> = path.split(CAT_SEPARATOR).map(absolute_path).collect();
+        return cat_path(&paths);
+    }
+    return path::absolute(path)
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or(path.to_owned());
+}
+
+// written next to the path then renamed, an interrupted write or another
+// instance saving meanwhile leaves a complete file
+fn write_atomically(path: &Path, content: &str) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let mut file = NamedTempFile::new_in(dir)?;
+    file.write_all(content.as_bytes())?;
+    file.persist(path).map_err(|e| e.error)?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDir;
+
+    #[test]
+    fn oldest_positions_dropped() {
+        let dir = TestDir::new("state");
+        let path = dir.write("log", "hello\n");
+        let mut state = State::default();
+        for n in 0..MAX_POSITIONS {
+            let position = FilePosition {
+                closed: n as u64,
+                ..Default::default()
+            };
+            state.positions.insert(format!("/old/{}", n), position);
+        }
+        state.set_position(&path, FilePosition::default());
+        assert_eq!(state.positions.len(), MAX_POSITIONS);
+        assert!(!state.positions.contains_key("/old/0"));
+        assert!(state.positions.contains_key("/old/1"));
+        assert!(state.position(&path).is_some());
+    }
+
+    #[test]
+    fn absolute_paths() {
+        let cwd = std::env::current_dir().unwrap();
+        let absolute = |x: &str| cwd.join(x).to_string_lossy().into_owned();
+        assert_eq!(absolute_path("app.log"), absolute("app.log"));
+        assert_eq!(absolute_path("/var/log/syslog"), "/var/log/syslog");
+        let cat = cat_path(&["a.log".to_owned(), "/b.log".to_owned()]);
+        assert_eq!(
+            absolute_path(&cat),
+            cat_path(&[absolute("a.log"), "/b.log".to_owned()])
+        );
+    }
+
+    #[test]
+    fn atomic_write() {
+        let dir = TestDir::new("state-write");
+        let path = dir.path("state.json");
+        fs::write(&path, "previous").unwrap();
+        write_atomically(&path, "next").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "next");
+        // no temporary file left
+        assert_eq!(fs::read_dir(dir.path("")).unwrap().count(), 1);
+    }
+}
@@ -1,19 +1,32 @@
 use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use log::error;
 use std::io;
+use tracing::error;
 
 pub struct ConfigureTerm {
     is_cleanup: bool,
+    mouse: bool,
+    // without it the last screen stays in the terminal after quitting
+    alternate_screen: bool,
 }
 
 impl ConfigureTerm {
-    pub fn new() -> io::Result<ConfigureTerm> {
+    pub fn new(mouse: bool, alternate_screen: bool) -> io::Result<ConfigureTerm> {
         enable_raw_mode()?;
-        execute!(io::stdout(), EnterAlternateScreen)?;
-        return Ok(ConfigureTerm { is_cleanup: false });
+        if alternate_screen {
+            execute!(io::stdout(), EnterAlternateScreen)?;
+        }
+        if mouse {
+            execute!(io::stdout(), EnableMouseCapture)?;
+        }
+        return Ok(ConfigureTerm {
+            is_cleanup: false,
+            mouse,
+            alternate_screen,
+        });
     }
 
     pub fn cleanup(&mut self) {
@@ -21,9 +34,15 @@ impl ConfigureTerm {
             return;
         }
         // try to reset befor the panic to get the info properly
+        if self.mouse {
+            execute!(io::stdout(), DisableMouseCapture)
+                .unwrap_or_else(|e| error!("error disabling mouse capture: {}", e));
+        }
         disable_raw_mode().unwrap_or_else(|e| error!("error disabling raw mode: {}", e));
-        execute!(io::stdout(), LeaveAlternateScreen)
-            .unwrap_or_else(|e| error!("error leaving alternate screen: {}", e));
+        if self.alternate_screen {
+            execute!(io::stdout(), LeaveAlternateScreen)
+                .unwrap_or_else(|e| error!("error leaving alternate screen: {}", e));
+        }
         self.is_cleanup = true;
     }
 }
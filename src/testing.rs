@@ -0,0 +1,241 @@
+// fixtures shared by the tests: files in a temporary directory, a clock that
+// only the test moves, and a backend driven like the frontend does
+use crate::{
+    errors::Result,
+    file_buffer::FileBuffer,
+    file_view::FileView,
+    ui::{
+        backend::{Backend, BackendState, Command},
+        file_watch::FileWatch,
+        sources::{Sources, SystemSources},
+    },
+};
+use async_trait::async_trait;
+use std::{
+    fs::{self, OpenOptions},
+    future::Future,
+    io::Write,
+    ops::Range,
+    path::PathBuf,
+    rc::Rc,
+};
+use tempfile::TempDir;
+use tokio::{
+    select,
+    sync::{
+        mpsc::{self, UnboundedSender},
+        watch::{self, Receiver},
+    },
+    task::{spawn_local, LocalSet},
+    time::{self, Duration, Instant},
+};
+
+// longest wait for a state, in real time
+const STATE_TIMEOUT: Duration = Duration::from_secs(10);
+// real time between two checks of the states, the backend reads the disk
+// meanwhile
+const STATE_POLL: Duration = Duration::from_millis(1);
+// time the clock of the backend moves between two checks of the states
+const CLOCK_STEP: Duration = Duration::from_millis(50);
+
+// files of a test, removed at the end
+pub struct TestDir(TempDir);
+
+impl TestDir {
+    pub fn new(name: &str) -> Self {
+        let dir = tempfile::Builder::new()
+            .prefix(&format!("bless-test-{}-", name))
+            .tempdir()
+            .unwrap();
+        return Self(dir);
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        return self.0.path().join(name);
+    }
+
+    pub fn write(&self, name: &str, data: impl AsRef<[u8]>) -> String {
+        let path = self.path(name);
+        fs::write(&path, data).unwrap();
+        return path.to_string_lossy().into_owned();
+    }
+
+    pub fn append(&self, name: &str, data: &str) {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(self.path(name))
+            .unwrap();
+        file.write_all(data.as_bytes()).unwrap();
+    }
+}
+
+// the files on disk with a clock that stands still until advanced
+pub struct TestSources {
+    start: Instant,
+    elapsed: watch::Sender<Duration>,
+    // watch the files or leave the clock alone to wake the backend
+    watch: bool,
+}
+
+impl TestSources {
+    pub fn new(watch: bool) -> Self {
+        let (elapsed, _) = watch::channel(Duration::ZERO);
+        return Self {
+            start: Instant::now(),
+            elapsed,
+            watch,
+        };
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.send_modify(|x| *x += duration);
+    }
+}
+
+#[async_trait(?Send)]
+impl Sources for TestSources {
+    fn now(&self) -> Instant {
+        return self.start + *self.elapsed.borrow();
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        let mut elapsed = self.elapsed.subscribe();
+        let end = elapsed.borrow().saturating_add(duration);
+        while *elapsed.borrow_and_update() < end {
+            elapsed.changed().await.ok();
+        }
+    }
+
+    async fn open(&self, path: &str) -> Result<FileView> {
+        return FileView::new(path).await;
+    }
+
+    fn watch(&self, path: &str) -> Option<FileWatch> {
+        return match self.watch {
+            true => SystemSources.watch(path),
+            false => None,
+        };
+    }
+}
+
+// a backend running on the local set, driven with commands like the frontend does
+pub struct Driver {
+    commands: UnboundedSender<Command>,
+    _cancel: UnboundedSender<()>,
+    pub states: Receiver<BackendState>,
+    pub sources: Rc<TestSources>,
+    // time the clock moves each time the states are checked
+    pub step: Duration,
+}
+
+impl Driver {
+    // a backend polling the file on the clock of the test
+    pub async fn start(path: &str, height: usize) -> Self {
+        return Self::start_with(path, height, TestSources::new(false)).await;
+    }
+
+    pub async fn start_with(path: &str, height: usize, sources: TestSources) -> Self {
+        let (state_sender, states) = watch::channel(BackendState::new());
+        let (activity_sender, _) = watch::channel(None);
+        let (commands, command_receiver) = mpsc::unbounded_channel();
+        let (cancel, cancel_receiver) = mpsc::unbounded_channel();
+        let sources = Rc::new(sources);
+        let mut backend = Backend::new(
+            command_receiver,
+            cancel_receiver,
+            state_sender,
+            activity_sender,
+            path,
+            60,
+            sources.clone(),
+        )
+        .await
+        .unwrap();
+        spawn_local(async move { backend.run().await });
+        let driver = Self {
+            commands,
+            _cancel: cancel,
+            states,
+            sources,
+            step: CLOCK_STEP,
+        };
+        driver.send(Command::Resize(None, height));
+        return driver;
+    }
+
+    pub fn send(&self, command: Command) {
+        self.commands.send(command).unwrap();
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        return self
+            .states
+            .borrow()
+            .text
+            .iter()
+            .map(|x| x.text.clone())
+            .collect();
+    }
+
+    // wait for the first state accepted by the check, the test fails
+    // with the last state otherwise
+    pub async fn wait_for(&mut self, what: &str, check: impl Fn(&BackendState) -> bool) {
+        let start = std::time::Instant::now();
+        while !check(&self.states.borrow()) {
+            if start.elapsed() > STATE_TIMEOUT {
+                let state = self.states.borrow();
+                panic!(
+                    "no state with {}, last lines {:?}, errors {:?}",
+                    what,
+                    self.lines(),
+                    state
+                        .errors
+                        .iter()
+                        .map(|x| x.to_string())
+                        .collect::<Vec<_>>()
+                );
+            }
+            select! {
+                _ = self.states.changed() => (),
+                _ = time::sleep(STATE_POLL) => (),
+            }
+            self.sources.advance(self.step);
+        }
+    }
+}
+
+// run a test that spawns local tasks, like the backend
+pub async fn run_local<F: Future>(test: F) -> F::Output {
+    return LocalSet::new().run_until(test).await;
+}
+
+pub fn lines(range: Range<usize>) -> String {
+    return range.map(|x| format!("line {}\n", x)).collect();
+}
+
+pub fn has_line(state: &BackendState, text: &str) -> bool {
+    return state.text.iter().any(|x| x.text == text);
+}
+
+// lines of text to compress, all different
+pub fn text(count: usize) -> Vec<u8> {
+    return (0..count)
+        .map(|n| format!("line {} hello world\n", n))
+        .collect::<String>()
+        .into_bytes();
+}
+
+// the whole content of a buffer, loaded from its start
+pub async fn load_forward(buffer: &mut dyn FileBuffer) -> Vec<u8> {
+    buffer.jump(0).unwrap();
+    while buffer.load_next().await.unwrap() > 0 {}
+    return buffer.data().to_vec();
+}
+
+// the whole content of a buffer, loaded from its end
+pub async fn load_backward(buffer: &mut dyn FileBuffer) -> Vec<u8> {
+    let size = buffer.total_size().await.unwrap();
+    buffer.jump(size).unwrap();
+    while buffer.load_prev().await.unwrap() > 0 {}
+    return buffer.data().to_vec();
+}
@@ -1,22 +1,53 @@
-use log::info;
+use human_bytes::human_bytes;
+use lru::LruCache;
 use regex::bytes;
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    cmp::{max, min},
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
     error::Error,
-    fs::canonicalize,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::BufWriter,
+    ops::Range,
     rc::Rc,
     sync::atomic::{AtomicBool, Ordering},
 };
 use tokio::{
     select,
-    sync::{mpsc::UnboundedReceiver, watch::Sender},
-    time::{self, Duration},
+    sync::{
+        mpsc::{error::TryRecvError, UnboundedReceiver},
+        watch::Sender,
+    },
+    task::yield_now,
+    time::{Duration, Instant},
 };
+use tracing::{debug, info, info_span, Instrument};
 
 use crate::{
+    crash,
     errors::Result,
-    file_view::{FileView, ViewError, ViewState},
-    ui::errors::{BackendError, ChannelError},
+    file_buffer::real_path,
+    file_view::{FileView, Line, ViewError, ViewState},
+    state::Session,
+    ui::{
+        errors::{BackendError, ChannelError},
+        file_info::FileInfo,
+        file_watch::FileWatch,
+        grep_out::GrepOut,
+        line_index::LineIndex,
+        match_scan::{MatchMap, MatchScan},
+        sources::Sources,
+    },
+    utils::{
+        filter::Predicate,
+        language::{interesting_words, WordStats},
+        log::{LogStats, Timestamp},
+        pattern::regex_error,
+        text::DEFAULT_MAX_RENDER_COLS,
+        words::WordSeparators,
+        wrap::{TabStops, WrapIndent, DEFAULT_TAB_WIDTH},
+    },
 };
 
 #[derive(Clone, PartialEq, Debug)]
@@ -24,25 +55,219 @@ pub enum Command {
     MoveLine(i64),
     JumpLine(i64),
     JumpFileRatio(f64),
+    JumpByte(u64),
+    // jump to the first byte added to the file since it was opened
+    JumpNew,
+    // open the file again, even when its path did not change
+    Reload,
+    // reload the file when replaced or rewritten at the same path
+    AutoReload(bool),
     SearchDown(String),
-    SearchDownNext(String),
-    SearchUp(String),
+    // the pattern and the number of matches to move by
+    SearchDownNext(String, i64),
+    SearchUp(String, i64),
     Follow(bool),
     Resize(Option<usize>, usize),
     SaveMark(String),
+    // place a mark at a byte offset, like the marks of a session
+    SetMark(String, u64),
     LoadMark(String),
+    // compute the N most interesting words of the view, None to disable
+    Entropy(Option<usize>),
+    // export word statistics of the whole file, as JSON or CSV depending on the extension
+    ExportWordStats(String),
+    // characters splitting words, on top of whitespace
+    WordSeparators(WordSeparators),
+    // compute log statistics over the whole file or a range of lines
+    Stats(Option<(i64, i64)>),
+    // scan the file in the background for matches of the pattern, None to stop
+    MatchMap(Option<String>),
+    GetLine(i64),
+    StripCr(bool),
+    // tab stops and wrap indent of the frontend, to count the rows of the
+    // wrapped lines like it does
+    Layout(TabStops, WrapIndent),
+    // show the control characters of the file as they are
+    AnsiPassthrough(bool),
+    // characters of a line shown before the frontend cuts it, 0 for no limit
+    MaxRenderCols(usize),
+    // check the crc of the compressed blocks read
+    VerifyChecksums(bool),
+    // drop the pages searches and background scans read from the page cache
+    NoCache(bool),
+    // stop searches after a while, None to search until the end of the file
+    SearchTimeout(Option<Duration>),
+    // resume the last timed out search where it stopped
+    ContinueSearch,
+    // where searches put the line of the match
+    SearchOffset(SearchOffset),
+    // lines shown above and below the current line, which moves apart from
+    // the top of the view
+    ScrollOff(usize),
+    // put the line of the jumps and marks in the middle of the view
+    JumpCenter(bool),
+    // save the tabs and search pattern gathered by the frontend under a
+    // session name
+    SaveSession(String, Session),
+    // find the first bad line, marking the top line as good or bad
+    Bisect(BisectStep),
+    // write the lines matching a pattern to a file, with some lines of context around them
+    GrepOut(String, String, usize),
+    // show only the lines matching a filter expression, None to show all lines
+    Filter(Option<String>),
+    // show only the lines of the files whose path contains a text, in views of several files
+    SourceFilter(Option<String>),
+    // show all the lines, the frontend dims those not matching the filter
+    Focus(bool),
+}
+
+// long work of the backend, shown by the frontend while it runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Searching,
+    Filtering,
+    // moving in a compressed file
+    Decoding,
+    // counting the lines from the top of the file
+    Counting,
+    // finding the line number of the view in the background
+    Indexing,
+    // reading the whole file, for statistics
+    Scanning,
+}
+
+impl Operation {
+    pub fn name(self) -> &'static str {
+        return match self {
+            Operation::Searching => "searching",
+            Operation::Filtering => "filtering",
+            Operation::Decoding => "decoding",
+            Operation::Counting => "counting lines",
+            Operation::Indexing => "indexing",
+            Operation::Scanning => "scanning the file",
+        };
+    }
+
+    // Esc stops it, the background work keeps going
+    pub fn abortable(self) -> bool {
+        return self != Operation::Indexing;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Activity {
+    pub operation: Operation,
+    pub started: Instant,
+    // fraction done, when known
+    pub progress: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BisectStep {
+    // halve the lines left by bytes, or by the time of their timestamps
+    Start { by_time: bool },
+    Good,
+    Bad,
+    // stop and go back to where the bisect started
+    Reset,
+}
+
+// where a search puts the line of the match, like the -j option of less
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchOffset {
+    Top,
+    Middle,
+    // lines above the match
+    Lines(usize),
+}
+
+impl SearchOffset {
+    // "top", "middle" or a number of lines
+    pub fn parse(value: &str) -> Option<Self> {
+        return match value.trim() {
+            "top" => Some(SearchOffset::Top),
+            "middle" => Some(SearchOffset::Middle),
+            lines => lines.parse().ok().map(SearchOffset::Lines),
+        };
+    }
+
+    pub fn text(self) -> String {
+        return match self {
+            SearchOffset::Top => "top".to_owned(),
+            SearchOffset::Middle => "middle".to_owned(),
+            SearchOffset::Lines(lines) => lines.to_string(),
+        };
+    }
+
+    // lines above the match in a view of this height
+    fn lines(self, height: usize) -> usize {
+        return match self {
+            SearchOffset::Top => 0,
+            SearchOffset::Middle => height / 2,
+            SearchOffset::Lines(lines) => lines.min(height.saturating_sub(1)),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewPosition {
+    Empty,
+    Top,
+    // the last line is on screen, even when the top is too
+    End,
+    // position of the last byte on screen, estimated for compressed files
+    Percent(f64),
 }
 
 pub struct BackendState {
     pub file_path: String,
     pub real_file_path: String,
+    // file of the top line when several files are read as one
+    pub source: Option<String>,
     pub file_size: u64,
     pub errors: Vec<Rc<Box<dyn Error>>>,
+    pub notices: Vec<Rc<String>>,
     pub current_line: Option<i64>,
     pub offset: u64,
-    pub text: Vec<String>,
+    // offset of the line and byte range in it of the match n and N start from
+    pub current_match: Option<(u64, Range<usize>)>,
+    // row of the current line in the text, when it is not the top line
+    pub cursor_row: Option<usize>,
+    pub position: ViewPosition,
+    pub compressed: bool,
+    pub text: Rc<Vec<Line>>,
     pub follow: bool,
-    pub marks: Vec<String>,
+    pub crlf: bool,
+    // lines added to the file while following is paused
+    pub new_lines: Option<u64>,
+    // bytes added to the file since it was opened, while not following
+    pub grown: Option<u64>,
+    // user marks and their offset, by name
+    pub marks: Vec<(String, u64)>,
+    pub entropy_words: Vec<String>,
+    pub stats: Option<Rc<LogStats>>,
+    pub match_map: Option<Rc<MatchMap>>,
+    // last line requested with GetLine, not truncated
+    pub line: Option<(i64, Rc<String>)>,
+    // bytes left to check while bisecting
+    pub bisect_left: Option<u64>,
+    // percentage of the file scanned by a running :grep-out
+    pub grep_out_progress: Option<f64>,
+    pub filter: Option<String>,
+    pub source_filter: Option<String>,
+    // some lines of the view had invalid UTF-8 replaced
+    pub invalid_utf8: bool,
+    pub file_info: Option<FileInfo>,
+    // time taken by the last commands and to build this state, for :profile.
+    // They are not compared, a new time shows with the next state sent
+    pub command_time: Duration,
+    pub state_time: Duration,
+}
+
+impl Default for BackendState {
+    fn default() -> Self {
+        return Self::new();
+    }
 }
 
 impl BackendState {
@@ -50,33 +275,293 @@ impl BackendState {
         return Self {
             file_path: String::new(),
             real_file_path: String::new(),
-            text: Vec::new(),
+            source: None,
+            text: Rc::new(Vec::new()),
             errors: Vec::new(),
+            notices: Vec::new(),
             follow: false,
+            crlf: false,
+            new_lines: None,
+            grown: None,
             file_size: 0,
             current_line: None,
             offset: 0,
+            current_match: None,
+            cursor_row: None,
+            position: ViewPosition::Empty,
+            compressed: false,
             marks: Vec::new(),
+            entropy_words: Vec::new(),
+            stats: None,
+            match_map: None,
+            line: None,
+            bisect_left: None,
+            grep_out_progress: None,
+            filter: None,
+            source_filter: None,
+            invalid_utf8: false,
+            file_info: None,
+            command_time: Duration::ZERO,
+            state_time: Duration::ZERO,
         };
     }
+
+    // true when both states would render the same
+    pub fn same_as(&self, other: &Self) -> bool {
+        fn same_rc<T: ?Sized>(a: &Option<Rc<T>>, b: &Option<Rc<T>>) -> bool {
+            return match (a, b) {
+                (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            };
+        }
+
+        return self.file_path == other.file_path
+            && self.real_file_path == other.real_file_path
+            && self.source == other.source
+            && self.file_size == other.file_size
+            && self.errors.len() == other.errors.len()
+            && self
+                .errors
+                .iter()
+                .zip(other.errors.iter())
+                .all(|(a, b)| Rc::ptr_eq(a, b))
+            && self.notices.len() == other.notices.len()
+            && self
+                .notices
+                .iter()
+                .zip(other.notices.iter())
+                .all(|(a, b)| Rc::ptr_eq(a, b))
+            && self.current_line == other.current_line
+            && self.offset == other.offset
+            && self.current_match == other.current_match
+            && self.cursor_row == other.cursor_row
+            && self.position == other.position
+            && self.compressed == other.compressed
+            && (Rc::ptr_eq(&self.text, &other.text) || self.text == other.text)
+            && self.follow == other.follow
+            && self.crlf == other.crlf
+            && self.new_lines == other.new_lines
+            && self.grown == other.grown
+            && self.marks == other.marks
+            && self.bisect_left == other.bisect_left
+            && self.grep_out_progress == other.grep_out_progress
+            && self.filter == other.filter
+            && self.source_filter == other.source_filter
+            && self.invalid_utf8 == other.invalid_utf8
+            && self.file_info == other.file_info
+            && self.entropy_words == other.entropy_words
+            && same_rc(&self.stats, &other.stats)
+            && same_rc(&self.match_map, &other.match_map)
+            && match (&self.line, &other.line) {
+                (Some((a, a_text)), Some((b, b_text))) => a == b && Rc::ptr_eq(a_text, b_text),
+                (None, None) => true,
+                _ => false,
+            };
+    }
+}
+
+const MATCH_SCAN_PROGRESS_MS: u64 = 100;
+// files that cannot be watched are polled, less and less often while they do
+// not change
+const FOLLOW_POLL_MS: u64 = 50;
+// the file grows slowly when nobody follows it
+const GROWTH_POLL_MS: u64 = 1000;
+const IDLE_POLL_MS: u64 = 10000;
+const LINE_CACHE_SIZE: usize = 256;
+// numbered marks set by the jumps, '0 to '9
+const JUMP_MARKS: usize = 10;
+
+// merge the commands waiting in the channel so that a flood of key repeats
+// does not lag behind: consecutive moves are added and only the last resize
+// is kept
+fn coalesce(commands: Vec<Command>) -> Vec<Command> {
+    let last_resize = commands
+        .iter()
+        .rposition(|x| matches!(x, Command::Resize(..)));
+    let mut merged: Vec<Command> = Vec::new();
+    for (index, command) in commands.into_iter().enumerate() {
+        if matches!(command, Command::Resize(..)) && Some(index) != last_resize {
+            continue;
+        }
+        if let (Some(Command::MoveLine(total)), Command::MoveLine(lines)) =
+            (merged.last_mut(), &command)
+        {
+            *total = total.saturating_add(*lines);
+            continue;
+        }
+        merged.push(command);
+    }
+    return merged;
+}
+
+// never ready for a file that is not watched
+async fn file_changed(watch: Option<&FileWatch>) -> std::io::Result<()> {
+    return match watch {
+        Some(watch) => watch.changed().await,
+        None => std::future::pending().await,
+    };
+}
+
+// commands making a running search pointless
+fn supersedes_search(command: &Command) -> bool {
+    return matches!(
+        command,
+        Command::SearchDown(_)
+            | Command::SearchDownNext(..)
+            | Command::SearchUp(..)
+            | Command::JumpLine(_)
+            | Command::JumpFileRatio(_)
+            | Command::JumpByte(_)
+            | Command::JumpNew
+    );
 }
 
+// commands moving the view elsewhere, the position before them is marked
+fn is_jump(command: &Command) -> bool {
+    return matches!(
+        command,
+        Command::SearchDown(_)
+            | Command::SearchDownNext(..)
+            | Command::SearchUp(..)
+            | Command::ContinueSearch
+            | Command::JumpLine(_)
+            | Command::JumpFileRatio(_)
+            | Command::JumpByte(_)
+            | Command::JumpNew
+            | Command::LoadMark(_)
+            | Command::Bisect(_)
+    );
+}
+
+// position of the view when the file was opened
+pub const STARTUP_MARK: &str = "^";
+
+// marks set by bless rather than by the user: ' and the numbered ones by the
+// jumps, and the startup mark
+pub fn is_jump_mark(name: &str) -> bool {
+    return name == "'"
+        || name == STARTUP_MARK
+        || name.len() == 1 && name.as_bytes()[0].is_ascii_digit();
+}
+
+// a mark is anchored to the byte offset of its line, which stays valid while
+// the file grows and across reloads. The fingerprint of the line tells if it
+// is still the same line when the mark is loaded.
+#[derive(Clone)]
+struct Mark {
+    offset: u64,
+    // counted from the top, lines counted from the bottom move as the file grows
+    line: Option<i64>,
+    fingerprint: Option<u64>,
+}
+
+// lines left to check while bisecting, by offset of their start
+struct Bisect {
+    // first line not known to be good
+    low: u64,
+    // first line known to be bad, or the file size
+    bad: u64,
+    by_time: bool,
+    start: ViewState,
+}
+
+// view offset, hash of the text of the view, view width and words count the
+// entropy words were computed for, the text changes at the same offset when
+// the file is rewritten
+type EntropyKey = (u64, u64, Option<usize>, usize);
+
+// view offset, current line, file size, height and width the view was built
+// for, it is reused while they stay the same and no command ran
+type ViewKey = (u64, Option<i64>, u64, usize, Option<usize>);
+
 struct CommandHandler {
     command_receiver: UnboundedReceiver<Command>,
     state_sender: Sender<BackendState>,
+    activity_sender: Sender<Option<Activity>>,
     file_path: String,
     file_view: FileView,
     view_width: Option<usize>,
     view_height: usize,
     cancelled: Rc<AtomicBool>,
-    marks: HashMap<String, ViewState>,
+    // flag of the running search, a newer search or a timeout stops it alone
+    search_cancelled: Rc<RefCell<Option<Rc<AtomicBool>>>>,
+    marks: HashMap<String, Mark>,
     follow: bool,
+    // view kept at the end of the file to count new lines while following is paused
+    follow_pause: Option<FileView>,
+    new_lines: u64,
+    // size of the file when it was opened, what it gains after is new
+    opened_size: u64,
     command_errors: Vec<Rc<Box<dyn Error>>>,
+    command_notices: Vec<Rc<String>>,
+    entropy_words: Option<usize>,
+    entropy_cache: Option<(EntropyKey, Vec<String>)>,
+    view_cache: Option<(ViewKey, Rc<Vec<Line>>)>,
+    word_separators: WordSeparators,
+    stats: Option<Rc<LogStats>>,
+    match_scan: Option<MatchScan>,
+    match_map: Option<Rc<MatchMap>>,
+    // pattern of the match map, its matches are given with the lines
+    highlight: Option<bytes::Regex>,
+    match_scan_progress_sent: Instant,
+    grep_out: Option<GrepOut>,
+    grep_out_progress_sent: Instant,
+    // finds the line number of the view after jumps to a byte offset
+    line_index: Option<LineIndex>,
+    // minimum time between two states sent on file updates
+    state_interval: Duration,
+    state_sent: Instant,
+    state_pending: bool,
+    // view used to fetch lines without moving the main view
+    line_view: Option<FileView>,
+    line_cache: LruCache<i64, Rc<String>>,
+    // size of the file when the cached lines were read, they are stale once
+    // it shrinks
+    line_cache_size: u64,
+    line: Option<(i64, Rc<String>)>,
+    strip_cr: bool,
+    tab_stops: TabStops,
+    wrap_indent: WrapIndent,
+    ansi_passthrough: bool,
+    max_render_cols: usize,
+    verify_checksums: bool,
+    no_cache: bool,
+    auto_reload: bool,
+    bisect: Option<Bisect>,
+    filter: Option<String>,
+    // the filter is not applied to the view, see Command::Focus
+    focus: bool,
+    source_filter: Option<String>,
+    // commands received while searching, run before the next ones of the channel
+    queued: VecDeque<Command>,
+    search_timeout: Option<Duration>,
+    search_offset: SearchOffset,
+    scrolloff: usize,
+    jump_center: bool,
+    // the view built from above the current line starts at the top of the
+    // file, and the row of the current line in it
+    view_at_top: bool,
+    cursor_row: Option<usize>,
+    // pattern, direction and offset reached of the last timed out search
+    search_resume: Option<(String, bool, u64)>,
+    // offset of the line and byte range in it of the last match found
+    current_match: Option<(u64, Range<usize>)>,
+    file_info: Option<FileInfo>,
+    // the user was told the file was replaced
+    replace_noticed: bool,
+    // None when the file is polled instead
+    file_watch: Option<FileWatch>,
+    poll_interval: Duration,
+    // time taken by the last commands run together
+    command_time: Duration,
+    sources: Rc<dyn Sources>,
 }
 
 struct CancelHandler {
     cancel_receiver: UnboundedReceiver<()>,
     cancelled: Rc<AtomicBool>,
+    search_cancelled: Rc<RefCell<Option<Rc<AtomicBool>>>>,
 }
 
 pub struct Backend {
@@ -89,26 +574,86 @@ impl Backend {
         command_receiver: UnboundedReceiver<Command>,
         cancel_receiver: UnboundedReceiver<()>,
         state_sender: Sender<BackendState>,
+        activity_sender: Sender<Option<Activity>>,
         path: &str,
+        max_fps: u32,
+        sources: Rc<dyn Sources>,
     ) -> Result<Self> {
         let cancelled = Rc::from(AtomicBool::from(false));
-        let file_view = FileView::new(path).await?;
+        let search_cancelled = Rc::new(RefCell::new(None));
+        let mut file_view = sources.open(path).await?;
+        let now = sources.now();
+        let file_info = FileInfo::collect(path).ok();
+        let opened_size = file_view.file_size().await?;
         return Ok(Self {
             command_handler: CommandHandler {
                 command_receiver,
                 state_sender,
+                activity_sender,
                 file_path: path.to_string(),
                 file_view,
                 view_width: None,
                 view_height: 0,
                 cancelled: cancelled.clone(),
+                search_cancelled: search_cancelled.clone(),
                 follow: false,
+                follow_pause: None,
+                new_lines: 0,
+                opened_size,
                 command_errors: Vec::new(),
+                command_notices: Vec::new(),
                 marks: HashMap::new(),
+                entropy_words: None,
+                entropy_cache: None,
+                view_cache: None,
+                word_separators: WordSeparators::default(),
+                stats: None,
+                match_scan: None,
+                highlight: None,
+                match_map: None,
+                match_scan_progress_sent: now,
+                grep_out: None,
+                grep_out_progress_sent: now,
+                line_index: None,
+                state_interval: Duration::from_secs(1) / max_fps.max(1),
+                state_sent: now,
+                state_pending: false,
+                line_view: None,
+                line_cache: LruCache::new(LINE_CACHE_SIZE),
+                line_cache_size: 0,
+                line: None,
+                strip_cr: true,
+                tab_stops: TabStops::new(DEFAULT_TAB_WIDTH),
+                wrap_indent: WrapIndent::default(),
+                ansi_passthrough: false,
+                max_render_cols: DEFAULT_MAX_RENDER_COLS,
+                verify_checksums: false,
+                no_cache: false,
+                auto_reload: false,
+                bisect: None,
+                filter: None,
+                focus: false,
+                source_filter: None,
+                queued: VecDeque::new(),
+                search_timeout: None,
+                search_offset: SearchOffset::Top,
+                scrolloff: 0,
+                jump_center: false,
+                view_at_top: false,
+                cursor_row: None,
+                search_resume: None,
+                current_match: None,
+                file_info,
+                replace_noticed: false,
+                file_watch: sources.watch(path),
+                poll_interval: Duration::from_millis(GROWTH_POLL_MS),
+                command_time: Duration::ZERO,
+                sources,
             },
             cancel_handler: CancelHandler {
                 cancel_receiver,
                 cancelled: cancelled.clone(),
+                search_cancelled,
             },
         });
     }
@@ -125,7 +670,12 @@ impl CancelHandler {
     async fn run(&mut self) -> Result<()> {
         loop {
             match self.cancel_receiver.recv().await {
-                Some(_) => self.cancelled.store(true, Ordering::Release),
+                Some(_) => {
+                    self.cancelled.store(true, Ordering::Release);
+                    if let Some(search) = self.search_cancelled.borrow().as_ref() {
+                        search.store(true, Ordering::Release);
+                    }
+                }
                 None => return Err(ChannelError::Cancel.into()),
             }
         }
@@ -141,88 +691,608 @@ impl CommandHandler {
             if self.cancelled.load(Ordering::Acquire) {
                 // flush all pending commands
                 while let Ok(_) = self.command_receiver.try_recv() {}
+                self.queued.clear();
                 self.cancelled.store(false, Ordering::Release);
+                self.match_scan = None;
+                self.match_map = None;
+                self.grep_out = None;
             }
 
-            let sleep_time_ms = if self.follow { 100 } else { 10000 };
+            if let Some(command) = self.queued.pop_front() {
+                self.run_commands(command).await;
+                self.send_state().await?;
+                continue;
+            }
 
+            if self.match_scan.is_some() || self.grep_out.is_some() || self.line_index_pending() {
+                // background work: only proceed when there is no pending command
+                match self.command_receiver.try_recv() {
+                    Ok(command) => self.run_commands(command).await,
+                    Err(TryRecvError::Empty) => {
+                        let match_scan_sent = self.match_scan_step().await;
+                        let grep_out_sent = self.grep_out_step().await;
+                        if !self.line_index_step().await && !grep_out_sent && !match_scan_sent {
+                            continue;
+                        }
+                    }
+                    Err(TryRecvError::Disconnected) => return Err(ChannelError::Command.into()),
+                }
+                self.send_state().await?;
+                continue;
+            }
+
+            // nothing wakes the backend but commands and changes of a
+            // watched file
+            let (min_poll, max_poll) = self.poll_bounds();
+            let sleep_time = if self.state_pending {
+                Some(
+                    self.state_interval
+                        .saturating_sub(self.sources.elapsed(self.state_sent)),
+                )
+            } else if self.file_watch.is_none() {
+                Some(self.poll_interval.clamp(min_poll, max_poll))
+            } else {
+                None
+            };
+
+            let sources = self.sources.clone();
+            let mut from_command = false;
             select! {
                  msg = self.command_receiver.recv() => {
                     let command = match msg {
                         Some(command) => command,
                         None => return Err(ChannelError::Command.into()),
                     };
-
-                    self.command_errors.clear();
-                    if let Err(e) = self.handle_command(command).await {
-                        self.command_errors.push(Rc::from(e));
-                    }
+                    self.run_commands(command).await;
+                    from_command = true;
                 },
-                _ = time::sleep(Duration::from_millis(sleep_time_ms)) => {
-                    let file_size = self.file_view.file_size().await;
-                    if file_size == prev_file_size {
-                        continue;
+                _ = sources.sleep(sleep_time.unwrap_or(Duration::MAX)), if sleep_time.is_some() => (),
+                res = file_changed(self.file_watch.as_ref()) => {
+                    if let Err(e) = res {
+                        info!("stopped watching the file, polling it: {}", e);
+                        self.file_watch = None;
                     }
-                    prev_file_size = file_size;
                 },
             }
+            if !from_command {
+                // the error is given with the state
+                let file_size = self.file_view.file_size().await.unwrap_or(prev_file_size);
+                if file_size == prev_file_size && !self.state_pending && !self.replace_pending() {
+                    self.poll_interval = (self.poll_interval * 2).min(max_poll);
+                    continue;
+                }
+                prev_file_size = file_size;
+            }
+            self.poll_interval = min_poll;
 
             self.maybe_reload_file().await?;
 
-            if self.follow {
+            if let Some(tail) = self.follow_pause.as_mut() {
+                match tail.count_lines_to_end().await {
+                    Ok(count) => self.new_lines += count,
+                    Err(e) => self.command_errors.push(Rc::from(e)),
+                }
+            } else if self.follow {
                 while self.file_view.down(1_000_000).await.is_ok() {}
             }
 
+            // coalesce file updates, commands are answered right away
+            if !from_command && self.sources.elapsed(self.state_sent) < self.state_interval {
+                self.state_pending = true;
+                continue;
+            }
             self.send_state().await?;
         }
     }
 
+    // shortest and longest time between two polls of the file
+    fn poll_bounds(&self) -> (Duration, Duration) {
+        let (min, max) = match self.follow {
+            true => (FOLLOW_POLL_MS, GROWTH_POLL_MS),
+            false => (GROWTH_POLL_MS, IDLE_POLL_MS),
+        };
+        return (Duration::from_millis(min), Duration::from_millis(max));
+    }
+
+    // run a command with the ones already waiting behind it, a cancel drops
+    // the commands left
+    async fn run_commands(&mut self, command: Command) {
+        let started = self.sources.now();
+        let mut commands = vec![command];
+        while let Some(command) = self.next_command() {
+            commands.push(command);
+        }
+        for command in coalesce(commands) {
+            if self.cancelled.load(Ordering::Acquire) {
+                break;
+            }
+            self.run_command(command).await;
+        }
+        self.command_time = self.sources.elapsed(started);
+    }
+
+    fn next_command(&mut self) -> Option<Command> {
+        return self
+            .queued
+            .pop_front()
+            .or_else(|| self.command_receiver.try_recv().ok());
+    }
+
+    // search from the top of the view, a search or a jump received meanwhile
+    // cancels it and runs next
+    async fn search(&mut self, pattern: &str, down: bool, skip_current: bool) -> Result<()> {
+        let regex =
+            bytes::Regex::new(pattern).map_err(|e| ViewError::InvalidRegex(regex_error(&e)))?;
+        let cancelled = Rc::new(AtomicBool::new(self.cancelled.load(Ordering::Acquire)));
+        *self.search_cancelled.borrow_mut() = Some(cancelled.clone());
+        let mut superseded = false;
+        let mut timed_out = false;
+        let (start, started) = (self.file_view.offset(), self.sources.now());
+        let res = {
+            let file_view = &mut self.file_view;
+            let search = async {
+                if down {
+                    return file_view
+                        .down_to_line_matching(&regex, skip_current, &cancelled)
+                        .await;
+                }
+                return file_view.up_to_line_matching(&regex, &cancelled).await;
+            };
+            tokio::pin!(search);
+            let deadline = self
+                .sources
+                .sleep(self.search_timeout.unwrap_or(Duration::MAX));
+            tokio::pin!(deadline);
+
+            loop {
+                select! {
+                    res = &mut search => break res,
+                    _ = &mut deadline, if self.search_timeout.is_some() && !timed_out => {
+                        info!("search timed out");
+                        timed_out = true;
+                        cancelled.store(true, Ordering::Release);
+                    }
+                    Some(command) = self.command_receiver.recv() => {
+                        if !superseded && supersedes_search(&command) {
+                            info!("search superseded by {:?}", command);
+                            superseded = true;
+                            cancelled.store(true, Ordering::Release);
+                        }
+                        self.queued.push_back(command);
+                    }
+                }
+            }
+        };
+        *self.search_cancelled.borrow_mut() = None;
+        let reached = match (&res, self.file_view.search_stop()) {
+            (Ok(_), _) => self.file_view.offset(),
+            (Err(_), Some(stop)) if superseded || timed_out => stop,
+            (Err(_), _) if down => self.file_view.file_size().await.unwrap_or(start),
+            (Err(_), _) => 0,
+        };
+        let searched = reached.abs_diff(start);
+        let elapsed = self.sources.elapsed(started);
+        info!(
+            searched,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "searched {}/s",
+            human_bytes(searched as f64 / elapsed.as_secs_f64().max(1e-6))
+        );
+        // the queued command superseding it runs next
+        if superseded {
+            return Ok(());
+        }
+        if res.is_ok() {
+            // the first match of the line going down, the last one going up
+            let matches = self.file_view.matches_in_top_line(&regex).await?;
+            let current = if down {
+                matches.first()
+            } else {
+                matches.last()
+            };
+            self.current_match = current.map(|x| (self.file_view.offset(), x.clone()));
+            self.show_above(self.search_offset.lines(self.view_height))
+                .await;
+        }
+        // the search may end without a match before it sees the timeout
+        let stopped = matches!(
+            &res,
+            Err(e) if matches!(e.downcast_ref::<ViewError>(), Some(ViewError::Cancelled))
+        );
+        if timed_out && stopped {
+            if let Some(offset) = self.file_view.search_stop() {
+                let file_size = self.file_view.file_size().await?.max(1);
+                self.search_resume = Some((pattern.to_owned(), down, offset));
+                return Err(
+                    BackendError::SearchTimeout(100.0 * offset as f64 / file_size as f64).into(),
+                );
+            }
+        }
+        return res;
+    }
+
+    // move the view up to show lines above the current position, less those
+    // the scrolloff already shows. The top of the file may leave fewer.
+    async fn show_above(&mut self, lines: usize) {
+        let lines = lines.saturating_sub(self.scrolloff);
+        if lines > 0 {
+            self.file_view
+                .move_filtered(-(lines as i64), &self.cancelled)
+                .await
+                .ok();
+        }
+    }
+
+    // n and N go through the matches of the line of the current match, then
+    // search from that line. They search from the top of the view when the
+    // current match is not shown.
+    // the view is not sent between the matches of a count, the match found
+    // last is still the current one
+    async fn search_next(&mut self, pattern: &str, down: bool, count: i64) -> Result<()> {
+        for n in 0..count.max(1) {
+            self.next_match(pattern, down, n > 0).await?;
+        }
+        return Ok(());
+    }
+
+    async fn next_match(&mut self, pattern: &str, down: bool, shown: bool) -> Result<()> {
+        let (offset, current) = match self.current_match.clone() {
+            Some((offset, current)) if shown || self.is_shown(offset) => (offset, current),
+            _ => return self.search(pattern, down, down).await,
+        };
+        let regex =
+            bytes::Regex::new(pattern).map_err(|e| ViewError::InvalidRegex(regex_error(&e)))?;
+        let state = self.file_view.save_state();
+        if offset != state.offset() {
+            self.file_view.jump_to_byte(offset).await?;
+        }
+        let matches = self.file_view.matches_in_top_line(&regex).await?;
+        if self.file_view.offset() == offset && matches.contains(&current) {
+            let next = if down {
+                matches.into_iter().find(|x| x.start > current.start)
+            } else {
+                matches.into_iter().rev().find(|x| x.start < current.start)
+            };
+            if let Some(next) = next {
+                self.current_match = Some((offset, next));
+                return self.file_view.restore_state(&state).await;
+            }
+        }
+        let res = self.search(pattern, down, down).await;
+        if res.is_err() {
+            self.file_view.restore_state(&state).await?;
+        }
+        return res;
+    }
+
+    // true when the line starting at the offset is in the last view sent
+    fn is_shown(&self, offset: u64) -> bool {
+        return self
+            .state_sender
+            .borrow()
+            .text
+            .iter()
+            .any(|x| x.byte_offset == offset);
+    }
+
+    // search again from where the last search timed out, the view goes back
+    // to where it was if nothing is found
+    async fn continue_search(&mut self) -> Result<()> {
+        let (pattern, down, offset) = self
+            .search_resume
+            .take()
+            .ok_or(BackendError::NoSearchToContinue)?;
+        let state = self.file_view.save_state();
+        self.file_view.jump_to_byte(offset).await?;
+        let res = self.search(&pattern, down, false).await;
+        if res.is_err() {
+            self.file_view.load_state(&state)?;
+        }
+        return res;
+    }
+
+    async fn run_command(&mut self, command: Command) {
+        // sent right after other commands, the errors of those stay shown
+        if !matches!(command, Command::Resize(..) | Command::MatchMap(_)) {
+            self.command_errors.clear();
+            self.command_notices.clear();
+        }
+        // the size is part of the key of the cached view, resizing back to
+        // the previous size reuses it
+        if !matches!(command, Command::Resize(..)) {
+            self.view_cache = None;
+        }
+        crash::record_command(&self.file_path, format!("{:?}", command));
+        self.report_activity(self.operation(&command), None);
+        let before = if is_jump(&command) {
+            Some(self.mark().await)
+        } else {
+            None
+        };
+        let span = info_span!("command", command = ?command);
+        if let Err(e) = self.handle_command(command).instrument(span).await {
+            self.command_errors.push(Rc::from(e));
+        }
+        if let Some(before) = before.filter(|x| x.offset != self.file_view.offset()) {
+            self.record_jump(before);
+        }
+        self.report_activity(None, None);
+    }
+
+    // progress the match scan, returns true when the state should be sent
+    async fn match_scan_step(&mut self) -> bool {
+        let scan = match self.match_scan.as_mut() {
+            Some(scan) => scan,
+            None => return false,
+        };
+        if let Err(e) = scan.step().await {
+            self.command_errors.push(Rc::from(e));
+            self.match_scan = None;
+            return true;
+        }
+
+        let done = scan.map().done;
+        let elapsed = self.sources.elapsed(self.match_scan_progress_sent);
+        if !done && elapsed < Duration::from_millis(MATCH_SCAN_PROGRESS_MS) {
+            yield_now().await;
+            return false;
+        }
+
+        self.match_map = Some(Rc::new(scan.map().clone()));
+        self.match_scan_progress_sent = self.sources.now();
+        if done {
+            info!("match scan done, {} matches", scan.map().count);
+            let notice = format!("{} matches for /{}/", scan.map().count, scan.map().pattern);
+            self.match_scan = None;
+            self.notice(notice);
+        }
+        return true;
+    }
+
+    // progress the :grep-out scan, returns true when the state should be sent
+    async fn grep_out_step(&mut self) -> bool {
+        let grep_out = match self.grep_out.as_mut() {
+            Some(grep_out) => grep_out,
+            None => return false,
+        };
+        if let Err(e) = grep_out.step().await {
+            self.command_errors.push(Rc::from(e));
+            self.grep_out = None;
+            return true;
+        }
+
+        let done = grep_out.done;
+        let elapsed = self.sources.elapsed(self.grep_out_progress_sent);
+        if !done && elapsed < Duration::from_millis(MATCH_SCAN_PROGRESS_MS) {
+            yield_now().await;
+            return false;
+        }
+
+        self.grep_out_progress_sent = self.sources.now();
+        if done {
+            let notice = format!(
+                "{} matching lines written to {}",
+                grep_out.matches, grep_out.path
+            );
+            info!("grep-out done: {}", notice);
+            self.grep_out = None;
+            self.notice(notice);
+        }
+        return true;
+    }
+
+    // the index is needed while the line number of the view is unknown, after
+    // jumps to a byte offset. Compressed files only have estimated offsets.
+    fn line_index_needed(&self) -> bool {
+        return self.file_view.current_line().is_none() && !self.file_view.is_compressed();
+    }
+
+    // the index must scan further to find the line number of the view
+    fn line_index_pending(&self) -> bool {
+        let offset = self.file_view.offset();
+        return self.line_index_needed()
+            && self
+                .line_index
+                .as_ref()
+                .is_some_and(|x| x.checkpoint(offset).is_none());
+    }
+
+    // count the lines from the closest checkpoint of the index to find the
+    // line number of the view, the index is started when missing
+    async fn resolve_current_line(&mut self) -> Result<()> {
+        if !self.line_index_needed() {
+            return Ok(());
+        }
+        if self.line_index.is_none() {
+            self.line_index = Some(LineIndex::new(self.sources.open(&self.file_path).await?));
+        }
+        let offset = self.file_view.offset();
+        if let Some((start, number)) = self.line_index.as_ref().unwrap().checkpoint(offset) {
+            self.file_view.resolve_current_line(start, number).await?;
+        }
+        return Ok(());
+    }
+
+    // progress the line index, returns true when the state should be sent
+    async fn line_index_step(&mut self) -> bool {
+        if !self.line_index_pending() {
+            return false;
+        }
+        if let Err(e) = self.line_index.as_mut().unwrap().step().await {
+            self.command_errors.push(Rc::from(e));
+        }
+        if self.line_index_pending() {
+            let scanned = self.line_index.as_ref().unwrap().scanned();
+            let progress = scanned as f64 / self.file_view.offset().max(1) as f64;
+            // in percents, not to wake the frontend on every step
+            let progress = (progress * 100.0).floor() / 100.0;
+            self.report_activity(Some(Operation::Indexing), Some(progress));
+            yield_now().await;
+            return false;
+        }
+        self.report_activity(None, None);
+        if let Err(e) = self.resolve_current_line().await {
+            self.command_errors.push(Rc::from(e));
+        }
+        return true;
+    }
+
+    // the position before a jump goes to the ' mark, and to the numbered
+    // marks that keep the ten last ones, '0 being the last
+    fn record_jump(&mut self, mark: Mark) {
+        for number in (0..JUMP_MARKS - 1).rev() {
+            if let Some(mark) = self.marks.remove(&number.to_string()) {
+                self.marks.insert((number + 1).to_string(), mark);
+            }
+        }
+        self.marks.insert("0".to_owned(), mark.clone());
+        self.marks.insert("'".to_owned(), mark);
+    }
+
+    // mark of the current position
+    async fn mark(&mut self) -> Mark {
+        return Mark {
+            offset: self.file_view.offset(),
+            line: self.file_view.current_line().filter(|&x| x > 0),
+            fingerprint: self.file_view.top_line_fingerprint().await.ok().flatten(),
+        };
+    }
+
+    // jump to the line of the mark, or as close as possible when the file
+    // shrunk. The line number is only kept when the line did not change.
+    async fn load_mark(&mut self, name: String) -> Result<()> {
+        let mark = match self.marks.get(&name) {
+            Some(mark) => mark.clone(),
+            None => return Err(BackendError::UnknownMark(name).into()),
+        };
+        let file_size = self.file_view.file_size().await?;
+        self.file_view
+            .jump_to_byte(mark.offset.min(file_size.saturating_sub(1)))
+            .await?;
+        if mark.fingerprint.is_none() {
+            return Ok(());
+        }
+        let fingerprint = self.file_view.top_line_fingerprint().await?;
+        if self.file_view.offset() != mark.offset || fingerprint != mark.fingerprint {
+            self.notice(format!("the line of mark {} changed", name));
+        } else if let Some(line) = mark.line {
+            self.file_view.set_current_line(line);
+        }
+        return Ok(());
+    }
+
+    // the long work a command may do, reported to the frontend while it runs
+    fn operation(&self, command: &Command) -> Option<Operation> {
+        let compressed = self.file_view.is_compressed();
+        return match command {
+            Command::SearchDown(_)
+            | Command::SearchDownNext(..)
+            | Command::SearchUp(..)
+            | Command::ContinueSearch => Some(Operation::Searching),
+            Command::Filter(_) | Command::SourceFilter(_) | Command::Focus(_) => {
+                Some(Operation::Filtering)
+            }
+            Command::MoveLine(_) if self.filter.is_some() && !self.focus => {
+                Some(Operation::Filtering)
+            }
+            Command::JumpLine(_) if !compressed => Some(Operation::Counting),
+            Command::Stats(_) | Command::ExportWordStats(_) => Some(Operation::Scanning),
+            Command::MoveLine(_)
+            | Command::JumpLine(_)
+            | Command::JumpFileRatio(_)
+            | Command::JumpByte(_)
+            | Command::JumpNew
+            | Command::LoadMark(_)
+            | Command::Follow(true)
+                if compressed =>
+            {
+                Some(Operation::Decoding)
+            }
+            _ => None,
+        };
+    }
+
+    // the frontend shows the operation once it takes a while, None when done
+    fn report_activity(&self, operation: Option<Operation>, progress: Option<f64>) {
+        self.activity_sender.send_if_modified(|activity| {
+            let started = match activity {
+                Some(x) if Some(x.operation) == operation => x.started,
+                _ => self.sources.now(),
+            };
+            let next = operation.map(|operation| Activity {
+                operation,
+                started,
+                progress,
+            });
+            if next == *activity {
+                return false;
+            }
+            *activity = next;
+            return true;
+        });
+    }
+
     async fn handle_command(&mut self, command: Command) -> Result<()> {
         info!("command: {:?}", command);
+        if self.follow {
+            match command {
+                Command::JumpLine(-1) => self.resume_follow(),
+                Command::MoveLine(_)
+                | Command::JumpLine(_)
+                | Command::JumpFileRatio(_)
+                | Command::JumpByte(_)
+                | Command::JumpNew
+                | Command::SearchDown(_)
+                | Command::SearchDownNext(..)
+                | Command::SearchUp(..)
+                | Command::LoadMark(_)
+                | Command::Bisect(_) => self.pause_follow().await?,
+                _ => (),
+            }
+        }
+        let moving_down = matches!(command, Command::MoveLine(x) if x > 0);
+        // the jump marks go back to the view they saved, without centering
+        let centered = self.jump_center
+            && match &command {
+                Command::JumpLine(_) | Command::JumpByte(_) | Command::JumpFileRatio(_) => true,
+                Command::LoadMark(name) => !is_jump_mark(name),
+                _ => false,
+            };
+
         let res = match command {
             Command::Follow(follow) => {
                 self.follow = follow;
+                self.resume_follow();
                 self.file_view.bottom().await
             }
-            Command::SearchDown(pattern) => {
-                self.file_view
-                    .down_to_line_matching(
-                        &bytes::Regex::new(&pattern).map_err(|_| ViewError::InvalidRegex)?,
-                        false,
-                        &self.cancelled,
-                    )
-                    .await
+            Command::SearchDown(pattern) => self.search(&pattern, true, false).await,
+            Command::SearchDownNext(pattern, count) => {
+                self.search_next(&pattern, true, count).await
             }
-            Command::SearchDownNext(pattern) => {
-                self.file_view
-                    .down_to_line_matching(
-                        &bytes::Regex::new(&pattern).map_err(|_| ViewError::InvalidRegex)?,
-                        true,
-                        &self.cancelled,
-                    )
-                    .await
+            Command::SearchUp(pattern, count) => self.search_next(&pattern, false, count).await,
+            Command::MoveLine(lines) => self.file_view.move_filtered(lines, &self.cancelled).await,
+            Command::JumpLine(line) => self.file_view.jump_to_line(line).await,
+            Command::JumpFileRatio(ratio) => {
+                let pos = self.file_view.file_size().await? as f64 * ratio;
+                self.file_view.jump_to_byte(pos as u64).await
             }
-            Command::SearchUp(pattern) => {
+            Command::JumpByte(byte) => {
+                let file_size = self.file_view.file_size().await?;
                 self.file_view
-                    .up_to_line_matching(
-                        &bytes::Regex::new(&pattern).map_err(|_| ViewError::InvalidRegex)?,
-                        &self.cancelled,
-                    )
+                    .jump_to_byte(byte.min(file_size.saturating_sub(1)))
                     .await
             }
-            Command::MoveLine(lines) => {
-                if lines > 0 {
-                    self.file_view.down(lines as u64).await
-                } else if lines < 0 {
-                    self.file_view.up((-lines) as u64).await
-                } else {
-                    Ok(())
+            Command::JumpNew => {
+                let file_size = self.file_view.file_size().await?;
+                if file_size <= self.opened_size {
+                    return Err(BackendError::NothingNew.into());
                 }
+                self.file_view.jump_to_byte(self.opened_size).await
             }
-            Command::JumpLine(line) => self.file_view.jump_to_line(line).await,
-            Command::JumpFileRatio(ratio) => {
-                let pos = self.file_view.file_size().await as f64 * ratio;
-                self.file_view.jump_to_byte(pos as u64).await
+            Command::Reload => self.reload().await,
+            Command::AutoReload(auto_reload) => {
+                self.auto_reload = auto_reload;
+                Ok(())
             }
             Command::Resize(w, h) => {
                 self.view_width = w;
@@ -230,21 +1300,196 @@ impl CommandHandler {
                 Ok(())
             }
             Command::SaveMark(name) => {
-                self.marks.insert(name, self.file_view.save_state());
+                let mark = self.mark().await;
+                self.marks.insert(name, mark);
                 Ok(())
             }
-            Command::LoadMark(name) => {
-                if let Some(state) = self.marks.get(&name) {
-                    self.file_view.load_state(state)
-                } else {
-                    Err(BackendError::UnknownMark(name).into())
+            Command::SetMark(name, offset) => {
+                self.marks.insert(
+                    name,
+                    Mark {
+                        offset,
+                        line: None,
+                        fingerprint: None,
+                    },
+                );
+                Ok(())
+            }
+            Command::LoadMark(name) => self.load_mark(name).await,
+            Command::Entropy(words) => {
+                self.entropy_words = words;
+                Ok(())
+            }
+            Command::ExportWordStats(path) => self.export_word_stats(&path).await,
+            Command::WordSeparators(separators) => {
+                self.word_separators = separators;
+                self.entropy_cache = None;
+                Ok(())
+            }
+            Command::Stats(range) => self.compute_stats(range).await,
+            Command::SaveSession(name, session) => self.save_session(&name, &session),
+            Command::Bisect(step) => self.bisect(step).await,
+            Command::Filter(expr) => {
+                self.filter = None;
+                let predicate = match &expr {
+                    Some(expr) => Some(Predicate::parse(expr)?),
+                    None => None,
+                };
+                self.file_view
+                    .set_filter(predicate.filter(|_| !self.focus), &self.cancelled)
+                    .await?;
+                self.filter = expr;
+                Ok(())
+            }
+            Command::Focus(focus) => {
+                self.focus = focus;
+                let expr = self.filter.take();
+                let predicate = match &expr {
+                    Some(expr) if !focus => Some(Predicate::parse(expr)?),
+                    _ => None,
+                };
+                self.file_view
+                    .set_filter(predicate, &self.cancelled)
+                    .await?;
+                self.filter = expr;
+                Ok(())
+            }
+            Command::SourceFilter(source) => {
+                self.source_filter = None;
+                self.file_view
+                    .set_source_filter(source.clone(), &self.cancelled)
+                    .await?;
+                self.source_filter = source;
+                Ok(())
+            }
+            Command::GrepOut(pattern, path, context) => {
+                info!("writing lines matching {} to {}", pattern, path);
+                let mut grep_out = GrepOut::new(
+                    self.sources.open(&self.file_path).await?,
+                    &pattern,
+                    &path,
+                    context,
+                )
+                .await?;
+                grep_out.set_no_cache(self.no_cache);
+                self.grep_out = Some(grep_out);
+                Ok(())
+            }
+            Command::SearchTimeout(timeout) => {
+                self.search_timeout = timeout;
+                Ok(())
+            }
+            Command::ContinueSearch => self.continue_search().await,
+            Command::SearchOffset(offset) => {
+                self.search_offset = offset;
+                Ok(())
+            }
+            Command::ScrollOff(lines) => {
+                self.scrolloff = lines;
+                Ok(())
+            }
+            Command::JumpCenter(center) => {
+                self.jump_center = center;
+                Ok(())
+            }
+            Command::Layout(tab_stops, wrap_indent) => {
+                self.file_view
+                    .set_layout(tab_stops.clone(), wrap_indent.clone());
+                self.tab_stops = tab_stops;
+                self.wrap_indent = wrap_indent;
+                Ok(())
+            }
+            Command::StripCr(strip_cr) => {
+                self.strip_cr = strip_cr;
+                self.file_view.set_strip_cr(strip_cr);
+                self.line_view = None;
+                self.line_cache.clear();
+                Ok(())
+            }
+            Command::AnsiPassthrough(ansi_passthrough) => {
+                self.ansi_passthrough = ansi_passthrough;
+                self.file_view.set_ansi_passthrough(ansi_passthrough);
+                self.line_view = None;
+                self.line_cache.clear();
+                Ok(())
+            }
+            Command::MaxRenderCols(max_render_cols) => {
+                self.max_render_cols = max_render_cols;
+                self.file_view.set_max_render_cols(max_render_cols);
+                Ok(())
+            }
+            Command::VerifyChecksums(verify) => {
+                self.verify_checksums = verify;
+                self.file_view.set_verify_checksums(verify);
+                Ok(())
+            }
+            Command::NoCache(no_cache) => {
+                self.no_cache = no_cache;
+                self.file_view.set_no_cache(no_cache);
+                Ok(())
+            }
+            Command::GetLine(line) => {
+                let text = self.get_line(line).await?;
+                self.line = Some((line, text));
+                Ok(())
+            }
+            Command::MatchMap(pattern) => {
+                self.match_scan = None;
+                self.match_map = None;
+                self.highlight = None;
+                self.file_view.set_highlight(None);
+                if let Some(pattern) = pattern {
+                    self.match_scan = Some(self.new_match_scan(&pattern).await?);
+                    // the lines come with the matches the frontend highlights
+                    let regex = bytes::Regex::new(&pattern)
+                        .map_err(|e| ViewError::InvalidRegex(regex_error(&e)))?;
+                    self.file_view.set_highlight(Some(regex.clone()));
+                    self.highlight = Some(regex);
                 }
+                Ok(())
             }
         };
 
+        if centered && res.is_ok() {
+            self.show_above(self.view_height / 2).await;
+        }
+        // scrolling past the end of the file resumes following
+        if let Err(e) = &res {
+            if moving_down && self.follow_pause.is_some() {
+                if let Some(ViewError::EOF) = e.downcast_ref::<ViewError>() {
+                    self.resume_follow();
+                    return Ok(());
+                }
+            }
+        }
         return res;
     }
 
+    fn notice(&mut self, notice: String) {
+        self.command_notices.push(Rc::new(notice));
+    }
+
+    async fn pause_follow(&mut self) -> Result<()> {
+        if self.follow_pause.is_some() {
+            return Ok(());
+        }
+        info!("pausing follow");
+        let mut tail = self.sources.open(&self.file_path).await?;
+        tail.bottom().await?;
+        // the bottom is in the last line, only the lines after it are new
+        tail.count_lines_to_end().await?;
+        self.follow_pause = Some(tail);
+        self.new_lines = 0;
+        return Ok(());
+    }
+
+    fn resume_follow(&mut self) {
+        if self.follow_pause.take().is_some() {
+            info!("resuming follow");
+        }
+        self.new_lines = 0;
+    }
+
     async fn generate_state(&mut self) -> BackendState {
         let mut state = BackendState::new();
 
@@ -252,20 +1497,80 @@ impl CommandHandler {
         state.real_file_path = self.file_view.real_file_path().to_owned();
 
         let offset_before = self.file_view.offset();
-        state.text = match self.file_view.view(self.view_height, self.view_width).await {
-            Ok(x) => x,
-            Err(e) => {
-                state.errors.push(Rc::from(e));
-                Vec::new()
-            }
-        };
+        match self.file_view.file_size().await {
+            Ok(file_size) => state.file_size = file_size,
+            Err(e) => state.errors.push(Rc::from(e)),
+        }
+        state.text = self.view(state.file_size, &mut state.errors).await;
 
-        state.file_size = self.file_view.file_size().await;
         state.current_line = self.file_view.current_line();
+        state.current_match = self.current_match.clone();
+        state.source = self.file_view.source().map(|x| x.to_owned());
         state.offset = self.file_view.offset();
+        state.compressed = self.file_view.is_compressed();
+        state.cursor_row = self.cursor_row;
+        state.position = match (
+            state.file_size,
+            self.file_view.at_top().await || self.view_at_top,
+            self.file_view.eof_in_view(),
+        ) {
+            (0, _, _) => ViewPosition::Empty,
+            (_, _, true) => ViewPosition::End,
+            (size, at_top, false) => {
+                // the end of the last line shown, a screen filled up to the
+                // end of the file shows it too
+                let end = state
+                    .text
+                    .last()
+                    .map(|x| x.byte_offset + x.text.len() as u64 + 1)
+                    .unwrap_or(state.offset);
+                if end >= size {
+                    ViewPosition::End
+                } else if at_top {
+                    ViewPosition::Top
+                } else {
+                    // the offsets of compressed files are those of the
+                    // compressed data, unlike what is read
+                    let end = self.file_view.decoded_offset(end).await;
+                    let size = self.file_view.decoded_offset(size).await;
+                    ViewPosition::Percent((100.0 * end as f64 / max(size, 1) as f64).min(100.0))
+                }
+            }
+        };
         state.follow = self.follow;
+        state.crlf = self.file_view.is_crlf().await;
+        state.new_lines = self.follow_pause.as_ref().map(|_| self.new_lines);
+        // what was followed is not new, nor is anything after a truncation
+        if self.follow || state.file_size < self.opened_size {
+            self.opened_size = state.file_size;
+        }
+        state.grown = Some(state.file_size - self.opened_size).filter(|&x| x > 0);
+        state.line = self.line.clone();
+        state.bisect_left = self.bisect.as_ref().map(|x| x.bad - x.low);
+        state.grep_out_progress = self.grep_out.as_ref().map(|x| x.progress());
+        state.filter = self.filter.clone();
+        state.source_filter = self.source_filter.clone();
+        state.invalid_utf8 = state.text.iter().any(|x| x.invalid_utf8);
+        state.file_info = self.file_info.clone();
+        for error in self.file_view.take_checksum_errors() {
+            self.command_errors
+                .push(Rc::new(Box::new(BackendError::ChecksumMismatch(error))));
+        }
         state.errors = self.command_errors.clone();
-        state.marks = self.marks.keys().map(|x| x.clone()).collect();
+        state.notices = self.command_notices.clone();
+        state.marks = self
+            .marks
+            .iter()
+            .filter(|(name, _)| !is_jump_mark(name))
+            .map(|(name, mark)| (name.clone(), mark.offset))
+            .collect();
+        state.marks.sort();
+        state.stats = self.stats.clone();
+        state.match_map = self.match_map.clone();
+
+        if let Some(count) = self.entropy_words {
+            state.entropy_words = self.entropy_words(&state.text, state.offset, count);
+        }
 
         if offset_before > state.offset {
             // building the view shifted the view upwards,
@@ -276,20 +1581,1186 @@ impl CommandHandler {
         return state;
     }
 
+    // lines of the view, built again only when it may have changed
+    async fn view(
+        &mut self,
+        file_size: u64,
+        errors: &mut Vec<Rc<Box<dyn Error>>>,
+    ) -> Rc<Vec<Line>> {
+        let key = (
+            self.file_view.offset(),
+            self.file_view.current_line(),
+            file_size,
+            self.view_height,
+            self.view_width,
+        );
+        if let Some((cached_key, text)) = &self.view_cache {
+            if *cached_key == key {
+                return text.clone();
+            }
+        }
+        return match self.build_view(file_size).await {
+            Ok(lines) => {
+                let text = Rc::new(lines);
+                self.view_cache = Some((key, text.clone()));
+                text
+            }
+            Err(e) => {
+                self.view_cache = None;
+                errors.push(Rc::from(e));
+                Rc::new(Vec::new())
+            }
+        };
+    }
+
+    // with a scrolloff the view starts up to that many lines above the current
+    // line, which keeps its position in the file. At the end of the file it
+    // goes down to the last line while the view stays.
+    async fn build_view(&mut self, file_size: u64) -> Result<Vec<Line>> {
+        self.view_at_top = false;
+        self.cursor_row = None;
+        let above = self.scrolloff.min(self.view_height.saturating_sub(1) / 2);
+        if above == 0 {
+            return self.file_view.view(self.view_height, self.view_width).await;
+        }
+        // the bottom is the end of the last line, the current line is its start
+        if self.file_view.offset() >= file_size {
+            self.file_view.up(0).await.ok();
+        }
+        let current = self.file_view.save_state();
+        for _ in 0..above {
+            if self
+                .file_view
+                .move_filtered(-1, &self.cancelled)
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+        let lines = self.file_view.view(self.view_height, self.view_width).await;
+        self.view_at_top = self.file_view.at_top().await;
+        self.file_view.restore_state(&current).await?;
+        let lines = lines?;
+        self.cursor_row = lines.iter().position(|x| x.byte_offset == current.offset());
+        if self.cursor_row.is_some() || lines.is_empty() {
+            return Ok(lines);
+        }
+        // taller lines above hide the current one
+        self.view_at_top = false;
+        return self.file_view.view(self.view_height, self.view_width).await;
+    }
+
+    fn entropy_words(&mut self, text: &[Line], offset: u64, count: usize) -> Vec<String> {
+        let mut hasher = DefaultHasher::new();
+        text.iter().for_each(|x| x.text.hash(&mut hasher));
+        let key = (offset, hasher.finish(), self.view_width, count);
+        if let Some((cached_key, words)) = &self.entropy_cache {
+            if *cached_key == key {
+                return words.clone();
+            }
+        }
+
+        let words = interesting_words(text, count, &self.word_separators);
+        debug!("most interesting words: {:?}", words);
+        self.entropy_cache = Some((key, words.clone()));
+        return words;
+    }
+
+    async fn export_word_stats(&mut self, path: &str) -> Result<()> {
+        info!("exporting word statistics to {}", path);
+
+        // scan with a separate view so that the displayed one does not move
+        let mut view = self.sources.open(&self.file_path).await?;
+        let mut stats = WordStats::new(self.word_separators.clone());
+        view.scan_lines(&self.cancelled, |_, line| {
+            stats.add_line(&String::from_utf8_lossy(line));
+            true
+        })
+        .await?;
+
+        let writer = BufWriter::new(File::create(path)?);
+        if path.ends_with(".json") {
+            stats.write_json(writer)?;
+        } else {
+            stats.write_csv(writer)?;
+        }
+        self.notice(format!("word statistics exported to {}", path));
+        return Ok(());
+    }
+
+    fn save_session(&mut self, name: &str, session: &Session) -> Result<()> {
+        session.save(name)?;
+        self.notice(format!("session saved: {}", name));
+        return Ok(());
+    }
+
+    async fn bisect(&mut self, step: BisectStep) -> Result<()> {
+        let file_size = self.file_view.file_size().await?;
+        let bisect = match (step, self.bisect.take()) {
+            (BisectStep::Start { by_time }, _) => {
+                // compressed offsets cannot point to a line precisely
+                if self.file_view.is_compressed() {
+                    return Err(BackendError::BisectCompressed.into());
+                }
+                Bisect {
+                    low: 0,
+                    bad: file_size,
+                    by_time,
+                    start: self.file_view.save_state(),
+                }
+            }
+            (_, None) => return Err(BackendError::NoBisect.into()),
+            (BisectStep::Reset, Some(bisect)) => return self.file_view.load_state(&bisect.start),
+            (BisectStep::Good, Some(mut bisect)) => {
+                let next = match self.file_view.down(1).await {
+                    Ok(()) => self.file_view.offset(),
+                    Err(_) => file_size,
+                };
+                bisect.low = max(bisect.low, next);
+                bisect
+            }
+            (BisectStep::Bad, Some(mut bisect)) => {
+                bisect.bad = min(bisect.bad, self.file_view.offset());
+                bisect
+            }
+        };
+
+        if bisect.low >= bisect.bad {
+            if bisect.bad >= file_size {
+                self.notice("bisect done, no bad line".to_owned());
+                return self.file_view.load_state(&bisect.start);
+            }
+            self.notice(format!(
+                "bisect done, first bad line at byte {}",
+                bisect.bad
+            ));
+            return self.file_view.jump_to_byte(bisect.bad).await;
+        }
+        // the line containing the middle byte starts after `low` and before `bad`
+        let mut middle = bisect.low + (bisect.bad - bisect.low) / 2;
+        if bisect.by_time {
+            if let Some(offset) = self.time_middle(bisect.low, bisect.bad).await? {
+                middle = offset;
+            }
+        }
+        self.bisect = Some(bisect);
+        return self.file_view.jump_to_byte(middle).await;
+    }
+
+    // the first line between `low` and `bad` at or after the middle of their
+    // times, the lines without timestamp take the one of the next line.
+    // None when no line has a timestamp.
+    async fn time_middle(&mut self, low: u64, bad: u64) -> Result<Option<u64>> {
+        self.file_view.jump_to_byte(low).await?;
+        let first = match self.next_timestamp(bad).await? {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+        // the last timestamp, from the last line before `bad`
+        let mut last = first;
+        self.file_view.jump_to_byte(bad - 1).await?;
+        while self.file_view.offset() > low {
+            if let Some(timestamp) = self.file_view.top_line_timestamp().await? {
+                last = timestamp;
+                break;
+            }
+            self.file_view.up(1).await?;
+        }
+        let middle = first.seconds + (last.seconds - first.seconds) / 2;
+
+        // lower bound of the bytes whose line is at or after the middle
+        let (mut lo, mut hi) = (low, bad);
+        while lo < hi {
+            let byte = lo + (hi - lo) / 2;
+            self.file_view.jump_to_byte(byte).await?;
+            match self.next_timestamp(bad).await? {
+                Some(timestamp) if timestamp.seconds < middle => lo = byte + 1,
+                _ => hi = byte,
+            }
+        }
+        return Ok(Some(lo));
+    }
+
+    // timestamp of the first line having one from the top line, before `end`
+    async fn next_timestamp(&mut self, end: u64) -> Result<Option<Timestamp>> {
+        while self.file_view.offset() < end {
+            if let Some(timestamp) = self.file_view.top_line_timestamp().await? {
+                return Ok(Some(timestamp));
+            }
+            if self.file_view.down(1).await.is_err() {
+                break;
+            }
+        }
+        return Ok(None);
+    }
+
+    // full content of a line, counted from the end of the file when negative
+    async fn get_line(&mut self, line: i64) -> Result<Rc<String>> {
+        // a truncated file is read again from its start
+        let file_size = self.file_view.file_size().await?;
+        if file_size < self.line_cache_size {
+            self.line_view = None;
+            self.line_cache.clear();
+        }
+        self.line_cache_size = file_size;
+
+        // lines counted from the end move as the file grows
+        if line > 0 {
+            if let Some(text) = self.line_cache.get(&line) {
+                return Ok(text.clone());
+            }
+        }
+
+        if self.line_view.is_none() {
+            let mut view = self.sources.open(&self.file_path).await?;
+            view.set_strip_cr(self.strip_cr);
+            view.set_ansi_passthrough(self.ansi_passthrough);
+            self.line_view = Some(view);
+        }
+        let view = self.line_view.as_mut().unwrap();
+        view.jump_to_line(line).await?;
+        let text = Rc::new(
+            view.view(1, None)
+                .await?
+                .pop()
+                .map(|x| x.text)
+                .unwrap_or_default(),
+        );
+
+        if line > 0 {
+            self.line_cache.put(line, text.clone());
+        }
+        return Ok(text);
+    }
+
+    async fn compute_stats(&mut self, range: Option<(i64, i64)>) -> Result<()> {
+        info!("computing statistics for {:?}", range);
+
+        self.stats = None;
+        let mut view = self.sources.open(&self.file_path).await?;
+        let mut nlines = match range {
+            Some((first, last)) => {
+                view.jump_to_line(first).await?;
+                last - first + 1
+            }
+            None => i64::MAX,
+        };
+
+        let mut stats = LogStats::default();
+        view.scan_lines(&self.cancelled, |_, line| {
+            stats.add_line(line);
+            nlines -= 1;
+            nlines > 0
+        })
+        .await?;
+        self.stats = Some(Rc::new(stats));
+        return Ok(());
+    }
+
     async fn send_state(&mut self) -> Result<()> {
-        let state = self.generate_state().await;
+        if let Err(e) = self.resolve_current_line().await {
+            self.command_errors.push(Rc::from(e));
+        }
+        let started = self.sources.now();
+        let mut state = self.generate_state().await;
+        state.command_time = self.command_time;
+        state.state_time = self.sources.elapsed(started);
+        self.record_crash_context(&state);
+        {
+            let previous = self.state_sender.borrow();
+            if state.same_as(&previous) {
+                return Ok(());
+            }
+            if !Rc::ptr_eq(&state.text, &previous.text) && state.text == previous.text {
+                // share the text so the frontend can reuse its rendering
+                state.text = previous.text.clone();
+            }
+        }
         self.state_sender
             .send(state)
             .map_err(|_| ChannelError::State)?;
+        self.state_sent = self.sources.now();
+        self.state_pending = false;
         Ok(())
     }
 
+    // the view as it is now, for the crash report
+    fn record_crash_context(&self, state: &BackendState) {
+        let range = self.file_view.buffer_range();
+        let mut view = format!(
+            "    real path: {}\n    size: {}\n    buffer: {}..{}\n    offset: {}\n    current line: {:?}\n    follow: {}\n    filter: {:?}",
+            state.real_file_path,
+            state.file_size,
+            range.start,
+            range.end,
+            state.offset,
+            state.current_line,
+            state.follow,
+            state.filter,
+        );
+        if let Some(info) = &self.file_info {
+            view.push_str(&format!(
+                "\n    format: {}\n    modified: {}\n    inode: {}\n    rotation: {:?}",
+                info.format(),
+                info.modified(),
+                info.inode(),
+                info.rotation,
+            ));
+        }
+        crash::record_view(&self.file_path, view);
+    }
+
     async fn maybe_reload_file(&mut self) -> Result<()> {
-        let real_file_path = canonicalize(&self.file_path)?.to_string_lossy().to_string();
+        let real_file_path = match real_path(&self.file_path) {
+            Ok(path) => path,
+            // deleted, or moved away and not created again yet during a
+            // rotation, the view keeps the file read
+            Err(_) => return Ok(()),
+        };
         if real_file_path != self.file_view.real_file_path() {
             info!("reloading file");
-            self.file_view = FileView::new(&self.file_path).await?;
+            self.notice(format!("file changed, reloaded {}", real_file_path));
+            self.reload_file().await?;
+            return Ok(());
+        }
+
+        // replaced or rewritten at the same path, the view shows the old file
+        let pending = self.replace_pending();
+        let replaced = self.file_info.as_ref().is_some_and(|x| x.replaced());
+        if replaced && self.auto_reload {
+            info!("file replaced, reloading");
+            self.notice(format!("file replaced, reloaded {}", real_file_path));
+            self.reload_file().await?;
+        } else if pending {
+            self.notice(format!("file replaced, R to reload {}", real_file_path));
+            self.replace_noticed = true;
         }
         return Ok(());
     }
+
+    // true when the file was replaced at the same path and nothing was done
+    // about it yet
+    fn replace_pending(&mut self) -> bool {
+        return match self.file_info.as_mut() {
+            Some(info) => {
+                info.refresh();
+                info.replaced() && !self.replace_noticed
+            }
+            None => false,
+        };
+    }
+
+    // open the file again, with the format detected again and nothing kept
+    // from the previous one but the options
+    async fn reload_file(&mut self) -> Result<()> {
+        self.file_view = self.sources.open(&self.file_path).await?;
+        self.view_cache = None;
+        self.file_info = FileInfo::collect(&self.file_path).ok();
+        self.file_watch = self.sources.watch(&self.file_path);
+        self.replace_noticed = false;
+        self.opened_size = self.file_view.file_size().await?;
+        self.current_match = None;
+        self.file_view.set_strip_cr(self.strip_cr);
+        self.file_view
+            .set_layout(self.tab_stops.clone(), self.wrap_indent.clone());
+        self.file_view.set_ansi_passthrough(self.ansi_passthrough);
+        self.file_view.set_max_render_cols(self.max_render_cols);
+        self.file_view.set_highlight(self.highlight.clone());
+        self.file_view.set_verify_checksums(self.verify_checksums);
+        self.file_view.set_no_cache(self.no_cache);
+        if let Some(expr) = self.filter.as_ref().filter(|_| !self.focus) {
+            let predicate = Predicate::parse(expr)?;
+            self.file_view
+                .set_filter(Some(predicate), &self.cancelled)
+                .await?;
+        }
+        if self.source_filter.is_some() {
+            self.file_view
+                .set_source_filter(self.source_filter.clone(), &self.cancelled)
+                .await?;
+        }
+        self.line_view = None;
+        self.line_cache.clear();
+        self.line_index = None;
+        self.entropy_cache = None;
+        self.stats = None;
+        // the matches are counted again in the new content
+        let pattern = self
+            .match_scan
+            .as_ref()
+            .map(|x| x.map().pattern.clone())
+            .or(self.match_map.as_ref().map(|x| x.pattern.clone()));
+        self.match_scan = None;
+        self.match_map = None;
+        if let Some(pattern) = pattern {
+            self.match_scan = Some(self.new_match_scan(&pattern).await?);
+        }
+        return Ok(());
+    }
+
+    async fn new_match_scan(&self, pattern: &str) -> Result<MatchScan> {
+        let mut scan = MatchScan::new(self.sources.open(&self.file_path).await?, pattern).await?;
+        scan.set_no_cache(self.no_cache);
+        return Ok(scan);
+    }
+
+    // reload the file on demand, for files rewritten in place which keep
+    // their path. The view stays at the same line.
+    async fn reload(&mut self) -> Result<()> {
+        info!("reloading file on demand");
+        let line = self.file_view.current_line();
+        let offset = self.file_view.offset();
+        self.reload_file().await?;
+        self.notice(format!("reloaded {}", self.file_view.real_file_path()));
+        return match line {
+            Some(line) => self.file_view.jump_to_line(line).await,
+            None => {
+                let file_size = self.file_view.file_size().await?;
+                self.file_view
+                    .jump_to_byte(offset.min(file_size.saturating_sub(1)))
+                    .await
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{has_line, lines, run_local, Driver, TestDir, TestSources},
+        ui::file_info::Rotation,
+    };
+    use std::fs;
+
+    #[tokio::test]
+    async fn follow_shows_appended_lines() {
+        let dir = TestDir::new("follow");
+        let path = dir.write("log", lines(0..20));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.wait_for("line 0", |x| has_line(x, "line 0")).await;
+
+            driver.send(Command::Follow(true));
+            driver.wait_for("the end", |x| has_line(x, "line 19")).await;
+
+            dir.append("log", &lines(20..22));
+            driver
+                .wait_for("appended lines", |x| has_line(x, "line 21"))
+                .await;
+            assert_eq!(
+                driver.lines(),
+                vec!["line 17", "line 18", "line 19", "line 20", "line 21"]
+            );
+            assert!(driver.states.borrow().follow);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn follow_empty_file() {
+        let dir = TestDir::new("follow-empty");
+        // like the output of --exec before the command printed anything
+        let path = dir.write("log", "");
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.send(Command::Follow(true));
+            driver.wait_for("follow", |x| x.follow).await;
+
+            dir.append("log", &lines(0..2));
+            driver
+                .wait_for("appended lines", |x| has_line(x, "line 1"))
+                .await;
+            assert_eq!(driver.lines(), vec!["line 0", "line 1"]);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn paused_follow_counts_new_lines() {
+        let dir = TestDir::new("pause");
+        let path = dir.write("log", lines(0..20));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.send(Command::Follow(true));
+            driver.wait_for("the end", |x| has_line(x, "line 19")).await;
+
+            // moving pauses following until the bottom is requested again
+            driver.send(Command::MoveLine(-2));
+            driver
+                .wait_for("follow paused", |x| x.new_lines == Some(0))
+                .await;
+            dir.append("log", &lines(20..23));
+            driver
+                .wait_for("3 new lines", |x| x.new_lines == Some(3))
+                .await;
+            assert_eq!(driver.lines()[0], "line 13");
+
+            driver.send(Command::JumpLine(-1));
+            driver
+                .wait_for("follow resumed", |x| {
+                    x.new_lines.is_none() && has_line(x, "line 22")
+                })
+                .await;
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn reload_replaced_file() {
+        let dir = TestDir::new("reload");
+        let first = dir.write("log.1", lines(0..3));
+        let second = dir.write("log.2", lines(100..110));
+        let link = dir.path("log");
+        std::os::unix::fs::symlink(&first, &link).unwrap();
+        let link = link.to_string_lossy().into_owned();
+        run_local(async {
+            let mut driver = Driver::start(&link, 5).await;
+            driver.wait_for("line 0", |x| has_line(x, "line 0")).await;
+            assert_eq!(driver.states.borrow().real_file_path, first);
+
+            // like a log rotation pointing the link to a new file
+            fs::remove_file(&link).unwrap();
+            std::os::unix::fs::symlink(&second, &link).unwrap();
+            driver
+                .wait_for("the new file", |x| has_line(x, "line 100"))
+                .await;
+            let state = driver.states.borrow();
+            assert_eq!(state.real_file_path, second);
+            assert!(state.notices.iter().any(|x| x.contains("reloaded")));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn position_after_byte_jump() {
+        let dir = TestDir::new("position");
+        let path = dir.write("log", lines(0..100));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver
+                .wait_for("the top", |x| x.position == ViewPosition::Top)
+                .await;
+            driver.send(Command::JumpLine(50));
+            driver
+                .wait_for(
+                    "a percent",
+                    |x| matches!(x.position, ViewPosition::Percent(p) if p > 50.0 && p < 60.0),
+                )
+                .await;
+
+            // a byte jump goes to the start of its line
+            driver.send(Command::JumpByte(3));
+            driver
+                .wait_for("the top again", |x| {
+                    has_line(x, "line 0") && x.position == ViewPosition::Top
+                })
+                .await;
+        })
+        .await;
+    }
+
+    // lines with a timestamp, most of them in the first minute
+    fn timed_lines() -> String {
+        return (0..100)
+            .map(|n| {
+                let seconds = if n < 80 { n } else { 3600 + (n - 80) * 60 };
+                format!(
+                    "2024-01-01 {:02}:{:02}:{:02} line {}\n",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60,
+                    n
+                )
+            })
+            .collect();
+    }
+
+    fn top_line_number(state: &BackendState) -> Option<usize> {
+        let line = state.text.first()?;
+        return line.text.rsplit(' ').next()?.parse().ok();
+    }
+
+    // bisect the lines with those from `first_bad` bad, returns the line it ends at
+    async fn run_bisect(driver: &mut Driver, by_time: bool, first_bad: usize) -> usize {
+        driver.send(Command::Bisect(BisectStep::Start { by_time }));
+        driver
+            .wait_for("bisect started", |x| x.bisect_left.is_some())
+            .await;
+        loop {
+            let (top, done) = {
+                let state = driver.states.borrow();
+                let done = state.notices.iter().any(|x| x.contains("bisect done"));
+                (top_line_number(&state).unwrap(), done)
+            };
+            if done {
+                return top;
+            }
+            let step = match top >= first_bad {
+                true => BisectStep::Bad,
+                false => BisectStep::Good,
+            };
+            driver.send(Command::Bisect(step));
+            driver
+                .wait_for("the next bisect step", |x| {
+                    top_line_number(x) != Some(top)
+                        || x.notices.iter().any(|x| x.contains("bisect done"))
+                })
+                .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn bisect_lines() {
+        let dir = TestDir::new("bisect");
+        let path = dir.write("log", timed_lines());
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver
+                .wait_for("line 0", |x| top_line_number(x) == Some(0))
+                .await;
+            assert_eq!(run_bisect(&mut driver, false, 37).await, 37);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn bisect_times() {
+        let dir = TestDir::new("bisect-time");
+        let path = dir.write("log", timed_lines());
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver
+                .wait_for("line 0", |x| top_line_number(x) == Some(0))
+                .await;
+            // the middle of the times is in the last lines
+            driver.send(Command::Bisect(BisectStep::Start { by_time: true }));
+            driver
+                .wait_for("the middle time", |x| top_line_number(x) == Some(80))
+                .await;
+            driver.send(Command::Bisect(BisectStep::Reset));
+            driver
+                .wait_for("line 0", |x| top_line_number(x) == Some(0))
+                .await;
+            assert_eq!(run_bisect(&mut driver, true, 91).await, 91);
+            assert_eq!(run_bisect(&mut driver, true, 12).await, 12);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn crlf_from_the_first_line() {
+        let dir = TestDir::new("crlf");
+        // the line endings of the rest of the file do not matter
+        let path = dir.write("log", format!("first\r\n{}", lines(0..0x10000)));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver
+                .wait_for("the first line", |x| has_line(x, "first"))
+                .await;
+            assert!(driver.states.borrow().crlf);
+            driver.send(Command::JumpLine(-1));
+            driver
+                .wait_for("the end", |x| has_line(x, "line 65535"))
+                .await;
+            assert!(driver.states.borrow().crlf);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn jump_marks() {
+        let dir = TestDir::new("jump-marks");
+        let path = dir.write("log", lines(0..100));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.send(Command::JumpLine(50));
+            driver.wait_for("line 49", |x| has_line(x, "line 49")).await;
+            driver.send(Command::JumpLine(80));
+            driver.wait_for("line 79", |x| has_line(x, "line 79")).await;
+
+            // '' bounces between the last two positions, each bounce is a jump
+            // that shifts the numbered marks
+            driver.send(Command::LoadMark("'".to_owned()));
+            driver
+                .wait_for("back to 49", |x| x.text[0].text == "line 49")
+                .await;
+            driver.send(Command::LoadMark("'".to_owned()));
+            driver
+                .wait_for("back to 79", |x| x.text[0].text == "line 79")
+                .await;
+            driver.send(Command::LoadMark("3".to_owned()));
+            driver
+                .wait_for("the start", |x| x.text[0].text == "line 0")
+                .await;
+            assert!(driver.states.borrow().marks.is_empty());
+
+            // the marks go back to their offset after a reload
+            driver.send(Command::SaveMark("a".to_owned()));
+            dir.write("log", lines(0..120));
+            driver.send(Command::Reload);
+            driver.send(Command::JumpLine(110));
+            driver
+                .wait_for("line 109", |x| has_line(x, "line 109"))
+                .await;
+            driver.send(Command::LoadMark("a".to_owned()));
+            driver
+                .wait_for("the mark", |x| x.text[0].text == "line 0")
+                .await;
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn reload_command() {
+        let dir = TestDir::new("reload-command");
+        let path = dir.write("log", lines(0..20));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.send(Command::JumpLine(3));
+            driver
+                .wait_for("current line 3", |x| x.current_line == Some(3))
+                .await;
+
+            // rewritten like editors save, the path stays the same
+            let new = dir.write("log.new", lines(100..120));
+            fs::rename(new, &path).unwrap();
+            driver.send(Command::Reload);
+            driver
+                .wait_for("the new content", |x| has_line(x, "line 102"))
+                .await;
+            let state = driver.states.borrow();
+            assert_eq!(state.current_line, Some(3));
+            assert!(state.notices.iter().any(|x| x.contains("reloaded")));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn rotated_file() {
+        let dir = TestDir::new("rotate");
+        let path = dir.write("log", lines(0..3));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.wait_for("line 0", |x| has_line(x, "line 0")).await;
+            let rotation = |x: &BackendState| x.file_info.as_ref().map(|x| x.rotation);
+            assert_eq!(rotation(&driver.states.borrow()), Some(Rotation::Current));
+
+            // renamed away and replaced by a new file at the same path
+            fs::rename(&path, dir.path("log.1")).unwrap();
+            dir.write("log", lines(100..110));
+            driver.send(Command::JumpLine(1));
+            driver
+                .wait_for("rotated", |x| rotation(x) == Some(Rotation::Rotated))
+                .await;
+            assert!(has_line(&driver.states.borrow(), "line 0"));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn rewritten_file() {
+        let dir = TestDir::new("rewrite");
+        let path = dir.write("log", lines(0..20));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.wait_for("line 0", |x| has_line(x, "line 0")).await;
+
+            // the status change time of the file is coarse
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            dir.write("log", lines(100..120));
+            driver
+                .wait_for("the replace notice", |x| {
+                    x.notices.iter().any(|x| x.contains("R to reload"))
+                })
+                .await;
+            let rotation = driver
+                .states
+                .borrow()
+                .file_info
+                .as_ref()
+                .map(|x| x.rotation);
+            assert_eq!(rotation, Some(Rotation::Rewritten));
+
+            driver.send(Command::AutoReload(true));
+            driver
+                .wait_for("the new content", |x| has_line(x, "line 100"))
+                .await;
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn grown_file() {
+        let dir = TestDir::new("grow");
+        let path = dir.write("log", lines(0..20));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.wait_for("line 0", |x| has_line(x, "line 0")).await;
+            assert_eq!(driver.states.borrow().grown, None);
+
+            dir.append("log", &lines(20..40));
+            driver
+                .wait_for("growth", |x| x.grown == Some(lines(20..40).len() as u64))
+                .await;
+            assert!(has_line(&driver.states.borrow(), "line 0"));
+
+            driver.send(Command::JumpNew);
+            driver
+                .wait_for("the first new line", |x| {
+                    x.text.first().is_some_and(|x| x.text == "line 20")
+                })
+                .await;
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn marks_anchored_to_lines() {
+        let dir = TestDir::new("mark-anchor");
+        let path = dir.write("log", lines(0..20));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.send(Command::JumpLine(10));
+            driver
+                .wait_for("current line 10", |x| x.current_line == Some(10))
+                .await;
+            driver.send(Command::SaveMark("a".to_owned()));
+
+            // the mark survives the file growing and being reloaded
+            dir.append("log", &lines(20..40));
+            driver.send(Command::Reload);
+            driver.send(Command::JumpLine(30));
+            driver
+                .wait_for("current line 30", |x| x.current_line == Some(30))
+                .await;
+            driver.send(Command::LoadMark("a".to_owned()));
+            driver
+                .wait_for("the mark", |x| {
+                    x.text[0].text == "line 9" && x.current_line == Some(10)
+                })
+                .await;
+            assert!(driver.states.borrow().notices.is_empty());
+
+            // rewritten, the mark still goes to its offset but says so
+            dir.write("log", lines(0..20).replace("line", "LINE"));
+            driver.send(Command::Reload);
+            driver.send(Command::LoadMark("a".to_owned()));
+            driver
+                .wait_for("the changed line", |x| {
+                    x.notices.iter().any(|x| x.contains("mark a changed"))
+                })
+                .await;
+            assert_eq!(driver.lines()[0], "LINE 9");
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn matches_within_line() {
+        let dir = TestDir::new("match-line");
+        let path = dir.write("log", "foo bar foo baz foo\nnothing\nfoo end\n");
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            let steps = [
+                (Command::SearchDown("foo".to_owned()), (0, 0..3)),
+                (Command::SearchDownNext("foo".to_owned(), 1), (0, 8..11)),
+                (Command::SearchDownNext("foo".to_owned(), 1), (0, 16..19)),
+                (Command::SearchDownNext("foo".to_owned(), 1), (28, 0..3)),
+                // the last match of the previous line
+                (Command::SearchUp("foo".to_owned(), 1), (0, 16..19)),
+                (Command::SearchUp("foo".to_owned(), 1), (0, 8..11)),
+                // with a count
+                (Command::SearchDownNext("foo".to_owned(), 2), (28, 0..3)),
+                (Command::SearchUp("foo".to_owned(), 3), (0, 0..3)),
+            ];
+            for (command, current) in steps {
+                driver.send(command);
+                driver
+                    .wait_for(&format!("match {:?}", current), |x| {
+                        x.current_match == Some(current.clone())
+                    })
+                    .await;
+            }
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn matches_with_lines() {
+        let dir = TestDir::new("match-ranges");
+        let path = dir.write("log", "\x1bfoo \u{e9}foo\nbar\n");
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            // ranges of the text shown, "^[foo \u{e9}foo"
+            let steps = [
+                ("foo", vec![2..5, 8..11]),
+                // half of the \u{e9} takes all of it
+                ("(?-u)\\x1b|\\xc3", vec![0..2, 6..8]),
+                ("nothing", vec![]),
+            ];
+            for (pattern, ranges) in steps {
+                driver.send(Command::MatchMap(Some(pattern.to_owned())));
+                driver
+                    .wait_for(pattern, |x| {
+                        x.text
+                            .first()
+                            .map(|x| x.matches.iter().map(|x| x.text.clone()).collect::<Vec<_>>())
+                            == Some(ranges.clone())
+                    })
+                    .await;
+            }
+            driver.send(Command::MatchMap(None));
+            driver
+                .wait_for("no matches", |x| {
+                    x.text.first().map(|x| x.matches.is_empty()) == Some(true)
+                })
+                .await;
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn search_offset() {
+        let dir = TestDir::new("search-offset");
+        let path = dir.write("log", lines(0..100));
+        run_local(async {
+            let mut driver = Driver::start(&path, 10).await;
+            let steps = [
+                (SearchOffset::Middle, "line 50", "line 45"),
+                (SearchOffset::Lines(2), "line 60", "line 58"),
+                // the top of the file leaves fewer lines above the match
+                (SearchOffset::Middle, "line 1", "line 0"),
+                (SearchOffset::Top, "line 70", "line 70"),
+            ];
+            for (offset, pattern, top) in steps {
+                driver.send(Command::SearchOffset(offset));
+                driver.send(Command::JumpLine(1));
+                driver.send(Command::SearchDown(format!("{}\\b", pattern)));
+                driver
+                    .wait_for(pattern, |x| {
+                        let current = x.current_match.as_ref().and_then(|(offset, _)| {
+                            x.text.iter().find(|x| x.byte_offset == *offset)
+                        });
+                        return current.map(|x| x.text.as_str()) == Some(pattern)
+                            && x.text[0].text == top;
+                    })
+                    .await;
+            }
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn scrolloff() {
+        let dir = TestDir::new("scrolloff");
+        let path = dir.write("log", lines(0..100));
+        run_local(async {
+            let mut driver = Driver::start(&path, 10).await;
+            driver.send(Command::ScrollOff(2));
+            let steps = [
+                (Command::JumpLine(50), "line 47", 2),
+                // the current line moves apart from the top of the file
+                (Command::JumpLine(1), "line 0", 0),
+                (Command::MoveLine(1), "line 0", 1),
+                (Command::MoveLine(3), "line 2", 2),
+                // and from the end of the file
+                (Command::JumpLine(-1), "line 90", 9),
+                (Command::MoveLine(-2), "line 90", 7),
+            ];
+            for (command, top, row) in steps {
+                driver.send(command);
+                driver
+                    .wait_for(&format!("{} on top, row {}", top, row), |x| {
+                        x.text.first().map(|x| x.text.as_str()) == Some(top)
+                            && x.cursor_row == Some(row)
+                    })
+                    .await;
+            }
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn jump_center() {
+        let dir = TestDir::new("jump-center");
+        let path = dir.write("log", lines(0..100));
+        run_local(async {
+            let mut driver = Driver::start(&path, 10).await;
+            driver.send(Command::JumpCenter(true));
+            driver.send(Command::JumpLine(50));
+            driver
+                .wait_for("line 44 on top", |x| has_line(x, "line 44"))
+                .await;
+            assert_eq!(driver.lines()[5], "line 49");
+
+            driver.send(Command::SaveMark("a".to_owned()));
+            driver.send(Command::JumpLine(3));
+            driver.wait_for("the top", |x| has_line(x, "line 0")).await;
+
+            // '' goes back to the same view, other marks are centered
+            driver.send(Command::LoadMark("'".to_owned()));
+            driver
+                .wait_for("line 44 on top", |x| has_line(x, "line 44"))
+                .await;
+            assert_eq!(driver.lines()[0], "line 44");
+            driver.send(Command::LoadMark("a".to_owned()));
+            driver
+                .wait_for("line 39 on top", |x| has_line(x, "line 39"))
+                .await;
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn view_position() {
+        let dir = TestDir::new("position");
+        let path = dir.write("log", lines(0..100));
+        run_local(async {
+            let mut driver = Driver::start(&path, 10).await;
+            driver
+                .wait_for("the top", |x| x.position == ViewPosition::Top)
+                .await;
+
+            // the percent is the one of the last byte on screen
+            driver.send(Command::JumpLine(50));
+            let percent = 100.0 * lines(0..59).len() as f64 / lines(0..100).len() as f64;
+            driver
+                .wait_for("the percent", |x| {
+                    x.position == ViewPosition::Percent(percent)
+                })
+                .await;
+
+            driver.send(Command::JumpLine(-1));
+            driver
+                .wait_for("the end", |x| x.position == ViewPosition::End)
+                .await;
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn shrunk_file() {
+        let dir = TestDir::new("shrink");
+        let path = dir.write("log", lines(0..20));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.send(Command::JumpLine(10));
+            driver
+                .wait_for("current line 10", |x| x.current_line == Some(10))
+                .await;
+
+            // truncated by a log rotation
+            dir.write("log", lines(0..3));
+            driver.send(Command::JumpLine(1));
+            driver
+                .wait_for("the new size", |x| {
+                    x.file_size == 21 && has_line(x, "line 0")
+                })
+                .await;
+            assert_eq!(driver.lines(), vec!["line 0", "line 1", "line 2"]);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn line_of_shrunk_file() {
+        let dir = TestDir::new("shrink-line");
+        let path = dir.write("log", lines(0..20));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.send(Command::GetLine(2));
+            driver
+                .wait_for("line 2", |x| {
+                    x.line.as_ref().is_some_and(|(_, text)| **text == "line 1")
+                })
+                .await;
+
+            // the cached line is read again in the new content
+            dir.write("log", "new 0\nnew 1\n");
+            driver.send(Command::GetLine(2));
+            driver
+                .wait_for("the new line 2", |x| {
+                    x.line.as_ref().is_some_and(|(_, text)| **text == "new 1")
+                })
+                .await;
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn shrunk_file_other_lines() {
+        let dir = TestDir::new("shrink-lines");
+        let path = dir.write("log", "alpha\n".repeat(3));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.wait_for("the lines", |x| has_line(x, "alpha")).await;
+
+            // the newlines are not where they were
+            dir.write("log", "beta\n".repeat(3));
+            driver
+                .wait_for("the new lines", |x| x.file_size == 15)
+                .await;
+            assert_eq!(driver.lines(), vec!["beta", "beta", "beta"]);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn entropy_of_rewritten_file() {
+        let dir = TestDir::new("entropy");
+        let path = dir.write("log", "alpha\n".repeat(3));
+        run_local(async {
+            let mut driver = Driver::start(&path, 5).await;
+            driver.send(Command::Entropy(Some(1)));
+            driver
+                .wait_for("the words", |x| x.entropy_words == vec!["alpha"])
+                .await;
+
+            // as many lines at the same offset, with other words
+            dir.write("log", "beta\n".repeat(3));
+            driver
+                .wait_for("the new words", |x| x.entropy_words == vec!["beta"])
+                .await;
+        })
+        .await;
+    }
+
+    #[test]
+    fn coalesce_resizes() {
+        let commands = vec![
+            Command::Resize(Some(80), 20),
+            Command::MoveLine(1),
+            Command::Resize(Some(81), 20),
+            Command::MoveLine(2),
+            Command::Resize(Some(82), 21),
+        ];
+        assert_eq!(
+            coalesce(commands),
+            vec![Command::MoveLine(3), Command::Resize(Some(82), 21)]
+        );
+    }
+
+    // the events of the watch come from another thread, the clock barely
+    // moves while waiting for them
+    #[tokio::test]
+    async fn watched_file() {
+        let dir = TestDir::new("watch");
+        let path = dir.write("log", lines(0..20));
+        run_local(async {
+            let mut driver = Driver::start_with(&path, 5, TestSources::new(true)).await;
+            driver.wait_for("line 0", |x| has_line(x, "line 0")).await;
+
+            // the growth is seen before the clock gets to the next poll
+            driver.step = Duration::from_millis(1);
+            let start = driver.sources.now();
+            dir.append("log", &lines(20..22));
+            driver
+                .wait_for("growth", |x| x.grown == Some(lines(20..22).len() as u64))
+                .await;
+            assert!(driver.sources.elapsed(start) < Duration::from_millis(GROWTH_POLL_MS));
+        })
+        .await;
+    }
 }
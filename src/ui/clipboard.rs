@@ -0,0 +1,52 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::{
+    env,
+    io::{self, Write},
+};
+use tracing::info;
+
+// sessions over SSH have no clipboard on the machine running bless
+fn is_remote() -> bool {
+    return env::var_os("SSH_CONNECTION").is_some() || env::var_os("SSH_TTY").is_some();
+}
+
+// ask the terminal to set its clipboard, this works through SSH and through
+// tmux when its set-clipboard option is on
+fn osc52(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    return stdout.flush();
+}
+
+pub struct Clipboard {
+    // on X11 the copied text is served by its owner, it must outlive the copy
+    native: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        return Self { native: None };
+    }
+
+    // copy with OSC 52, and with the native clipboard when running locally for
+    // the terminals ignoring OSC 52
+    pub fn copy(&mut self, text: &str) -> io::Result<()> {
+        osc52(text)?;
+        if is_remote() {
+            return Ok(());
+        }
+        if self.native.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(native) => self.native = Some(native),
+                Err(e) => {
+                    info!("no native clipboard: {}", e);
+                    return Ok(());
+                }
+            }
+        }
+        if let Err(e) = self.native.as_mut().unwrap().set_text(text.to_owned()) {
+            info!("native clipboard error: {}", e);
+        }
+        return Ok(());
+    }
+}
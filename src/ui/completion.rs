@@ -0,0 +1,130 @@
+use crate::ui::theme::Theme;
+use std::fs;
+use tui::text::{Span, Spans, Text};
+
+pub const MAX_MENU_HEIGHT: usize = 8;
+
+// replace a leading ~ with the home directory, like a shell
+pub fn expand_home(path: &str) -> String {
+    if path == "~" || path.starts_with("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return format!("{}{}", home.display(), &path[1..]);
+        }
+    }
+    return path.to_owned();
+}
+
+// candidates of a path read off the ui loop, for the command line they
+// were read for
+pub struct PathCompletion {
+    pub command: String,
+    pub prefix: String,
+    pub candidates: Vec<String>,
+}
+
+// paths completing the last component of `partial`, directories end with a
+// slash. Slow on network filesystems, it runs with spawn_blocking.
+pub fn path_candidates(partial: &str) -> Vec<String> {
+    let (dir, prefix) = match partial.rfind('/') {
+        Some(pos) => (&partial[..pos + 1], &partial[pos + 1..]),
+        None => ("", partial),
+    };
+    let read_path = if dir.is_empty() {
+        ".".to_owned()
+    } else {
+        expand_home(dir)
+    };
+    let read_dir = match fs::read_dir(read_path) {
+        Ok(read_dir) => read_dir,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut candidates = Vec::new();
+    for entry in read_dir.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // hidden files are only listed when asked for
+        if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+            continue;
+        }
+        let is_dir = fs::metadata(entry.path())
+            .map(|x| x.is_dir())
+            .unwrap_or(false);
+        candidates.push(if is_dir {
+            format!("{}{}/", dir, name)
+        } else {
+            format!("{}{}", dir, name)
+        });
+    }
+    candidates.sort();
+    return candidates;
+}
+
+pub fn common_prefix(words: &[String]) -> String {
+    let mut common = words.first().cloned().unwrap_or_default();
+    for word in words.iter().skip(1) {
+        while !word.starts_with(&common) {
+            common.pop();
+        }
+    }
+    return common;
+}
+
+// candidates shown while cycling through them with Tab
+#[derive(Debug, Clone)]
+pub struct CompletionMenu {
+    // command line before the completed word
+    pub prefix: String,
+    pub candidates: Vec<String>,
+    pub selected: Option<usize>,
+}
+
+impl CompletionMenu {
+    // select the next or previous candidate, returns the new command line
+    pub fn cycle(&mut self, forward: bool) -> String {
+        let count = self.candidates.len();
+        let selected = match (self.selected, forward) {
+            (None, true) => 0,
+            (None, false) => count - 1,
+            (Some(x), true) => (x + 1) % count,
+            (Some(x), false) => (x + count - 1) % count,
+        };
+        self.selected = Some(selected);
+        return format!("{}{}", self.prefix, self.candidates[selected]);
+    }
+}
+
+// last component of a candidate, like shells show them
+pub fn menu_label(candidate: &str) -> String {
+    let name = candidate
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(candidate);
+    if candidate.ends_with('/') {
+        return format!(" {}/ ", name);
+    }
+    return format!(" {} ", name);
+}
+
+pub fn menu_text(menu: &CompletionMenu, theme: &Theme, height: usize) -> Text<'static> {
+    let height = height.max(1);
+    let start = menu
+        .selected
+        .map(|x| (x + 1).saturating_sub(height))
+        .unwrap_or(0);
+    let lines = menu
+        .candidates
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(height)
+        .map(|(index, candidate)| {
+            let name = menu_label(candidate);
+            if Some(index) == menu.selected {
+                return Spans::from(Span::styled(name, theme.search_match));
+            }
+            return Spans::from(Span::styled(name, theme.status));
+        })
+        .collect::<Vec<Spans>>();
+    return Text::from(lines);
+}
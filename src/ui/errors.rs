@@ -26,6 +26,13 @@ impl Error for ChannelError {}
 pub enum BackendError {
     Stopped,
     UnknownMark(String),
+    NoBisect,
+    BisectCompressed,
+    // percentage of the file reached by the search
+    SearchTimeout(f64),
+    NoSearchToContinue,
+    ChecksumMismatch(String),
+    NothingNew,
 }
 
 impl Display for BackendError {
@@ -33,6 +40,16 @@ impl Display for BackendError {
         match self {
             Self::Stopped => f.write_str("backend stopped"),
             Self::UnknownMark(x) => write!(f, "unknown mark: {}", x),
+            Self::NoBisect => f.write_str("no bisect in progress, use :bisect start"),
+            Self::BisectCompressed => f.write_str("cannot bisect a compressed file"),
+            Self::SearchTimeout(x) => write!(
+                f,
+                "search timed out at {:.0}% of the file, :continue to search further",
+                x
+            ),
+            Self::NoSearchToContinue => f.write_str("no timed out search to continue"),
+            Self::ChecksumMismatch(x) => f.write_str(x),
+            Self::NothingNew => f.write_str("nothing new since the file was opened"),
         }
     }
 }
@@ -55,3 +72,24 @@ impl Display for FrontendError {
 }
 
 impl Error for FrontendError {}
+
+#[derive(Debug, Clone)]
+pub enum ThemeError {
+    UnknownTheme(String),
+    InvalidColor(String),
+    InvalidColorDepth(String),
+    EmptyPalette,
+}
+
+impl Display for ThemeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownTheme(x) => write!(f, "unknown theme: {}", x),
+            Self::InvalidColor(x) => write!(f, "invalid color: {}", x),
+            Self::InvalidColorDepth(x) => write!(f, "invalid color depth: {}", x),
+            Self::EmptyPalette => f.write_str("empty entropy palette"),
+        }
+    }
+}
+
+impl Error for ThemeError {}
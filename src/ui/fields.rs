@@ -0,0 +1,71 @@
+use crate::errors::Result;
+use regex::Regex;
+use tui::{
+    style::Style,
+    text::{Span, Spans},
+};
+
+pub struct FieldColorizer {
+    regex: Regex,
+    // capture group indices to color, with their style
+    groups: Vec<(usize, Style)>,
+}
+
+impl FieldColorizer {
+    pub fn new(pattern: &str, palette: &[Style]) -> Result<Self> {
+        let regex = Regex::new(pattern)?;
+
+        // color only named groups when there are some, all groups otherwise
+        let named: Vec<usize> = regex
+            .capture_names()
+            .enumerate()
+            .filter(|(_, name)| name.is_some())
+            .map(|(idx, _)| idx)
+            .collect();
+        let indices = if named.is_empty() {
+            (1..regex.captures_len()).collect()
+        } else {
+            named
+        };
+
+        let groups = indices
+            .into_iter()
+            .enumerate()
+            .map(|(nth, idx)| (idx, palette[nth % palette.len()]))
+            .collect();
+        return Ok(Self { regex, groups });
+    }
+
+    pub fn color_line<'a>(&self, line: &'a str) -> Spans<'a> {
+        let captures = match self.regex.captures(line) {
+            Some(captures) => captures,
+            None => return Spans::from(Span::raw(line)),
+        };
+
+        let mut ranges: Vec<_> = self
+            .groups
+            .iter()
+            .filter_map(|(idx, style)| captures.get(*idx).map(|m| (m.range(), *style)))
+            .filter(|(range, _)| !range.is_empty())
+            .collect();
+        ranges.sort_by_key(|(range, _)| range.start);
+
+        let mut spans = Vec::new();
+        let mut last_end = 0;
+        for (range, style) in ranges {
+            // nested groups: the outer group wins
+            if range.start < last_end {
+                continue;
+            }
+            if range.start > last_end {
+                spans.push(Span::raw(&line[last_end..range.start]));
+            }
+            spans.push(Span::styled(&line[range.clone()], style));
+            last_end = range.end;
+        }
+        if last_end < line.len() {
+            spans.push(Span::raw(&line[last_end..]));
+        }
+        return Spans::from(spans);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{
+    errors::Result,
+    file_buffer::{
+        expand_pattern,
+        format::{sniff, Format},
+        is_cat, is_pattern, CAT_SEPARATOR,
+    },
+};
+use chrono::{DateTime, Local};
+use human_bytes::human_bytes;
+use std::{
+    fs::{self, File},
+    io::{ErrorKind, Read},
+    os::unix::fs::MetadataExt,
+    time::SystemTime,
+};
+use tui::text::{Spans, Text};
+
+// start of the file compared to tell a rewrite from an append
+const HEAD_SIZE: u64 = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    // the path is still the file read
+    Current,
+    // the path is another file now, like after a log rotation or a save
+    // replacing the file
+    Rotated,
+    // the same file with another content, the view may show stale data
+    Rewritten,
+    Deleted,
+}
+
+// metadata of the file of a view, collected when it is opened
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileInfo {
+    pub paths: Vec<String>,
+    // detected format of each file
+    pub formats: Vec<Format>,
+    // last modification of the most recent file
+    pub modified: Option<SystemTime>,
+    // inode of the file read, for views of a single file
+    pub inode: Option<u64>,
+    pub rotation: Rotation,
+    // seconds and nanoseconds of the last status change, and the start of
+    // the file, for views of a single file
+    ctime: Option<(i64, i64)>,
+    head: Vec<u8>,
+}
+
+fn read_head(path: &str) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    File::open(path)?.take(HEAD_SIZE).read_to_end(&mut head)?;
+    return Ok(head);
+}
+
+impl FileInfo {
+    pub fn collect(path: &str) -> Result<Self> {
+        let paths = if is_cat(path) {
+            path.split(CAT_SEPARATOR).map(|x| x.to_owned()).collect()
+        } else if is_pattern(path) {
+            expand_pattern(path)?
+        } else {
+            vec![path.to_owned()]
+        };
+        let mut formats = Vec::new();
+        let mut modified = None;
+        let mut inode = None;
+        let mut ctime = None;
+        for path in paths.iter() {
+            let metadata = fs::metadata(path)?;
+            formats.push(sniff(path)?);
+            modified = modified.max(metadata.modified().ok());
+            inode = Some(metadata.ino());
+            ctime = Some((metadata.ctime(), metadata.ctime_nsec()));
+        }
+        let mut head = Vec::new();
+        if paths.len() > 1 {
+            inode = None;
+            ctime = None;
+        } else {
+            head = read_head(&paths[0])?;
+        }
+        return Ok(Self {
+            paths,
+            formats,
+            modified,
+            inode,
+            rotation: Rotation::Current,
+            ctime,
+            head,
+        });
+    }
+
+    // check whether the path still leads to the file read with the same
+    // content, and when it was last modified
+    pub fn refresh(&mut self) {
+        let inode = match self.inode {
+            Some(inode) => inode,
+            None => return,
+        };
+        self.rotation = match fs::metadata(&self.paths[0]) {
+            Ok(metadata) if metadata.ino() != inode => Rotation::Rotated,
+            // stays rewritten until reloaded
+            Ok(_) if self.rotation == Rotation::Rewritten => Rotation::Rewritten,
+            Ok(metadata) => {
+                self.modified = metadata.modified().ok();
+                let ctime = Some((metadata.ctime(), metadata.ctime_nsec()));
+                if ctime != self.ctime && !self.same_head() {
+                    Rotation::Rewritten
+                } else {
+                    self.ctime = ctime;
+                    Rotation::Current
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Rotation::Deleted,
+            Err(_) => self.rotation,
+        };
+    }
+
+    // appends change the status of the file but keep its start
+    fn same_head(&self) -> bool {
+        return match read_head(&self.paths[0]) {
+            Ok(head) => head.starts_with(&self.head),
+            Err(_) => true,
+        };
+    }
+
+    // the path leads to another file or content than the one read
+    pub fn replaced(&self) -> bool {
+        return matches!(self.rotation, Rotation::Rotated | Rotation::Rewritten);
+    }
+
+    // the distinct formats of the files, like "gzip" or "plain, gzip"
+    pub fn format(&self) -> String {
+        let mut names: Vec<&str> = self.formats.iter().map(|x| x.name()).collect();
+        names.sort_unstable();
+        names.dedup();
+        return names.join(", ");
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        return match self.formats.first() {
+            Some(Format::Utf16Le) => "UTF-16LE",
+            Some(Format::Utf16Be) => "UTF-16BE",
+            _ => "UTF-8",
+        };
+    }
+
+    pub fn modified(&self) -> String {
+        return self
+            .modified
+            .map(|x| {
+                DateTime::<Local>::from(x)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or("?".to_owned());
+    }
+
+    pub fn inode(&self) -> String {
+        return self.inode.map(|x| x.to_string()).unwrap_or("?".to_owned());
+    }
+}
+
+pub fn info_text(info: &FileInfo, size: u64) -> Text<'static> {
+    let mut lines = vec![Spans::from("  FILE INFO"), Spans::from("")];
+    for path in info.paths.iter() {
+        lines.push(Spans::from(format!("  Path       {}", path)));
+    }
+    lines.push(Spans::from(format!("  Format     {}", info.format())));
+    lines.push(Spans::from(format!("  Encoding   {}", info.encoding())));
+    lines.push(Spans::from(format!(
+        "  Size       {} ({} bytes)",
+        human_bytes(size as f64),
+        size
+    )));
+    lines.push(Spans::from(format!("  Modified   {}", info.modified())));
+    lines.push(Spans::from(format!("  Inode      {}", info.inode())));
+    lines.push(Spans::from(format!(
+        "  Status     {}",
+        match info.rotation {
+            Rotation::Current => "current",
+            Rotation::Rotated => "rotated, the path is another file now, R to reload",
+            Rotation::Rewritten => "rewritten in place, R to reload",
+            Rotation::Deleted => "deleted",
+        }
+    )));
+    return Text::from(lines);
+}
@@ -0,0 +1,129 @@
+use crate::{
+    errors::Result,
+    file_buffer::{expand_pattern, is_cat, is_pattern, CAT_SEPARATOR},
+};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    ffi::OsString,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+use tokio::sync::{mpsc, Mutex};
+
+// wakes the backend when the files of a view change, so that it does not
+// poll them while nothing happens
+pub struct FileWatch {
+    // events are sent while it is alive
+    watcher: RecommendedWatcher,
+    events: Mutex<mpsc::UnboundedReceiver<notify::Result<Event>>>,
+    files: Vec<PathBuf>,
+    // names of the files of each watched directory, None to wake for any file
+    // of it, like for a pattern; files created, moved or removed next to a
+    // file are how a log rotation shows
+    dirs: Vec<(PathBuf, Option<Vec<OsString>>)>,
+}
+
+impl FileWatch {
+    pub fn new(path: &str) -> Result<Self> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            sender.send(event).ok();
+        })
+        .map_err(watch_error)?;
+        let mut watch = Self {
+            watcher,
+            events: Mutex::new(receiver),
+            files: Vec::new(),
+            dirs: Vec::new(),
+        };
+        if is_pattern(path) {
+            for path in expand_pattern(path)?.iter() {
+                watch.add_file(Path::new(path))?;
+            }
+            watch.add_dir(parent(Path::new(path)), None)?;
+            return Ok(watch);
+        }
+        let paths: Vec<&str> = match is_cat(path) {
+            true => path.split(CAT_SEPARATOR).collect(),
+            false => vec![path],
+        };
+        for path in paths {
+            let path = Path::new(path);
+            watch.add_file(path)?;
+            watch.add_dir(parent(path), path.file_name().map(|x| x.to_owned()))?;
+        }
+        return Ok(watch);
+    }
+
+    fn add_file(&mut self, path: &Path) -> io::Result<()> {
+        self.watcher
+            .watch(path, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+        self.files.push(path.to_owned());
+        return Ok(());
+    }
+
+    fn add_dir(&mut self, dir: &Path, name: Option<OsString>) -> io::Result<()> {
+        if let Some((_, names)) = self.dirs.iter_mut().find(|(x, _)| x == dir) {
+            match (names, name) {
+                (Some(names), Some(name)) => names.push(name),
+                (names, _) => *names = None,
+            }
+            return Ok(());
+        }
+        self.watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+        self.dirs.push((dir.to_owned(), name.map(|x| vec![x])));
+        return Ok(());
+    }
+
+    // wait until one of the files changed, the events of other files of the
+    // watched directories are skipped
+    pub async fn changed(&self) -> io::Result<()> {
+        let mut events = self.events.lock().await;
+        loop {
+            match events.recv().await {
+                Some(Ok(event)) if self.relevant(&event) => return Ok(()),
+                Some(Ok(_)) => (),
+                Some(Err(e)) => return Err(watch_error(e)),
+                None => return Err(io::Error::new(ErrorKind::BrokenPipe, "file watch stopped")),
+            }
+        }
+    }
+
+    fn relevant(&self, event: &Event) -> bool {
+        if event.need_rescan() {
+            return true;
+        }
+        if let EventKind::Access(_) = event.kind {
+            return false;
+        }
+        return event.paths.iter().any(|path| {
+            self.files.iter().any(|x| x == path)
+                || self.dirs.iter().any(|(dir, names)| {
+                    path.parent() == Some(dir.as_path())
+                        && match names {
+                            Some(names) => path
+                                .file_name()
+                                .is_some_and(|x| names.iter().any(|n| n == x)),
+                            None => true,
+                        }
+                })
+        });
+    }
+}
+
+fn watch_error(e: notify::Error) -> io::Error {
+    return match e.kind {
+        notify::ErrorKind::Io(e) => e,
+        kind => io::Error::other(notify::Error::new(kind)),
+    };
+}
+
+fn parent(path: &Path) -> &Path {
+    return match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+}
@@ -1,38 +1,301 @@
-use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyModifiers};
+use chrono::Local;
+use crossterm::event::{
+    Event, EventStream, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use futures::{future::FutureExt, select, StreamExt};
 use human_bytes::human_bytes;
-use log::{debug, info};
-use regex::Regex;
+use regex::{bytes, Regex};
 use signal_hook::consts::TERM_SIGNALS;
 use signal_hook_async_std::Signals;
 use std::{
-    borrow::Cow,
     cell::RefCell,
     collections::HashMap,
     io::{self, Stdout},
+    ops::Range,
+    os::unix::process::ExitStatusExt,
+    path::Path,
+    rc::Rc,
+};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    task,
+    time::{self, Duration, Instant},
 };
-use tokio::sync::{mpsc::UnboundedSender, watch::Receiver};
+use tracing::info;
 use tui::{
     backend,
-    layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
     text::{Span, Spans, Text},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, Paragraph},
     Frame, Terminal,
 };
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::{
+    config::{Config, HeaderMode},
     errors::Result,
-    file_view::ViewError,
+    file_view::{Line, ViewError},
+    pager::ExecStatus,
+    state::{absolute_path, Session, TabSession},
     ui::{
-        backend::{BackendState, Command},
+        backend::{
+            is_jump_mark, Activity, Backend, BackendState, BisectStep, Command, SearchOffset,
+            ViewPosition,
+        },
+        clipboard::Clipboard,
+        completion::{
+            common_prefix, expand_home, menu_label, menu_text, path_candidates, CompletionMenu,
+            PathCompletion, MAX_MENU_HEIGHT,
+        },
         errors::{ChannelError, FrontendError},
+        fields::FieldColorizer,
+        file_info::{info_text, Rotation},
+        log_rules::LogColorizer,
+        match_scan::MatchMap,
+        messages::{messages_text, MessageLog, Notifications, Severity, Source},
+        minimap::{cluster_ratio, minimap_text},
+        options::{options_reference_text, options_text, parse_option, OptionValue},
+        palette::{palette_text, Palette},
+        picker::{picker_text, FilePicker},
+        profile::{profile_text, FrameProfile, PROFILE_HEIGHT, PROFILE_WIDTH},
+        stats_panel::stats_text,
+        status::{
+            format_status, uses_placeholder, DEFAULT_COMPACT_HEADER_FORMAT, DEFAULT_HEADER_FORMAT,
+        },
+        tabs::{tab_bar, Tab},
+        theme::Theme,
+    },
+    utils::{
+        diff::HUNK_PATTERN,
+        filter::Predicate,
+        pattern::{has_uppercase, regex_error, word_pattern},
+        text::{truncate_text, truncation_marker, DEFAULT_MAX_RENDER_COLS},
+        words::WordSeparators,
+        wrap::{expand_tabs, shift_lines, wrap_lines, TabStops, WrapIndent, DEFAULT_TAB_WIDTH},
     },
-    utils::{language::word_entropy, text::convert_tabs},
 };
 
 const FAST_SCROLL_LINES: i64 = 5;
-const WORD_SEPARATOR: &str = "<>()[]{},;:='\",";
+
+// columns of the file name shown before each line of views of several files
+const DEFAULT_SOURCE_TAG_WIDTH: usize = 8;
+
+// columns of the line numbers of :set number, larger numbers push the text
+const NUMBER_WIDTH: usize = 7;
+
+// longest delay between the two clicks of a double click
+const DOUBLE_CLICK_DELAY: Duration = Duration::from_millis(400);
+
+// the backend gets the new size once the terminal stopped resizing for this
+// long, dragging a corner would rebuild the view at every step otherwise
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(100);
+
+// the work of the backend shows in the status once it lasts this long, with
+// a spinner turning at this interval
+const SPINNER_DELAY: Duration = Duration::from_millis(300);
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+// commands of the command line, completed with Tab
+const EX_COMMANDS: [&str; 23] = [
+    "bisect",
+    "colorize",
+    "continue",
+    "e",
+    "filter",
+    "grep-out",
+    "header",
+    "help",
+    "info",
+    "line",
+    "messages",
+    "mksession",
+    "profile",
+    "q",
+    "q!",
+    "quit",
+    "quit!",
+    "set",
+    "source",
+    "stats",
+    "tabnew",
+    "w",
+    "wordstats",
+];
+
+const COUNTED_MOTIONS: [&str; 11] = ["j", "J", "k", "K", "l", "L", "h", "H", "n", "N", "yy"];
+
+// keys of more than one key press, shown by the hint bar while they are typed;
+// "<nr>" ones need a count, and a key typed in full waits for an argument
+const KEY_HINTS: [(&str, &str); 29] = [
+    ("m", "place mark: press a letter"),
+    (
+        "'",
+        "jump to mark: press a letter, ' for the last jump or 0 to 9",
+    ),
+    (":", "command: Enter to run, Tab to complete"),
+    ("/", "search: Enter to jump to the first match"),
+    (
+        "&",
+        "filter: Enter to show the matching lines, alone to show all",
+    ),
+    ("-", "line counted from the end: <nr>gg"),
+    ("gg", "first line"),
+    ("gt", "next tab"),
+    ("gT", "previous tab"),
+    ("gn", "first new line"),
+    ("GG", "last line"),
+    ("yy", "copy the top line"),
+    ("ru", "toggle the ruler"),
+    ("]c", "next diff hunk"),
+    ("[c", "previous diff hunk"),
+    ("cdef", "default colors"),
+    ("clog", "log colors"),
+    ("cent", "entropy colors"),
+    ("cfld", "field colors"),
+    ("cdiff", "diff colors"),
+    ("<nr>gg", "jump to line"),
+    ("<nr>GG", "jump to line"),
+    ("<nr>gt", "switch to tab"),
+    ("<nr>go", "jump to byte"),
+    ("<nr>pp", "jump to percent"),
+    ("<nr>|", "scroll to column"),
+    ("<nr>tw", "set tab width"),
+    ("<nr>j", "move down, also J, k, K, l, L, h, H, n and N"),
+    ("<nr>yy", "copy lines"),
+];
+
+// hint for a command being typed: what its argument is, or the keys completing it
+fn key_hint(command: &str) -> Option<String> {
+    if command.is_empty() {
+        return None;
+    }
+    let (count, motion) = split_count(command);
+    let mut completions = Vec::new();
+    for (keys, hint) in KEY_HINTS.iter() {
+        let (counted, keys) = match keys.strip_prefix("<nr>") {
+            Some(keys) => (true, keys),
+            None => (false, *keys),
+        };
+        if counted != count.is_some() {
+            continue;
+        }
+        if motion.starts_with(keys) {
+            return Some(hint.to_string());
+        }
+        if keys.starts_with(motion) {
+            completions.push(format!("{} {}", keys, hint));
+        }
+    }
+    if completions.is_empty() {
+        return Some(format!("no command starts with {}, Esc to clear", command));
+    }
+    return Some(completions.join(" | "));
+}
+
+// split the count prefix of a command: "12j" -> (Some(12), "j")
+fn split_count(command: &str) -> (Option<i64>, &str) {
+    let digits = command
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(command.len());
+    return (command[..digits].parse().ok(), &command[digits..]);
+}
+
+// line address of a jump: "12" from the top, "-12" from the bottom and "$"
+// for the last line, like the lines of jump_to_line
+fn parse_line_address(address: &str) -> Option<i64> {
+    if address == "$" {
+        return Some(-1);
+    }
+    return address.parse().ok().filter(|x| *x != 0);
+}
+
+// columns `first` to `first + width - 1`, with their number every 10 columns
+fn ruler(first: usize, width: usize) -> String {
+    let mut ruler = (first..first + width)
+        .map(|column| if column % 5 == 0 { '+' } else { '.' })
+        .collect::<Vec<char>>();
+    for column in (first..first + width).filter(|x| x % 10 == 0) {
+        let number = column.to_string();
+        let end = column - first + 1;
+        if end >= number.len() {
+            ruler.splice(end - number.len()..end, number.chars());
+        }
+    }
+    return ruler.into_iter().collect();
+}
+
+// file name of a line shortened or padded to `width` columns, colored by name so
+// that each file keeps its color
+fn source_tag(source: Option<&str>, width: usize, colors: &[Style]) -> Span<'static> {
+    let source = match source {
+        Some(source) => source,
+        None => return Span::raw(" ".repeat(width + 1)),
+    };
+    let name = Path::new(source)
+        .file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_else(|| source.to_owned());
+    // rotated files differ by their end, long names keep it
+    let mut tag = name.clone();
+    if UnicodeWidthStr::width(name.as_str()) > width {
+        tag.clear();
+        for c in name.chars().rev() {
+            if UnicodeWidthStr::width(tag.as_str()) + UnicodeWidthChar::width(c).unwrap_or(0)
+                >= width
+            {
+                break;
+            }
+            tag.insert(0, c);
+        }
+        tag.insert(0, '…');
+    }
+    let padding = width - UnicodeWidthStr::width(tag.as_str());
+    tag.push_str(&" ".repeat(padding + 1));
+    let hash = name
+        .bytes()
+        .fold(0usize, |h, c| h.wrapping_mul(31).wrapping_add(c as usize));
+    let style = match colors.len() {
+        0 => Style::default(),
+        x => colors[hash % x],
+    };
+    return Span::styled(tag, style);
+}
+
+// spans of a range of a line with the styles of its escape sequences, the
+// line may be cut before the end of the styles
+fn styled_spans<'a>(
+    line: &'a str,
+    styles: &[(Range<usize>, Style)],
+    range: Range<usize>,
+) -> Vec<Span<'a>> {
+    let mut spans = Vec::new();
+    let mut start = range.start;
+    for (styled, style) in styles.iter() {
+        let styled = styled.start.max(start)..styled.end.min(range.end);
+        if styled.start >= styled.end {
+            continue;
+        }
+        if start < styled.start {
+            spans.push(Span::raw(&line[start..styled.start]));
+        }
+        spans.push(Span::styled(&line[styled.clone()], *style));
+        start = styled.end;
+    }
+    if start < range.end || spans.is_empty() {
+        spans.push(Span::raw(&line[start..range.end]));
+    }
+    return spans;
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    return column >= area.x
+        && column < area.x + area.width
+        && row >= area.y
+        && row < area.y + area.height;
+}
+
 const HELP: &str = r#"
   MOVING
 
@@ -40,27 +303,86 @@ j, J, PageDown | Move down
 k, K, PageUp   | Move up
 l, L           | Move right
 h, H           | Move left
-<nr>gg         | Jump to line <nr>
+0, $           | Scroll horizontally to the first column, or to the end of the longest line
+<nr>|          | Scroll horizontally to show column <nr>
+<nr>j, <nr>k   | Repeat a motion <nr> times, also for J, K, l, L, h, H, n and N
+<nr>gg, :<nr>  | Jump to line <nr>, -<nr> counts from the end and :$ is the last line
+GG             | Jump to the last line
 <nr>pp         | Jump to <nr>th percent of the file
+<nr>go         | Jump to the line containing byte <nr>
+gn             | Jump to the first line added since the file was opened
 m<letter>      | Place marker <letter>
 '<leter>       | Jump to marker <letter>
+''             | Jump back to where the last jump started
+'0 to '9       | Jump to where the last ten jumps started, '0 being the last
+'^             | Jump back to the position at startup
 
 
   SEARCHING
 
 /pattern       | Jump to the first line matching "pattern"
-n              | Jump to next match
-N              | Jump to previous match
+               | with flags like (?i) or (?x), and bytes like (?-u)\xde\xad
+n              | Jump to next match, within the line first
+N              | Jump to previous match, within the line first
+}              | Jump to the next cluster of matches
+{              | Jump to the previous cluster of matches
+double-click   | Highlight the word under the mouse, with mouse enabled
+]c, [c         | Jump to the next or previous diff hunk
+&expr          | Show only the lines matching expr, regexes combined
+               | with !, && and ||, like "ERROR && !healthcheck"
+&              | Show all the lines
+:set focus     | Dim the lines not matching the filter or the search instead
 
 
   DISPLAY / BEHAVIOR
 
+gt, gT         | Switch to the next or previous tab
+<nr>gt         | Switch to tab <nr>
 w              | Toggle line wrap
-f              | Follow updates
+yy, <nr>yy     | Copy the top line, or <nr> lines, to the clipboard with OSC 52
+f              | Follow updates, moving pauses it until GG
+R              | Reload the file, for files rewritten in place
 <nr>tw         | Set tab width to <nr>
+ru             | Toggle the column ruler above the text
+:set hints     | Show the keys completing the command being typed at the bottom
 cdef           | Default color mode
 clog           | Color log mode
 cent           | Color word entropy mode
+cfld           | Color fields mode
+cdiff          | Color diff mode, added and removed lines
+
+
+  COMMANDS
+
+:set                    | List the options with their values
+:set <option>=<value>   | Set an option, e.g. :set tab-width=8 or :set search-timeout=30s
+:set <option>           | Turn an on/off option on, :set no<option> turns it off
+:continue               | Continue the last timed out search where it stopped
+:source [<text>]        | Show only the lines of the files whose path contains <text>, all without text
+:stats [<nr>,<nr>]      | Show log levels and lines per time histograms
+:line <nr>              | Show the full content of line <nr>
+:header [<mode>]        | Header mode: full, compact or hidden, cycles without mode
+:messages               | Show the errors and notices of the session
+:info                   | Show the format, modification time and inode of the file
+:profile                | Show the time taken by the last frame, command and state, again to hide
+:e <path>               | Open a file, or pick one if <path> is a directory
+:tabnew <path>          | Open a file in a new tab
+:w <file>               | Write all the lines of the file to <file>, decompressed
+:filter [<expr>]        | Same as &expr, all the lines without expression
+:help, :q               | Show this help, exit
+:help options           | Show what each option of :set does
+:q <code>               | Exit with the exit code <code>
+:q!                     | Same as Q
+Tab, Shift-Tab          | Complete the commands and the path of :e and :tabnew, again to cycle
+:bisect start           | Find the first bad line, jumps to the middle of the file
+:bisect start time      | Same, jumps to the middle of the times of the timestamps
+:bisect good|bad        | Mark the top line, jumps to the middle of the lines left
+:bisect reset           | Stop bisecting and go back to where it started
+:mksession <name>       | Save the tabs, their position, marks and filter, and the search, reopen with --session
+:grep-out [<file>|-]    | Write the lines matching the search to <file>, or to stdout when quitting
+:grep-out -C <nr> ...   | Also write <nr> lines of context around each match
+:wordstats <file>       | Export word frequency/entropy of the whole file (CSV or .json)
+:colorize <regex>       | Color each capture group of <re> (e.g. (?P<ts>\S+) (?P<lvl>\S+))
 
 
   OTHER
@@ -68,118 +390,592 @@ cent           | Color word entropy mode
 Ctrl-C         | Cancel search, clear command, exit
 Esc            | Cancel search, clear command
 q              | Exit
+Q              | Exit with a failure code, 1 unless abort-exit-code is set in the config
 ?              | Show/hide this help
+Ctrl-P         | Find an action by name and run it, Enter runs the selected one
 "#;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 enum ColorMode {
     Default,
     Log,
     Entropy,
+    Fields,
+    // added and removed lines of a diff
+    Diff,
+}
+
+// what follows the last line of the file
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EofMarker {
+    // a single line saying so
+    Line,
+    // a ~ on each row after it, like vim
+    Tilde,
+    None,
+}
+
+impl EofMarker {
+    fn parse(value: &str) -> Option<Self> {
+        return match value.trim() {
+            "line" => Some(EofMarker::Line),
+            "tilde" => Some(EofMarker::Tilde),
+            "none" => Some(EofMarker::None),
+            _ => None,
+        };
+    }
+
+    fn name(self) -> &'static str {
+        return match self {
+            EofMarker::Line => "line",
+            EofMarker::Tilde => "tilde",
+            EofMarker::None => "none",
+        };
+    }
+}
+
+const EOF_LINE: &str = "--- END OF FILE ---";
+
+// colored lines of the last frame, reused while their inputs are unchanged
+struct LinesCache {
+    text: Rc<Vec<Line>>,
+    tab_stops: TabStops,
+    color_mode: ColorMode,
+    search: Option<String>,
+    current_match: Option<(u64, Range<usize>)>,
+    entropy_words: Vec<String>,
+    max_render_cols: usize,
+    lines: Vec<Spans<'static>>,
 }
 
 pub struct Frontend {
     terminal: Option<Terminal<backend::CrosstermBackend<Stdout>>>,
     command: String,
-    errors: RefCell<Vec<String>>,
-    search: Option<Regex>,
+    // errors and notices shown in the status
+    notifications: RefCell<Notifications>,
+    // checked like the backend does, which matches bytes
+    search: Option<bytes::Regex>,
     wrap: bool,
     stop: bool,
+    // exit code requested by a quit command
+    exit_code: Option<i32>,
+    abort_exit_code: i32,
+    not_found_exit_code: i32,
+    // search given at startup, until one of its matches is seen
+    unmatched_search: Option<String>,
     follow: bool,
     right_offset: usize,
-    tab_width: usize,
+    // current match last scrolled to
+    revealed_match: Option<(u64, Range<usize>)>,
+    tab_stops: TabStops,
     color_mode: ColorMode,
+    eof_marker: EofMarker,
     show_help: bool,
+    // the help shows the options reference rather than the keys
+    help_options: bool,
+    show_stats: bool,
+    show_line: Option<i64>,
+    show_messages: bool,
+    show_info: bool,
+    // frame timings shown over the text by :profile
+    profile: Option<FrameProfile>,
+    // values of the options, from :set without argument
+    show_options: bool,
+    header_format: Option<String>,
+    header_mode: HeaderMode,
+    text_height: usize,
+    messages_scroll: usize,
+    messages: RefCell<MessageLog>,
+    // requested offset of the last <nr>go jump
+    byte_jump: Option<u64>,
+    minimap_area: Option<Rect>,
+    // area of the file text, without the source tags, and its rows as shown
+    // to find the word under the mouse
+    text_area: Option<Rect>,
+    text_rows: RefCell<Vec<String>>,
+    // time and position of the last left click
+    last_click: Option<(Instant, u16, u16)>,
+    // last resize of the terminal, until the backend gets the new size
+    resized: Option<Instant>,
+    // the work of the backend was cancelled, until it stops
+    aborting: bool,
+    clipboard: Clipboard,
+    redraw: bool,
+    lines_cache: RefCell<Option<LinesCache>>,
     last_sent_resize: Command,
     last_sent_command: RefCell<Command>,
-    command_sender: RefCell<UnboundedSender<Command>>,
-    cancel_sender: RefCell<UnboundedSender<()>>,
-    state_receiver: Receiver<BackendState>,
-    log_colors: Vec<(Regex, Style)>,
+    tabs: Vec<Tab>,
+    current_tab: usize,
+    // file to open from :e or :tabnew, and whether it opens in a new tab
+    file_to_open: Option<(String, bool)>,
+    picker: Option<FilePicker>,
+    // fuzzy list of the actions, opened with Ctrl-P
+    palette: Option<Palette>,
+    // Tab was pressed, true to complete forward and false for Shift-Tab
+    complete_requested: Option<bool>,
+    completion: Option<CompletionMenu>,
+    path_completion_sender: UnboundedSender<PathCompletion>,
+    path_completion_receiver: UnboundedReceiver<PathCompletion>,
+    // files written to stdout when quitting
+    print_on_exit: Vec<String>,
+    // output file and exit status of the command of --exec
+    exec: Option<(String, ExecStatus)>,
+    // sends the backends of new tabs to be run
+    backend_sender: UnboundedSender<Backend>,
+    max_fps: u32,
+    log_colorizer: LogColorizer,
+    field_colorizer: Option<FieldColorizer>,
     entropy_colors: Vec<Style>,
     entropy_last_words: RefCell<Vec<(String, Style)>>,
+    entropy_words: usize,
+    source_tag_width: usize,
+    word_separators: WordSeparators,
+    // options of the backends, sent to each tab
+    strip_cr: bool,
+    ansi_passthrough: bool,
+    search_timeout: Option<Duration>,
+    search_offset: SearchOffset,
+    scrolloff: usize,
+    jump_center: bool,
+    // hint bar for the keys being typed
+    hints: bool,
+    verify_checksums: bool,
+    no_cache: bool,
+    auto_reload: bool,
+    // show the line numbers before the lines
+    number: bool,
+    // show the columns of the text above it
+    ruler: bool,
+    // columns kept on screen when scrolling horizontally
+    freeze: usize,
+    // longer lines are cut, 0 for no limit
+    max_render_cols: usize,
+    wrap_indent: WrapIndent,
+    // dim the lines not matching the filter, or the search, instead of hiding them
+    focus: bool,
+    // filter expression of the dimmed lines, parsed once
+    focus_filter: RefCell<Option<(String, Predicate)>>,
+    ignore_case: bool,
+    aliases: HashMap<String, String>,
+    theme: Theme,
 }
 
 impl Frontend {
     pub fn new(
-        command_sender: UnboundedSender<Command>,
-        cancel_sender: UnboundedSender<()>,
-        state_receiver: Receiver<BackendState>,
-    ) -> io::Result<Self> {
+        tab: Tab,
+        backend_sender: UnboundedSender<Backend>,
+        max_fps: u32,
+        theme: Theme,
+        config: &Config,
+    ) -> Result<Self> {
         let crossterm_backend = backend::CrosstermBackend::new(io::stdout());
         let terminal = Terminal::new(crossterm_backend)?;
-        let log_colors = Frontend::make_log_colors();
-        let entropy_colors = Frontend::make_entropy_colors();
-        return Ok(Self {
+        let log_colorizer = LogColorizer::new(&theme, &config.log)?;
+        let entropy_colors = theme.entropy.clone();
+        let field_colorizer = match &config.fields_pattern {
+            Some(pattern) => Some(FieldColorizer::new(pattern, &theme.entropy)?),
+            None => None,
+        };
+        let (path_completion_sender, path_completion_receiver) = mpsc::unbounded_channel();
+        let mut frontend = Self {
             terminal: Some(terminal),
             command: String::new(),
-            errors: RefCell::from(Vec::new()),
+            notifications: RefCell::from(Notifications::default()),
             last_sent_resize: Command::Resize(None, 0),
             last_sent_command: RefCell::from(Command::Resize(None, 0)),
             right_offset: 0,
-            tab_width: 4,
+            revealed_match: None,
+            tab_stops: TabStops::new(DEFAULT_TAB_WIDTH),
             color_mode: ColorMode::Default,
+            eof_marker: EofMarker::Line,
             show_help: false,
+            help_options: false,
+            show_stats: false,
+            show_line: None,
+            show_messages: false,
+            show_info: false,
+            profile: None,
+            show_options: false,
+            header_format: config.header_format.clone(),
+            header_mode: config.header,
+            text_height: 0,
+            messages_scroll: 0,
+            messages: RefCell::from(MessageLog::default()),
+            byte_jump: None,
+            minimap_area: None,
+            text_area: None,
+            text_rows: RefCell::from(Vec::new()),
+            last_click: None,
+            resized: None,
+            aborting: false,
+            clipboard: Clipboard::new(),
+            redraw: true,
+            lines_cache: RefCell::from(None),
             search: None,
             wrap: true,
             stop: false,
+            exit_code: None,
+            abort_exit_code: config.abort_exit_code.unwrap_or(1),
+            not_found_exit_code: config.not_found_exit_code.unwrap_or(0),
+            unmatched_search: None,
             follow: false,
-            command_sender: RefCell::from(command_sender),
-            cancel_sender: RefCell::from(cancel_sender),
-            state_receiver,
-            log_colors,
+            tabs: vec![tab],
+            current_tab: 0,
+            file_to_open: None,
+            picker: None,
+            palette: None,
+            complete_requested: None,
+            print_on_exit: Vec::new(),
+            exec: None,
+            completion: None,
+            path_completion_sender,
+            path_completion_receiver,
+            backend_sender,
+            max_fps,
+            log_colorizer,
+            field_colorizer,
             entropy_colors,
             entropy_last_words: RefCell::from(Vec::new()),
+            entropy_words: theme.entropy.len(),
+            source_tag_width: config.source_tag_width.unwrap_or(DEFAULT_SOURCE_TAG_WIDTH),
+            word_separators: config
+                .word_separators
+                .as_deref()
+                .map(WordSeparators::new)
+                .unwrap_or_default(),
+            strip_cr: true,
+            ansi_passthrough: false,
+            search_timeout: None,
+            search_offset: SearchOffset::Top,
+            scrolloff: 0,
+            jump_center: false,
+            hints: false,
+            verify_checksums: false,
+            no_cache: false,
+            auto_reload: false,
+            number: false,
+            ruler: false,
+            freeze: 0,
+            max_render_cols: DEFAULT_MAX_RENDER_COLS,
+            wrap_indent: WrapIndent::default(),
+            focus: false,
+            focus_filter: RefCell::new(None),
+            ignore_case: false,
+            aliases: config.aliases.clone(),
+            theme,
+        };
+        for command in frontend.tab_option_commands() {
+            frontend.send_command(command);
+        }
+        let mut options = config.options.iter().collect::<Vec<_>>();
+        options.sort_by_key(|(name, _)| name.as_str());
+        for (name, value) in options {
+            let value = match value {
+                toml::Value::Boolean(true) => "on".to_owned(),
+                toml::Value::Boolean(false) => "off".to_owned(),
+                toml::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            frontend.set_option(&format!("{}={}", name, value));
+        }
+        return Ok(frontend);
+    }
+
+    pub fn print_on_exit(&self) -> &[String] {
+        return &self.print_on_exit;
+    }
+
+    pub fn show_diff(&mut self) {
+        self.set_color_mode(ColorMode::Diff);
+    }
+
+    pub fn follow_command(&mut self, status: ExecStatus) {
+        self.exec = Some((self.tabs[0].path.clone(), status));
+        self.follow = true;
+        self.send_command(Command::Follow(true));
+    }
+
+    pub fn position(&self) -> (String, Option<i64>, u64) {
+        let tab = &self.tabs[0];
+        let back = tab.state_receiver.borrow();
+        return (
+            tab.path.clone(),
+            back.current_line.filter(|x| *x > 0),
+            back.offset,
+        );
+    }
+
+    // the first tab is already open at the position of the session, the
+    // others are opened after it
+    pub async fn restore_session(&mut self, session: &Session) {
+        // restore the search pattern without moving the views, switching
+        // tabs maps its matches
+        if let Some(pattern) = &session.search {
+            match bytes::Regex::new(pattern) {
+                Ok(re) => {
+                    self.search = Some(re);
+                    self.send_command(Command::MatchMap(Some(pattern.to_owned())));
+                }
+                Err(e) => self.push_error(format!("invalid regex: {}", regex_error(&e))),
+            }
+        }
+
+        let mut current = 0;
+        for (index, tab) in session.tabs.iter().enumerate() {
+            if index > 0 {
+                let count = self.tabs.len();
+                self.open_file(&tab.path, true).await;
+                if self.tabs.len() == count {
+                    continue;
+                }
+                self.send_command(Command::JumpByte(tab.offset));
+            }
+            if index == session.current {
+                current = self.current_tab;
+            }
+            for (name, offset) in tab.marks.iter() {
+                self.send_command(Command::SetMark(name.clone(), *offset));
+            }
+            if let Some(filter) = &tab.filter {
+                self.set_filter(filter);
+            }
+            if tab.source_filter.is_some() {
+                self.send_command(Command::SourceFilter(tab.source_filter.clone()));
+            }
+        }
+        self.switch_tab(current);
+    }
+
+    // the tabs as they are now, their path made absolute to reopen them from
+    // another directory
+    fn session(&self) -> Session {
+        let tabs = self.tabs.iter().map(|tab| {
+            let back = tab.state_receiver.borrow();
+            TabSession {
+                path: absolute_path(&tab.path),
+                offset: back.offset,
+                marks: back.marks.iter().cloned().collect(),
+                filter: back.filter.clone(),
+                source_filter: back.source_filter.clone(),
+            }
         });
+        return Session {
+            tabs: tabs.collect(),
+            current: self.current_tab,
+            search: self.search.as_ref().map(|x| x.as_str().to_owned()),
+        };
     }
 
-    fn make_log_colors() -> Vec<(Regex, Style)> {
-        return vec![
-            (
-                Regex::new("(?i)trace").unwrap(),
-                Style::default().fg(Color::Cyan),
-            ),
-            (
-                Regex::new("(?i)debug").unwrap(),
-                Style::default().fg(Color::Green),
-            ),
-            (
-                Regex::new("(?i)info").unwrap(),
-                Style::default().fg(Color::Gray),
-            ),
-            (
-                Regex::new("(?i)warn").unwrap(),
-                Style::default().fg(Color::Yellow),
-            ),
-            (
-                Regex::new("(?i)error").unwrap(),
-                Style::default().fg(Color::Red),
-            ),
-            (
-                Regex::new("(?i)fatal|critical").unwrap(),
-                Style::default().fg(Color::LightRed),
-            ),
-        ];
+    // search a pattern given at startup, the exit code tells if it matched
+    pub fn initial_search(&mut self, pattern: &str) {
+        self.search_pattern(pattern);
+        self.unmatched_search = self.search.as_ref().map(|x| x.as_str().to_owned());
     }
 
-    fn make_entropy_colors() -> Vec<Style> {
-        return vec![
-            Style::default().fg(Color::LightRed),
-            Style::default().fg(Color::LightYellow),
-            Style::default().fg(Color::LightGreen),
-            Style::default().fg(Color::LightCyan),
-            Style::default().fg(Color::LightBlue),
-            Style::default().fg(Color::LightMagenta),
-            Style::default().fg(Color::Red),
-            Style::default().fg(Color::Yellow),
-            Style::default().fg(Color::Green),
-            Style::default().fg(Color::Cyan),
-            Style::default().fg(Color::Blue),
-            Style::default().fg(Color::Magenta),
-        ];
+    // a command line given at startup, the leading : is optional and / and &
+    // search and filter like their keys
+    pub fn execute_startup_command(&mut self, command: &str) {
+        let command = command.trim();
+        match command.get(..1) {
+            Some("/") => self.search_pattern(&command[1..]),
+            Some("&") => self.set_filter(&command[1..]),
+            Some(":") => self.execute_command_line(&command[1..]),
+            _ => self.execute_command_line(command),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        if let Some(code) = self.exit_code {
+            return code;
+        }
+        if self.unmatched_search.is_some() {
+            return self.not_found_exit_code;
+        }
+        return 0;
+    }
+
+    // forget the search given at startup once the match map finds it
+    fn check_initial_search(&mut self) {
+        let found = match (
+            &self.unmatched_search,
+            &self.tab().state_receiver.borrow().match_map,
+        ) {
+            (Some(pattern), Some(map)) => map.pattern == *pattern && map.count > 0,
+            _ => false,
+        };
+        if found {
+            self.unmatched_search = None;
+        }
+    }
+
+    fn quit(&mut self, exit_code: Option<i32>) {
+        self.exit_code = exit_code;
+        self.stop = true;
+    }
+
+    // jump to the first match of a pattern and highlight the others, an empty
+    // pattern clears the search
+    pub fn search_pattern(&mut self, pattern: &str) {
+        if pattern.is_empty() {
+            self.search = None;
+            self.send_command(Command::MatchMap(None));
+            return;
+        }
+        let pattern = if self.ignore_case && !has_uppercase(pattern) {
+            format!("(?i){}", pattern)
+        } else {
+            pattern.to_owned()
+        };
+        match bytes::Regex::new(&pattern) {
+            Ok(re) => {
+                self.search = Some(re);
+                self.send_command(Command::SearchDown(pattern.clone()));
+                self.send_command(Command::MatchMap(Some(pattern)));
+            }
+            Err(e) => self.push_error(format!("invalid regex: {}", regex_error(&e))),
+        }
+    }
+
+    fn tab(&self) -> &Tab {
+        return &self.tabs[self.current_tab];
+    }
+
+    // open a file in a new tab or in place of the current one
+    async fn open_file(&mut self, path: &str, new_tab: bool) {
+        match Tab::open(path, self.max_fps).await {
+            Ok((tab, backend)) => {
+                if self.backend_sender.send(backend).is_err() {
+                    self.push_error("backend channel error".to_owned());
+                    return;
+                }
+                if new_tab {
+                    self.tabs.push(tab);
+                    self.switch_tab(self.tabs.len() - 1);
+                } else {
+                    // dropping the tab stops its backend
+                    self.tabs[self.current_tab] = tab;
+                    self.switch_tab(self.current_tab);
+                }
+                for command in self.tab_option_commands() {
+                    self.send_command(command);
+                }
+            }
+            Err(e) => self.push_error(format!("cannot open {}: {}", path, e)),
+        }
+    }
+
+    fn open_picked(&mut self) {
+        let entry = match self.picker.as_ref().and_then(|x| x.selected()) {
+            Some(entry) => entry.clone(),
+            None => return,
+        };
+        if entry.is_dir {
+            match FilePicker::new(&entry.path) {
+                Ok(picker) => self.picker = Some(picker),
+                Err(e) => self.push_error(format!("cannot read {}: {}", entry.name, e)),
+            }
+        } else {
+            self.picker = None;
+            self.file_to_open = Some((entry.path.to_string_lossy().into_owned(), false));
+        }
+    }
+
+    // complete the command names and the path argument of :e and :tabnew,
+    // further calls cycle through the candidates
+    fn complete_command(&mut self, forward: bool) {
+        if let Some(menu) = self.completion.as_mut() {
+            self.command = menu.cycle(forward);
+            return;
+        }
+
+        let (prefix, candidates) = match self.command.split_once(' ') {
+            Some((name, partial)) if name == ":e" || name == ":tabnew" => {
+                // the directory is read off the loop, the candidates come back
+                // through the channel
+                let sender = self.path_completion_sender.clone();
+                let command = self.command.clone();
+                let prefix = format!("{} ", name);
+                let partial = partial.to_owned();
+                task::spawn_blocking(move || {
+                    let candidates = path_candidates(&partial);
+                    sender
+                        .send(PathCompletion {
+                            command,
+                            prefix,
+                            candidates,
+                        })
+                        .ok();
+                });
+                return;
+            }
+            Some(_) => return,
+            None => match self.command.strip_prefix(':') {
+                Some(partial) => (":".to_owned(), self.command_candidates(partial)),
+                None => return,
+            },
+        };
+        self.show_candidates(prefix, candidates);
+    }
+
+    // the candidates of paths read for another command line are dropped
+    fn show_path_candidates(&mut self, completion: PathCompletion) {
+        if completion.command == self.command && self.completion.is_none() {
+            self.show_candidates(completion.prefix, completion.candidates);
+        }
+    }
+
+    fn show_candidates(&mut self, prefix: String, candidates: Vec<String>) {
+        match candidates.len() {
+            0 => (),
+            1 => self.command = format!("{}{}", prefix, candidates[0]),
+            _ => {
+                self.command = format!("{}{}", prefix, common_prefix(&candidates));
+                self.completion = Some(CompletionMenu {
+                    prefix,
+                    candidates,
+                    selected: None,
+                });
+            }
+        }
+    }
+
+    // commands and aliases starting with `partial`
+    fn command_candidates(&self, partial: &str) -> Vec<String> {
+        let mut candidates = EX_COMMANDS
+            .iter()
+            .map(|x| x.to_string())
+            .chain(self.aliases.keys().cloned())
+            .filter(|x| x.starts_with(partial))
+            .collect::<Vec<String>>();
+        candidates.sort();
+        candidates.dedup();
+        return candidates;
+    }
+
+    fn switch_tab(&mut self, index: usize) {
+        if index >= self.tabs.len() {
+            self.push_error(format!("no tab {}", index + 1));
+            return;
+        }
+        self.current_tab = index;
+        // each backend has its own view size and match map
+        self.last_sent_resize = Command::Resize(None, 0);
+        let (follow, match_map) = {
+            let back = self.tab().state_receiver.borrow();
+            (back.follow, back.match_map.clone())
+        };
+        self.follow = follow;
+        if let Some(re) = self.search.as_ref() {
+            if match_map.map(|x| x.pattern != re.as_str()).unwrap_or(true) {
+                self.send_command(Command::MatchMap(Some(re.as_str().to_owned())));
+            }
+        }
     }
 
     fn update_backend_size(&mut self, width: usize, height: usize) {
+        if self.resized.is_some() {
+            return;
+        }
         let cmd = Command::Resize(if self.wrap { Some(width) } else { None }, height);
         if cmd != self.last_sent_resize {
             self.last_sent_resize = cmd;
@@ -187,6 +983,13 @@ impl Frontend {
         }
     }
 
+    // time left before the backend gets the size of a resized terminal
+    fn resize_wait(&self) -> Option<Duration> {
+        return self
+            .resized
+            .map(|x| RESIZE_DEBOUNCE.saturating_sub(x.elapsed()));
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         let mut events_reader = EventStream::new();
         let mut signals_reader = Signals::new(TERM_SIGNALS)?;
@@ -194,21 +997,85 @@ impl Frontend {
         let term_size = self.terminal.as_ref().unwrap().size().unwrap();
         self.update_backend_size(term_size.width.into(), term_size.height.into());
 
+        // refresh every second when the header shows the clock or waits for
+        // the command of --exec to exit
+        let clock_interval =
+            if uses_placeholder(self.header_format(), "clock") || self.exec.is_some() {
+                Duration::from_secs(1)
+            } else {
+                Duration::from_secs(3600)
+            };
+
         while !self.stop {
-            self.update()?;
+            if self.redraw {
+                self.update()?;
+                self.redraw = false;
+            }
 
+            let mut wait = self.resize_wait().unwrap_or(clock_interval);
+            if let Some(expiry) = self.notifications.borrow().next_expiry() {
+                wait = wait.min(expiry);
+            }
+            if self.activity().is_some() {
+                wait = wait.min(SPINNER_INTERVAL);
+            }
+            // the state receiver of the tab is borrowed by the select too
+            let mut activity_receiver = self.tab().activity_receiver.clone();
             select! {
                 maybe_event = events_reader.next().fuse() => match maybe_event {
-                    Some(Ok(Event::Key(key))) => self.handle_key(key),
-                    Some(Ok(Event::Resize(_, height))) => self.send_command(Command::Resize(None, height as usize)),
-                    Some(Ok(_)) => {},
+                    Some(Ok(Event::Key(key))) => {
+                        self.handle_key(key);
+                        if let Some(forward) = self.complete_requested.take() {
+                            self.complete_command(forward);
+                        }
+                        if let Some((path, new_tab)) = self.file_to_open.take() {
+                            self.open_file(&path, new_tab).await;
+                        }
+                        self.redraw = true;
+                    },
+                    Some(Ok(Event::Mouse(mouse))) => self.redraw = self.handle_mouse(mouse),
+                    // the backend size is updated when drawing, once the
+                    // resizes stop
+                    Some(Ok(Event::Resize(_, _))) => {
+                        self.resized = Some(Instant::now());
+                        self.redraw = true;
+                    },
                     Some(Err(e)) => return Err(e.into()),
                     None => return Err(FrontendError::EndOfEventStream.into()),
                 },
-                maybe_state = self.state_receiver.changed().fuse() => match maybe_state {
-                    Ok(_) => (),
+                maybe_state = self.tabs[self.current_tab].state_receiver.changed().fuse() => match maybe_state {
+                    Ok(_) => {
+                        self.log_backend_messages();
+                        self.check_initial_search();
+                        self.reveal_current_match();
+                        self.redraw = true;
+                    },
+                    Err(_) => return Err(ChannelError::State.into())
+                },
+                maybe_completion = self.path_completion_receiver.recv().fuse() => {
+                    if let Some(completion) = maybe_completion {
+                        self.show_path_candidates(completion);
+                        self.redraw = true;
+                    }
+                },
+                maybe_activity = activity_receiver.changed().fuse() => match maybe_activity {
+                    Ok(_) => {
+                        // seen, the next clone waits for the next change
+                        self.tabs[self.current_tab].activity_receiver.borrow_and_update();
+                        if self.activity().is_none() {
+                            self.aborting = false;
+                        }
+                        self.redraw = true;
+                    },
                     Err(_) => return Err(ChannelError::State.into())
                 },
+                _ = time::sleep(wait).fuse() => {
+                    if self.resize_wait() == Some(Duration::ZERO) {
+                        self.resized = None;
+                    }
+                    self.notifications.borrow_mut().expire();
+                    self.redraw = true;
+                },
                 maybe_signal = signals_reader.next().fuse() => match maybe_signal {
                     Some(signal) => {
                         info!("received signal {}", signal);
@@ -223,15 +1090,26 @@ impl Frontend {
     }
 
     fn update(&mut self) -> Result<()> {
+        let start = Instant::now();
         let mut terminal = self.terminal.take().unwrap();
         terminal.draw(|f| self.refresh(f)).unwrap();
         self.terminal = Some(terminal);
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record(start);
+        }
         Ok(())
     }
 
     fn handle_key(&mut self, key: KeyEvent) {
-        let height = self.terminal.as_ref().unwrap().size().unwrap().height as i64;
+        if self.palette.is_some() {
+            return self.handle_palette_key(key);
+        }
+        let height = self.text_height as i64;
         let mut command_done = true;
+        self.byte_jump = None;
+        if key.code != KeyCode::Tab && key.code != KeyCode::BackTab {
+            self.completion = None;
+        }
 
         match key {
             KeyEvent {
@@ -240,14 +1118,37 @@ impl Frontend {
             } => {
                 if self.show_help {
                     self.show_help = false;
+                } else if self.show_options {
+                    self.show_options = false;
+                } else if self.show_stats {
+                    self.show_stats = false;
+                    self.send_cancel();
+                } else if self.show_line.is_some() {
+                    self.show_line = None;
+                } else if self.show_messages {
+                    self.show_messages = false;
+                } else if self.show_info {
+                    self.show_info = false;
+                } else if self.picker.is_some() {
+                    self.picker = None;
                 } else if !self.command.is_empty() || self.search.is_some() {
                     self.command.clear();
                     self.search = None;
                     self.send_cancel();
+                } else if self.activity().is_some_and(|x| x.operation.abortable()) {
+                    self.aborting = true;
+                    self.send_cancel();
                 } else {
                     self.stop = true;
                 }
             }
+            KeyEvent {
+                modifiers: KeyModifiers::CONTROL,
+                code: KeyCode::Char('p'),
+            } => {
+                self.command.clear();
+                self.palette = Some(Palette::new());
+            }
             KeyEvent {
                 code: KeyCode::Char(c),
                 ..
@@ -296,9 +1197,24 @@ impl Frontend {
             } => {
                 if self.show_help {
                     self.show_help = false;
+                } else if self.show_options {
+                    self.show_options = false;
+                } else if self.show_stats {
+                    self.show_stats = false;
+                    self.send_cancel();
+                } else if self.show_line.is_some() {
+                    self.show_line = None;
+                } else if self.show_messages {
+                    self.show_messages = false;
+                } else if self.show_info {
+                    self.show_info = false;
+                } else if self.picker.is_some() {
+                    self.picker = None;
                 } else {
                     self.command.clear();
                     self.search = None;
+                    self.notifications.borrow_mut().dismiss();
+                    self.aborting = self.activity().is_some_and(|x| x.operation.abortable());
                     self.send_cancel();
                 }
             }
@@ -306,6 +1222,13 @@ impl Frontend {
                 code: KeyCode::Enter,
                 ..
             } => self.command.push('\n'),
+            KeyEvent {
+                code: KeyCode::Tab, ..
+            } => self.complete_requested = Some(true),
+            KeyEvent {
+                code: KeyCode::BackTab,
+                ..
+            } => self.complete_requested = Some(false),
             KeyEvent {
                 code: KeyCode::Backspace,
                 ..
@@ -315,9 +1238,22 @@ impl Frontend {
             _ => (),
         };
 
-        match self.command.as_str() {
-            "?" => self.show_help = !self.show_help,
+        // plain motions accept a count prefix, like "12j"
+        let (count, motion) = split_count(&self.command);
+        let repeat = count.unwrap_or(1).max(1);
+        let command = if count.is_some() && COUNTED_MOTIONS.contains(&motion) {
+            motion
+        } else {
+            self.command.as_str()
+        };
+
+        match command {
+            "?" => {
+                self.show_help = !self.show_help;
+                self.help_options = false;
+            }
             "q" => self.stop = true,
+            "Q" => self.quit(Some(self.abort_exit_code)),
             "w" => {
                 self.wrap = !self.wrap;
                 self.right_offset = 0;
@@ -329,7 +1265,7 @@ impl Frontend {
             "n" => {
                 if let Some(re) = self.search.as_ref() {
                     self.follow = false;
-                    self.send_command(Command::SearchDownNext(re.as_str().to_owned()));
+                    self.send_command(Command::SearchDownNext(re.as_str().to_owned(), repeat));
                 } else {
                     self.push_error("nothing to search".to_owned());
                 }
@@ -337,7 +1273,7 @@ impl Frontend {
             "N" => {
                 if let Some(re) = self.search.as_ref() {
                     self.follow = false;
-                    self.send_command(Command::SearchUp(re.as_str().to_owned()));
+                    self.send_command(Command::SearchUp(re.as_str().to_owned(), repeat));
                 } else {
                     self.push_error("nothing to search".to_owned());
                 }
@@ -350,49 +1286,105 @@ impl Frontend {
                 self.follow = false;
                 self.send_command(Command::JumpLine(-1))
             }
+            "R" => self.send_command(Command::Reload),
+            "gn" => {
+                self.follow = false;
+                self.send_command(Command::JumpNew)
+            }
+            "j" if self.picker.is_some() => self.picker.as_mut().unwrap().move_selection(repeat),
+            "k" if self.picker.is_some() => self.picker.as_mut().unwrap().move_selection(-repeat),
+            "\n" if self.picker.is_some() => self.open_picked(),
+            "j" if self.show_messages => {
+                self.messages_scroll = self.messages_scroll.saturating_sub(repeat as usize)
+            }
+            "k" if self.show_messages => {
+                let max_scroll = self.messages.borrow().len().saturating_sub(1);
+                self.messages_scroll = (self.messages_scroll + repeat as usize).min(max_scroll)
+            }
             "j" => {
                 self.follow = false;
-                self.send_command(Command::MoveLine(1))
+                self.send_command(Command::MoveLine(repeat))
             }
             "J" => {
                 self.follow = false;
-                self.send_command(Command::MoveLine(FAST_SCROLL_LINES))
+                self.send_command(Command::MoveLine(FAST_SCROLL_LINES.saturating_mul(repeat)))
             }
             "k" => {
                 self.follow = false;
-                self.send_command(Command::MoveLine(-1))
+                self.send_command(Command::MoveLine(-repeat))
             }
             "K" => {
                 self.follow = false;
-                self.send_command(Command::MoveLine(-FAST_SCROLL_LINES))
-            }
-            "l" => self.right_offset += 1,
-            "L" => self.right_offset += FAST_SCROLL_LINES as usize,
-            "h" => self.right_offset = self.right_offset.saturating_sub(1),
-            "H" => self.right_offset = self.right_offset.saturating_sub(FAST_SCROLL_LINES as usize),
-            "clog" => self.color_mode = ColorMode::Log,
-            "cent" => self.color_mode = ColorMode::Entropy,
-            "cdef" => self.color_mode = ColorMode::Default,
+                self.send_command(Command::MoveLine(-FAST_SCROLL_LINES.saturating_mul(repeat)))
+            }
+            "yy" => self.copy_lines(repeat as usize),
+            "ru" => self.ruler = !self.ruler,
+            "}" => self.jump_to_cluster(true),
+            "{" => self.jump_to_cluster(false),
+            "0" => self.right_offset = 0,
+            "$" => self.scroll_to_end(),
+            "l" => self.right_offset = self.right_offset.saturating_add(repeat as usize),
+            "L" => {
+                self.right_offset = self
+                    .right_offset
+                    .saturating_add(FAST_SCROLL_LINES.saturating_mul(repeat) as usize)
+            }
+            "h" => self.right_offset = self.right_offset.saturating_sub(repeat as usize),
+            "H" => {
+                self.right_offset = self
+                    .right_offset
+                    .saturating_sub(FAST_SCROLL_LINES.saturating_mul(repeat) as usize)
+            }
+            "gt" => self.switch_tab((self.current_tab + 1) % self.tabs.len()),
+            "gT" => self.switch_tab((self.current_tab + self.tabs.len() - 1) % self.tabs.len()),
+            "clog" => self.set_color_mode(ColorMode::Log),
+            "cent" => self.set_color_mode(ColorMode::Entropy),
+            "cdef" => self.set_color_mode(ColorMode::Default),
+            "cdiff" => self.set_color_mode(ColorMode::Diff),
+            "]c" => {
+                self.follow = false;
+                self.send_command(Command::SearchDownNext(HUNK_PATTERN.to_owned(), 1));
+            }
+            "[c" => {
+                self.follow = false;
+                self.send_command(Command::SearchUp(HUNK_PATTERN.to_owned(), 1));
+            }
+            "cfld" => {
+                if self.field_colorizer.is_some() {
+                    self.set_color_mode(ColorMode::Fields);
+                } else {
+                    self.push_error("no fields pattern, use :colorize <regex>".to_owned());
+                }
+            }
             x => match x.get(..1).unwrap_or("") {
+                ":" => {
+                    if x.ends_with("\n") {
+                        let line = x[1..x.len() - 1].to_owned();
+                        self.execute_command_line(&line);
+                    } else {
+                        command_done = false;
+                    }
+                }
                 "/" => {
                     if x.ends_with("\n") {
-                        let pattern = x.get(1..x.len() - 1).unwrap_or("");
-                        if pattern.is_empty() {
-                            self.search = None;
-                        } else if let Ok(re) =
-                            Regex::new(pattern).map_err(|_| ViewError::InvalidRegex)
-                        {
-                            self.search = Some(re);
-                            self.send_command(Command::SearchDown(pattern.to_string()));
-                        } else {
-                            self.push_error("invalid regex".to_owned());
-                        }
+                        let pattern = x[1..x.len() - 1].to_owned();
+                        self.search_pattern(&pattern);
+                    } else {
+                        command_done = false;
+                    }
+                }
+                "&" => {
+                    if x.ends_with("\n") {
+                        let expr = x[1..x.len() - 1].to_owned();
+                        self.set_filter(&expr);
                     } else {
                         command_done = false;
                     }
                 }
                 "m" => {
-                    if x.len() > 1 {
+                    if x.len() > 1 && is_jump_mark(&x[1..2]) {
+                        self.push_error("marks ', ^ and 0 to 9 are set by bless".to_owned())
+                    } else if x.len() > 1 {
                         self.send_command(Command::SaveMark(String::from(&x[1..2])))
                     } else {
                         command_done = false;
@@ -408,8 +1400,23 @@ impl Frontend {
                 }
                 _ => match x {
                     x if x.to_lowercase().ends_with("gg") => {
-                        if let Ok(line) = x.get(..x.len() - 2).unwrap().parse::<i64>() {
+                        if let Some(line) = parse_line_address(x.get(..x.len() - 2).unwrap()) {
                             self.send_command(Command::JumpLine(line))
+                        } else {
+                            self.push_error("not a line number".to_owned());
+                        }
+                    }
+                    x if x.ends_with("gt") => {
+                        match x.get(..x.len() - 2).unwrap().parse::<usize>() {
+                            Ok(index) if index > 0 => self.switch_tab(index - 1),
+                            _ => self.push_error("not a tab number".to_owned()),
+                        }
+                    }
+                    x if x.ends_with("go") => {
+                        if let Ok(byte) = x.get(..x.len() - 2).unwrap().parse::<u64>() {
+                            self.follow = false;
+                            self.byte_jump = Some(byte);
+                            self.send_command(Command::JumpByte(byte))
                         } else {
                             self.push_error("not a number".to_owned());
                         }
@@ -421,9 +1428,14 @@ impl Frontend {
                             self.push_error("not a number".to_owned());
                         }
                     }
+                    x if x.ends_with('|') => match x[..x.len() - 1].parse::<usize>() {
+                        Ok(column) if column > 0 => self.scroll_to_column(column),
+                        _ => self.push_error("not a column number".to_owned()),
+                    },
                     x if x.ends_with("tw") => {
                         if let Ok(width) = x.get(..x.len() - 2).unwrap().parse::<usize>() {
-                            self.tab_width = width
+                            self.tab_stops.width = width;
+                            self.send_layout();
                         } else {
                             self.push_error("not a number".to_owned());
                         }
@@ -438,205 +1450,1502 @@ impl Frontend {
         }
     }
 
-    fn refresh<B: backend::Backend>(&mut self, f: &mut Frame<B>) {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Min(4), Constraint::Percentage(100)].as_ref())
-            .split(f.size());
-
-        let text_width = chunks[1].width as usize;
-        let text_height = chunks[1].height as usize;
-        self.update_backend_size(text_width, text_height);
-
-        let back = self.state_receiver.borrow();
-        let backend_text = convert_tabs(
-            back.text.iter().map(|x| Cow::from(x)).collect(),
-            self.tab_width,
-        );
-
-        let text = if self.show_help {
-            Text::from(HELP)
-        } else {
-            let lines: Vec<&str> = backend_text.iter().map(|x| x.as_ref()).collect();
-            let mut lines = self.color_lines(lines);
-            if lines.len() < text_height {
-                lines.push(Spans::from(Span::styled(
-                    "<EOF>",
-                    Style::default().fg(Color::Red).bg(Color::DarkGray),
-                )));
+    fn handle_palette_key(&mut self, key: KeyEvent) {
+        let palette = self.palette.as_mut().unwrap();
+        match key {
+            KeyEvent {
+                modifiers: KeyModifiers::CONTROL,
+                code: KeyCode::Char('c'),
             }
-
-            if self.right_offset > 0 {
-                lines = self.shift_lines(lines, self.right_offset);
+            | KeyEvent {
+                code: KeyCode::Esc, ..
+            } => self.palette = None,
+            KeyEvent {
+                modifiers: KeyModifiers::CONTROL,
+                code: KeyCode::Char('n'),
             }
-
-            Text::from(lines)
-        };
-
-        let mut flags = Vec::new();
-        if back.follow {
-            flags.push("Follow".to_owned())
-        }
-        if self.wrap {
-            flags.push("Wrap".to_owned())
-        }
-        if !back.marks.is_empty() {
-            flags.push(format!("Marks: {}", back.marks.join("")));
-        }
-        if let Some(re) = &self.search {
-            flags.push(format!("/{}", re.to_string()));
-        } else if self.color_mode != ColorMode::Default {
-            flags.push(format!("{:?}", self.color_mode))
+            | KeyEvent {
+                code: KeyCode::Down,
+                ..
+            } => palette.move_selection(1),
+            KeyEvent {
+                modifiers: KeyModifiers::CONTROL,
+                code: KeyCode::Char('p'),
+            }
+            | KeyEvent {
+                code: KeyCode::Up, ..
+            } => palette.move_selection(-1),
+            KeyEvent {
+                code: KeyCode::Char(c),
+                ..
+            } => palette.push(c),
+            KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            } => palette.pop(),
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } => {
+                let action = palette.selected();
+                self.palette = None;
+                // type the keys of the action, the last one runs it like a key press
+                if let Some(action) = action {
+                    let mut keys = action.keys.to_owned();
+                    let last = keys.pop().unwrap();
+                    self.command = keys;
+                    self.handle_key(KeyEvent::new(
+                        match last {
+                            '\n' => KeyCode::Enter,
+                            c => KeyCode::Char(c),
+                        },
+                        KeyModifiers::NONE,
+                    ));
+                }
+            }
+            _ => (),
         }
+    }
 
-        let header_title = format!(
-            "{} - {}",
-            back.real_file_path,
-            human_bytes(back.file_size as f64)
-        );
-        let header = Text::from(
-            [
-                format!(
-                    "Line {}, Offset {} ({:.1}%){}",
-                    back.current_line
-                        .map(|x| x.to_string())
-                        .unwrap_or("?".to_owned()),
-                    human_bytes(back.offset as f64),
-                    100.0 * back.offset as f64 / back.file_size as f64,
-                    if flags.is_empty() {
-                        "".to_owned()
+    // show only the lines matching a filter expression, all of them without one
+    fn set_filter(&mut self, expr: &str) {
+        let expr = expr.trim();
+        if expr.is_empty() {
+            self.send_command(Command::Filter(None));
+        } else if let Err(e) = Predicate::parse(expr) {
+            self.push_error(e.to_string());
+        } else {
+            self.send_command(Command::Filter(Some(expr.to_owned())));
+        }
+    }
+
+    fn execute_command_line(&mut self, line: &str) {
+        let (name, args) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        // aliases are expanded once, they can use the command they shadow
+        if let Some(expansion) = self.aliases.get(name) {
+            let line = format!("{} {}", expansion, args);
+            let (name, args) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+            return self.execute_command(name, args.trim());
+        }
+        self.execute_command(name, args);
+    }
+
+    fn execute_command(&mut self, name: &str, args: &str) {
+        match name {
+            "" => (),
+            x if parse_line_address(x).is_some() => {
+                self.follow = false;
+                self.send_command(Command::JumpLine(parse_line_address(x).unwrap()))
+            }
+            "help" if args.is_empty() || args == "options" => {
+                self.show_help = true;
+                self.help_options = !args.is_empty();
+            }
+            "help" => self.push_error("usage: help [options]".to_owned()),
+            "q" | "quit" if args.is_empty() => self.stop = true,
+            "q" | "quit" => match args.parse::<i32>() {
+                Ok(code) => self.quit(Some(code)),
+                Err(_) => self.push_error("usage: q [<exit code>]".to_owned()),
+            },
+            "q!" | "quit!" => self.quit(Some(self.abort_exit_code)),
+            "filter" => self.set_filter(args),
+            // every line is written, decompressed
+            "w" => {
+                if args.is_empty() {
+                    self.push_error("usage: w <file>".to_owned());
+                } else {
+                    self.send_command(Command::GrepOut(String::new(), expand_home(args), 0));
+                }
+            }
+            "colorize" => {
+                if args.is_empty() {
+                    if self.field_colorizer.is_some() {
+                        self.set_color_mode(ColorMode::Fields);
                     } else {
-                        format!(", {}", flags.join(", "))
-                    },
+                        self.push_error("usage: colorize <regex>".to_owned());
+                    }
+                    return;
+                }
+                if let Err(e) = Regex::new(args) {
+                    self.push_error(format!("invalid regex: {}", regex_error(&e)));
+                    return;
+                }
+                match FieldColorizer::new(args, &self.theme.entropy) {
+                    Ok(colorizer) => {
+                        self.field_colorizer = Some(colorizer);
+                        *self.lines_cache.borrow_mut() = None;
+                        self.set_color_mode(ColorMode::Fields);
+                    }
+                    Err(e) => self.push_error(format!("invalid regex: {}", e)),
+                }
+            }
+            "set" => self.set_option(args),
+            "bisect" => {
+                let step = match args {
+                    "start" => BisectStep::Start { by_time: false },
+                    "start time" => BisectStep::Start { by_time: true },
+                    "good" => BisectStep::Good,
+                    "bad" => BisectStep::Bad,
+                    "reset" => BisectStep::Reset,
+                    _ => {
+                        self.push_error("usage: bisect start [time]|good|bad|reset".to_owned());
+                        return;
+                    }
+                };
+                self.follow = false;
+                self.send_command(Command::Bisect(step));
+            }
+            "continue" => self.send_command(Command::ContinueSearch),
+            "source" => {
+                let source = if args.is_empty() {
+                    None
+                } else {
+                    Some(args.to_owned())
+                };
+                self.send_command(Command::SourceFilter(source));
+            }
+            "stats" => {
+                let range = if args.is_empty() {
+                    None
+                } else {
+                    match args
+                        .split_once(',')
+                        .map(|(a, b)| (a.trim().parse(), b.trim().parse()))
+                    {
+                        Some((Ok(first), Ok(last))) if first <= last => Some((first, last)),
+                        _ => {
+                            self.push_error("usage: stats [<first line>,<last line>]".to_owned());
+                            return;
+                        }
+                    }
+                };
+                self.show_stats = true;
+                self.send_command(Command::Stats(range));
+            }
+            "header" => {
+                self.header_mode = match (args, self.header_mode) {
+                    ("full", _) | ("", HeaderMode::Hidden) => HeaderMode::Full,
+                    ("compact", _) | ("", HeaderMode::Full) => HeaderMode::Compact,
+                    ("hidden", _) | ("", HeaderMode::Compact) => HeaderMode::Hidden,
+                    _ => {
+                        self.push_error("usage: header [full|compact|hidden]".to_owned());
+                        return;
+                    }
+                }
+            }
+            "messages" => {
+                self.show_messages = true;
+                self.messages_scroll = 0;
+            }
+            "info" => self.show_info = true,
+            "profile" => {
+                self.profile = match self.profile {
+                    Some(_) => None,
+                    None => Some(FrameProfile::new()),
+                }
+            }
+            "line" => match args.parse::<i64>() {
+                Ok(line) if line != 0 => {
+                    self.show_line = Some(line);
+                    self.send_command(Command::GetLine(line));
+                }
+                _ => self.push_error("usage: line <nr>".to_owned()),
+            },
+            "mksession" => {
+                if args.is_empty() {
+                    self.push_error("usage: mksession <name>".to_owned());
+                } else {
+                    self.send_command(Command::SaveSession(args.to_owned(), self.session()));
+                }
+            }
+            "e" => {
+                let path = expand_home(args);
+                if args.is_empty() {
+                    self.push_error("usage: e <path>".to_owned());
+                } else if Path::new(&path).is_dir() {
+                    match FilePicker::new(Path::new(&path)) {
+                        Ok(picker) => self.picker = Some(picker),
+                        Err(e) => self.push_error(format!("cannot read {}: {}", args, e)),
+                    }
+                } else {
+                    self.file_to_open = Some((path, false));
+                }
+            }
+            "tabnew" => {
+                if args.is_empty() {
+                    self.push_error("usage: tabnew <path>".to_owned());
+                } else {
+                    self.file_to_open = Some((expand_home(args), true));
+                }
+            }
+            "grep-out" => {
+                let usage = "usage: grep-out [-C <nr>] [<file>|-]";
+                let pattern = match self.search.as_ref() {
+                    Some(re) => re.as_str().to_owned(),
+                    None => {
+                        self.push_error("nothing to search".to_owned());
+                        return;
+                    }
+                };
+                let mut words = args.split_whitespace();
+                let mut context = 0;
+                let mut path = None;
+                while let Some(word) = words.next() {
+                    match (word, words.clone().next()) {
+                        ("-C", Some(value)) => match value.parse() {
+                            Ok(value) => {
+                                context = value;
+                                words.next();
+                            }
+                            Err(_) => return self.push_error(usage.to_owned()),
+                        },
+                        (word, _) if path.is_none() => path = Some(expand_home(word)),
+                        _ => return self.push_error(usage.to_owned()),
+                    }
+                }
+                let path = match path.as_deref() {
+                    // printed once the interface is closed
+                    None | Some("-") => {
+                        let file = tempfile::Builder::new()
+                            .prefix("bless-grep-")
+                            .suffix(".txt")
+                            .tempfile()
+                            .and_then(|x| x.into_temp_path().keep().map_err(|e| e.error));
+                        let path = match file {
+                            Ok(path) => path.to_string_lossy().into_owned(),
+                            Err(e) => return self.push_error(format!("grep-out: {}", e)),
+                        };
+                        self.print_on_exit.push(path.clone());
+                        path
+                    }
+                    Some(path) => path.to_owned(),
+                };
+                self.send_command(Command::GrepOut(pattern, path, context));
+            }
+            "wordstats" => {
+                if args.is_empty() {
+                    self.push_error("usage: wordstats <file.csv|file.json>".to_owned());
+                } else {
+                    self.send_command(Command::ExportWordStats(args.to_owned()));
+                }
+            }
+            x => self.push_error(format!("unknown command: {}", x)),
+        }
+    }
+
+    fn set_option(&mut self, args: &str) {
+        if args.is_empty() {
+            self.show_options = true;
+            return;
+        }
+        let (spec, value) = match parse_option(args) {
+            Ok(option) => option,
+            Err(e) => return self.push_error(e.to_string()),
+        };
+        let layout = (self.tab_stops.clone(), self.wrap_indent.clone());
+        match (spec.name, value) {
+            ("wrap", OptionValue::Bool(wrap)) => {
+                self.wrap = wrap;
+                self.right_offset = 0;
+            }
+            ("number", OptionValue::Bool(number)) => self.number = number,
+            ("ruler", OptionValue::Bool(ruler)) => self.ruler = ruler,
+            ("freeze", OptionValue::Number(columns)) => self.freeze = columns,
+            ("eof-marker", OptionValue::Text(value)) => match EofMarker::parse(&value) {
+                Some(marker) => self.eof_marker = marker,
+                None => self.push_error("eof-marker: expected line, tilde or none".to_owned()),
+            },
+            ("scrolloff", OptionValue::Number(lines)) => {
+                self.scrolloff = lines;
+                self.send_to_tabs(Command::ScrollOff(lines));
+            }
+            ("jump-center", OptionValue::Bool(center)) => {
+                self.jump_center = center;
+                self.send_to_tabs(Command::JumpCenter(center));
+            }
+            ("hints", OptionValue::Bool(hints)) => self.hints = hints,
+            ("max-render-cols", OptionValue::Number(columns)) => {
+                self.max_render_cols = columns;
+                self.send_to_tabs(Command::MaxRenderCols(columns));
+            }
+            ("wrap-indent", OptionValue::Bool(indent)) => self.wrap_indent.indent = indent,
+            ("focus", OptionValue::Bool(focus)) => {
+                self.focus = focus;
+                self.send_to_tabs(Command::Focus(focus));
+            }
+            ("wrap-marker", OptionValue::Text(marker)) => self.wrap_indent.marker = marker,
+            ("ignore-case", OptionValue::Bool(ignore_case)) => self.ignore_case = ignore_case,
+            ("follow", OptionValue::Bool(follow)) => {
+                self.follow = follow;
+                self.send_command(Command::Follow(follow));
+            }
+            ("tab-width", OptionValue::Number(width)) => self.tab_stops.width = width,
+            ("tab-stops", OptionValue::Text(value)) => match TabStops::parse_stops(&value) {
+                Some(stops) => self.tab_stops.stops = stops,
+                None if value.is_empty() => self.tab_stops.stops.clear(),
+                None => self.push_error("tab-stops: not increasing columns".to_owned()),
+            },
+            ("entropy-words", OptionValue::Number(count)) if count > 0 => {
+                self.entropy_words = count;
+                if self.color_mode == ColorMode::Entropy {
+                    self.send_command(Command::Entropy(Some(count)));
+                }
+            }
+            ("entropy-words", _) => {
+                self.push_error("entropy-words: not a positive number".to_owned())
+            }
+            ("source-tags", OptionValue::Number(width)) => self.source_tag_width = width,
+            // the options of the backends are the same in every tab
+            ("word-separators", OptionValue::Text(value)) => {
+                self.word_separators = WordSeparators::new(&value);
+                self.send_to_tabs(Command::WordSeparators(self.word_separators.clone()));
+            }
+            ("strip-cr", OptionValue::Bool(strip_cr)) => {
+                self.strip_cr = strip_cr;
+                self.send_to_tabs(Command::StripCr(strip_cr));
+            }
+            ("ansi-passthrough", OptionValue::Bool(passthrough)) => {
+                self.ansi_passthrough = passthrough;
+                self.send_to_tabs(Command::AnsiPassthrough(passthrough));
+            }
+            ("search-timeout", OptionValue::Duration(timeout)) => {
+                self.search_timeout = timeout;
+                self.send_to_tabs(Command::SearchTimeout(timeout));
+            }
+            ("search-offset", OptionValue::Text(value)) => match SearchOffset::parse(&value) {
+                Some(offset) => {
+                    self.search_offset = offset;
+                    self.send_to_tabs(Command::SearchOffset(offset));
+                }
+                None => self.push_error(
+                    "search-offset: expected top, middle or a number of lines".to_owned(),
                 ),
-                self.build_status(&back),
-            ]
-            .join("\n"),
-        );
+            },
+            ("auto-reload", OptionValue::Bool(auto_reload)) => {
+                self.auto_reload = auto_reload;
+                self.send_to_tabs(Command::AutoReload(auto_reload));
+            }
+            ("verify-checksums", OptionValue::Bool(verify)) => {
+                self.verify_checksums = verify;
+                self.send_to_tabs(Command::VerifyChecksums(verify));
+            }
+            ("no-cache", OptionValue::Bool(no_cache)) => {
+                self.no_cache = no_cache;
+                self.send_to_tabs(Command::NoCache(no_cache));
+            }
+            (name, value) => self.push_error(format!("{}: unexpected value {}", name, value)),
+        }
+        if layout != (self.tab_stops.clone(), self.wrap_indent.clone()) {
+            self.send_layout();
+        }
+    }
 
-        let paragraph = Paragraph::new(header)
-            .style(Style::default())
-            .block(Block::default().title(header_title).borders(Borders::ALL))
-            .alignment(Alignment::Left);
-        f.render_widget(paragraph, chunks[0]);
+    fn send_layout(&self) {
+        self.send_to_tabs(Command::Layout(
+            self.tab_stops.clone(),
+            self.wrap_indent.clone(),
+        ));
+    }
+
+    // current value of an option of the registry
+    fn option_value(&self, name: &str) -> OptionValue {
+        return match name {
+            "wrap" => OptionValue::Bool(self.wrap),
+            "number" => OptionValue::Bool(self.number),
+            "ruler" => OptionValue::Bool(self.ruler),
+            "freeze" => OptionValue::Number(self.freeze),
+            "eof-marker" => OptionValue::Text(self.eof_marker.name().to_owned()),
+            "scrolloff" => OptionValue::Number(self.scrolloff),
+            "jump-center" => OptionValue::Bool(self.jump_center),
+            "hints" => OptionValue::Bool(self.hints),
+            "max-render-cols" => OptionValue::Number(self.max_render_cols),
+            "wrap-indent" => OptionValue::Bool(self.wrap_indent.indent),
+            "focus" => OptionValue::Bool(self.focus),
+            "wrap-marker" => OptionValue::Text(self.wrap_indent.marker.clone()),
+            "ignore-case" => OptionValue::Bool(self.ignore_case),
+            "follow" => OptionValue::Bool(self.follow),
+            "tab-width" => OptionValue::Number(self.tab_stops.width),
+            "tab-stops" => OptionValue::Text(
+                self.tab_stops
+                    .stops
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            "entropy-words" => OptionValue::Number(self.entropy_words),
+            "source-tags" => OptionValue::Number(self.source_tag_width),
+            "word-separators" => OptionValue::Text(self.word_separators.chars().to_owned()),
+            "strip-cr" => OptionValue::Bool(self.strip_cr),
+            "ansi-passthrough" => OptionValue::Bool(self.ansi_passthrough),
+            "search-timeout" => OptionValue::Duration(self.search_timeout),
+            "search-offset" => OptionValue::Text(self.search_offset.text()),
+            "verify-checksums" => OptionValue::Bool(self.verify_checksums),
+            "no-cache" => OptionValue::Bool(self.no_cache),
+            "auto-reload" => OptionValue::Bool(self.auto_reload),
+            _ => OptionValue::Text(String::new()),
+        };
+    }
+
+    // commands setting the options of a new backend which differ from its defaults
+    fn tab_option_commands(&self) -> Vec<Command> {
+        let mut commands = Vec::new();
+        if self.word_separators != WordSeparators::default() {
+            commands.push(Command::WordSeparators(self.word_separators.clone()));
+        }
+        if !self.strip_cr {
+            commands.push(Command::StripCr(false));
+        }
+        if self.tab_stops != TabStops::new(DEFAULT_TAB_WIDTH)
+            || self.wrap_indent != WrapIndent::default()
+        {
+            commands.push(Command::Layout(
+                self.tab_stops.clone(),
+                self.wrap_indent.clone(),
+            ));
+        }
+        if self.ansi_passthrough {
+            commands.push(Command::AnsiPassthrough(true));
+        }
+        if self.search_timeout.is_some() {
+            commands.push(Command::SearchTimeout(self.search_timeout));
+        }
+        if self.scrolloff > 0 {
+            commands.push(Command::ScrollOff(self.scrolloff));
+        }
+        if self.jump_center {
+            commands.push(Command::JumpCenter(true));
+        }
+        if self.search_offset != SearchOffset::Top {
+            commands.push(Command::SearchOffset(self.search_offset));
+        }
+        if self.focus {
+            commands.push(Command::Focus(true));
+        }
+        if self.verify_checksums {
+            commands.push(Command::VerifyChecksums(true));
+        }
+        if self.no_cache {
+            commands.push(Command::NoCache(true));
+        }
+        if self.max_render_cols != DEFAULT_MAX_RENDER_COLS {
+            commands.push(Command::MaxRenderCols(self.max_render_cols));
+        }
+        if self.auto_reload {
+            commands.push(Command::AutoReload(true));
+        }
+        return commands;
+    }
+
+    fn send_to_tabs(&self, command: Command) {
+        for tab in self.tabs.iter() {
+            if let Err(e) = tab.command_sender.send(command.clone()) {
+                self.push_error(format!("command channel error: {}", e));
+            }
+        }
+    }
+
+    // scroll horizontally when the column is out of the screen, to show it in the middle
+    fn scroll_to_column(&mut self, column: usize) {
+        if column <= self.freeze {
+            return;
+        }
+        // columns after the frozen ones
+        let column = column - self.freeze;
+        let width = self
+            .text_area
+            .map(|x| (x.width as usize).saturating_sub(self.freeze))
+            .unwrap_or(0);
+        if column <= self.right_offset || column > self.right_offset + width {
+            self.right_offset = (column - 1).saturating_sub(width / 2);
+        }
+    }
+
+    // scroll horizontally to a new current match out of the screen, n and N
+    // can go to a match further in the line
+    fn reveal_current_match(&mut self) {
+        let current = self.tab().state_receiver.borrow().current_match.clone();
+        if current == self.revealed_match {
+            return;
+        }
+        self.revealed_match = current;
+        if self.wrap || self.search.is_none() {
+            return;
+        }
+        let column = {
+            let back = self.tab().state_receiver.borrow();
+            // the column after the spans before the current match
+            let lines = self.text_lines(&back);
+            let column = lines.iter().find_map(|spans| {
+                let position = spans
+                    .0
+                    .iter()
+                    .position(|x| x.style == self.theme.search_current)?;
+                return Some(spans.0[..position].iter().map(|x| x.width()).sum::<usize>() + 1);
+            });
+            match column {
+                Some(column) => column,
+                None => return,
+            }
+        };
+        self.scroll_to_column(column);
+    }
+
+    // scroll horizontally so that the longest line of the view ends at the
+    // right of the screen
+    fn scroll_to_end(&mut self) {
+        if self.wrap {
+            return;
+        }
+        let longest = {
+            let back = self.tab().state_receiver.borrow();
+            let lines = self.text_lines(&back);
+            back.text
+                .iter()
+                .zip(lines.iter())
+                .map(|(line, spans)| {
+                    // cut lines end with their length
+                    if line.width > self.max_render_cols && self.max_render_cols > 0 {
+                        return spans.width();
+                    }
+                    return line.width;
+                })
+                .max()
+                .unwrap_or(0)
+        };
+        let width = self.text_area.map(|x| x.width as usize).unwrap_or(0);
+        self.right_offset = longest.saturating_sub(width);
+    }
+
+    fn set_color_mode(&mut self, mode: ColorMode) {
+        let entropy = if mode == ColorMode::Entropy {
+            Some(self.entropy_words)
+        } else {
+            None
+        };
+        if (self.color_mode == ColorMode::Entropy) != entropy.is_some() {
+            self.send_command(Command::Entropy(entropy));
+        }
+        self.color_mode = mode;
+    }
+
+    fn refresh<B: backend::Backend>(&mut self, f: &mut Frame<B>) {
+        let header_height = match self.header_mode {
+            HeaderMode::Full => 4,
+            HeaderMode::Compact => 1,
+            HeaderMode::Hidden => 0,
+        };
+        // the tab bar is only shown with several files open
+        let tab_bar_height = if self.tabs.len() > 1 { 1 } else { 0 };
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(tab_bar_height),
+                    Constraint::Length(header_height),
+                    Constraint::Min(0),
+                ]
+                .as_ref(),
+            )
+            .split(f.size());
+        let (tab_bar_area, header_area, body_area) = (chunks[0], chunks[1], chunks[2]);
+
+        let show_text = !self.show_help
+            && !self.show_options
+            && !self.show_stats
+            && self.show_line.is_none()
+            && !self.show_messages
+            && !self.show_info
+            && self.picker.is_none()
+            && self.palette.is_none();
+        let show_minimap = show_text && self.current_match_map().is_some();
+        let text_area = if show_minimap {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+                .split(body_area);
+            self.minimap_area = Some(columns[1]);
+            columns[0]
+        } else {
+            self.minimap_area = None;
+            body_area
+        };
+        let (ruler_area, text_area) = if show_text && self.ruler && text_area.height > 0 {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+                .split(text_area);
+            (Some(rows[0]), rows[1])
+        } else {
+            (None, text_area)
+        };
+
+        let text_width = text_area.width as usize;
+        let text_height = text_area.height as usize;
+        self.text_height = text_height;
+        // lines of several files start with the name of their file, then
+        // come the line numbers
+        let tag_width = match self.tab().state_receiver.borrow().source {
+            Some(_) if self.source_tag_width > 0 => self.source_tag_width + 1,
+            _ => 0,
+        };
+        let number_width = if self.number { NUMBER_WIDTH + 1 } else { 0 };
+        let prefix_width = tag_width + number_width;
+        self.update_backend_size(text_width.saturating_sub(prefix_width), text_height);
+        self.text_area = if show_text {
+            let prefix_width = prefix_width.min(text_width) as u16;
+            Some(Rect::new(
+                text_area.x + prefix_width,
+                text_area.y,
+                text_area.width - prefix_width,
+                text_area.height,
+            ))
+        } else {
+            None
+        };
+
+        let back = self.tab().state_receiver.borrow();
+
+        let text = if self.show_help && self.help_options {
+            options_reference_text()
+        } else if self.show_help {
+            Text::from(HELP)
+        } else if self.show_options {
+            options_text(|name| self.option_value(name), &self.theme)
+        } else if self.show_stats {
+            match &back.stats {
+                Some(stats) => stats_text(stats, &self.theme, text_width, text_height),
+                None => Text::from("computing statistics... (Esc to cancel)"),
+            }
+        } else if let Some(palette) = &self.palette {
+            palette_text(palette, &self.theme, text_height)
+        } else if let Some(picker) = &self.picker {
+            picker_text(picker, &self.theme, text_height)
+        } else if self.show_info {
+            match &back.file_info {
+                Some(info) => info_text(info, back.file_size),
+                None => Text::from("no file info"),
+            }
+        } else if self.show_messages {
+            messages_text(
+                &self.messages.borrow(),
+                &self.theme,
+                text_height,
+                self.messages_scroll,
+            )
+        } else if let Some(line) = self.show_line {
+            match &back.line {
+                Some((nr, text)) if *nr == line => Text::from(vec![
+                    Spans::from(format!("  LINE {} ({} chars)", line, text.chars().count())),
+                    Spans::from(""),
+                    expand_tabs(vec![Spans::from(text.as_str())], &self.tab_stops).remove(0),
+                ]),
+                _ => Text::from("loading line... (Esc to close)"),
+            }
+        } else {
+            let mut lines = self.text_lines(&back);
+            if self.focus {
+                lines = self.focus_lines(lines, &back);
+            }
+            if let Some(spans) = back.cursor_row.and_then(|row| lines.get_mut(row)) {
+                for span in spans.0.iter_mut() {
+                    span.style = span.style.add_modifier(Modifier::UNDERLINED);
+                }
+            }
+            if self.right_offset > 0 {
+                lines = shift_lines(lines, self.right_offset, self.freeze);
+            }
+            if prefix_width > 0 {
+                let width = text_width - prefix_width.min(text_width);
+                lines = self.prefix_lines(lines, &back.text, width, tag_width > 0);
+            } else if self.wrap {
+                lines = wrap_lines(lines, text_width, &self.wrap_indent);
+            }
+            // rows left after the end of the file, a filtered view may stop
+            // short of it when matches are sparse
+            if lines.len() < text_height
+                && matches!(back.position, ViewPosition::End | ViewPosition::Empty)
+            {
+                match self.eof_marker {
+                    EofMarker::Line => {
+                        lines.push(Spans::from(Span::styled(EOF_LINE, self.theme.eof)))
+                    }
+                    EofMarker::Tilde => {
+                        lines.resize(text_height, Spans::from(Span::styled("~", self.theme.eof)))
+                    }
+                    EofMarker::None => (),
+                }
+            }
+
+            Text::from(lines)
+        };
+
+        let mut flags = Vec::new();
+        match (back.follow, back.new_lines) {
+            (true, Some(new_lines)) => {
+                flags.push(format!("Follow paused, {} new lines", new_lines))
+            }
+            (true, None) => flags.push("Follow".to_owned()),
+            _ => (),
+        }
+        if let Some((path, status)) = &self.exec {
+            if *path == self.tabs[self.current_tab].path {
+                flags.push(match *status.lock().unwrap() {
+                    None => "Running".to_owned(),
+                    Some(status) => match (status.code(), status.signal()) {
+                        (Some(code), _) => format!("Exit {}", code),
+                        (None, Some(signal)) => format!("Killed by signal {}", signal),
+                        (None, None) => "Exited".to_owned(),
+                    },
+                });
+            }
+        }
+        if let Some(grown) = back.grown {
+            flags.push(format!("+{} since open, gn", human_bytes(grown as f64)));
+        }
+        if self.wrap {
+            flags.push("Wrap".to_owned())
+        }
+        if self.focus {
+            flags.push("Focus".to_owned())
+        }
+        if back.crlf {
+            flags.push("CRLF".to_owned())
+        }
+        if back.invalid_utf8 {
+            flags.push("Invalid UTF-8".to_owned())
+        }
+        match back.file_info.as_ref().map(|x| x.rotation) {
+            Some(Rotation::Rotated) => flags.push("Rotated, R to reload".to_owned()),
+            Some(Rotation::Rewritten) => flags.push("Rewritten, R to reload".to_owned()),
+            Some(Rotation::Deleted) => flags.push("Deleted".to_owned()),
+            _ => (),
+        }
+        if let Some(byte) = self.byte_jump {
+            flags.push(format!("Byte {} (requested {})", back.offset, byte));
+        }
+        if let Some(progress) = back.grep_out_progress {
+            flags.push(format!("Grep-out {:.0}%", progress));
+        }
+        if let Some(left) = back.bisect_left {
+            flags.push(format!("Bisect, {} left", human_bytes(left as f64)));
+        }
+        if !back.marks.is_empty() {
+            let names: String = back.marks.iter().map(|(name, _)| name.as_str()).collect();
+            flags.push(format!("Marks: {}", names));
+        }
+        if let Some(filter) = &back.filter {
+            flags.push(format!("&{}", filter));
+        }
+        if let Some(source) = &back.source_filter {
+            flags.push(format!("Source: {}", source));
+        }
+        let matches = self.match_counts(&back);
+        if let Some(re) = &self.search {
+            flags.push(format!("/{}: {}", re.to_string(), matches));
+        } else if self.color_mode != ColorMode::Default {
+            flags.push(format!("{:?}", self.color_mode))
+        }
 
-        let mut paragraph = Paragraph::new(text)
+        let header_title = match &back.source {
+            Some(source) => format!(
+                "{} in {} - {}",
+                source,
+                back.real_file_path,
+                human_bytes(back.file_size as f64)
+            ),
+            None => format!(
+                "{} - {}",
+                back.real_file_path,
+                human_bytes(back.file_size as f64)
+            ),
+        };
+        let values = [
+            (
+                "line",
+                back.current_line
+                    .map(|x| x.to_string())
+                    .unwrap_or("?".to_owned()),
+            ),
+            ("offset", human_bytes(back.offset as f64)),
+            ("byte", back.offset.to_string()),
+            (
+                "percent",
+                match back.position {
+                    ViewPosition::Empty => "empty".to_owned(),
+                    ViewPosition::Top => "TOP".to_owned(),
+                    ViewPosition::End => "END".to_owned(),
+                    ViewPosition::Percent(percent) if back.compressed => {
+                        format!("~{:.1}%", percent)
+                    }
+                    ViewPosition::Percent(percent) => format!("{:.1}%", percent),
+                },
+            ),
+            ("file", back.real_file_path.clone()),
+            ("size", human_bytes(back.file_size as f64)),
+            (
+                "flags",
+                if flags.is_empty() {
+                    "".to_owned()
+                } else {
+                    format!(", {}", flags.join(", "))
+                },
+            ),
+            (
+                "search",
+                self.search
+                    .as_ref()
+                    .map(|x| x.as_str().to_owned())
+                    .unwrap_or_default(),
+            ),
+            ("clock", Local::now().format("%H:%M:%S").to_string()),
+            ("matches", matches),
+            (
+                "format",
+                back.file_info
+                    .as_ref()
+                    .map(|x| x.format())
+                    .unwrap_or_default(),
+            ),
+            (
+                "mtime",
+                back.file_info
+                    .as_ref()
+                    .map(|x| x.modified())
+                    .unwrap_or_default(),
+            ),
+            (
+                "inode",
+                back.file_info
+                    .as_ref()
+                    .map(|x| x.inode())
+                    .unwrap_or_default(),
+            ),
+        ];
+        let position = format_status(self.header_format(), &values);
+        let status = self.build_status();
+
+        match self.header_mode {
+            HeaderMode::Full => {
+                let header = Text::from(vec![
+                    Spans::from(position),
+                    Spans::from(Span::styled(status, self.theme.status)),
+                ]);
+                let paragraph = Paragraph::new(header)
+                    .style(self.theme.header)
+                    .block(
+                        Block::default()
+                            .title(Span::styled(header_title, self.theme.header_title))
+                            .borders(Borders::ALL),
+                    )
+                    .alignment(Alignment::Left);
+                f.render_widget(paragraph, header_area);
+            }
+            HeaderMode::Compact => {
+                let mut spans = vec![Span::raw(position)];
+                if !status.is_empty() {
+                    spans.push(Span::raw(" | "));
+                    spans.push(Span::styled(status, self.theme.status));
+                }
+                let paragraph = Paragraph::new(Spans::from(spans)).style(self.theme.header);
+                f.render_widget(paragraph, header_area);
+            }
+            HeaderMode::Hidden => (),
+        }
+
+        let text = if self.wrap {
+            Text::from(wrap_lines(text.lines, text_width, &WrapIndent::default()))
+        } else {
+            text
+        };
+        if show_text {
+            *self.text_rows.borrow_mut() = text
+                .lines
+                .iter()
+                .map(|spans| {
+                    let skip = [tag_width, number_width].iter().filter(|x| **x > 0).count();
+                    spans
+                        .0
+                        .iter()
+                        .skip(skip)
+                        .map(|x| x.content.as_ref())
+                        .collect()
+                })
+                .collect();
+        }
+        let paragraph = Paragraph::new(text)
             .style(Style::default())
             .block(Block::default())
             .alignment(Alignment::Left);
-        if self.wrap {
-            paragraph = paragraph.wrap(Wrap { trim: false });
+        f.render_widget(paragraph, text_area);
+
+        if let Some(area) = ruler_area {
+            let width = (area.width as usize).saturating_sub(prefix_width);
+            let frozen = if self.right_offset > 0 {
+                self.freeze.min(width)
+            } else {
+                0
+            };
+            let paragraph = Paragraph::new(Spans::from(vec![
+                Span::raw(" ".repeat(prefix_width)),
+                Span::styled(ruler(1, frozen), self.theme.status),
+                Span::styled(
+                    ruler(frozen + self.right_offset + 1, width - frozen),
+                    self.theme.status,
+                ),
+            ]));
+            f.render_widget(paragraph, area);
+        }
+
+        // in the top right corner of the text, over it
+        if let Some(profile) = self.profile.as_ref() {
+            let width = PROFILE_WIDTH.min(text_area.width);
+            let area = Rect::new(
+                text_area.right() - width,
+                text_area.y,
+                width,
+                PROFILE_HEIGHT.min(text_area.height),
+            );
+            let paragraph =
+                Paragraph::new(profile_text(profile, back.command_time, back.state_time))
+                    .style(self.theme.header)
+                    .block(Block::default().title("Profile").borders(Borders::ALL));
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph, area);
+        }
+
+        // hint bar over the last line, for the keys being typed
+        let hint = key_hint(&self.command).filter(|_| self.hints && text_height > 0);
+        if let Some(hint) = hint.as_ref() {
+            let area = Rect::new(text_area.x, text_area.bottom() - 1, text_area.width, 1);
+            let paragraph = Paragraph::new(Span::styled(hint.as_str(), self.theme.header))
+                .style(self.theme.header);
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph, area);
+        }
+
+        // without header, show the command being typed over the last line, above the hints
+        let hint_height = if hint.is_some() { 1 } else { 0 };
+        if self.header_mode == HeaderMode::Hidden
+            && !self.command.is_empty()
+            && text_height > hint_height
+        {
+            let bottom = text_area.bottom() - hint_height as u16;
+            let area = Rect::new(text_area.x, bottom - 1, text_area.width, 1);
+            let paragraph = Paragraph::new(Span::styled(
+                format!("Command: {}", self.command),
+                self.theme.status,
+            ))
+            .style(self.theme.header);
+            f.render_widget(Clear, area);
+            f.render_widget(paragraph, area);
+        }
+
+        // completion menu next to the command line: under the header, or over the command
+        if let Some(menu) = self.completion.as_ref() {
+            let height = menu
+                .candidates
+                .len()
+                .min(MAX_MENU_HEIGHT)
+                .min(text_height.saturating_sub(1));
+            let width = menu
+                .candidates
+                .iter()
+                .map(|x| UnicodeWidthStr::width(menu_label(x).as_str()))
+                .max()
+                .unwrap_or(0)
+                .min(text_area.width as usize);
+            let y = if self.header_mode == HeaderMode::Hidden {
+                text_area.bottom().saturating_sub(1 + height as u16)
+            } else {
+                text_area.y
+            };
+            let area = Rect::new(text_area.x, y, width as u16, height as u16);
+            f.render_widget(Clear, area);
+            f.render_widget(
+                Paragraph::new(menu_text(menu, &self.theme, height)).style(self.theme.status),
+                area,
+            );
+        }
+
+        if self.tabs.len() > 1 {
+            let tabs = tab_bar(&self.tabs, self.current_tab, &self.theme);
+            f.render_widget(Paragraph::new(tabs).style(self.theme.header), tab_bar_area);
+        }
+
+        if let (Some(area), Some(map)) = (self.minimap_area, back.match_map.as_ref()) {
+            let minimap = minimap_text(
+                map,
+                area.height as usize,
+                back.offset,
+                back.file_size,
+                &self.theme,
+            );
+            f.render_widget(Paragraph::new(minimap), area);
+        }
+    }
+
+    // match map of the current search, if the backend computed it
+    // lines matching the search on screen, and in the whole file once the
+    // match scan counted them
+    fn match_counts(&self, back: &BackendState) -> String {
+        if self.search.is_none() {
+            return String::new();
+        }
+        let on_screen = back.text.iter().filter(|x| !x.matches.is_empty()).count();
+        return match self.current_match_map() {
+            Some(map) if map.done => format!("{} on screen, {} total", on_screen, map.count),
+            Some(map) => format!("{} on screen, {}+ total", on_screen, map.count),
+            None => format!("{} on screen", on_screen),
+        };
+    }
+
+    fn current_match_map(&self) -> Option<Rc<MatchMap>> {
+        let search = self.search.as_ref()?;
+        let map = self.tab().state_receiver.borrow().match_map.clone()?;
+        if map.pattern != search.as_str() {
+            return None;
         }
-        f.render_widget(paragraph, chunks[1]);
+        return Some(map);
     }
 
-    fn build_status(&self, back: &BackendState) -> String {
-        // Go over all backend errors and remove what's irrelevant
-        // to the user
-        let back_errors = back
-            .errors
+    fn jump_to_cluster(&mut self, forward: bool) {
+        let map = match self.current_match_map() {
+            Some(map) => map,
+            None => {
+                self.push_error("no match map".to_owned());
+                return;
+            }
+        };
+        let (offset, file_size) = {
+            let back = self.tab().state_receiver.borrow();
+            (back.offset, back.file_size)
+        };
+        let height = self.minimap_area.map(|x| x.height).unwrap_or(1) as usize;
+        match cluster_ratio(&map, height, offset, file_size, forward) {
+            Some(ratio) => self.jump_to_match_at(ratio),
+            None => self.push_error("no more clusters".to_owned()),
+        }
+    }
+
+    fn jump_to_match_at(&mut self, ratio: f64) {
+        if let Some(re) = self.search.as_ref() {
+            let pattern = re.as_str().to_owned();
+            self.follow = false;
+            self.send_command(Command::JumpFileRatio(ratio));
+            self.send_command(Command::SearchDown(pattern));
+        }
+    }
+
+    // returns true when the event had an effect
+    fn handle_mouse(&mut self, event: MouseEvent) -> bool {
+        if let MouseEventKind::Down(MouseButton::Left) = event.kind {
+            if let Some(area) = self.minimap_area {
+                if contains(area, event.column, event.row) {
+                    self.jump_to_match_at((event.row - area.y) as f64 / area.height as f64);
+                    return true;
+                }
+            }
+
+            let now = Instant::now();
+            let double_click = match self.last_click {
+                Some((time, column, row)) => {
+                    now.duration_since(time) <= DOUBLE_CLICK_DELAY
+                        && column == event.column
+                        && row == event.row
+                }
+                None => false,
+            };
+            if double_click {
+                self.last_click = None;
+                return self.search_word_at(event.column, event.row);
+            }
+            self.last_click = Some((now, event.column, event.row));
+        }
+        return false;
+    }
+
+    // copy lines from the top of the view to the clipboard
+    fn copy_lines(&mut self, count: usize) {
+        let text = self
+            .tab()
+            .state_receiver
+            .borrow()
+            .text
             .iter()
-            .filter(|x| match x.downcast_ref::<ViewError>() {
+            .take(count)
+            .map(|x| x.text.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        if text.is_empty() {
+            self.push_error("nothing to copy".to_owned());
+            return;
+        }
+        match self.clipboard.copy(&text) {
+            Ok(_) => self.notify(
+                Severity::Info,
+                Source::Frontend,
+                format!("copied {} lines", text.lines().count()),
+            ),
+            Err(e) => self.push_error(format!("cannot copy: {}", e)),
+        }
+    }
+
+    // search the word shown at a position of the screen, returns true when
+    // there is one
+    fn search_word_at(&mut self, column: u16, row: u16) -> bool {
+        let area = match self.text_area {
+            Some(area) if contains(area, column, row) => area,
+            _ => return false,
+        };
+        let rows = self.text_rows.borrow();
+        let text = match rows.get((row - area.y) as usize) {
+            Some(text) => text,
+            None => return false,
+        };
+        // byte index of the character covering the column
+        let mut width = 0;
+        let index = text.char_indices().find_map(|(index, c)| {
+            width += UnicodeWidthChar::width(c).unwrap_or(0);
+            if width > (column - area.x) as usize {
+                return Some(index);
+            }
+            return None;
+        });
+        let pattern = match index.and_then(|x| self.word_separators.word_at(text, x)) {
+            Some(word) => word_pattern(word),
+            None => return false,
+        };
+        drop(rows);
+
+        // highlight the matches without moving, n and N go through them
+        match bytes::Regex::new(&pattern) {
+            Ok(re) => {
+                self.search = Some(re);
+                self.send_command(Command::MatchMap(Some(pattern)));
+            }
+            Err(e) => self.push_error(format!("invalid regex: {}", regex_error(&e))),
+        }
+        return true;
+    }
+
+    fn log_backend_messages(&mut self) {
+        let tab = &mut self.tabs[self.current_tab];
+        let back = tab.state_receiver.borrow();
+        let mut messages = self.messages.borrow_mut();
+        let mut notifications = self.notifications.borrow_mut();
+        for error in back.errors.iter() {
+            if tab.logged_errors.iter().any(|x| Rc::ptr_eq(x, error)) {
+                continue;
+            }
+            let severity = match error.downcast_ref::<ViewError>() {
+                // moving past the ends of the file is not worth a message,
+                // only a notice when moving line by line
                 Some(ViewError::EOF) | Some(ViewError::BOF) => {
-                    matches![*self.last_sent_command.borrow(), Command::MoveLine(_)]
+                    if matches![*self.last_sent_command.borrow(), Command::MoveLine(_)] {
+                        notifications.push(Severity::Info, Source::Backend, error.to_string());
+                    }
+                    continue;
                 }
-                _ => true,
-            })
-            .map(|x| format!("{}", x))
-            .collect::<Vec<String>>();
+                Some(ViewError::NoMatchFound) | Some(ViewError::Cancelled) => Severity::Warning,
+                _ => Severity::Error,
+            };
+            messages.push(severity, Source::Backend, error.to_string());
+            notifications.push(severity, Source::Backend, error.to_string());
+        }
+        for notice in back.notices.iter() {
+            if !tab.logged_notices.iter().any(|x| Rc::ptr_eq(x, notice)) {
+                messages.push(Severity::Info, Source::Backend, notice.to_string());
+                notifications.push(Severity::Info, Source::Backend, notice.to_string());
+            }
+        }
+        let (errors, notices) = (back.errors.clone(), back.notices.clone());
+        drop(back);
+        tab.logged_errors = errors;
+        tab.logged_notices = notices;
+    }
 
+    fn header_format(&self) -> &str {
+        return match (&self.header_format, self.header_mode) {
+            (Some(format), _) => format,
+            (None, HeaderMode::Compact) => DEFAULT_COMPACT_HEADER_FORMAT,
+            (None, _) => DEFAULT_HEADER_FORMAT,
+        };
+    }
+
+    fn build_status(&self) -> String {
         if !self.command.is_empty() {
-            format!("Command: {}", self.command)
-        } else if !back_errors.is_empty() {
-            format!("Backend error: {}", back_errors.join(", "))
-        } else if !self.errors.borrow().is_empty() {
-            format!("Frontend error: {}", self.errors.borrow().join(", "))
+            return format!("Command: {}", self.command);
+        }
+        let notifications = self.notifications.borrow().status();
+        let activity = match self.activity() {
+            Some(activity) if activity.started.elapsed() >= SPINNER_DELAY => activity,
+            _ => return notifications,
+        };
+        let elapsed = activity.started.elapsed();
+        let spinner =
+            SPINNER[(elapsed.as_millis() / SPINNER_INTERVAL.as_millis()) as usize % SPINNER.len()];
+        let name = activity.operation.name();
+        let mut status = if self.aborting {
+            format!("{} {}, aborting", spinner, name)
         } else {
-            "".to_string()
+            match activity.progress {
+                Some(progress) => format!("{} {} {:.0}%", spinner, name, progress * 100.0),
+                None => format!("{} {} {}s", spinner, name, elapsed.as_secs()),
+            }
+        };
+        if !self.aborting && activity.operation.abortable() {
+            status.push_str(", Esc to abort");
+        }
+        if !notifications.is_empty() {
+            status = format!("{}, {}", status, notifications);
         }
+        return status;
     }
 
-    fn color_lines<'a>(&self, lines: Vec<&'a str>) -> Vec<Spans<'a>> {
-        if let Some(re) = self.search.as_ref() {
+    // long work the backend of the current tab is busy with
+    fn activity(&self) -> Option<Activity> {
+        return self.tab().activity_receiver.borrow().clone();
+    }
+
+    // prefix the lines with the tag of their file and with their number,
+    // wrapped lines are wrapped here so that their next rows are aligned
+    // after the prefix
+    fn prefix_lines(
+        &self,
+        lines: Vec<Spans<'static>>,
+        text: &[Line],
+        width: usize,
+        tagged: bool,
+    ) -> Vec<Spans<'static>> {
+        let mut rows = Vec::new();
+        for (index, spans) in lines.into_iter().enumerate() {
+            let line = text.get(index);
+            let source = line.and_then(|x| x.source.as_deref());
+            let number = line
+                .and_then(|x| x.number)
+                .map(|x| x.to_string())
+                .unwrap_or_default();
+            let line_rows = if self.wrap {
+                wrap_lines(vec![spans], width, &self.wrap_indent)
+            } else {
+                vec![spans]
+            };
+            for (row, mut spans) in line_rows.into_iter().enumerate() {
+                if self.number {
+                    let number = if row == 0 { number.as_str() } else { "" };
+                    spans.0.insert(
+                        0,
+                        Span::styled(format!("{:>1$} ", number, NUMBER_WIDTH), self.theme.eof),
+                    );
+                }
+                if tagged {
+                    let tag = match row {
+                        0 => source_tag(source, self.source_tag_width, &self.theme.entropy),
+                        _ => source_tag(None, self.source_tag_width, &self.theme.entropy),
+                    };
+                    spans.0.insert(0, tag);
+                }
+                rows.push(spans);
+            }
+        }
+        return rows;
+    }
+
+    // dim the lines not matching the filter, or the search when there is no filter
+    fn focus_lines(&self, lines: Vec<Spans<'static>>, back: &BackendState) -> Vec<Spans<'static>> {
+        let mut focus_filter = self.focus_filter.borrow_mut();
+        let predicate = match &back.filter {
+            Some(expr) => {
+                if focus_filter.as_ref().map(|(x, _)| x) != Some(expr) {
+                    // the backend already checked the expression
+                    *focus_filter = Predicate::parse(expr).ok().map(|x| (expr.clone(), x));
+                }
+                focus_filter.as_ref().map(|(_, predicate)| predicate)
+            }
+            None => None,
+        };
+        if predicate.is_none() && self.search.is_none() {
+            return lines;
+        }
+
+        return lines
+            .into_iter()
+            .zip(back.text.iter())
+            .map(|(mut spans, line)| {
+                let matching = match (predicate, &self.search) {
+                    (Some(predicate), _) => predicate.is_match(line.text.as_bytes()),
+                    (None, Some(_)) => !line.matches.is_empty(),
+                    (None, None) => true,
+                };
+                if !matching {
+                    for span in spans.0.iter_mut() {
+                        span.style = span.style.add_modifier(Modifier::DIM);
+                    }
+                }
+                return spans;
+            })
+            .collect();
+    }
+
+    fn text_lines(&self, back: &BackendState) -> Vec<Spans<'static>> {
+        let search = self.search.as_ref().map(|x| x.as_str().to_owned());
+        if let Some(cache) = self.lines_cache.borrow().as_ref() {
+            if Rc::ptr_eq(&cache.text, &back.text)
+                && cache.tab_stops == self.tab_stops
+                && cache.color_mode == self.color_mode
+                && cache.search == search
+                && cache.current_match == back.current_match
+                && cache.entropy_words == back.entropy_words
+                && cache.max_render_cols == self.max_render_cols
+            {
+                return cache.lines.clone();
+            }
+        }
+
+        let truncated: Vec<Option<(&str, usize)>> = back
+            .text
+            .iter()
+            .map(|x| truncate_text(&x.text, self.max_render_cols))
+            .collect();
+        // start of the current match in the text of its line
+        let current: Vec<Option<usize>> = back
+            .text
+            .iter()
+            .map(|line| match &back.current_match {
+                Some((offset, range)) if *offset == line.byte_offset => line
+                    .matches
+                    .iter()
+                    .find(|x| x.bytes.start == range.start)
+                    .map(|x| x.text.start),
+                _ => None,
+            })
+            .collect();
+        let lines = self.color_lines(
+            back.text
+                .iter()
+                .zip(truncated.iter())
+                .map(|(line, truncated)| truncated.map(|x| x.0).unwrap_or(&line.text))
+                .collect(),
+            &back.text,
+            &current,
+            &back.entropy_words,
+        );
+        let mut lines = expand_tabs(lines, &self.tab_stops);
+        for (spans, truncated) in lines.iter_mut().zip(truncated.iter()) {
+            if let Some((_, length)) = truncated {
+                spans
+                    .0
+                    .push(Span::styled(truncation_marker(*length), self.theme.eof));
+            }
+        }
+
+        *self.lines_cache.borrow_mut() = Some(LinesCache {
+            text: back.text.clone(),
+            tab_stops: self.tab_stops.clone(),
+            color_mode: self.color_mode,
+            search,
+            current_match: back.current_match.clone(),
+            entropy_words: back.entropy_words.clone(),
+            max_render_cols: self.max_render_cols,
+            lines: lines.clone(),
+        });
+        return lines;
+    }
+
+    fn color_lines<'a>(
+        &self,
+        lines: Vec<&'a str>,
+        text: &[Line],
+        current: &[Option<usize>],
+        entropy_words: &[String],
+    ) -> Vec<Spans<'a>> {
+        if self.search.is_some() {
             return lines
                 .iter()
-                .map(|lines| self.color_line_regex(lines, re))
+                .zip(text.iter().zip(current.iter()))
+                .map(|(line, (text, current))| self.color_line_matches(line, text, *current))
                 .collect();
         } else {
             match self.color_mode {
-                ColorMode::Entropy => self.color_lines_entropy(lines),
+                ColorMode::Entropy => self.color_lines_entropy(lines, entropy_words),
                 ColorMode::Log => lines
                     .iter()
-                    .map(|lines| self.color_line_log(lines))
+                    .map(|line| self.log_colorizer.color_line(line))
                     .collect(),
+                ColorMode::Diff => lines
+                    .iter()
+                    .map(|line| self.color_line_diff(line))
+                    .collect(),
+                ColorMode::Fields => match &self.field_colorizer {
+                    Some(colorizer) => lines
+                        .iter()
+                        .map(|line| colorizer.color_line(line))
+                        .collect(),
+                    None => lines
+                        .iter()
+                        .zip(text.iter())
+                        .map(|(line, text)| self.color_line_default(line, &text.styles))
+                        .collect(),
+                },
                 _ => lines
                     .iter()
-                    .map(|line| self.color_line_default(line))
+                    .zip(text.iter())
+                    .map(|(line, text)| self.color_line_default(line, &text.styles))
                     .collect(),
             }
         }
     }
 
-    fn color_line_regex<'a>(&self, mut line: &'a str, re: &Regex) -> Spans<'a> {
+    // matches found by the backend on the bytes of the line, the one starting
+    // at byte `current` of the text is the current one. The text may be cut.
+    fn color_line_matches<'a>(
+        &self,
+        line: &'a str,
+        text: &Line,
+        current: Option<usize>,
+    ) -> Spans<'a> {
         let mut spans = Vec::new();
+        let mut start = 0;
 
-        while let Some(m) = re.find(line) {
-            spans.push(Span::raw(&line[..m.start()]));
-            spans.push(Span::styled(
-                m.as_str(),
-                Style::default().bg(Color::Yellow).fg(Color::Black),
-            ));
-
-            line = &line.get(m.end()..).unwrap_or("");
+        for m in text.matches.iter() {
+            let range = m.text.start.max(start)..m.text.end.min(line.len());
+            let matched = match line.get(range.clone()) {
+                Some(matched) if !matched.is_empty() => matched,
+                _ => continue,
+            };
+            let style = if current == Some(m.text.start) {
+                self.theme.search_current
+            } else {
+                self.theme.search_match
+            };
+            spans.extend(styled_spans(line, &text.styles, start..range.start));
+            spans.push(Span::styled(matched, style));
+            start = range.end;
         }
 
-        spans.push(Span::raw(line));
+        spans.extend(styled_spans(line, &text.styles, start..line.len()));
         return Spans::from(spans);
     }
 
-    fn color_line_log<'a>(&self, line: &'a str) -> Spans<'a> {
-        let mut spans = Vec::new();
-        for (regex, style) in self.log_colors.iter() {
-            if regex.is_match(line) {
-                spans.push(Span::styled(line, style.clone()));
-                return Spans::from(spans);
-            }
-        }
-        spans.push(Span::raw(line));
-        return Spans::from(spans);
-    }
-
-    fn color_lines_entropy<'a>(&self, lines: Vec<&'a str>) -> Vec<Spans<'a>> {
-        // collect interesting words
-        let word_regex = Regex::new(".*\\w").unwrap();
-        let mut words_count: HashMap<&str, u64> = HashMap::new();
-        for word in lines
-            .iter()
-            .map(|line| line.split_whitespace())
-            .flatten()
-            .map(|word| word.split(|x| WORD_SEPARATOR.contains(x)))
-            .flatten()
-            .filter(|word| word.len() >= 4)
-            .map(|word| word_regex.find(word).map(|m| m.as_str()).unwrap_or(""))
-        {
-            *words_count.entry(word).or_default() += 1;
+    fn color_lines_entropy<'a>(&self, lines: Vec<&'a str>, words: &[String]) -> Vec<Spans<'a>> {
+        let same_words = {
+            let last_words = self.entropy_last_words.borrow();
+            last_words.len() == words.len() && last_words.iter().all(|(w, _)| words.contains(w))
+        };
+        if !same_words {
+            self.assign_entropy_styles(words);
         }
-        debug!("found {} interesting words", words_count.len());
 
-        let mut words: Vec<String> = words_count
-            .iter()
-            .map(|(word, _)| word.to_string())
-            .collect();
-        words.sort_by_cached_key(|x| {
-            (1000000.0 * word_entropy(x)) as u64 * words_count.get(x.as_str()).unwrap_or(&0)
-        });
-        words = words
+        let words = self.entropy_last_words.borrow();
+        return lines
             .into_iter()
-            .rev()
-            .take(self.entropy_colors.len())
+            .map(|line| self.color_words(line, &words))
             .collect();
-        debug!("most interesting words: {:?}", words);
+    }
 
+    fn assign_entropy_styles(&self, words: &[String]) {
+        // keep the style of words that are still on screen to avoid flickering
         let reused_words_styles: Vec<(String, Style)> = self
             .entropy_last_words
             .borrow()
@@ -651,6 +2960,8 @@ impl Frontend {
             .entropy_colors
             .iter()
             .filter(|x| !reused_styles.contains(x))
+            .chain(self.entropy_colors.iter().cycle())
+            .take(words.len())
             .cloned()
             .collect();
         let new_words: Vec<String> = words
@@ -661,16 +2972,9 @@ impl Frontend {
 
         let words: Vec<(String, Style)> = reused_words_styles
             .into_iter()
-            .chain(new_words.into_iter().zip(new_styles.into_iter()))
+            .chain(new_words.into_iter().zip(new_styles))
             .collect();
-
-        let mut res = Vec::new();
-        for line in lines {
-            res.push(self.color_words(line, &words));
-        }
         *self.entropy_last_words.borrow_mut() = words;
-
-        return res;
     }
 
     fn color_words<'a>(&self, line: &'a str, words: &Vec<(String, Style)>) -> Spans<'a> {
@@ -707,49 +3011,40 @@ impl Frontend {
         Spans::from(spans)
     }
 
-    fn color_line_default<'a>(&self, line: &'a str) -> Spans<'a> {
-        let mut spans = Vec::new();
-        spans.push(Span::raw(line));
-        Spans::from(spans)
+    fn color_line_diff<'a>(&self, line: &'a str) -> Spans<'a> {
+        return match line.get(..1) {
+            Some("+") => Spans::from(Span::styled(line, self.theme.diff_added)),
+            Some("-") => Spans::from(Span::styled(line, self.theme.diff_removed)),
+            _ => Spans::from(line),
+        };
     }
 
-    fn shift_lines<'a>(&self, lines: Vec<Spans<'a>>, offset: usize) -> Vec<Spans<'a>> {
-        let mut out_lines = Vec::new();
-        for spans in lines {
-            let mut out_spans = Vec::new();
-            let mut offset_left = offset;
-
-            for span in spans.0 {
-                if offset_left == 0 {
-                    out_spans.push(span);
-                } else if span.content.chars().count() <= offset_left {
-                    offset_left -= span.content.chars().count()
-                } else {
-                    let content: String = span.content.chars().skip(offset_left).collect();
-                    out_spans.push(Span::styled(content, span.style));
-                    offset_left = 0;
-                }
-            }
-
-            out_lines.push(Spans::from(out_spans));
-        }
-        return out_lines;
+    fn color_line_default<'a>(&self, line: &'a str, styles: &[(Range<usize>, Style)]) -> Spans<'a> {
+        return Spans::from(styled_spans(line, styles, 0..line.len()));
     }
 
     fn send_command(&self, command: Command) {
-        if let Err(e) = self.command_sender.borrow_mut().send(command.clone()) {
+        if let Err(e) = self.tab().command_sender.send(command.clone()) {
             self.push_error(format!("command channel error: {}", e));
         }
         *self.last_sent_command.borrow_mut() = command;
     }
 
     fn send_cancel(&self) {
-        if let Err(e) = self.cancel_sender.borrow_mut().send(()) {
+        if let Err(e) = self.tab().cancel_sender.send(()) {
             self.push_error(format!("cancel channel error: {}", e));
         }
     }
 
+    // log a message and show it in the status
+    fn notify(&self, severity: Severity, source: Source, text: String) {
+        self.messages
+            .borrow_mut()
+            .push(severity, source, text.clone());
+        self.notifications.borrow_mut().push(severity, source, text);
+    }
+
     fn push_error(&self, error: String) {
-        self.errors.borrow_mut().push(error);
+        self.notify(Severity::Error, Source::Frontend, error);
     }
 }
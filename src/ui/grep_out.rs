@@ -0,0 +1,128 @@
+use crate::{
+    errors::Result, file_view::FileView, file_view::ViewError, utils::pattern::regex_error,
+};
+use regex::bytes;
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+// background scan of the whole file writing the matching lines to a file
+pub struct GrepOut {
+    pub path: String,
+    pub matches: u64,
+    pub done: bool,
+    view: FileView,
+    regex: bytes::Regex,
+    // lines written around each match
+    context: usize,
+    writer: BufWriter<File>,
+    file_size: u64,
+    scanned: u64,
+    // last lines before the next match, with their offset
+    before: VecDeque<(u64, Vec<u8>)>,
+    // lines left to write after the last match
+    after: usize,
+    // end of the last written line, groups of lines are separated like grep does
+    written_end: Option<u64>,
+}
+
+impl GrepOut {
+    pub async fn new(
+        mut view: FileView,
+        pattern: &str,
+        output: &str,
+        context: usize,
+    ) -> Result<Self> {
+        let regex =
+            bytes::Regex::new(pattern).map_err(|e| ViewError::InvalidRegex(regex_error(&e)))?;
+        let file_size = view.file_size().await?;
+        return Ok(Self {
+            path: output.to_owned(),
+            matches: 0,
+            done: false,
+            view,
+            regex,
+            context,
+            writer: BufWriter::new(File::create(output)?),
+            file_size,
+            scanned: 0,
+            before: VecDeque::new(),
+            after: 0,
+            written_end: None,
+        });
+    }
+
+    pub fn set_no_cache(&mut self, no_cache: bool) {
+        self.view.set_no_cache(no_cache);
+    }
+
+    // percentage of the file scanned
+    pub fn progress(&self) -> f64 {
+        return 100.0 * self.scanned as f64 / self.file_size.max(1) as f64;
+    }
+
+    pub async fn step(&mut self) -> Result<()> {
+        let regex = &self.regex;
+        let context = self.context;
+        let writer = &mut self.writer;
+        let before = &mut self.before;
+        let after = &mut self.after;
+        let written_end = &mut self.written_end;
+        let matches = &mut self.matches;
+        let scanned = &mut self.scanned;
+        let mut error = None;
+
+        let mut write_line = |offset: u64, line: &[u8]| -> io::Result<()> {
+            if context > 0 && written_end.is_some_and(|end| end != offset) {
+                writer.write_all(b"--\n")?;
+            }
+            writer.write_all(line)?;
+            writer.write_all(b"\n")?;
+            *written_end = Some(offset + line.len() as u64 + 1);
+            return Ok(());
+        };
+
+        let more = self
+            .view
+            .scan_step(&mut |offset, line| {
+                *scanned = offset;
+                let res = if regex.is_match(line) {
+                    *matches += 1;
+                    *after = context;
+                    before
+                        .drain(..)
+                        .try_for_each(|(offset, line)| write_line(offset, &line))
+                        .and_then(|_| write_line(offset, line))
+                } else if *after > 0 {
+                    *after -= 1;
+                    write_line(offset, line)
+                } else {
+                    if context > 0 {
+                        before.push_back((offset, line.to_vec()));
+                        if before.len() > context {
+                            before.pop_front();
+                        }
+                    }
+                    Ok(())
+                };
+                if let Err(e) = res {
+                    error = Some(e);
+                    return false;
+                }
+                return true;
+            })
+            .await?;
+        if let Some(e) = error {
+            return Err(e.into());
+        }
+
+        if !more {
+            self.writer.flush()?;
+            self.scanned = self.file_size;
+            self.done = true;
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{errors::Result, file_view::FileView};
+
+// lines between two checkpoints, the lines counted to find a line number
+const CHECKPOINT_LINES: u64 = 10000;
+
+// background scan of the file recording the offset of every CHECKPOINT_LINES
+// line, to find the line number of a byte offset
+pub struct LineIndex {
+    view: Option<FileView>,
+    // offset of the start of a line and its number, in order
+    checkpoints: Vec<(u64, u64)>,
+    // start of the last line scanned
+    scanned: u64,
+    // number of the next line scanned
+    next_number: u64,
+    pub done: bool,
+}
+
+impl LineIndex {
+    pub fn new(view: FileView) -> Self {
+        return Self {
+            view: Some(view),
+            checkpoints: vec![(0, 1)],
+            scanned: 0,
+            next_number: 1,
+            done: false,
+        };
+    }
+
+    // last checkpoint before the offset, once the scan went past it. Lines
+    // added after the scan are counted from the last checkpoint.
+    pub fn checkpoint(&self, offset: u64) -> Option<(u64, u64)> {
+        if !self.done && self.scanned < offset {
+            return None;
+        }
+        let idx = self
+            .checkpoints
+            .partition_point(|&(start, _)| start <= offset);
+        return Some(self.checkpoints[idx - 1]);
+    }
+
+    // start of the last line scanned
+    pub fn scanned(&self) -> u64 {
+        return self.scanned;
+    }
+
+    // the index stops at the first error, with the checkpoints found so far
+    pub async fn step(&mut self) -> Result<()> {
+        let view = match self.view.as_mut() {
+            Some(view) => view,
+            None => return Ok(()),
+        };
+        let checkpoints = &mut self.checkpoints;
+        let scanned = &mut self.scanned;
+        let number = &mut self.next_number;
+        let res = view
+            .scan_step(&mut |offset, _| {
+                if *number % CHECKPOINT_LINES == 1 && offset > checkpoints.last().unwrap().0 {
+                    checkpoints.push((offset, *number));
+                }
+                *scanned = offset;
+                *number += 1;
+                true
+            })
+            .await;
+        if !matches!(res, Ok(true)) {
+            self.view = None;
+            self.done = true;
+        }
+        return res.map(|_| ());
+    }
+}
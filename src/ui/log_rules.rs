@@ -0,0 +1,104 @@
+use crate::{
+    config::{LogConfig, LogHighlight},
+    errors::Result,
+    ui::theme::{parse_style, Theme},
+    utils::log::{LEVEL_PATTERNS, TIMESTAMP_PATTERN},
+};
+use regex::Regex;
+use tui::{
+    style::Style,
+    text::{Span, Spans},
+};
+
+pub struct LogRule {
+    pub regex: Regex,
+    pub style: Style,
+    pub highlight: LogHighlight,
+}
+
+pub struct LogColorizer {
+    rules: Vec<LogRule>,
+    timestamp_regex: Regex,
+    timestamp_style: Style,
+}
+
+impl LogColorizer {
+    pub fn new(theme: &Theme, config: &LogConfig) -> Result<Self> {
+        let rules = if config.rules.is_empty() {
+            LEVEL_PATTERNS
+                .iter()
+                .zip(theme.log_levels.iter())
+                .map(|(pattern, style)| LogRule {
+                    regex: Regex::new(pattern).unwrap(),
+                    style: *style,
+                    highlight: config.highlight,
+                })
+                .collect()
+        } else {
+            let mut rules = Vec::new();
+            for (idx, rule) in config.rules.iter().enumerate() {
+                let style = match &rule.style {
+                    Some(style) => parse_style(style)?,
+                    None => theme.log_levels[idx % theme.log_levels.len()],
+                };
+                rules.push(LogRule {
+                    regex: Regex::new(&rule.pattern)?,
+                    style,
+                    highlight: rule.highlight.unwrap_or(config.highlight),
+                });
+            }
+            rules
+        };
+
+        return Ok(Self {
+            rules,
+            timestamp_regex: Regex::new(
+                config
+                    .timestamp_pattern
+                    .as_deref()
+                    .unwrap_or(TIMESTAMP_PATTERN),
+            )?,
+            timestamp_style: theme.log_timestamp,
+        });
+    }
+
+    pub fn color_line<'a>(&self, line: &'a str) -> Spans<'a> {
+        for rule in self.rules.iter() {
+            let m = match rule.regex.find(line) {
+                Some(m) => m,
+                None => continue,
+            };
+
+            // styled ranges, sorted and non-overlapping
+            let mut ranges = Vec::new();
+            match rule.highlight {
+                LogHighlight::Line => return Spans::from(Span::styled(line, rule.style)),
+                LogHighlight::Token => ranges.push((m.range(), rule.style)),
+                LogHighlight::TokenAndTimestamp => {
+                    if let Some(ts) = self.timestamp_regex.find(line) {
+                        if ts.end() <= m.start() || ts.start() >= m.end() {
+                            ranges.push((ts.range(), self.timestamp_style));
+                        }
+                    }
+                    ranges.push((m.range(), rule.style));
+                    ranges.sort_by_key(|(range, _)| range.start);
+                }
+            }
+
+            let mut spans = Vec::new();
+            let mut last_end = 0;
+            for (range, style) in ranges {
+                if range.start > last_end {
+                    spans.push(Span::raw(&line[last_end..range.start]));
+                }
+                spans.push(Span::styled(&line[range.clone()], style));
+                last_end = range.end;
+            }
+            if last_end < line.len() {
+                spans.push(Span::raw(&line[last_end..]));
+            }
+            return Spans::from(spans);
+        }
+        return Spans::from(Span::raw(line));
+    }
+}
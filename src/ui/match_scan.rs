@@ -0,0 +1,84 @@
+use crate::{
+    errors::Result, file_view::FileView, file_view::ViewError, utils::pattern::regex_error,
+};
+use regex::bytes;
+
+const MATCH_MAP_BINS: usize = 1024;
+
+// density of matches of a pattern across the file
+#[derive(Debug, Clone)]
+pub struct MatchMap {
+    pub pattern: String,
+    pub count: u64,
+    pub done: bool,
+    file_size: u64,
+    bins: Vec<u64>,
+}
+
+impl MatchMap {
+    // matches count per row, for a map of the given height
+    pub fn rows(&self, height: usize) -> Vec<u64> {
+        let mut rows = vec![0; height];
+        if height == 0 {
+            return rows;
+        }
+        for (idx, count) in self.bins.iter().enumerate() {
+            rows[idx * height / self.bins.len()] += count;
+        }
+        return rows;
+    }
+}
+
+// background scan of the whole file building a MatchMap
+pub struct MatchScan {
+    view: FileView,
+    regex: bytes::Regex,
+    map: MatchMap,
+}
+
+impl MatchScan {
+    pub async fn new(mut view: FileView, pattern: &str) -> Result<Self> {
+        let regex =
+            bytes::Regex::new(pattern).map_err(|e| ViewError::InvalidRegex(regex_error(&e)))?;
+        let file_size = view.file_size().await?;
+        return Ok(Self {
+            view,
+            regex,
+            map: MatchMap {
+                pattern: pattern.to_owned(),
+                count: 0,
+                done: false,
+                file_size,
+                bins: vec![0; MATCH_MAP_BINS],
+            },
+        });
+    }
+
+    pub fn set_no_cache(&mut self, no_cache: bool) {
+        self.view.set_no_cache(no_cache);
+    }
+
+    pub fn map(&self) -> &MatchMap {
+        return &self.map;
+    }
+
+    pub async fn step(&mut self) -> Result<()> {
+        let regex = &self.regex;
+        let map = &mut self.map;
+        let more = self
+            .view
+            .scan_step(&mut |offset, line| {
+                if regex.is_match(line) {
+                    let nbins = map.bins.len();
+                    let bin =
+                        (offset as u128 * nbins as u128 / map.file_size.max(1) as u128) as usize;
+                    map.bins[bin.min(nbins - 1)] += 1;
+                    map.count += 1;
+                }
+                true
+            })
+            .await?;
+        self.map.done = !more;
+        return Ok(());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{ui::theme::Theme, utils::log::Timestamp};
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::time::{Duration, Instant};
+use tui::text::{Span, Spans, Text};
+
+const MAX_MESSAGES: usize = 1000;
+// notifications shown at once in the status, the oldest ones go first
+const MAX_NOTIFICATIONS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    // time a notification of this severity stays in the status
+    fn ttl(self) -> Duration {
+        return match self {
+            Severity::Info => Duration::from_secs(3),
+            Severity::Warning => Duration::from_secs(5),
+            Severity::Error => Duration::from_secs(10),
+        };
+    }
+
+    fn name(self) -> &'static str {
+        return match self {
+            Severity::Info => "notice",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    Frontend,
+    Backend,
+}
+
+impl Source {
+    fn name(self) -> &'static str {
+        return match self {
+            Source::Frontend => "Frontend",
+            Source::Backend => "Backend",
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub time: Timestamp,
+    pub severity: Severity,
+    pub source: Source,
+    pub text: String,
+}
+
+// scrollback of the errors and notices of the session
+#[derive(Debug, Default)]
+pub struct MessageLog {
+    messages: VecDeque<Message>,
+}
+
+impl MessageLog {
+    pub fn push(&mut self, severity: Severity, source: Source, text: String) {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs() as i64)
+            .unwrap_or(0);
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Message {
+            time: Timestamp {
+                seconds,
+                has_date: true,
+            },
+            severity,
+            source,
+            text,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        return self.messages.len();
+    }
+}
+
+#[derive(Debug)]
+struct Notification {
+    severity: Severity,
+    source: Source,
+    text: String,
+    expires: Instant,
+}
+
+// messages shown in the status until they expire or are dismissed, the
+// most recent first
+#[derive(Debug, Default)]
+pub struct Notifications {
+    notifications: VecDeque<Notification>,
+}
+
+impl Notifications {
+    pub fn push(&mut self, severity: Severity, source: Source, text: String) {
+        // the same message again only stays longer
+        self.notifications
+            .retain(|x| x.text != text || x.source != source);
+        if self.notifications.len() == MAX_NOTIFICATIONS {
+            self.notifications.pop_back();
+        }
+        self.notifications.push_front(Notification {
+            severity,
+            source,
+            text,
+            expires: Instant::now() + severity.ttl(),
+        });
+    }
+
+    pub fn dismiss(&mut self) {
+        self.notifications.clear();
+    }
+
+    pub fn expire(&mut self) {
+        let now = Instant::now();
+        self.notifications.retain(|x| x.expires > now);
+    }
+
+    // time left before the next notification expires
+    pub fn next_expiry(&self) -> Option<Duration> {
+        return self
+            .notifications
+            .iter()
+            .map(|x| x.expires.saturating_duration_since(Instant::now()))
+            .min();
+    }
+
+    // like "Backend error: no match found, Frontend notice: copied 3 lines"
+    pub fn status(&self) -> String {
+        let now = Instant::now();
+        return self
+            .notifications
+            .iter()
+            .filter(|x| x.expires > now)
+            .map(|x| format!("{} {}: {}", x.source.name(), x.severity.name(), x.text))
+            .collect::<Vec<String>>()
+            .join(", ");
+    }
+}
+
+// the messages fitting in `height` lines, `scroll` messages above the last one
+pub fn messages_text(
+    log: &MessageLog,
+    theme: &Theme,
+    height: usize,
+    scroll: usize,
+) -> Text<'static> {
+    let mut lines = vec![
+        Spans::from(format!("  MESSAGES ({})", log.len())),
+        Spans::from(""),
+    ];
+    if log.messages.is_empty() {
+        lines.push(Spans::from("  no message"));
+        return Text::from(lines);
+    }
+
+    let visible = height.saturating_sub(lines.len()).max(1);
+    let end = log.messages.len().saturating_sub(scroll).max(1);
+    let start = end.saturating_sub(visible);
+    for message in log.messages.range(start..end) {
+        let (label, style) = match message.severity {
+            Severity::Info => ("info", theme.log_levels[2]),
+            Severity::Warning => ("warn", theme.log_levels[3]),
+            Severity::Error => ("error", theme.log_levels[4]),
+        };
+        let source = match message.source {
+            Source::Frontend => "frontend",
+            Source::Backend => "backend",
+        };
+        lines.push(Spans::from(vec![
+            Span::raw(format!("{} ", message.time.format(1))),
+            Span::styled(format!("{:<5}", label), style),
+            Span::raw(format!(" {}: {}", source, message.text)),
+        ]));
+    }
+    return Text::from(lines);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notifications() {
+        let mut notifications = Notifications::default();
+        notifications.push(Severity::Error, Source::Frontend, "a".to_owned());
+        notifications.push(Severity::Info, Source::Backend, "b".to_owned());
+        notifications.push(Severity::Error, Source::Frontend, "a".to_owned());
+        assert_eq!(
+            notifications.status(),
+            "Frontend error: a, Backend notice: b"
+        );
+        assert!(notifications.next_expiry().unwrap() <= Severity::Info.ttl());
+
+        for text in ["c", "d", "e"] {
+            notifications.push(Severity::Warning, Source::Backend, text.to_owned());
+        }
+        assert_eq!(
+            notifications.status(),
+            "Backend warning: e, Backend warning: d, Backend warning: c"
+        );
+
+        notifications.dismiss();
+        assert_eq!(notifications.status(), "");
+        assert_eq!(notifications.next_expiry(), None);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::ui::{match_scan::MatchMap, theme::Theme};
+use tui::text::{Span, Spans, Text};
+
+const DENSITY_CHARS: [&str; 5] = [" ", "░", "▒", "▓", "█"];
+
+fn row_of(offset: u64, file_size: u64, height: usize) -> usize {
+    if file_size == 0 || height == 0 {
+        return 0;
+    }
+    return ((offset as u128 * height as u128 / file_size as u128) as usize).min(height - 1);
+}
+
+pub fn minimap_text(
+    map: &MatchMap,
+    height: usize,
+    offset: u64,
+    file_size: u64,
+    theme: &Theme,
+) -> Text<'static> {
+    let rows = map.rows(height);
+    let max_count = rows.iter().cloned().max().unwrap_or(0);
+    let current_row = row_of(offset, file_size, height);
+
+    let lines: Vec<Spans> = rows
+        .iter()
+        .enumerate()
+        .map(|(row, count)| {
+            let level = if *count == 0 {
+                0
+            } else {
+                1 + (*count * (DENSITY_CHARS.len() as u64 - 2) / max_count.max(1)) as usize
+            };
+            let style = if row == current_row {
+                theme.search_match
+            } else {
+                theme.header
+            };
+            Spans::from(Span::styled(DENSITY_CHARS[level], style))
+        })
+        .collect();
+    return Text::from(lines);
+}
+
+// file ratio of the start of the next (or previous) cluster of matches
+pub fn cluster_ratio(
+    map: &MatchMap,
+    height: usize,
+    offset: u64,
+    file_size: u64,
+    forward: bool,
+) -> Option<f64> {
+    let rows = map.rows(height);
+    let current_row = row_of(offset, file_size, height);
+    let is_cluster_start = |row: usize| rows[row] > 0 && (row == 0 || rows[row - 1] == 0);
+
+    let row = if forward {
+        (current_row + 1..height).find(|row| is_cluster_start(*row))
+    } else {
+        (0..current_row).rev().find(|row| is_cluster_start(*row))
+    }?;
+    return Some(row as f64 / height as f64);
+}
@@ -1,38 +1,141 @@
-mod backend;
+pub mod backend;
+mod clipboard;
+mod completion;
 mod errors;
+mod fields;
+mod file_info;
+pub mod file_watch;
 mod frontend;
+mod grep_out;
+mod line_index;
+mod log_rules;
+mod match_scan;
+mod messages;
+mod minimap;
+pub mod options;
+mod palette;
+pub mod picker;
+mod profile;
+pub mod sources;
+mod stats_panel;
+mod status;
+mod tabs;
+pub mod theme;
 
 use crate::{
+    config::Config,
     errors::Result,
-    ui::errors::BackendError,
-    ui::{
-        backend::{Backend, BackendState},
-        frontend::Frontend,
-    },
+    pager::ExecStatus,
+    state::Session,
+    ui::errors::{BackendError, ChannelError},
+    ui::{backend::Backend, frontend::Frontend, tabs::Tab, theme::Theme},
 };
+use futures::stream::{FuturesUnordered, StreamExt};
 use tokio::{
-    select,
-    sync::{mpsc, watch},
+    pin, select,
+    sync::mpsc::{self, UnboundedReceiver},
 };
 
+const DEFAULT_MAX_FPS: u32 = 10;
+
 pub struct Ui {
-    backend: Backend,
     frontend: Frontend,
+    // backends of the tabs, sent by the frontend when opening files
+    backend_receiver: UnboundedReceiver<Backend>,
 }
 
 impl Ui {
-    pub async fn new(path: &str) -> Result<Self> {
-        let (state_sender, state_receiver) = watch::channel(BackendState::new());
-        let (command_sender, command_receiver) = mpsc::unbounded_channel();
-        let (cancel_sender, cancel_receiver) = mpsc::unbounded_channel();
-        let backend = Backend::new(command_receiver, cancel_receiver, state_sender, path).await?;
-        let frontend = Frontend::new(command_sender, cancel_sender, state_receiver)?;
-        return Ok(Self { backend, frontend });
+    pub async fn new(
+        path: &str,
+        config: &Config,
+        theme: Theme,
+        start_byte: Option<u64>,
+    ) -> Result<Self> {
+        let max_fps = config.max_fps.unwrap_or(DEFAULT_MAX_FPS);
+        let (tab, backend) = Tab::open(path, max_fps).await?;
+        if let Some(byte) = start_byte {
+            tab.command_sender
+                .send(backend::Command::JumpByte(byte))
+                .map_err(|_| ChannelError::Command)?;
+        }
+        // the startup position, jumped back to with '^
+        tab.command_sender
+            .send(backend::Command::SaveMark(backend::STARTUP_MARK.to_owned()))
+            .map_err(|_| ChannelError::Command)?;
+        let (backend_sender, backend_receiver) = mpsc::unbounded_channel();
+        backend_sender
+            .send(backend)
+            .map_err(|_| ChannelError::Command)?;
+        let frontend = Frontend::new(tab, backend_sender, max_fps, theme, config)?;
+        return Ok(Self {
+            frontend,
+            backend_receiver,
+        });
+    }
+
+    // reopen the tabs of a session with their marks and filters, and
+    // restore its search pattern
+    pub async fn restore_session(&mut self, session: &Session) {
+        self.frontend.restore_session(session).await;
+    }
+
+    // search a pattern like / does, exit_code tells if it matched
+    pub fn search(&mut self, pattern: &str) {
+        self.frontend.initial_search(pattern);
+    }
+
+    // run a command of the rc file or of --cmd
+    pub fn execute(&mut self, command: &str) {
+        self.frontend.execute_startup_command(command);
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        return self.frontend.exit_code();
+    }
+
+    // color added and removed lines, for files made by --diff
+    pub fn show_diff(&mut self) {
+        self.frontend.show_diff();
+    }
+
+    // follow the output of the command of --exec, the header shows its exit
+    // status once it finished
+    pub fn follow_command(&mut self, status: ExecStatus) {
+        self.frontend.follow_command(status);
+    }
+
+    // files to write to stdout once the terminal is restored
+    pub fn print_on_exit(&self) -> &[String] {
+        return self.frontend.print_on_exit();
     }
+
+    // file, line and byte offset at the top of the view of the first tab
+    pub fn position(&self) -> (String, Option<i64>, u64) {
+        return self.frontend.position();
+    }
+
     pub async fn run(&mut self) -> Result<()> {
-        return select! {
-            res = self.frontend.run() => res,
-            res = self.backend.run() => res.and(Err(BackendError::Stopped.into())),
-        };
+        // the frontend must not be dropped while running, this would cancel it
+        let frontend = self.frontend.run();
+        pin!(frontend);
+        let mut backends = FuturesUnordered::new();
+        loop {
+            select! {
+                res = &mut frontend => return res,
+                Some(res) = backends.next() => {
+                    let res: Result<()> = res;
+                    res?;
+                }
+                Some(backend) = self.backend_receiver.recv() => backends.push(run_backend(backend)),
+            }
+        }
     }
 }
+
+// runs until the tab of the backend is closed
+async fn run_backend(mut backend: Backend) -> Result<()> {
+    return match backend.run().await {
+        Err(e) if e.downcast_ref::<ChannelError>().is_some() => Ok(()),
+        res => res.and(Err(BackendError::Stopped.into())),
+    };
+}
@@ -0,0 +1,404 @@
+use crate::ui::theme::Theme;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+use tokio::time::Duration;
+use tui::text::{Span, Spans, Text};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptionKind {
+    // on or off, `:set name` turns it on and `:set noname` off
+    Bool,
+    Number,
+    // like 30s or 5m, off or 0 for none
+    Duration,
+    Text,
+}
+
+impl OptionKind {
+    // the values it takes, for the references
+    pub fn name(self) -> &'static str {
+        return match self {
+            OptionKind::Bool => "on/off",
+            OptionKind::Number => "number",
+            OptionKind::Duration => "duration",
+            OptionKind::Text => "text",
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    Bool(bool),
+    Number(usize),
+    Duration(Option<Duration>),
+    Text(String),
+}
+
+impl Display for OptionValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(true) => f.write_str("on"),
+            Self::Bool(false) => f.write_str("off"),
+            Self::Number(value) => write!(f, "{}", value),
+            Self::Duration(Some(value)) => write!(f, "{:?}", value),
+            Self::Duration(None) => f.write_str("off"),
+            Self::Text(value) => write!(f, "\"{}\"", value),
+        }
+    }
+}
+
+pub struct OptionSpec {
+    pub name: &'static str,
+    // shorter or vi names
+    pub aliases: &'static [&'static str],
+    pub kind: OptionKind,
+    pub help: &'static str,
+}
+
+pub const OPTIONS: [OptionSpec; 26] = [
+    OptionSpec {
+        name: "wrap",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Wrap the lines longer than the screen",
+    },
+    OptionSpec {
+        name: "wrap-indent",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Indent the wrapped rows like the start of their line",
+    },
+    OptionSpec {
+        name: "wrap-marker",
+        aliases: &[],
+        kind: OptionKind::Text,
+        help: "Text starting the wrapped rows of a line, like \"> \"",
+    },
+    OptionSpec {
+        name: "number",
+        aliases: &["nu"],
+        kind: OptionKind::Bool,
+        help: "Show the line numbers",
+    },
+    OptionSpec {
+        name: "ruler",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Show the columns above the text",
+    },
+    OptionSpec {
+        name: "freeze",
+        aliases: &[],
+        kind: OptionKind::Number,
+        help: "Columns kept on screen while scrolling horizontally",
+    },
+    OptionSpec {
+        name: "eof-marker",
+        aliases: &[],
+        kind: OptionKind::Text,
+        help: "After the last line: a line saying so, a ~ on each row, or none",
+    },
+    OptionSpec {
+        name: "scrolloff",
+        aliases: &["so"],
+        kind: OptionKind::Number,
+        help: "Lines kept above and below the current line, which is underlined when set",
+    },
+    OptionSpec {
+        name: "jump-center",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Put the line of the jumps to a line, byte, percentage or mark in the middle, '' is not moved",
+    },
+    OptionSpec {
+        name: "hints",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Bar at the bottom with the keys completing the command being typed",
+    },
+    OptionSpec {
+        name: "max-render-cols",
+        aliases: &[],
+        kind: OptionKind::Number,
+        help: "Characters of a line shown before it is cut with its length, 0 for all",
+    },
+    OptionSpec {
+        name: "follow",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Follow updates of the file",
+    },
+    OptionSpec {
+        name: "tab-width",
+        aliases: &["tabstop", "ts"],
+        kind: OptionKind::Number,
+        help: "Columns between the tab stops after the last one of tab-stops",
+    },
+    OptionSpec {
+        name: "tab-stops",
+        aliases: &[],
+        kind: OptionKind::Text,
+        help: "Tab stops columns, like 8,20,32",
+    },
+    OptionSpec {
+        name: "focus",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Dim the lines not matching the filter or the search instead of hiding them",
+    },
+    OptionSpec {
+        name: "ignore-case",
+        aliases: &["ic"],
+        kind: OptionKind::Bool,
+        help: "Search patterns without upper case letters match any case",
+    },
+    OptionSpec {
+        name: "entropy-words",
+        aliases: &[],
+        kind: OptionKind::Number,
+        help: "Number of words colored in entropy mode",
+    },
+    OptionSpec {
+        name: "word-separators",
+        aliases: &[],
+        kind: OptionKind::Text,
+        help: "Characters splitting words on top of whitespace",
+    },
+    OptionSpec {
+        name: "strip-cr",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Hide the carriage return of CRLF line endings",
+    },
+    OptionSpec {
+        name: "ansi-passthrough",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Show the colors of ANSI escape sequences, shown like ^[ when off",
+    },
+    OptionSpec {
+        name: "search-timeout",
+        aliases: &[],
+        kind: OptionKind::Duration,
+        help: "Stop searches after a duration, :continue searches further",
+    },
+    OptionSpec {
+        name: "search-offset",
+        aliases: &[],
+        kind: OptionKind::Text,
+        help: "Where searches put the matching line: top, middle or a number of lines from the top",
+    },
+    OptionSpec {
+        name: "source-tags",
+        aliases: &[],
+        kind: OptionKind::Number,
+        help: "Width of the file names before the lines of several files",
+    },
+    OptionSpec {
+        name: "verify-checksums",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Check the crc of the blocks of compressed files, the mismatches go to :messages",
+    },
+    OptionSpec {
+        name: "auto-reload",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Reload files replaced or rewritten at the same path instead of asking",
+    },
+    OptionSpec {
+        name: "no-cache",
+        aliases: &[],
+        kind: OptionKind::Bool,
+        help: "Drop the pages searches read from the page cache, even those cached before",
+    },
+];
+
+#[derive(Debug, Clone)]
+pub enum OptionError {
+    Unknown(String),
+    // option name and expected value
+    InvalidValue(&'static str, &'static str),
+}
+
+impl Display for OptionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(name) => write!(f, "unknown option: {}", name),
+            Self::InvalidValue(name, expected) => write!(f, "{}: expected {}", name, expected),
+        }
+    }
+}
+
+impl Error for OptionError {}
+
+// "500ms", "30s", "5m" or "1h", seconds without unit
+fn parse_duration(value: &str) -> Option<Duration> {
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let count: u64 = value[..digits].parse().ok()?;
+    // None on overflow, shown as an invalid value
+    return match &value[digits..] {
+        "ms" => Some(Duration::from_millis(count)),
+        "" | "s" => Some(Duration::from_secs(count)),
+        "m" => Some(Duration::from_secs(count.checked_mul(60)?)),
+        "h" => Some(Duration::from_secs(count.checked_mul(3600)?)),
+        _ => None,
+    };
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    return match value {
+        "on" | "true" | "yes" | "1" => Some(true),
+        "off" | "false" | "no" | "0" => Some(false),
+        _ => None,
+    };
+}
+
+fn find_option(name: &str) -> Option<&'static OptionSpec> {
+    return OPTIONS
+        .iter()
+        .find(|x| x.name == name || x.aliases.contains(&name));
+}
+
+// parse "name=value", "name value", and "name" or "noname" for the on/off options.
+// The trailing spaces of text values are kept, like in "wrap-marker=> ".
+pub fn parse_option(args: &str) -> Result<(&'static OptionSpec, OptionValue), OptionError> {
+    let (name, value) = args
+        .split_once([' ', '='])
+        .map(|(name, value)| (name, Some(value.trim_start())))
+        .unwrap_or((args, None));
+    let (spec, value) = match (find_option(name), value) {
+        (Some(spec), value) => (spec, value),
+        (None, None) => match name.strip_prefix("no").and_then(find_option) {
+            Some(spec) if spec.kind == OptionKind::Bool => (spec, Some("off")),
+            _ => return Err(OptionError::Unknown(name.to_owned())),
+        },
+        (None, Some(_)) => return Err(OptionError::Unknown(name.to_owned())),
+    };
+
+    let invalid = |expected| OptionError::InvalidValue(spec.name, expected);
+    let value = match spec.kind {
+        OptionKind::Bool => OptionValue::Bool(
+            parse_bool(value.unwrap_or("on").trim()).ok_or_else(|| invalid("on or off"))?,
+        ),
+        OptionKind::Number => OptionValue::Number(
+            value
+                .unwrap_or("")
+                .trim()
+                .parse()
+                .map_err(|_| invalid("a number"))?,
+        ),
+        OptionKind::Duration => match value.unwrap_or("").trim() {
+            "off" => OptionValue::Duration(None),
+            value => match parse_duration(value) {
+                Some(duration) if duration.is_zero() => OptionValue::Duration(None),
+                Some(duration) => OptionValue::Duration(Some(duration)),
+                None => return Err(invalid("a duration like 30s, or off")),
+            },
+        },
+        OptionKind::Text => OptionValue::Text(value.unwrap_or("").to_owned()),
+    };
+    return Ok((spec, value));
+}
+
+// value of each option, shown by :set without arguments
+pub fn options_text(value_of: impl Fn(&str) -> OptionValue, theme: &Theme) -> Text<'static> {
+    let mut lines = vec![Spans::from("  OPTIONS"), Spans::from("")];
+    for spec in OPTIONS.iter() {
+        let value = value_of(spec.name);
+        let mut name = spec.name.to_owned();
+        if !spec.aliases.is_empty() {
+            name = format!("{} ({})", name, spec.aliases.join(", "));
+        }
+        lines.push(Spans::from(vec![
+            Span::raw(format!("{:<24}", name)),
+            Span::styled(format!("{:<10}", value.to_string()), theme.search_match),
+            Span::raw(format!(" | {}", spec.help)),
+        ]));
+    }
+    return Text::from(lines);
+}
+
+// what the options do, for :help options
+pub fn options_reference_text() -> Text<'static> {
+    let mut lines = vec![
+        Spans::from("  OPTIONS, set with :set <option>=<value> or in the options of the config"),
+        Spans::from(""),
+    ];
+    for spec in OPTIONS.iter() {
+        let mut name = spec.name.to_owned();
+        if !spec.aliases.is_empty() {
+            name = format!("{} ({})", name, spec.aliases.join(", "));
+        }
+        lines.push(Spans::from(format!(
+            "{:<24}{:<10} | {}",
+            name,
+            spec.kind.name(),
+            spec.help
+        )));
+    }
+    return Text::from(lines);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<(&'static str, OptionValue), String> {
+        return parse_option(args)
+            .map(|(spec, value)| (spec.name, value))
+            .map_err(|e| e.to_string());
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(parse("wrap"), Ok(("wrap", OptionValue::Bool(true))));
+        assert_eq!(parse("nowrap"), Ok(("wrap", OptionValue::Bool(false))));
+        assert_eq!(parse("nu=off"), Ok(("number", OptionValue::Bool(false))));
+        assert_eq!(parse("so 5"), Ok(("scrolloff", OptionValue::Number(5))));
+        assert_eq!(
+            parse("wrap-marker=> "),
+            Ok(("wrap-marker", OptionValue::Text("> ".to_owned())))
+        );
+        assert_eq!(
+            parse("search-timeout=0"),
+            Ok(("search-timeout", OptionValue::Duration(None)))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("nothing"), Err("unknown option: nothing".to_owned()));
+        assert_eq!(parse("noso"), Err("unknown option: noso".to_owned()));
+        assert_eq!(
+            parse("wrap=maybe"),
+            Err("wrap: expected on or off".to_owned())
+        );
+        assert_eq!(
+            parse("so=-1"),
+            Err("scrolloff: expected a number".to_owned())
+        );
+        assert_eq!(
+            parse("search-timeout=5d"),
+            Err("search-timeout: expected a duration like 30s, or off".to_owned())
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("1.5s"), None);
+        // too many minutes or hours for the seconds of a duration
+        assert_eq!(parse_duration(&format!("{}m", u64::MAX / 30)), None);
+        assert_eq!(parse_duration(&format!("{}h", u64::MAX)), None);
+        assert!(parse(&format!("search-timeout={}h", u64::MAX / 60)).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::ui::theme::Theme;
+use std::cmp::Reverse;
+use tui::text::{Span, Spans, Text};
+
+// an action of the palette and the keys doing it; keys not ending a command
+// leave it to be finished, like a mark letter or the file of :w
+#[derive(Debug, Clone, Copy)]
+pub struct Action {
+    pub name: &'static str,
+    pub keys: &'static str,
+}
+
+const fn action(name: &'static str, keys: &'static str) -> Action {
+    return Action { name, keys };
+}
+
+pub const ACTIONS: [Action; 56] = [
+    action("search", "/"),
+    action("search next match", "n"),
+    action("search previous match", "N"),
+    action("next cluster of matches", "}"),
+    action("previous cluster of matches", "{"),
+    action("continue timed out search", ":continue\n"),
+    action("filter lines", "&"),
+    action("show all lines", "&\n"),
+    action("jump to line", ":"),
+    action("jump to first line", "gg"),
+    action("jump to last line", "GG"),
+    action("jump to first new line", "gn"),
+    action("jump to next diff hunk", "]c"),
+    action("jump to previous diff hunk", "[c"),
+    action("place mark", "m"),
+    action("jump to mark", "'"),
+    action("jump back", "''"),
+    action("jump to startup position", "'^"),
+    action("toggle wrap", "w"),
+    action("toggle follow", "f"),
+    action("toggle ruler", "ru"),
+    action("show line numbers", ":set number\n"),
+    action("hide line numbers", ":set nonumber\n"),
+    action("scroll to first column", "0"),
+    action("scroll to end of longest line", "$"),
+    action("cycle header mode", ":header\n"),
+    action("set color mode: default", "cdef"),
+    action("set color mode: log", "clog"),
+    action("set color mode: entropy", "cent"),
+    action("set color mode: fields", "cfld"),
+    action("set color mode: diff", "cdiff"),
+    action("colorize fields", ":colorize "),
+    action("copy top line", "yy"),
+    action("reload file", "R"),
+    action("open file", ":e "),
+    action("open file in new tab", ":tabnew "),
+    action("next tab", "gt"),
+    action("previous tab", "gT"),
+    action("export matches", ":grep-out "),
+    action("write file", ":w "),
+    action("export word statistics", ":wordstats "),
+    action("save session", ":mksession "),
+    action("bisect: start", ":bisect start\n"),
+    action("bisect: good", ":bisect good\n"),
+    action("bisect: bad", ":bisect bad\n"),
+    action("bisect: reset", ":bisect reset\n"),
+    action("show statistics", ":stats\n"),
+    action("show messages", ":messages\n"),
+    action("show file info", ":info\n"),
+    action("show options", ":set\n"),
+    action("show profile", ":profile\n"),
+    action("show help", "?"),
+    action("set option", ":set "),
+    action("run command", ":"),
+    action("quit", "q"),
+    action("quit with failure code", "Q"),
+];
+
+// score of `name` for a fuzzy query, None unless it contains the characters of
+// the query in order; consecutive characters and word starts score more
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    let name = name.to_lowercase().chars().collect::<Vec<char>>();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous = None;
+    for c in query.to_lowercase().chars().filter(|x| !x.is_whitespace()) {
+        let found = position + name[position..].iter().position(|x| *x == c)?;
+        if previous == Some(found.wrapping_sub(1)) {
+            score += 2;
+        }
+        if found == 0 || !name[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (found - position) as i64;
+        previous = Some(found);
+        position = found + 1;
+    }
+    return Some(score);
+}
+
+// actions matching a query, the best first
+#[derive(Debug, Clone, Default)]
+pub struct Palette {
+    pub query: String,
+    pub matches: Vec<Action>,
+    pub selected: usize,
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        let mut palette = Self::default();
+        palette.filter();
+        return palette;
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.filter();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.filter();
+    }
+
+    pub fn move_selection(&mut self, delta: i64) {
+        let max = self.matches.len().saturating_sub(1) as i64;
+        self.selected = (self.selected as i64 + delta).max(0).min(max) as usize;
+    }
+
+    pub fn selected(&self) -> Option<Action> {
+        return self.matches.get(self.selected).copied();
+    }
+
+    fn filter(&mut self) {
+        let mut scored = ACTIONS
+            .iter()
+            .filter_map(|x| fuzzy_score(&self.query, x.name).map(|score| (score, *x)))
+            .collect::<Vec<(i64, Action)>>();
+        // stable, equal scores keep the order of the registry
+        scored.sort_by_key(|x| Reverse(x.0));
+        self.matches = scored.into_iter().map(|(_, x)| x).collect();
+        self.selected = 0;
+    }
+}
+
+pub fn palette_text(palette: &Palette, theme: &Theme, height: usize) -> Text<'static> {
+    let mut lines = vec![
+        Spans::from(format!("  > {}", palette.query)),
+        Spans::from(""),
+    ];
+    if palette.matches.is_empty() {
+        lines.push(Spans::from("  no matching action"));
+        return Text::from(lines);
+    }
+
+    // keep the selection visible
+    let visible = height.saturating_sub(lines.len()).max(1);
+    let start = (palette.selected + 1).saturating_sub(visible);
+    for (index, action) in palette.matches.iter().enumerate().skip(start).take(visible) {
+        let line = format!(
+            "  {:32}  {}",
+            action.name,
+            action.keys.replace('\n', "<Enter>")
+        );
+        if index == palette.selected {
+            lines.push(Spans::from(Span::styled(line, theme.search_match)));
+        } else {
+            lines.push(Spans::from(line));
+        }
+    }
+    return Text::from(lines);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_matching() {
+        assert!(fuzzy_score("wp", "toggle wrap").is_some());
+        assert_eq!(fuzzy_score("xyz", "toggle wrap"), None);
+        assert!(fuzzy_score("tab", "next tab") > fuzzy_score("tab", "toggle wrap, all buffers"));
+
+        let mut palette = Palette::new();
+        assert_eq!(palette.matches.len(), ACTIONS.len());
+        for c in "expmat".chars() {
+            palette.push(c);
+        }
+        assert_eq!(palette.selected().unwrap().keys, ":grep-out ");
+        palette.pop();
+        palette.move_selection(5);
+        assert_eq!(palette.selected, palette.matches.len().min(6) - 1);
+    }
+}
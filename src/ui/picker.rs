@@ -0,0 +1,161 @@
+use crate::{
+    errors::Result,
+    ui::{errors::FrontendError, theme::Theme},
+};
+use chrono::{DateTime, Local};
+use crossterm::event::{Event, EventStream, KeyCode, KeyModifiers};
+use futures::StreamExt;
+use human_bytes::human_bytes;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tui::{
+    backend::CrosstermBackend,
+    text::{Span, Spans, Text},
+    widgets::Paragraph,
+    Terminal,
+};
+
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+    pub is_dir: bool,
+}
+
+// files of a directory, the most recently modified first
+#[derive(Debug, Clone)]
+pub struct FilePicker {
+    pub dir: PathBuf,
+    pub entries: Vec<FileEntry>,
+    pub selected: usize,
+}
+
+impl FilePicker {
+    pub fn new(dir: &Path) -> Result<Self> {
+        let dir = fs::canonicalize(dir)?;
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            // skip entries that vanished or cannot be read
+            let metadata = match fs::metadata(entry.path()) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            entries.push(FileEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                path: entry.path(),
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                is_dir: metadata.is_dir(),
+            });
+        }
+        // directories first, then files from the most recent
+        entries.sort_by(|a, b| {
+            b.is_dir
+                .cmp(&a.is_dir)
+                .then(b.modified.cmp(&a.modified))
+                .then(a.name.cmp(&b.name))
+        });
+        if let Some(parent) = dir.parent() {
+            entries.insert(
+                0,
+                FileEntry {
+                    name: "..".to_owned(),
+                    path: parent.to_path_buf(),
+                    size: 0,
+                    modified: SystemTime::UNIX_EPOCH,
+                    is_dir: true,
+                },
+            );
+        }
+        return Ok(Self {
+            dir,
+            entries,
+            selected: 0,
+        });
+    }
+
+    pub fn move_selection(&mut self, delta: i64) {
+        let max = self.entries.len().saturating_sub(1) as i64;
+        self.selected = (self.selected as i64 + delta).max(0).min(max) as usize;
+    }
+
+    pub fn selected(&self) -> Option<&FileEntry> {
+        return self.entries.get(self.selected);
+    }
+}
+
+pub fn picker_text(picker: &FilePicker, theme: &Theme, height: usize) -> Text<'static> {
+    let mut lines = vec![
+        Spans::from(format!("  {}", picker.dir.display())),
+        Spans::from(""),
+    ];
+    if picker.entries.is_empty() {
+        lines.push(Spans::from("  empty directory"));
+        return Text::from(lines);
+    }
+
+    // keep the selection visible
+    let visible = height.saturating_sub(lines.len()).max(1);
+    let start = (picker.selected + 1).saturating_sub(visible);
+    for (index, entry) in picker.entries.iter().enumerate().skip(start).take(visible) {
+        let line = if entry.is_dir {
+            format!("  {:>10}  {:16}  {}/", "", "", entry.name)
+        } else {
+            format!(
+                "  {:>10}  {:16}  {}",
+                human_bytes(entry.size as f64),
+                DateTime::<Local>::from(entry.modified).format("%Y-%m-%d %H:%M"),
+                entry.name
+            )
+        };
+        if index == picker.selected {
+            lines.push(Spans::from(Span::styled(line, theme.search_match)));
+        } else {
+            lines.push(Spans::from(line));
+        }
+    }
+    return Text::from(lines);
+}
+
+// let the user choose a file before the interface starts, None if cancelled
+pub async fn pick_file(dir: &Path, theme: &Theme) -> Result<Option<String>> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut events = EventStream::new();
+    let mut picker = FilePicker::new(dir)?;
+    loop {
+        terminal.draw(|f| {
+            let text = picker_text(&picker, theme, f.size().height as usize);
+            f.render_widget(Paragraph::new(text), f.size());
+        })?;
+
+        let key = match events.next().await {
+            Some(Ok(Event::Key(key))) => key,
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err(FrontendError::EndOfEventStream.into()),
+        };
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => picker.move_selection(1),
+            KeyCode::Char('k') | KeyCode::Up => picker.move_selection(-1),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => return Ok(None),
+            KeyCode::Enter => match picker.selected() {
+                Some(entry) if entry.is_dir => {
+                    // stay in the current directory if the other cannot be read
+                    if let Ok(other) = FilePicker::new(&entry.path) {
+                        picker = other;
+                    }
+                }
+                Some(entry) => return Ok(Some(entry.path.to_string_lossy().into_owned())),
+                None => (),
+            },
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use tokio::time::{Duration, Instant};
+use tui::text::{Spans, Text};
+
+// width and height of the overlay of :profile, borders included
+pub const PROFILE_WIDTH: u16 = 26;
+pub const PROFILE_HEIGHT: u16 = 6;
+
+// timings of the frames drawn while :profile is on
+pub struct FrameProfile {
+    // time taken to draw the last frame
+    draw: Duration,
+    // time between the last two frames
+    interval: Option<Duration>,
+    last_frame: Option<Instant>,
+}
+
+impl FrameProfile {
+    pub fn new() -> Self {
+        return Self {
+            draw: Duration::ZERO,
+            interval: None,
+            last_frame: None,
+        };
+    }
+
+    // a frame started at `start` was drawn
+    pub fn record(&mut self, start: Instant) {
+        self.draw = start.elapsed();
+        self.interval = self.last_frame.map(|x| start - x);
+        self.last_frame = Some(start);
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    return format!("{:.2} ms", duration.as_secs_f64() * 1000.0);
+}
+
+// the timings of the frontend with the ones of the backend for the state shown
+pub fn profile_text(profile: &FrameProfile, command: Duration, state: Duration) -> Text<'static> {
+    return Text::from(vec![
+        Spans::from(format!("draw     {}", millis(profile.draw))),
+        Spans::from(format!(
+            "frame    {}",
+            profile.interval.map(millis).unwrap_or("-".to_owned())
+        )),
+        Spans::from(format!("command  {}", millis(command))),
+        Spans::from(format!("state    {}", millis(state))),
+    ]);
+}
@@ -0,0 +1,47 @@
+use crate::{errors::Result, file_view::FileView, ui::file_watch::FileWatch};
+use async_trait::async_trait;
+use tokio::time::{self, Duration, Instant};
+use tracing::info;
+
+// where the backend takes the time and its files from, the tests give it a
+// clock they move themselves
+#[async_trait(?Send)]
+pub trait Sources {
+    fn now(&self) -> Instant;
+    async fn sleep(&self, duration: Duration);
+    async fn open(&self, path: &str) -> Result<FileView>;
+    // None when the file is polled instead
+    fn watch(&self, path: &str) -> Option<FileWatch>;
+
+    fn elapsed(&self, since: Instant) -> Duration {
+        return self.now().saturating_duration_since(since);
+    }
+}
+
+// the clock of tokio and the files on disk
+pub struct SystemSources;
+
+#[async_trait(?Send)]
+impl Sources for SystemSources {
+    fn now(&self) -> Instant {
+        return Instant::now();
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        time::sleep(duration).await;
+    }
+
+    async fn open(&self, path: &str) -> Result<FileView> {
+        return FileView::new(path).await;
+    }
+
+    fn watch(&self, path: &str) -> Option<FileWatch> {
+        return match FileWatch::new(path) {
+            Ok(watch) => Some(watch),
+            Err(e) => {
+                info!("cannot watch {}, polling it: {}", path, e);
+                None
+            }
+        };
+    }
+}
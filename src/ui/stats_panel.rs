@@ -0,0 +1,84 @@
+use crate::{
+    ui::theme::Theme,
+    utils::log::{LogStats, LEVEL_NAMES},
+};
+use tui::{
+    style::Style,
+    text::{Span, Spans, Text},
+};
+
+const BAR_CHAR: &str = "█";
+
+fn bar_line(
+    label: String,
+    count: u64,
+    max_count: u64,
+    width: usize,
+    style: Style,
+) -> Spans<'static> {
+    let count_str = count.to_string();
+    let bar_width = width.saturating_sub(label.len() + count_str.len() + 4);
+    let bar_len = if max_count == 0 {
+        0
+    } else {
+        (count as f64 / max_count as f64 * bar_width as f64).ceil() as usize
+    };
+    return Spans::from(vec![
+        Span::raw(format!("{} | ", label)),
+        Span::styled(BAR_CHAR.repeat(bar_len), style),
+        Span::raw(format!(" {}", count_str)),
+    ]);
+}
+
+fn format_width(seconds: i64) -> String {
+    return match seconds {
+        x if x % 86400 == 0 => format!("{}d", x / 86400),
+        x if x % 3600 == 0 => format!("{}h", x / 3600),
+        x if x % 60 == 0 => format!("{}min", x / 60),
+        x => format!("{}s", x),
+    };
+}
+
+pub fn stats_text(stats: &LogStats, theme: &Theme, width: usize, height: usize) -> Text<'static> {
+    let mut lines = vec![
+        Spans::from(format!("  LOG LEVELS ({} lines)", stats.lines)),
+        Spans::from(""),
+    ];
+
+    let max_level_count = stats.levels.iter().cloned().max().unwrap_or(0);
+    for (idx, count) in stats.levels.iter().enumerate() {
+        lines.push(bar_line(
+            format!("{:<5}", LEVEL_NAMES[idx]),
+            *count,
+            max_level_count,
+            width,
+            theme.log_levels[idx],
+        ));
+    }
+
+    let max_buckets = height.saturating_sub(lines.len() + 4).max(1);
+    let (bucket_width, buckets) = stats.time_buckets(max_buckets);
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(""));
+    if buckets.is_empty() {
+        lines.push(Spans::from("  NO TIMESTAMP FOUND"));
+        return Text::from(lines);
+    }
+
+    lines.push(Spans::from(format!(
+        "  LINES PER {}",
+        format_width(bucket_width)
+    )));
+    lines.push(Spans::from(""));
+    let max_bucket_count = buckets.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    for (start, count) in buckets {
+        lines.push(bar_line(
+            start.format(bucket_width),
+            count,
+            max_bucket_count,
+            width,
+            theme.search_match,
+        ));
+    }
+    return Text::from(lines);
+}
@@ -0,0 +1,29 @@
+pub const DEFAULT_HEADER_FORMAT: &str = "Line {line}, Offset {offset} ({percent}){flags}";
+pub const DEFAULT_COMPACT_HEADER_FORMAT: &str = "{file} | Line {line} ({percent}){flags}";
+
+// replace the {name} placeholders of the format, unknown ones are kept as is
+pub fn format_status(format: &str, values: &[(&str, String)]) -> String {
+    let mut out = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        let name = &rest[1..end];
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    return out;
+}
+
+pub fn uses_placeholder(format: &str, name: &str) -> bool {
+    return format.contains(&format!("{{{}}}", name));
+}
@@ -0,0 +1,79 @@
+use crate::{
+    errors::Result,
+    ui::{
+        backend::{Activity, Backend, BackendState, Command},
+        sources::SystemSources,
+        theme::Theme,
+    },
+};
+use std::{error::Error, path::Path, rc::Rc};
+use tokio::sync::{
+    mpsc::{self, UnboundedSender},
+    watch::{self, Receiver},
+};
+use tui::text::{Span, Spans};
+
+// frontend side of the backend of an open file
+pub struct Tab {
+    pub path: String,
+    pub command_sender: UnboundedSender<Command>,
+    pub cancel_sender: UnboundedSender<()>,
+    pub state_receiver: Receiver<BackendState>,
+    // long work the backend is busy with
+    pub activity_receiver: Receiver<Option<Activity>>,
+    // backend messages already added to the messages panel
+    pub logged_errors: Vec<Rc<Box<dyn Error>>>,
+    pub logged_notices: Vec<Rc<String>>,
+}
+
+impl Tab {
+    // open a file, the backend must then be run for the tab to be updated
+    pub async fn open(path: &str, max_fps: u32) -> Result<(Tab, Backend)> {
+        let (state_sender, state_receiver) = watch::channel(BackendState::new());
+        let (activity_sender, activity_receiver) = watch::channel(None);
+        let (command_sender, command_receiver) = mpsc::unbounded_channel();
+        let (cancel_sender, cancel_receiver) = mpsc::unbounded_channel();
+        let backend = Backend::new(
+            command_receiver,
+            cancel_receiver,
+            state_sender,
+            activity_sender,
+            path,
+            max_fps,
+            Rc::new(SystemSources),
+        )
+        .await?;
+        let tab = Tab {
+            path: path.to_owned(),
+            command_sender,
+            cancel_sender,
+            state_receiver,
+            activity_receiver,
+            logged_errors: Vec::new(),
+            logged_notices: Vec::new(),
+        };
+        return Ok((tab, backend));
+    }
+
+    pub fn name(&self) -> String {
+        return Path::new(&self.path)
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or(self.path.clone());
+    }
+}
+
+pub fn tab_bar(tabs: &[Tab], current: usize, theme: &Theme) -> Spans<'static> {
+    let spans = tabs
+        .iter()
+        .enumerate()
+        .map(|(index, tab)| {
+            let label = format!(" {}:{} ", index + 1, tab.name());
+            if index == current {
+                return Span::styled(label, theme.header_title);
+            }
+            return Span::styled(label, theme.header);
+        })
+        .collect::<Vec<Span>>();
+    return Spans::from(spans);
+}
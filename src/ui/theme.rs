@@ -0,0 +1,449 @@
+use crate::{
+    config::{Config, StyleConfig, ThemeConfig},
+    errors::Result,
+    ui::errors::ThemeError,
+};
+use std::env;
+use tui::style::{Color, Modifier, Style};
+
+const ANSI_16_RGB: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    pub fn detect() -> Self {
+        if let Ok(colorterm) = env::var("COLORTERM") {
+            if colorterm == "truecolor" || colorterm == "24bit" {
+                return ColorDepth::TrueColor;
+            }
+        }
+        if let Ok(term) = env::var("TERM") {
+            if term.contains("256color") {
+                return ColorDepth::Ansi256;
+            }
+        }
+        return ColorDepth::Ansi16;
+    }
+
+    pub fn parse(depth: &str) -> Result<Self> {
+        return match depth {
+            "16" => Ok(ColorDepth::Ansi16),
+            "256" => Ok(ColorDepth::Ansi256),
+            "truecolor" | "24bit" => Ok(ColorDepth::TrueColor),
+            x => Err(ThemeError::InvalidColorDepth(x.to_owned()).into()),
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub header: Style,
+    pub header_title: Style,
+    pub status: Style,
+    pub search_match: Style,
+    // the match n and N go from
+    pub search_current: Style,
+    pub eof: Style,
+    pub log_timestamp: Style,
+    pub diff_added: Style,
+    pub diff_removed: Style,
+    // trace, debug, info, warn, error, fatal
+    pub log_levels: Vec<Style>,
+    pub entropy: Vec<Style>,
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Self> {
+        return match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "solarized" => Some(Theme::solarized()),
+            _ => None,
+        };
+    }
+
+    // themes --theme accepts, the built-in ones and those of the config
+    pub fn names(config: &Config) -> Vec<String> {
+        let mut names = ["dark", "light", "solarized"]
+            .iter()
+            .map(|x| x.to_string())
+            .chain(config.themes.keys().cloned())
+            .collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+        return names;
+    }
+
+    pub fn from_config(name: Option<&str>, config: &Config) -> Result<Self> {
+        let depth = match config.color_depth.as_ref() {
+            Some(depth) => ColorDepth::parse(depth)?,
+            None => ColorDepth::detect(),
+        };
+        let name = name.or(config.theme.as_deref()).unwrap_or("dark");
+
+        let theme = if let Some(theme_config) = config.themes.get(name) {
+            Theme::from_theme_config(theme_config)?
+        } else if let Some(theme) = Theme::builtin(name) {
+            theme
+        } else {
+            return Err(ThemeError::UnknownTheme(name.to_owned()).into());
+        };
+        return Ok(theme.with_depth(depth));
+    }
+
+    fn from_theme_config(config: &ThemeConfig) -> Result<Self> {
+        let base = config.base.as_deref().unwrap_or("dark");
+        let mut theme =
+            Theme::builtin(base).ok_or_else(|| ThemeError::UnknownTheme(base.to_owned()))?;
+
+        let overrides = [
+            (&config.header, &mut theme.header),
+            (&config.header_title, &mut theme.header_title),
+            (&config.status, &mut theme.status),
+            (&config.search_match, &mut theme.search_match),
+            (&config.search_current, &mut theme.search_current),
+            (&config.eof, &mut theme.eof),
+            (&config.log_timestamp, &mut theme.log_timestamp),
+            (&config.diff_added, &mut theme.diff_added),
+            (&config.diff_removed, &mut theme.diff_removed),
+        ];
+        for (style_config, style) in overrides {
+            if let Some(style_config) = style_config {
+                *style = parse_style(style_config)?;
+            }
+        }
+        if let Some(styles) = &config.log_levels {
+            for (style, style_config) in theme.log_levels.iter_mut().zip(styles.iter()) {
+                *style = parse_style(style_config)?;
+            }
+        }
+        if let Some(styles) = &config.entropy {
+            // the fields and the entropy colors cycle through it
+            if styles.is_empty() {
+                return Err(ThemeError::EmptyPalette.into());
+            }
+            theme.entropy = styles
+                .iter()
+                .map(parse_style)
+                .collect::<Result<Vec<Style>>>()?;
+        }
+        return Ok(theme);
+    }
+
+    fn dark() -> Self {
+        return Self {
+            header: Style::default(),
+            header_title: Style::default().add_modifier(Modifier::BOLD),
+            status: Style::default(),
+            search_match: Style::default().bg(Color::Yellow).fg(Color::Black),
+            search_current: Style::default().bg(Color::LightRed).fg(Color::Black),
+            eof: Style::default().fg(Color::Red).bg(Color::DarkGray),
+            log_timestamp: Style::default().fg(Color::DarkGray),
+            diff_added: Style::default().fg(Color::Green),
+            diff_removed: Style::default().fg(Color::Red),
+            log_levels: vec![
+                Style::default().fg(Color::Cyan),
+                Style::default().fg(Color::Green),
+                Style::default().fg(Color::Gray),
+                Style::default().fg(Color::Yellow),
+                Style::default().fg(Color::Red),
+                Style::default().fg(Color::LightRed),
+            ],
+            entropy: vec![
+                Style::default().fg(Color::LightRed),
+                Style::default().fg(Color::LightYellow),
+                Style::default().fg(Color::LightGreen),
+                Style::default().fg(Color::LightCyan),
+                Style::default().fg(Color::LightBlue),
+                Style::default().fg(Color::LightMagenta),
+                Style::default().fg(Color::Red),
+                Style::default().fg(Color::Yellow),
+                Style::default().fg(Color::Green),
+                Style::default().fg(Color::Cyan),
+                Style::default().fg(Color::Blue),
+                Style::default().fg(Color::Magenta),
+            ],
+        };
+    }
+
+    fn light() -> Self {
+        return Self {
+            header: Style::default().fg(Color::Black),
+            header_title: Style::default()
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            status: Style::default().fg(Color::Black),
+            search_match: Style::default().bg(Color::Blue).fg(Color::White),
+            search_current: Style::default().bg(Color::Magenta).fg(Color::White),
+            eof: Style::default().fg(Color::White).bg(Color::Red),
+            log_timestamp: Style::default().fg(Color::Blue),
+            diff_added: Style::default().fg(Color::Green),
+            diff_removed: Style::default().fg(Color::Red),
+            log_levels: vec![
+                Style::default().fg(Color::Cyan),
+                Style::default().fg(Color::Green),
+                Style::default().fg(Color::DarkGray),
+                Style::default().fg(Color::Magenta),
+                Style::default().fg(Color::Red),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ],
+            entropy: vec![
+                Style::default().fg(Color::Red),
+                Style::default().fg(Color::Blue),
+                Style::default().fg(Color::Green),
+                Style::default().fg(Color::Magenta),
+                Style::default().fg(Color::Cyan),
+                Style::default().fg(Color::DarkGray),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Blue)
+                    .add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+                Style::default()
+                    .fg(Color::Black)
+                    .add_modifier(Modifier::BOLD),
+            ],
+        };
+    }
+
+    fn solarized() -> Self {
+        let base01 = Color::Rgb(0x58, 0x6e, 0x75);
+        let base02 = Color::Rgb(0x07, 0x36, 0x42);
+        let base1 = Color::Rgb(0x93, 0xa1, 0xa1);
+        let yellow = Color::Rgb(0xb5, 0x89, 0x00);
+        let orange = Color::Rgb(0xcb, 0x4b, 0x16);
+        let red = Color::Rgb(0xdc, 0x32, 0x2f);
+        let magenta = Color::Rgb(0xd3, 0x36, 0x82);
+        let violet = Color::Rgb(0x6c, 0x71, 0xc4);
+        let blue = Color::Rgb(0x26, 0x8b, 0xd2);
+        let cyan = Color::Rgb(0x2a, 0xa1, 0x98);
+        let green = Color::Rgb(0x85, 0x99, 0x00);
+
+        return Self {
+            header: Style::default().fg(base1),
+            header_title: Style::default().fg(yellow).add_modifier(Modifier::BOLD),
+            status: Style::default().fg(base1),
+            search_match: Style::default().bg(yellow).fg(base02),
+            search_current: Style::default().bg(orange).fg(base02),
+            eof: Style::default().fg(red).bg(base02),
+            log_timestamp: Style::default().fg(violet),
+            diff_added: Style::default().fg(green),
+            diff_removed: Style::default().fg(red),
+            log_levels: vec![
+                Style::default().fg(cyan),
+                Style::default().fg(green),
+                Style::default().fg(base01),
+                Style::default().fg(yellow),
+                Style::default().fg(red),
+                Style::default().fg(magenta).add_modifier(Modifier::BOLD),
+            ],
+            entropy: vec![
+                Style::default().fg(yellow),
+                Style::default().fg(orange),
+                Style::default().fg(red),
+                Style::default().fg(magenta),
+                Style::default().fg(violet),
+                Style::default().fg(blue),
+                Style::default().fg(cyan),
+                Style::default().fg(green),
+            ],
+        };
+    }
+
+    fn with_depth(mut self, depth: ColorDepth) -> Self {
+        let styles = [
+            &mut self.header,
+            &mut self.header_title,
+            &mut self.status,
+            &mut self.search_match,
+            &mut self.search_current,
+            &mut self.eof,
+            &mut self.log_timestamp,
+            &mut self.diff_added,
+            &mut self.diff_removed,
+        ];
+        for style in styles
+            .into_iter()
+            .chain(self.log_levels.iter_mut())
+            .chain(self.entropy.iter_mut())
+        {
+            *style = downgrade_style(*style, depth);
+        }
+        return self;
+    }
+}
+
+pub fn parse_color(color: &str) -> Result<Color> {
+    let lower = color.to_lowercase();
+    let color = match lower.as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        // the channels are sliced by bytes
+        x if x.starts_with('#') && x.len() == 7 && x.is_ascii() => {
+            let channel = |range| {
+                u8::from_str_radix(&x[range], 16)
+                    .map_err(|_| ThemeError::InvalidColor(x.to_owned()))
+            };
+            Color::Rgb(channel(1..3)?, channel(3..5)?, channel(5..7)?)
+        }
+        x => Color::Indexed(
+            x.parse::<u8>()
+                .map_err(|_| ThemeError::InvalidColor(x.to_owned()))?,
+        ),
+    };
+    return Ok(color);
+}
+
+pub fn parse_style(config: &StyleConfig) -> Result<Style> {
+    let mut style = Style::default();
+    if let Some(fg) = &config.fg {
+        style = style.fg(parse_color(fg)?);
+    }
+    if let Some(bg) = &config.bg {
+        style = style.bg(parse_color(bg)?);
+    }
+    if config.bold {
+        style = style.add_modifier(Modifier::BOLD);
+    }
+    if config.italic {
+        style = style.add_modifier(Modifier::ITALIC);
+    }
+    if config.underline {
+        style = style.add_modifier(Modifier::UNDERLINED);
+    }
+    return Ok(style);
+}
+
+fn downgrade_style(mut style: Style, depth: ColorDepth) -> Style {
+    style.fg = style.fg.map(|c| downgrade_color(c, depth));
+    style.bg = style.bg.map(|c| downgrade_color(c, depth));
+    return style;
+}
+
+pub fn downgrade_color(color: Color, depth: ColorDepth) -> Color {
+    return match (color, depth) {
+        (_, ColorDepth::TrueColor) => color,
+        (Color::Rgb(r, g, b), ColorDepth::Ansi256) => Color::Indexed(rgb_to_ansi256(r, g, b)),
+        (Color::Rgb(r, g, b), ColorDepth::Ansi16) => nearest_ansi16(r, g, b),
+        (Color::Indexed(idx), ColorDepth::Ansi16) if idx >= 16 => {
+            let (r, g, b) = ansi256_to_rgb(idx);
+            nearest_ansi16(r, g, b)
+        }
+        _ => color,
+    };
+}
+
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    // grayscale ramp gives a better match for unsaturated colors
+    if r == g && g == b {
+        if r < 8 {
+            return 16;
+        }
+        if r > 248 {
+            return 231;
+        }
+        return 232 + ((r as u16 - 8) * 24 / 247) as u8;
+    }
+    let cube = |x: u8| ((x as u16 * 5 + 127) / 255) as u8;
+    return 16 + 36 * cube(r) + 6 * cube(g) + cube(b);
+}
+
+fn ansi256_to_rgb(idx: u8) -> (u8, u8, u8) {
+    if idx < 16 {
+        return ANSI_16_RGB[idx as usize].1;
+    }
+    if idx >= 232 {
+        let level = 8 + (idx - 232) * 10;
+        return (level, level, level);
+    }
+    let level = |x: u8| if x == 0 { 0 } else { 55 + x * 40 };
+    let idx = idx - 16;
+    return (level(idx / 36), level((idx / 6) % 6), level(idx % 6));
+}
+
+fn nearest_ansi16(r: u8, g: u8, b: u8) -> Color {
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        let dr = r as i32 - cr as i32;
+        let dg = g as i32 - cg as i32;
+        let db = b as i32 - cb as i32;
+        dr * dr + dg * dg + db * db
+    };
+    return ANSI_16_RGB
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("Red").unwrap(), Color::Red);
+        assert_eq!(
+            parse_color("#10a0FF").unwrap(),
+            Color::Rgb(0x10, 0xa0, 0xff)
+        );
+        assert_eq!(parse_color("42").unwrap(), Color::Indexed(42));
+        assert!(parse_color("#é1234").is_err());
+        assert!(parse_color("#12345g").is_err());
+        assert!(parse_color("256").is_err());
+    }
+
+    #[test]
+    fn empty_palette() {
+        let config = ThemeConfig {
+            entropy: Some(Vec::new()),
+            ..ThemeConfig::default()
+        };
+        assert!(Theme::from_theme_config(&config).is_err());
+        assert!(Theme::from_theme_config(&ThemeConfig::default()).is_ok());
+    }
+}
@@ -1,28 +1,25 @@
-pub fn find_nth_or_last<T: Eq>(data: &[T], char: T, nth: usize) -> Option<(usize, usize)> {
+use memchr::{memchr_iter, memrchr_iter};
+
+// index of the nth occurrence of the byte, or of the last one when there are
+// fewer, along with its rank
+pub fn find_nth_or_last(data: &[u8], byte: u8, nth: usize) -> Option<(usize, usize)> {
     let mut last_found = None;
-    let mut cnt = 0 as usize;
-    for (idx, data_char) in data.iter().enumerate() {
-        if char == *data_char {
-            last_found = Some((cnt, idx));
-            if nth == cnt {
-                break;
-            }
-            cnt += 1;
+    for (cnt, idx) in memchr_iter(byte, data).enumerate() {
+        last_found = Some((cnt, idx));
+        if nth == cnt {
+            break;
         }
     }
     return last_found;
 }
 
-pub fn rfind_nth_or_last<T: Eq>(data: &[T], char: T, nth: usize) -> Option<(usize, usize)> {
+// same as find_nth_or_last, counting from the end
+pub fn rfind_nth_or_last(data: &[u8], byte: u8, nth: usize) -> Option<(usize, usize)> {
     let mut last_found = None;
-    let mut cnt = 0 as usize;
-    for (idx, data_char) in data.iter().enumerate().rev() {
-        if char == *data_char {
-            last_found = Some((cnt, idx));
-            if nth == cnt {
-                break;
-            }
-            cnt += 1;
+    for (cnt, idx) in memrchr_iter(byte, data).enumerate() {
+        last_found = Some((cnt, idx));
+        if nth == cnt {
+            break;
         }
     }
     return last_found;
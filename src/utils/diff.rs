@@ -0,0 +1,191 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    thread,
+};
+use tracing::{error, info};
+
+// lines read ahead in each file to find where they match again
+const DIFF_WINDOW: usize = 1000;
+
+// common line or header followed by a changed line, used to jump between
+// hunks. The header is there for a hunk at the start of the files.
+pub const HUNK_PATTERN: &str = r"(?m)^[^-+\n].*\n[-+]";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiffStats {
+    pub added: u64,
+    pub removed: u64,
+    pub hunks: u64,
+}
+
+struct LineReader<R: Read> {
+    reader: BufReader<R>,
+    pending: VecDeque<Vec<u8>>,
+    eof: bool,
+}
+
+impl<R: Read> LineReader<R> {
+    fn new(reader: R) -> Self {
+        return Self {
+            reader: BufReader::new(reader),
+            pending: VecDeque::new(),
+            eof: false,
+        };
+    }
+
+    fn fill(&mut self, count: usize) -> io::Result<()> {
+        while !self.eof && self.pending.len() < count {
+            let mut line = Vec::new();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                self.eof = true;
+                break;
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            self.pending.push_back(line);
+        }
+        return Ok(());
+    }
+}
+
+// number of lines to remove from `old` and to add from `new` before they match
+// again, the whole windows when they do not
+fn resync(old: &VecDeque<Vec<u8>>, new: &VecDeque<Vec<u8>>) -> (usize, usize) {
+    let mut new_index = HashMap::new();
+    for (j, line) in new.iter().enumerate() {
+        new_index.entry(line.as_slice()).or_insert(j);
+    }
+    let mut best = (old.len(), new.len());
+    for (i, line) in old.iter().enumerate() {
+        if i >= best.0 + best.1 {
+            break;
+        }
+        if let Some(&j) = new_index.get(line.as_slice()) {
+            if i + j < best.0 + best.1 {
+                best = (i, j);
+            }
+        }
+    }
+    return best;
+}
+
+// write the diff of two files to `output` from a thread, bless follows it
+// while it grows
+pub fn spawn_diff(old: &Path, new: &Path, output: &Path) -> io::Result<()> {
+    let header = format!("diff {} {}\n", old.display(), new.display());
+    let (old, new) = (File::open(old)?, File::open(new)?);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(output)?;
+    file.write_all(header.as_bytes())?;
+    thread::spawn(move || match write_diff(old, new, file) {
+        Ok(stats) => info!(
+            "diff: {} hunks, {} added, {} removed",
+            stats.hunks, stats.added, stats.removed
+        ),
+        Err(e) => error!("error writing the diff: {}", e),
+    });
+    return Ok(());
+}
+
+// write the lines of both files prefixed like a unified diff: " " for common
+// lines, "-" for lines only in `old` and "+" for lines only in `new`.
+// The files are compared through a window of lines so they do not have to fit
+// in memory, a change larger than the window shows as removed then added lines.
+pub fn write_diff(old: impl Read, new: impl Read, output: impl Write) -> io::Result<DiffStats> {
+    let mut old = LineReader::new(old);
+    let mut new = LineReader::new(new);
+    let mut writer = BufWriter::new(output);
+    let mut stats = DiffStats::default();
+    let mut in_hunk = false;
+
+    loop {
+        old.fill(DIFF_WINDOW)?;
+        new.fill(DIFF_WINDOW)?;
+        match (old.pending.front(), new.pending.front()) {
+            (None, None) => break,
+            (Some(a), Some(b)) if a == b => {
+                writer.write_all(b" ")?;
+                writer.write_all(a)?;
+                writer.write_all(b"\n")?;
+                old.pending.pop_front();
+                new.pending.pop_front();
+                in_hunk = false;
+                continue;
+            }
+            _ => (),
+        }
+
+        if !in_hunk {
+            stats.hunks += 1;
+            in_hunk = true;
+        }
+        let (removed, added) = resync(&old.pending, &new.pending);
+        for line in old.pending.drain(..removed) {
+            writer.write_all(b"-")?;
+            writer.write_all(&line)?;
+            writer.write_all(b"\n")?;
+        }
+        for line in new.pending.drain(..added) {
+            writer.write_all(b"+")?;
+            writer.write_all(&line)?;
+            writer.write_all(b"\n")?;
+        }
+        stats.removed += removed as u64;
+        stats.added += added as u64;
+    }
+    writer.flush()?;
+    return Ok(stats);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn diff(old: &str, new: &str) -> (String, DiffStats) {
+        let mut output = Vec::new();
+        let stats = write_diff(old.as_bytes(), new.as_bytes(), &mut output).unwrap();
+        return (String::from_utf8(output).unwrap(), stats);
+    }
+
+    #[test]
+    fn changed_lines() {
+        let (output, stats) = diff("a\nb\nc\nd\n", "a\nB\nc\nd\ne\n");
+        assert_eq!(output, " a\n-b\n+B\n c\n d\n+e\n");
+        assert_eq!((stats.hunks, stats.added, stats.removed), (2, 2, 1));
+    }
+
+    #[test]
+    fn same_files() {
+        let (output, stats) = diff("a\nb\n", "a\nb");
+        assert_eq!(output, " a\n b\n");
+        assert_eq!(stats.hunks, 0);
+    }
+
+    #[test]
+    fn change_larger_than_window() {
+        let old: String = (0..DIFF_WINDOW + 10).map(|x| format!("{}\n", x)).collect();
+        let new = format!("{}{}", "x\n".repeat(DIFF_WINDOW + 1), old);
+        // the common lines are past the window, all the lines changed
+        let (output, stats) = diff(&old, &new);
+        assert_eq!(stats.removed, DIFF_WINDOW as u64 + 10);
+        assert_eq!(stats.added, 2 * DIFF_WINDOW as u64 + 11);
+        assert!(!output.lines().any(|x| x.starts_with(' ')));
+    }
+
+    #[test]
+    fn hunk_pattern() {
+        let re = Regex::new(HUNK_PATTERN).unwrap();
+        let (output, _) = diff("a\nb\nc\nd\n", "A\nb\nc\nD\n");
+        let output = format!("diff old new\n{}", output);
+        // the first hunk starts right after the header
+        let starts: Vec<usize> = re.find_iter(&output).map(|x| x.start()).collect();
+        assert_eq!(starts, vec![0, output.find(" c").unwrap()]);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::utils::pattern::regex_error;
+use regex::bytes::Regex;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+#[derive(Debug, Clone)]
+pub enum FilterError {
+    EmptyTerm,
+    // term and description of the error
+    InvalidRegex(String, String),
+}
+
+impl Display for FilterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyTerm => f.write_str("empty filter term"),
+            Self::InvalidRegex(term, e) => write!(f, "invalid regex in filter: {}: {}", term, e),
+        }
+    }
+}
+
+impl Error for FilterError {}
+
+// lines selected by regexes combined with "!", "&&" and "||", in decreasing
+// order of precedence: "ERROR && !healthcheck || FATAL". Parentheses are
+// left to the regexes, they do not group terms, and operators in a group, a
+// class or escaped belong to the regex: "(a||b)", "[|&]{2}", "a\|\|b".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Term(&'a str),
+    And,
+    Or,
+}
+
+// split an expression on the operators outside of the regex syntax
+fn tokenize(expr: &str) -> Vec<Token<'_>> {
+    let bytes = expr.as_bytes();
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    let mut in_class = false;
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'[' if !in_class => {
+                in_class = true;
+                // a leading "]" is part of the class, not its end
+                if bytes.get(i + 1) == Some(&b'^') {
+                    i += 1;
+                }
+                if bytes.get(i + 1) == Some(&b']') {
+                    i += 1;
+                }
+            }
+            b']' if in_class => in_class = false,
+            b'(' if !in_class => depth += 1,
+            b')' if !in_class => depth = depth.saturating_sub(1),
+            op @ (b'&' | b'|') if !in_class && depth == 0 && bytes.get(i + 1) == Some(&op) => {
+                tokens.push(Token::Term(&expr[start..i]));
+                tokens.push(if op == b'&' { Token::And } else { Token::Or });
+                i += 1;
+                start = i + 1;
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    tokens.push(Token::Term(&expr[start.min(expr.len())..]));
+    return tokens;
+}
+
+#[derive(Debug, Clone)]
+pub enum Predicate {
+    Match(Regex),
+    Not(Box<Predicate>),
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+}
+
+impl Predicate {
+    pub fn parse(expr: &str) -> Result<Self, FilterError> {
+        let mut any = Vec::new();
+        let mut all = Vec::new();
+        for token in tokenize(expr) {
+            match token {
+                Token::Term(term) => all.push(Predicate::parse_term(term.trim())?),
+                Token::And => (),
+                Token::Or => any.push(Predicate::and(std::mem::take(&mut all))),
+            }
+        }
+        any.push(Predicate::and(all));
+        if any.len() == 1 {
+            return Ok(any.pop().unwrap());
+        }
+        return Ok(Predicate::Or(any));
+    }
+
+    fn and(mut all: Vec<Predicate>) -> Self {
+        if all.len() == 1 {
+            return all.pop().unwrap();
+        }
+        return Predicate::And(all);
+    }
+
+    fn parse_term(term: &str) -> Result<Self, FilterError> {
+        if let Some(negated) = term.strip_prefix('!') {
+            return Ok(Predicate::Not(Box::new(Predicate::parse_term(
+                negated.trim_start(),
+            )?)));
+        }
+        if term.is_empty() {
+            return Err(FilterError::EmptyTerm);
+        }
+        return Regex::new(term)
+            .map(Predicate::Match)
+            .map_err(|e| FilterError::InvalidRegex(term.to_owned(), regex_error(&e)));
+    }
+
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        return match self {
+            Predicate::Match(re) => re.is_match(line),
+            Predicate::Not(predicate) => !predicate.is_match(line),
+            Predicate::And(predicates) => predicates.iter().all(|x| x.is_match(line)),
+            Predicate::Or(predicates) => predicates.iter().any(|x| x.is_match(line)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators_outside_regexes() {
+        assert_eq!(
+            tokenize("a && !b || c"),
+            vec![
+                Token::Term("a "),
+                Token::And,
+                Token::Term(" !b "),
+                Token::Or,
+                Token::Term(" c"),
+            ]
+        );
+        assert_eq!(tokenize("(a||b)"), vec![Token::Term("(a||b)")]);
+        assert_eq!(tokenize("[|&]{2}"), vec![Token::Term("[|&]{2}")]);
+        assert_eq!(tokenize("[]||]"), vec![Token::Term("[]||]")]);
+        assert_eq!(tokenize("[^]&&]"), vec![Token::Term("[^]&&]")]);
+        assert_eq!(tokenize(r"a\|\|b"), vec![Token::Term(r"a\|\|b")]);
+        assert_eq!(tokenize("a | b"), vec![Token::Term("a | b")]);
+    }
+
+    #[test]
+    fn parse_and_match() {
+        let predicate = Predicate::parse("ERROR && !health || FATAL").unwrap();
+        assert!(predicate.is_match(b"ERROR disk full"));
+        assert!(!predicate.is_match(b"ERROR healthcheck"));
+        assert!(predicate.is_match(b"FATAL healthcheck"));
+        assert!(!predicate.is_match(b"INFO"));
+
+        let predicate = Predicate::parse("x(a||b) && [&]{2}").unwrap();
+        assert!(predicate.is_match(b"xb &&"));
+        assert!(predicate.is_match(b"x &&"));
+        assert!(!predicate.is_match(b"xa &"));
+
+        assert!(matches!(
+            Predicate::parse("a && || b"),
+            Err(FilterError::EmptyTerm)
+        ));
+        assert!(matches!(Predicate::parse("!"), Err(FilterError::EmptyTerm)));
+        assert!(matches!(
+            Predicate::parse("a && (b"),
+            Err(FilterError::InvalidRegex(term, _)) if term == "(b"
+        ));
+    }
+}
@@ -1,8 +1,8 @@
-use log::info;
 use std::{
     error::Error,
     fmt::{self, Display, Formatter},
 };
+use tracing::info;
 
 #[derive(Debug, Clone)]
 pub struct InfiniteLoopError;
@@ -1,5 +1,11 @@
+use crate::utils::words::WordSeparators;
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
 
 pub fn word_entropy(word: &str) -> f64 {
     lazy_static! {
@@ -51,3 +57,117 @@ pub fn word_entropy(word: &str) -> f64 {
     }
     return -entropy;
 }
+
+// words worth coloring or counting in a line
+pub fn words<'a>(
+    line: &'a str,
+    separators: &'a WordSeparators,
+) -> impl Iterator<Item = &'a str> + 'a {
+    lazy_static! {
+        static ref WORD_REGEX: Regex = Regex::new(".*\\w").unwrap();
+    }
+
+    return separators
+        .split(line)
+        .filter(|word| word.len() >= 4)
+        .map(|word| WORD_REGEX.find(word).map(|m| m.as_str()).unwrap_or(""));
+}
+
+fn word_score(word: &str, count: u64) -> u64 {
+    return (1000000.0 * word_entropy(word)) as u64 * count;
+}
+
+// most interesting words of the text, ranked by entropy weighted by occurrences
+pub fn interesting_words<S: AsRef<str>>(
+    lines: &[S],
+    count: usize,
+    separators: &WordSeparators,
+) -> Vec<String> {
+    let mut words_count: HashMap<&str, u64> = HashMap::new();
+    for word in lines
+        .iter()
+        .flat_map(|line| words(line.as_ref(), separators))
+    {
+        *words_count.entry(word).or_default() += 1;
+    }
+
+    let mut words: Vec<(u64, &str)> = words_count
+        .iter()
+        .map(|(word, count)| (word_score(word, *count), *word))
+        .collect();
+    // sort on the word as well so that ties don't depend on the hash map order
+    words.sort_unstable_by(|a, b| b.cmp(a));
+    return words
+        .into_iter()
+        .take(count)
+        .map(|(_, word)| word.to_owned())
+        .collect();
+}
+
+#[derive(Serialize)]
+pub struct WordStat {
+    pub word: String,
+    pub count: u64,
+    pub entropy: f64,
+    pub score: u64,
+}
+
+pub struct WordStats {
+    counts: HashMap<String, u64>,
+    separators: WordSeparators,
+}
+
+impl WordStats {
+    pub fn new(separators: WordSeparators) -> Self {
+        return Self {
+            counts: HashMap::new(),
+            separators,
+        };
+    }
+
+    pub fn add_line(&mut self, line: &str) {
+        for word in words(line, &self.separators) {
+            if let Some(count) = self.counts.get_mut(word) {
+                *count += 1;
+            } else {
+                self.counts.insert(word.to_owned(), 1);
+            }
+        }
+    }
+
+    // all words, the most interesting first
+    pub fn ranked(&self) -> Vec<WordStat> {
+        let mut stats: Vec<WordStat> = self
+            .counts
+            .iter()
+            .map(|(word, count)| WordStat {
+                word: word.clone(),
+                count: *count,
+                entropy: word_entropy(word),
+                score: word_score(word, *count),
+            })
+            .collect();
+        stats.sort_unstable_by(|a, b| (b.score, &b.word).cmp(&(a.score, &a.word)));
+        return stats;
+    }
+
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "word,count,entropy,score")?;
+        for stat in self.ranked() {
+            writeln!(
+                writer,
+                "\"{}\",{},{:.6},{}",
+                stat.word.replace('"', "\"\""),
+                stat.count,
+                stat.entropy,
+                stat.score
+            )?;
+        }
+        return Ok(());
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, &self.ranked())?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,67 @@
+use std::env;
+
+// options of less taking an argument, the rest of their word is the argument
+const LESS_ARGUMENT_OPTIONS: &str = "#bDhjkoOpPtTxyz";
+
+// the options of less and more that bless understands, so that it can replace
+// them as PAGER without changing their environment variables
+#[derive(Debug, Clone, Default)]
+pub struct LessOptions {
+    // -i and -I
+    pub ignore_case: bool,
+    // -r and -R, both only show the colors of escape sequences like -R does,
+    // the other controls stay escaped
+    pub ansi_colors: bool,
+    // -S
+    pub chop_long_lines: bool,
+    // -N
+    pub line_numbers: bool,
+    // -F
+    pub quit_if_one_screen: bool,
+    // -X
+    pub no_init: bool,
+    // +F
+    pub follow: bool,
+    // +/pattern
+    pub search: Option<String>,
+}
+
+impl LessOptions {
+    // options of $MORE then $LESS, the later ones win
+    pub fn from_env() -> Self {
+        let mut options = Self::default();
+        for name in ["MORE", "LESS"] {
+            if let Ok(value) = env::var(name) {
+                options.parse(&value);
+            }
+        }
+        return options;
+    }
+
+    // words like "-R -S", "-RS" or "RS" like less reads them, and "+F" or
+    // "+/pattern" commands
+    pub fn parse(&mut self, value: &str) {
+        for word in value.split_whitespace() {
+            if let Some(command) = word.strip_prefix('+') {
+                match command {
+                    "F" => self.follow = true,
+                    x if x.starts_with('/') && x.len() > 1 => self.search = Some(x[1..].to_owned()),
+                    _ => (),
+                }
+                continue;
+            }
+            for flag in word.trim_start_matches('-').chars() {
+                match flag {
+                    'i' | 'I' => self.ignore_case = true,
+                    'r' | 'R' => self.ansi_colors = true,
+                    'S' => self.chop_long_lines = true,
+                    'N' => self.line_numbers = true,
+                    'F' => self.quit_if_one_screen = true,
+                    'X' => self.no_init = true,
+                    x if LESS_ARGUMENT_OPTIONS.contains(x) => break,
+                    _ => (),
+                }
+            }
+        }
+    }
+}
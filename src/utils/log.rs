@@ -0,0 +1,168 @@
+use lazy_static::lazy_static;
+use regex::bytes;
+use std::collections::BTreeMap;
+
+pub const LEVEL_NAMES: [&str; 6] = ["trace", "debug", "info", "warn", "error", "fatal"];
+pub const LEVEL_PATTERNS: [&str; 6] = [
+    "(?i)trace",
+    "(?i)debug",
+    "(?i)info",
+    "(?i)warn",
+    "(?i)error",
+    "(?i)fatal|critical",
+];
+pub const TIMESTAMP_PATTERN: &str =
+    r"(?:(\d{4})-(\d{2})-(\d{2})[T ])?(\d{2}):(\d{2}):(\d{2})(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    // seconds since 1970-01-01, or since midnight if the date is unknown
+    pub seconds: i64,
+    pub has_date: bool,
+}
+
+impl Timestamp {
+    pub fn format(&self, precision_seconds: i64) -> String {
+        let days = self.seconds.div_euclid(86400);
+        let secs = self.seconds.rem_euclid(86400);
+        let (h, m, s) = (secs / 3600, (secs / 60) % 60, secs % 60);
+
+        let time = if precision_seconds >= 86400 {
+            String::new()
+        } else if precision_seconds >= 60 {
+            format!("{:02}:{:02}", h, m)
+        } else {
+            format!("{:02}:{:02}:{:02}", h, m, s)
+        };
+        if !self.has_date {
+            return time;
+        }
+
+        let (y, mo, d) = civil_from_days(days);
+        let date = format!("{:04}-{:02}-{:02}", y, mo, d);
+        return if time.is_empty() {
+            date
+        } else {
+            format!("{} {}", date, time)
+        };
+    }
+}
+
+// index of the first matching level in LEVEL_PATTERNS
+pub fn log_level(line: &[u8]) -> Option<usize> {
+    lazy_static! {
+        static ref LEVEL_REGEXES: Vec<bytes::Regex> = LEVEL_PATTERNS
+            .iter()
+            .map(|x| bytes::Regex::new(x).unwrap())
+            .collect();
+    }
+    return LEVEL_REGEXES.iter().position(|re| re.is_match(line));
+}
+
+pub fn parse_timestamp(line: &[u8]) -> Option<Timestamp> {
+    lazy_static! {
+        static ref TIMESTAMP_REGEX: bytes::Regex = bytes::Regex::new(TIMESTAMP_PATTERN).unwrap();
+    }
+
+    let captures = TIMESTAMP_REGEX.captures(line)?;
+    let number = |idx: usize| -> Option<i64> {
+        let m = captures.get(idx)?;
+        return std::str::from_utf8(m.as_bytes()).ok()?.parse().ok();
+    };
+
+    let (days, has_date) = match (number(1), number(2), number(3)) {
+        (Some(y), Some(m), Some(d)) => (days_from_civil(y, m, d), true),
+        _ => (0, false),
+    };
+    let seconds = days * 86400 + number(4)? * 3600 + number(5)? * 60 + number(6)?;
+    return Some(Timestamp { seconds, has_date });
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    return era * 146097 + doe - 719468;
+}
+
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    return (y, m, d);
+}
+
+const BUCKET_WIDTHS: [i64; 14] = [
+    1,
+    5,
+    10,
+    30,
+    60,
+    300,
+    600,
+    1800,
+    3600,
+    3 * 3600,
+    6 * 3600,
+    12 * 3600,
+    86400,
+    7 * 86400,
+];
+
+#[derive(Debug, Default)]
+pub struct LogStats {
+    pub lines: u64,
+    pub levels: [u64; LEVEL_PATTERNS.len()],
+    // count of lines per timestamp
+    pub seconds: BTreeMap<Timestamp, u64>,
+}
+
+impl LogStats {
+    pub fn add_line(&mut self, line: &[u8]) {
+        self.lines += 1;
+        if let Some(level) = log_level(line) {
+            self.levels[level] += 1;
+        }
+        if let Some(ts) = parse_timestamp(line) {
+            *self.seconds.entry(ts).or_default() += 1;
+        }
+    }
+
+    // lines count per time bucket, with the bucket width in seconds
+    pub fn time_buckets(&self, max_buckets: usize) -> (i64, Vec<(Timestamp, u64)>) {
+        let (first, last) = match (self.seconds.keys().next(), self.seconds.keys().last()) {
+            (Some(first), Some(last)) => (first.seconds, last.seconds),
+            _ => return (1, Vec::new()),
+        };
+        let span = last - first + 1;
+        let width = BUCKET_WIDTHS
+            .iter()
+            .cloned()
+            .find(|width| span / width < max_buckets as i64)
+            .unwrap_or(span / max_buckets as i64 + 1);
+
+        let mut buckets: Vec<(Timestamp, u64)> = Vec::new();
+        for (ts, count) in self.seconds.iter() {
+            let start = Timestamp {
+                seconds: ts.seconds - ts.seconds.rem_euclid(width),
+                has_date: ts.has_date,
+            };
+            match buckets.last_mut() {
+                Some((last_start, last_count)) if last_start.seconds == start.seconds => {
+                    *last_count += count
+                }
+                _ => buckets.push((start, *count)),
+            }
+        }
+        return (width, buckets);
+    }
+}
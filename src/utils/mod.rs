@@ -1,4 +1,11 @@
 pub mod algorithm;
+pub mod diff;
+pub mod filter;
 pub mod infinite_loop_breaker;
 pub mod language;
+pub mod less;
+pub mod log;
+pub mod pattern;
 pub mod text;
+pub mod words;
+pub mod wrap;
@@ -0,0 +1,89 @@
+use human_bytes::human_bytes;
+
+// one line description of a regex error, the error message of the regex crate
+// spans several lines to point at the faulty part of the pattern
+pub fn regex_error(error: &regex::Error) -> String {
+    let message = match error {
+        regex::Error::Syntax(message) => message,
+        regex::Error::CompiledTooBig(limit) => {
+            return format!("larger than {} once compiled", human_bytes(*limit as f64));
+        }
+        e => return e.to_string(),
+    };
+    let lines = message.lines().collect::<Vec<&str>>();
+    let reason = lines
+        .iter()
+        .find_map(|x| x.strip_prefix("error: "))
+        .unwrap_or(message.as_str());
+    // single line patterns are indented by 4 spaces, with carets under the error
+    let column = lines
+        .iter()
+        .skip(1)
+        .find(|x| x.starts_with("    ") && x.trim().chars().all(|c| c == '^'))
+        .and_then(|x| x.find('^'))
+        .map(|x| x - 3);
+    return match column {
+        Some(column) => format!("{} at column {}", reason, column),
+        None => reason.to_owned(),
+    };
+}
+
+// uppercase letters of a pattern other than those of its escapes, like \S,
+// \p{Lu} or \xDE, for the ignore-case option to keep case sensitive searches
+pub fn has_uppercase(pattern: &str) -> bool {
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('x') | Some('p') | Some('P') if chars.next_if_eq(&'{').is_some() => {
+                    while chars.next().is_some_and(|x| x != '}') {}
+                }
+                Some('x') => {
+                    chars.next_if(char::is_ascii_hexdigit);
+                    chars.next_if(char::is_ascii_hexdigit);
+                }
+                Some('p') | Some('P') => {
+                    chars.next();
+                }
+                _ => (),
+            },
+            c if c.is_uppercase() => return true,
+            _ => (),
+        }
+    }
+    return false;
+}
+
+fn is_word_char(c: char) -> bool {
+    return c.is_alphanumeric() || c == '_';
+}
+
+// pattern matching a whole word, \b only holds next to word characters so the
+// ends made of punctuation are left unbounded
+pub fn word_pattern(word: &str) -> String {
+    let start = match word.chars().next() {
+        Some(c) if is_word_char(c) => r"\b",
+        _ => "",
+    };
+    let end = match word.chars().last() {
+        Some(c) if is_word_char(c) => r"\b",
+        _ => "",
+    };
+    return format!("{}{}{}", start, regex::escape(word), end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uppercase_letters() {
+        assert!(has_uppercase("Error"));
+        assert!(has_uppercase(r"\sFoo"));
+        assert!(has_uppercase(r"\xdeA"));
+        assert!(!has_uppercase(r"(?-u)\xDE\xAD"));
+        assert!(!has_uppercase(r"\S+\W\p{Lu}\x{1F600}\PL"));
+        assert!(has_uppercase(r"\pLA"));
+        assert!(!has_uppercase("(?x) foo # comment"));
+    }
+}
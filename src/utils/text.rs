@@ -1,41 +1,307 @@
-use num_integer::div_ceil;
-use std::{
-    borrow::Cow,
-    str::{from_utf8, from_utf8_unchecked},
-};
-
-pub fn decode_utf8(data: &[u8]) -> Cow<str> {
-    match from_utf8(data) {
-        Ok(string) => Cow::Borrowed(string),
-        Err(e) => {
-            if e.valid_up_to() > data.len() - 4 {
-                Cow::Borrowed(unsafe { from_utf8_unchecked(&data[..e.valid_up_to()]) })
-            } else {
-                String::from_utf8_lossy(data)
-            }
+use std::{borrow::Cow, ops::Range, str::from_utf8};
+use tui::style::{Color, Modifier, Style};
+
+// characters of a line shown before it is cut, wrapping or shifting a line
+// of megabytes would take a while at each frame
+pub const DEFAULT_MAX_RENDER_COLS: usize = 100000;
+
+// text is valid utf-8 without nul bytes, a character may be cut at the end
+pub fn looks_like_text(data: &[u8]) -> bool {
+    let valid = match from_utf8(data) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    return valid && !data.contains(&0);
+}
+
+// show the control characters other than tabs like less does, "^[" for an
+// escape and "<U+009B>" for C1 controls, so that escape sequences in the file
+// cannot drive the terminal
+pub fn escape_controls(text: &str) -> Cow<str> {
+    let is_control = |c: char| c != '\t' && c.is_control();
+    if !text.contains(is_control) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        push_escaped(&mut escaped, c);
+    }
+    return Cow::Owned(escaped);
+}
+
+fn push_escaped(escaped: &mut String, c: char) {
+    match c as u32 {
+        _ if c == '\t' || !c.is_control() => escaped.push(c),
+        x if x < 0x20 || x == 0x7f => {
+            escaped.push('^');
+            escaped.push((x as u8 ^ 0x40) as char);
         }
+        x => escaped.push_str(&format!("<U+{:04X}>", x)),
+    }
+}
+
+// length of the SGR escape sequence setting colors and attributes, like
+// "\x1b[1;31m", at the start of a text
+fn sgr_len(text: &str) -> Option<usize> {
+    let params = text.strip_prefix("\x1b[")?;
+    let end = params.find(|c: char| !c.is_ascii_digit() && c != ';' && c != ':')?;
+    if params.as_bytes()[end] != b'm' {
+        return None;
     }
+    return Some(end + 3);
+}
+
+// style without some modifiers, rather than one removing them from the
+// style below
+fn without(style: Style, modifier: Modifier) -> Style {
+    return Style {
+        add_modifier: style.add_modifier - modifier,
+        ..style
+    };
 }
 
-pub fn convert_tabs(mut lines: Vec<Cow<str>>, tab_width: usize) -> Vec<Cow<str>> {
-    for cow_line in lines.iter_mut() {
-        if !cow_line.contains('\t') {
+// style once the parameters of an SGR sequence are applied, the unknown ones
+// are ignored
+fn apply_sgr(mut style: Style, params: &str) -> Style {
+    let mut codes = params
+        .split([';', ':'])
+        .map(|x| x.parse::<u8>().unwrap_or(0));
+    while let Some(code) = codes.next() {
+        style = match code {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            8 => style.add_modifier(Modifier::HIDDEN),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            22 => without(style, Modifier::BOLD | Modifier::DIM),
+            23 => without(style, Modifier::ITALIC),
+            24 => without(style, Modifier::UNDERLINED),
+            25 => without(style, Modifier::SLOW_BLINK),
+            27 => without(style, Modifier::REVERSED),
+            28 => without(style, Modifier::HIDDEN),
+            29 => without(style, Modifier::CROSSED_OUT),
+            30..=37 => style.fg(Color::Indexed(code - 30)),
+            39 => style.fg(Color::Reset),
+            40..=47 => style.bg(Color::Indexed(code - 40)),
+            49 => style.bg(Color::Reset),
+            90..=97 => style.fg(Color::Indexed(code - 90 + 8)),
+            100..=107 => style.bg(Color::Indexed(code - 100 + 8)),
+            38 | 48 => {
+                let color = match codes.next() {
+                    Some(5) => codes.next().map(Color::Indexed),
+                    Some(2) => match (codes.next(), codes.next(), codes.next()) {
+                        (Some(r), Some(g), Some(b)) => Some(Color::Rgb(r, g, b)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                match (color, code) {
+                    (Some(color), 38) => style.fg(color),
+                    (Some(color), _) => style.bg(color),
+                    (None, _) => style,
+                }
+            }
+            _ => style,
+        };
+    }
+    return style;
+}
+
+// text with its SGR escape sequences turned into the styles of ranges of it,
+// like less -R does, and the other controls escaped like escape_controls
+pub fn ansi_styles(text: &str) -> (String, Vec<(Range<usize>, Style)>) {
+    let mut plain = String::with_capacity(text.len());
+    let mut styles = Vec::new();
+    let mut style = Style::default();
+    let mut start = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some(len) = sgr_len(rest) {
+            let next = apply_sgr(style, &rest[2..len - 1]);
+            if next != style {
+                if style != Style::default() && start < plain.len() {
+                    styles.push((start..plain.len(), style));
+                }
+                start = plain.len();
+                style = next;
+            }
+            rest = &rest[len..];
             continue;
         }
+        push_escaped(&mut plain, c);
+        rest = &rest[c.len_utf8()..];
+    }
+    if style != Style::default() && start < plain.len() {
+        styles.push((start..plain.len(), style));
+    }
+    return (plain, styles);
+}
 
-        if tab_width == 0 {
-            *cow_line.to_mut() = cow_line.replace("\t", "");
-            continue;
+// text for the terminal with the controls escaped, except the SGR sequences
+// which only change colors, like the output of less -R
+pub fn escape_controls_but_sgr(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match sgr_len(rest) {
+            Some(len) => {
+                escaped.push_str(&rest[..len]);
+                len
+            }
+            None => {
+                push_escaped(&mut escaped, c);
+                c.len_utf8()
+            }
+        };
+        rest = &rest[len..];
+    }
+    return escaped;
+}
+
+// bytes taken by a character once escaped by escape_controls
+fn escaped_len(c: char) -> usize {
+    return match c as u32 {
+        _ if c == '\t' || !c.is_control() => c.len_utf8(),
+        x if x < 0x20 || x == 0x7f => 2,
+        x => format!("<U+{:04X}>", x).len(),
+    };
+}
+
+// ranges of `data` in its text decoded like from_utf8_lossy does, with the
+// controls escaped and the SGR sequences removed or not. A range starting or
+// ending inside a character takes all of it, ends past the data go to the end
+// of the text.
+pub fn text_ranges(data: &[u8], ranges: &[Range<usize>], sgr: bool) -> Vec<Range<usize>> {
+    // offsets of the character starts in the data and in the text
+    let mut starts = vec![(0, 0)];
+    let (mut offset, mut text_offset) = (0, 0);
+    for chunk in data.utf8_chunks() {
+        let mut rest = chunk.valid();
+        while let Some(c) = rest.chars().next() {
+            let len = match sgr_len(rest) {
+                Some(len) if sgr => len,
+                _ => {
+                    text_offset += escaped_len(c);
+                    c.len_utf8()
+                }
+            };
+            offset += len;
+            rest = &rest[len..];
+            starts.push((offset, text_offset));
+        }
+        if !chunk.invalid().is_empty() {
+            offset += chunk.invalid().len();
+            text_offset += char::REPLACEMENT_CHARACTER.len_utf8();
+            starts.push((offset, text_offset));
         }
+    }
+    let to_text = |offset: usize, up: bool| {
+        return match starts.binary_search_by_key(&offset, |x| x.0) {
+            Ok(index) => starts[index].1,
+            Err(index) if up => starts.get(index).unwrap_or(&starts[starts.len() - 1]).1,
+            Err(index) => starts[index - 1].1,
+        };
+    };
+    return ranges
+        .iter()
+        .map(|x| to_text(x.start, false)..to_text(x.end, true))
+        .collect();
+}
+
+// the first `max` characters of a text longer than that, with its length in
+// characters, 0 for no limit
+pub fn truncate_text(text: &str, max: usize) -> Option<(&str, usize)> {
+    if max == 0 || text.len() <= max {
+        return None;
+    }
+    let (end, _) = text.char_indices().nth(max)?;
+    return Some((&text[..end], text.chars().count()));
+}
+
+// shown after a line cut by truncate_text
+pub fn truncation_marker(length: usize) -> String {
+    return format!("… ({} chars)", length);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate() {
+        assert_eq!(truncate_text("abcdef", 0), None);
+        assert_eq!(truncate_text("abcdef", 6), None);
+        assert_eq!(truncate_text("abcdef", 4), Some(("abcd", 6)));
+        assert_eq!(truncate_text("ééé", 4), None);
+        assert_eq!(truncate_text("éééééé", 4), Some(("éééé", 6)));
+    }
+
+    #[test]
+    fn ranges_in_text() {
+        let data = b"a\x1bb\xffc\xc3\xa9";
+        let text = escape_controls(&String::from_utf8_lossy(data)).into_owned();
+        assert_eq!(text, "a^[b\u{FFFD}cé");
+        let ranges = [2..3, 3..4, 5..7, 6..7, 4..20];
+        assert_eq!(
+            text_ranges(data, &ranges, false),
+            vec![3..4, 4..7, 8..10, 8..10, 7..10]
+        );
+        assert_eq!(
+            text_ranges(data, &ranges, true),
+            text_ranges(data, &ranges, false)
+        );
+
+        let data = b"a\x1b[31mbc\x1b[0m\x07d";
+        assert_eq!(
+            text_ranges(data, &[1..3, 6..8, 12..14], true),
+            vec![1..1, 1..3, 3..6]
+        );
+    }
 
-        let parts: Vec<String> = cow_line
-            .split("\t")
-            .map(|x| {
-                let width = div_ceil(x.len() + 1, tab_width) * tab_width;
-                format!("{:width$}", x, width = width)
-            })
-            .collect();
-        *cow_line.to_mut() = parts.join("");
+    #[test]
+    fn sgr_styles() {
+        let red = Style::default().fg(Color::Indexed(1));
+        assert_eq!(ansi_styles("plain"), ("plain".to_owned(), vec![]));
+        assert_eq!(
+            ansi_styles("a\x1b[31mbc\x1b[0m\x07d"),
+            ("abc^Gd".to_owned(), vec![(1..3, red)])
+        );
+        assert_eq!(
+            ansi_styles("\x1b[1;31mx\x1b[22my\x1b[39;48;5;200mz\x1b[m"),
+            (
+                "xyz".to_owned(),
+                vec![
+                    (0..1, red.add_modifier(Modifier::BOLD)),
+                    (1..2, red),
+                    (
+                        2..3,
+                        Style::default().fg(Color::Reset).bg(Color::Indexed(200))
+                    ),
+                ]
+            )
+        );
+        assert_eq!(
+            ansi_styles("\x1b[38;2;1;2;3m\x1b[4mx"),
+            (
+                "x".to_owned(),
+                vec![(
+                    0..1,
+                    Style::default()
+                        .fg(Color::Rgb(1, 2, 3))
+                        .add_modifier(Modifier::UNDERLINED)
+                )]
+            )
+        );
+        // other sequences stay visible, only colors are interpreted
+        assert_eq!(ansi_styles("\x1b[2Jx").0, "^[[2Jx");
+        assert_eq!(ansi_styles("\x1b[31").0, "^[[31");
+        assert_eq!(
+            escape_controls_but_sgr("\x1b[31mx\x1b[2J\x1b[m"),
+            "\x1b[31mx^[[2J\x1b[m"
+        );
     }
-    lines
 }
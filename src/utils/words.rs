@@ -0,0 +1,58 @@
+// characters splitting words on top of whitespace, by default
+pub const DEFAULT_WORD_SEPARATORS: &str = "<>()[]{},;:='\",";
+
+// how lines are cut in words, shared by the entropy mode, the word statistics
+// and the actions on the word under the cursor
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordSeparators {
+    chars: String,
+}
+
+impl Default for WordSeparators {
+    fn default() -> Self {
+        return Self::new(DEFAULT_WORD_SEPARATORS);
+    }
+}
+
+impl WordSeparators {
+    pub fn new(chars: &str) -> Self {
+        return Self {
+            chars: chars.to_owned(),
+        };
+    }
+
+    pub fn chars(&self) -> &str {
+        return &self.chars;
+    }
+
+    pub fn is_separator(&self, c: char) -> bool {
+        return c.is_whitespace() || self.chars.contains(c);
+    }
+
+    // words of a line, without the empty ones between consecutive separators
+    pub fn split<'a>(&'a self, line: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        return line
+            .split(move |c| self.is_separator(c))
+            .filter(|word| !word.is_empty());
+    }
+
+    // word containing the byte at `index`, none on a separator
+    pub fn word_at<'a>(&self, line: &'a str, index: usize) -> Option<&'a str> {
+        match line.get(index..).and_then(|x| x.chars().next()) {
+            Some(c) if !self.is_separator(c) => (),
+            _ => return None,
+        }
+        let start = line[..index]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| self.is_separator(*c))
+            .map(|(pos, c)| pos + c.len_utf8())
+            .unwrap_or(0);
+        let end = line[index..]
+            .char_indices()
+            .find(|(_, c)| self.is_separator(*c))
+            .map(|(pos, _)| index + pos)
+            .unwrap_or(line.len());
+        return Some(&line[start..end]);
+    }
+}
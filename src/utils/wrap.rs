@@ -0,0 +1,367 @@
+use tui::{
+    style::Style,
+    text::{Span, Spans},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// builds spans while merging consecutive pieces with the same style
+#[derive(Default)]
+struct SpansBuilder {
+    spans: Vec<Span<'static>>,
+    current: String,
+    style: Style,
+}
+
+impl SpansBuilder {
+    fn push(&mut self, grapheme: &str, style: Style) {
+        if style != self.style && !self.current.is_empty() {
+            self.flush();
+        }
+        self.style = style;
+        self.current.push_str(grapheme);
+    }
+
+    fn flush(&mut self) {
+        if !self.current.is_empty() {
+            let content = std::mem::take(&mut self.current);
+            self.spans.push(Span::styled(content, self.style));
+        }
+    }
+
+    fn take(&mut self) -> Spans<'static> {
+        self.flush();
+        return Spans::from(std::mem::take(&mut self.spans));
+    }
+}
+
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+// columns where tabs stop: the explicit stops first, then every `width` columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabStops {
+    pub stops: Vec<usize>,
+    pub width: usize,
+}
+
+impl TabStops {
+    pub fn new(width: usize) -> Self {
+        return Self {
+            stops: Vec::new(),
+            width,
+        };
+    }
+
+    // parse a comma separated list of increasing columns
+    pub fn parse_stops(value: &str) -> Option<Vec<usize>> {
+        let stops = value
+            .split(',')
+            .map(|x| x.trim().parse::<usize>().ok())
+            .collect::<Option<Vec<usize>>>()?;
+        if stops.windows(2).any(|x| x[0] >= x[1]) || stops.first() == Some(&0) {
+            return None;
+        }
+        return Some(stops);
+    }
+
+    // column reached by a tab starting at `column`
+    fn next(&self, column: usize) -> usize {
+        if let Some(stop) = self.stops.iter().find(|x| **x > column) {
+            return *stop;
+        }
+        if self.width == 0 {
+            return column;
+        }
+        let last = self.stops.last().cloned().unwrap_or(0);
+        return last + ((column - last) / self.width + 1) * self.width;
+    }
+}
+
+// start of the next rows of wrapped lines
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WrapIndent {
+    // indent the rows like the start of their line
+    pub indent: bool,
+    pub marker: String,
+}
+
+impl WrapIndent {
+    // text starting the next rows of the line, at most half of the width
+    fn prefix(&self, spans: &Spans, width: usize) -> String {
+        let marker_width = UnicodeWidthStr::width(self.marker.as_str());
+        if marker_width > width / 2 {
+            return String::new();
+        }
+        let mut indent = 0;
+        if self.indent {
+            indent = spans
+                .0
+                .iter()
+                .flat_map(|x| x.content.chars())
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .count();
+        }
+        return " ".repeat(indent.min(width / 2 - marker_width)) + &self.marker;
+    }
+}
+
+// replace tabs with spaces up to the next tab stop, keeping the styles
+pub fn expand_tabs(lines: Vec<Spans>, tab_stops: &TabStops) -> Vec<Spans<'static>> {
+    let mut out_lines = Vec::new();
+    for spans in lines {
+        let mut builder = SpansBuilder::default();
+        let mut column = 0;
+        for span in spans.0 {
+            for grapheme in span.content.graphemes(true) {
+                if grapheme == "\t" {
+                    let next = tab_stops.next(column);
+                    builder.push(&" ".repeat(next - column), span.style);
+                    column = next;
+                } else {
+                    builder.push(grapheme, span.style);
+                    column += UnicodeWidthStr::width(grapheme);
+                }
+            }
+        }
+        out_lines.push(builder.take());
+    }
+    return out_lines;
+}
+
+// split lines in rows of at most `width` columns, wide characters that
+// do not fit at the end of a row are moved to the next one
+pub fn wrap_lines(lines: Vec<Spans>, width: usize, indent: &WrapIndent) -> Vec<Spans<'static>> {
+    let width = width.max(1);
+    let mut rows = Vec::new();
+    for spans in lines {
+        let prefix = indent.prefix(&spans, width);
+        let prefix_width = UnicodeWidthStr::width(prefix.as_str());
+        let mut builder = SpansBuilder::default();
+        let mut column = 0;
+        let mut row_start = 0;
+        for span in spans.0 {
+            for grapheme in span.content.graphemes(true) {
+                let grapheme_width = UnicodeWidthStr::width(grapheme);
+                if column > row_start && column + grapheme_width > width {
+                    rows.push(builder.take());
+                    builder.push(&prefix, Style::default());
+                    column = prefix_width;
+                    row_start = prefix_width;
+                }
+                builder.push(grapheme, span.style);
+                column += grapheme_width;
+            }
+        }
+        rows.push(builder.take());
+    }
+    return rows;
+}
+
+// rows of a line wrapped at `width` columns, the same as wrap_lines once
+// expand_tabs replaced its tabs
+pub fn wrapped_rows(text: &str, width: usize, tab_stops: &TabStops, indent: &WrapIndent) -> usize {
+    let lines = expand_tabs(vec![Spans::from(text)], tab_stops);
+    return wrap_lines(lines, width, indent).len();
+}
+
+// columns of a line once its tabs are expanded
+pub fn expanded_width(text: &str, tab_stops: &TabStops) -> usize {
+    return expand_tabs(vec![Spans::from(text)], tab_stops)[0].width();
+}
+
+// drop `offset` columns of each line after its first `frozen` columns, the
+// visible part of a wide character cut by the offset is replaced by spaces
+pub fn shift_lines(lines: Vec<Spans>, offset: usize, frozen: usize) -> Vec<Spans<'static>> {
+    let start = frozen + offset;
+    let mut out_lines = Vec::new();
+    for spans in lines {
+        let mut builder = SpansBuilder::default();
+        let mut column = 0;
+        for span in spans.0 {
+            for grapheme in span.content.graphemes(true) {
+                let end = column + UnicodeWidthStr::width(grapheme);
+                if (column < frozen && end <= frozen) || column >= start {
+                    builder.push(grapheme, span.style);
+                } else {
+                    if column < frozen {
+                        builder.push(&" ".repeat(frozen - column), span.style);
+                    }
+                    if end > start {
+                        builder.push(&" ".repeat(end - start), span.style);
+                    }
+                }
+                column = end;
+            }
+        }
+        out_lines.push(builder.take());
+    }
+    return out_lines;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(lines: Vec<Spans>) -> Vec<String> {
+        return lines
+            .iter()
+            .map(|spans| spans.0.iter().map(|x| x.content.as_ref()).collect())
+            .collect();
+    }
+
+    #[test]
+    fn wrap_ascii() {
+        let lines = vec![Spans::from("abcdefg"), Spans::from("")];
+        assert_eq!(
+            rows(wrap_lines(lines, 3, &WrapIndent::default())),
+            vec!["abc", "def", "g", ""]
+        );
+    }
+
+    #[test]
+    fn wrap_wide_characters() {
+        // each character is 2 columns wide
+        let lines = vec![Spans::from("日本語")];
+        assert_eq!(
+            rows(wrap_lines(lines.clone(), 4, &WrapIndent::default())),
+            vec!["日本", "語"]
+        );
+        assert_eq!(
+            rows(wrap_lines(lines, 3, &WrapIndent::default())),
+            vec!["日", "本", "語"]
+        );
+
+        let lines = vec![Spans::from("a😀b")];
+        assert_eq!(
+            rows(wrap_lines(lines, 2, &WrapIndent::default())),
+            vec!["a", "😀", "b"]
+        );
+    }
+
+    #[test]
+    fn rows_of_a_line() {
+        let tabs = TabStops::new(DEFAULT_TAB_WIDTH);
+        let indent = WrapIndent::default();
+        // 5 columns, but the wide character moves to the third row
+        assert_eq!(wrapped_rows("a日本", 2, &tabs, &indent), 3);
+        assert_eq!(wrapped_rows("\tab", 3, &tabs, &indent), 2);
+        assert_eq!(wrapped_rows("", 3, &tabs, &indent), 1);
+        let indent = WrapIndent {
+            indent: false,
+            marker: "> ".to_owned(),
+        };
+        assert_eq!(wrapped_rows("abcdefgh", 4, &tabs, &indent), 3);
+        assert_eq!(expanded_width("a\tb", &tabs), 5);
+    }
+
+    #[test]
+    fn wrap_combining_characters() {
+        // "e" followed by a combining acute accent is a single column
+        let lines = vec![Spans::from("e\u{301}e\u{301}e\u{301}")];
+        assert_eq!(
+            rows(wrap_lines(lines, 2, &WrapIndent::default())),
+            vec!["e\u{301}e\u{301}", "e\u{301}"]
+        );
+    }
+
+    #[test]
+    fn wrap_keeps_styles() {
+        let style = Style::default().fg(tui::style::Color::Red);
+        let lines = vec![Spans::from(vec![
+            Span::raw("ab"),
+            Span::styled("cd", style),
+        ])];
+        let wrapped = wrap_lines(lines, 3, &WrapIndent::default());
+        assert_eq!(rows(wrapped.clone()), vec!["abc", "d"]);
+        assert_eq!(wrapped[0].0[1].style, style);
+        assert_eq!(wrapped[1].0[0].style, style);
+    }
+
+    #[test]
+    fn wrap_indented() {
+        let lines = vec![Spans::from("  at foo(bar)")];
+        let indent = WrapIndent {
+            indent: true,
+            marker: "> ".to_owned(),
+        };
+        assert_eq!(
+            rows(wrap_lines(lines.clone(), 8, &indent)),
+            vec!["  at foo", "  > (bar", "  > )"]
+        );
+        // the rows keep half of the width for the text
+        assert_eq!(
+            rows(wrap_lines(lines, 4, &indent)),
+            vec!["  at", ">  f", "> oo", "> (b", "> ar", "> )"]
+        );
+    }
+
+    #[test]
+    fn expand_tabs_across_spans() {
+        let style = Style::default().fg(tui::style::Color::Red);
+        let lines = vec![Spans::from(vec![
+            Span::raw("a\tb"),
+            Span::styled("c\td", style),
+        ])];
+        let expanded = expand_tabs(lines, &TabStops::new(4));
+        assert_eq!(rows(expanded.clone()), vec!["a   bc  d"]);
+        assert_eq!(expanded[0].0[1].content, "c  d");
+        assert_eq!(expanded[0].0[1].style, style);
+    }
+
+    #[test]
+    fn expand_tabs_wide_characters() {
+        let lines = vec![Spans::from("日\tx")];
+        assert_eq!(rows(expand_tabs(lines, &TabStops::new(4))), vec!["日  x"]);
+    }
+
+    #[test]
+    fn expand_tabs_explicit_stops() {
+        let tab_stops = TabStops {
+            stops: vec![3, 10],
+            width: 4,
+        };
+        let lines = vec![Spans::from("\ta\tb\tc"), Spans::from("\t\t")];
+        assert_eq!(
+            rows(expand_tabs(lines, &tab_stops)),
+            vec!["   a      b   c", "          "]
+        );
+        assert_eq!(
+            rows(expand_tabs(vec![Spans::from("a\tb")], &TabStops::new(0))),
+            vec!["ab"]
+        );
+        assert_eq!(TabStops::parse_stops("4, 8,20"), Some(vec![4, 8, 20]));
+        assert_eq!(TabStops::parse_stops("8,4"), None);
+    }
+
+    #[test]
+    fn shift_ascii() {
+        let lines = vec![Spans::from(vec![Span::raw("ab"), Span::raw("cd")])];
+        assert_eq!(rows(shift_lines(lines.clone(), 3, 0)), vec!["d"]);
+        assert_eq!(rows(shift_lines(lines, 10, 0)), vec![""]);
+    }
+
+    #[test]
+    fn shift_wide_characters() {
+        let lines = vec![Spans::from("日本語")];
+        assert_eq!(rows(shift_lines(lines.clone(), 2, 0)), vec!["本語"]);
+        assert_eq!(rows(shift_lines(lines, 3, 0)), vec![" 語"]);
+    }
+
+    #[test]
+    fn shift_combining_characters() {
+        let lines = vec![Spans::from("e\u{301}xe\u{301}")];
+        assert_eq!(rows(shift_lines(lines.clone(), 1, 0)), vec!["xe\u{301}"]);
+        assert_eq!(rows(shift_lines(lines, 2, 0)), vec!["e\u{301}"]);
+    }
+
+    #[test]
+    fn shift_frozen_columns() {
+        let lines = vec![Spans::from("12:00 abcdef")];
+        assert_eq!(rows(shift_lines(lines.clone(), 3, 6)), vec!["12:00 def"]);
+        assert_eq!(rows(shift_lines(lines, 10, 6)), vec!["12:00 "]);
+
+        let lines = vec![Spans::from("日本語")];
+        assert_eq!(rows(shift_lines(lines.clone(), 1, 3)), vec!["日 語"]);
+        assert_eq!(rows(shift_lines(lines, 2, 3)), vec!["日  "]);
+    }
+}